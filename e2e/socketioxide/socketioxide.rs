@@ -4,7 +4,10 @@ use std::time::Duration;
 
 use hyper::Server;
 use serde_json::Value;
-use socketioxide::SocketIo;
+use socketioxide::{
+    extract::{AckSender, Bin, Data, SocketRef},
+    SocketIo,
+};
 use tracing::{info, Level};
 use tracing_subscriber::FmtSubscriber;
 
@@ -23,24 +26,36 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .max_payload(1e6 as u64)
         .build_svc();
 
-    io.ns("/", |socket, data: Value| async move {
-        info!("Socket.IO connected: {:?} {:?}", socket.ns(), socket.id);
-        socket.emit("auth", data).ok();
-
-        socket.on("message", |socket, data: Value, bin, _| async move {
-            info!("Received event: {:?} {:?}", data, bin);
-            socket.bin(bin).emit("message-back", data).ok();
-        });
-
-        socket.on("message-with-ack", |_, data: Value, bin, ack| async move {
-            info!("Received event: {:?} {:?}", data, bin);
-            ack.bin(bin).send(data).ok();
-        });
-    });
-    io.ns("/custom", |socket, data: Value| async move {
-        info!("Socket.IO connected on: {:?} {:?}", socket.ns(), socket.id);
-        socket.emit("auth", data).ok();
-    });
+    io.ns(
+        "/",
+        |socket: SocketRef, Data(data): Data<Value>| async move {
+            info!("Socket.IO connected: {:?} {:?}", socket.ns(), socket.id);
+            socket.emit("auth", data).ok();
+
+            socket.on(
+                "message",
+                |socket: SocketRef, Data(data): Data<Value>, Bin(bin)| async move {
+                    info!("Received event: {:?} {:?}", data, bin);
+                    socket.bin(bin).emit("message-back", data).await.ok();
+                },
+            );
+
+            socket.on(
+                "message-with-ack",
+                |Data(data): Data<Value>, Bin(bin), ack: AckSender| async move {
+                    info!("Received event: {:?} {:?}", data, bin);
+                    ack.bin(bin).send(data).ok();
+                },
+            );
+        },
+    );
+    io.ns(
+        "/custom",
+        |socket: SocketRef, Data(data): Data<Value>| async move {
+            info!("Socket.IO connected on: {:?} {:?}", socket.ns(), socket.id);
+            socket.emit("auth", data).ok();
+        },
+    );
 
     #[cfg(feature = "v5")]
     info!("Starting server with v5 protocol");
@@ -0,0 +1,387 @@
+//! An engine.io client to connect to an engine.io server, available with the `client` feature.
+//!
+//! It speaks the engine.io v4 protocol and reuses the [`Packet`] and the polling payload encoder and decoder
+//! of the server. It supports the polling and websocket transports and the upgrade from polling to websocket.
+//! TLS connections are not supported.
+//!
+//! #### Example
+//! ```no_run
+//! # use engineioxide::client::{ClientConfig, ClientEvent, EngineClient};
+//! # #[tokio::main]
+//! # async fn main() {
+//! let config = ClientConfig::builder("http://127.0.0.1:3000/engine.io/").build();
+//! let (client, mut events) = EngineClient::connect(config).await.unwrap();
+//! client.emit("hello".into()).unwrap();
+//!
+//! while let Some(event) = events.recv().await {
+//!     match event {
+//!         ClientEvent::Message(msg) => println!("message: {msg}"),
+//!         ClientEvent::Binary(data) => println!("binary: {data:?}"),
+//!         ClientEvent::Close(reason) => println!("closed: {reason:?}"),
+//!     }
+//! }
+//! # }
+//! ```
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU8, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tokio::{
+    sync::{
+        mpsc::{self, error::TrySendError},
+        Mutex, Notify,
+    },
+    task::JoinHandle,
+};
+
+use crate::{
+    errors::Error,
+    packet::{OpenPacket, Packet},
+    peekable::PeekableReceiver,
+    sid::Sid,
+    transport::TransportType,
+    DisconnectReason,
+};
+
+mod polling;
+mod ws;
+
+/// Configuration of an [`EngineClient`]
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// The url of the engine.io endpoint of the server, e.g. `http://localhost:3000/engine.io/`.
+    pub url: String,
+
+    /// The maximum number of packets that can be buffered before being sent to the server.
+    ///
+    /// If the buffer if full the `emit()` method will return an error.
+    /// Defaults to 128 packets.
+    pub max_buffer_size: usize,
+
+    /// The maximum number of bytes that can be received per http polling response.
+    /// Defaults to 100kb.
+    pub max_payload: u64,
+
+    /// Allowed transports for this client.
+    /// It is represented as a bitfield to allow to combine any number of transports easily.
+    ///
+    /// If both transports are allowed, the client connects with polling and then upgrades to websocket.
+    pub transports: u8,
+}
+
+impl ClientConfig {
+    pub fn builder(url: impl Into<String>) -> ClientConfigBuilder {
+        ClientConfigBuilder::new(url)
+    }
+
+    /// Check if a [`TransportType`] is enabled in the [`ClientConfig`]
+    #[inline(always)]
+    pub fn allowed_transport(&self, transport: TransportType) -> bool {
+        self.transports & transport as u8 == transport as u8
+    }
+
+    /// The url of a request with the given transport and session id
+    fn req_url(&self, transport: TransportType, sid: Option<Sid>) -> String {
+        let mut url = match transport {
+            TransportType::Polling => self.url.clone(),
+            TransportType::Websocket => self.url.replacen("http", "ws", 1),
+        };
+        let transport: &str = transport.into();
+        url.push_str(&format!("?EIO=4&transport={transport}"));
+        if let Some(sid) = sid {
+            url.push_str(&format!("&sid={sid}"));
+        }
+        url
+    }
+}
+
+pub struct ClientConfigBuilder {
+    config: ClientConfig,
+}
+
+impl ClientConfigBuilder {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            config: ClientConfig {
+                url: url.into(),
+                max_buffer_size: 128,
+                max_payload: 1e5 as u64, // 100kb
+                transports: TransportType::Polling as u8 | TransportType::Websocket as u8,
+            },
+        }
+    }
+
+    /// The maximum number of packets that can be buffered before being sent to the server.
+    /// Defaults to 128 packets.
+    pub fn max_buffer_size(mut self, max_buffer_size: usize) -> Self {
+        self.config.max_buffer_size = max_buffer_size;
+        self
+    }
+
+    /// The maximum number of bytes that can be received per http polling response.
+    /// Defaults to 100kb.
+    pub fn max_payload(mut self, max_payload: u64) -> Self {
+        self.config.max_payload = max_payload;
+        self
+    }
+
+    /// Allowed transports for this client
+    ///
+    /// The `transports` array should have a size of 1 or 2
+    ///
+    /// Defaults to :
+    /// `[TransportType::Polling, TransportType::Websocket]`
+    pub fn transports<const N: usize>(mut self, transports: [TransportType; N]) -> Self {
+        assert!(N > 0 && N <= 2);
+        self.config.transports = 0;
+        for transport in transports {
+            self.config.transports |= transport as u8;
+        }
+        self
+    }
+
+    /// Build the config
+    pub fn build(self) -> ClientConfig {
+        self.config
+    }
+}
+
+/// An event received by an [`EngineClient`] from the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientEvent {
+    /// A text message
+    Message(String),
+    /// A binary message
+    Binary(Vec<u8>),
+    /// The connection is closed, it is the last event received
+    Close(DisconnectReason),
+}
+
+/// A connection to an engine.io server.
+///
+/// It is cheaply clonable and stays open until [`EngineClient::close`] is called or the server closes it.
+#[derive(Debug, Clone)]
+pub struct EngineClient {
+    inner: Arc<ClientSocket>,
+}
+
+impl EngineClient {
+    /// Connect to the server and return the client with the receiver of the events sent by the server.
+    ///
+    /// If both transports are allowed, this function returns once the connection is upgraded to websocket,
+    /// if the upgrade fails the client keeps using the polling transport.
+    pub async fn connect(
+        config: ClientConfig,
+    ) -> Result<(Self, mpsc::UnboundedReceiver<ClientEvent>), Error> {
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let inner = if config.allowed_transport(TransportType::Polling) {
+            let socket = polling::connect(config, event_tx).await?;
+            if socket.config.allowed_transport(TransportType::Websocket)
+                && socket.open.upgrades.iter().any(|t| t == "websocket")
+            {
+                if let Err(_e) = ws::upgrade(&socket).await {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!("[sid={}] websocket upgrade error: {:?}", socket.id, _e);
+                }
+            }
+            socket
+        } else {
+            ws::connect(config, event_tx).await?
+        };
+        Ok((Self { inner }, event_rx))
+    }
+
+    /// The session id given by the server
+    pub fn id(&self) -> Sid {
+        self.inner.id
+    }
+
+    /// The transport currently used by the client
+    pub fn transport(&self) -> TransportType {
+        if self.inner.transport.load(Ordering::Relaxed) == TransportType::Websocket as u8 {
+            TransportType::Websocket
+        } else {
+            TransportType::Polling
+        }
+    }
+
+    /// Emits a message to the server.
+    ///
+    /// ⚠️ If the buffer is full or the client is closed, an error will be returned with the original data
+    pub fn emit(&self, msg: String) -> Result<(), TrySendError<String>> {
+        self.inner
+            .internal_tx
+            .try_send(Packet::Message(msg))
+            .map_err(|e| match e {
+                TrySendError::Full(p) => TrySendError::Full(p.into_message()),
+                TrySendError::Closed(p) => TrySendError::Closed(p.into_message()),
+            })
+    }
+
+    /// Emits a binary message to the server.
+    ///
+    /// ⚠️ If the buffer is full or the client is closed, an error will be returned with the original data
+    pub fn emit_binary(&self, data: Vec<u8>) -> Result<(), TrySendError<Vec<u8>>> {
+        self.inner
+            .internal_tx
+            .try_send(Packet::Binary(data))
+            .map_err(|e| match e {
+                TrySendError::Full(p) => TrySendError::Full(p.into_binary()),
+                TrySendError::Closed(p) => TrySendError::Closed(p.into_binary()),
+            })
+    }
+
+    /// Gracefully closes the connection, the last event received is a
+    /// [`ClientEvent::Close`] with [`DisconnectReason::TransportClose`].
+    pub fn close(&self) {
+        if self.inner.internal_tx.try_send(Packet::Close).is_err() {
+            self.inner.close(DisconnectReason::TransportClose);
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.closed.load(Ordering::Relaxed)
+    }
+}
+
+/// The state of a connection shared between the transport tasks
+pub(crate) struct ClientSocket {
+    id: Sid,
+    config: ClientConfig,
+    open: OpenPacket,
+
+    /// The transport type represented as a bitfield, like in the server [`Socket`](crate::socket::Socket)
+    transport: AtomicU8,
+
+    /// Channel of the packets waiting to be sent to the server by the current transport
+    internal_rx: Mutex<PeekableReceiver>,
+    internal_tx: mpsc::Sender<Packet>,
+
+    /// Channel of the events forwarded to the user
+    event_tx: mpsc::UnboundedSender<ClientEvent>,
+
+    /// Channel to notify the heartbeat job that a ping was received
+    heartbeat_tx: mpsc::Sender<()>,
+
+    /// Notified to stop sending polling requests before upgrading to websocket
+    upgrade: Notify,
+
+    closed: AtomicBool,
+    /// The transport and heartbeat tasks, aborted when the connection is closed
+    tasks: std::sync::Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl ClientSocket {
+    fn new(
+        config: ClientConfig,
+        open: OpenPacket,
+        transport: TransportType,
+        event_tx: mpsc::UnboundedSender<ClientEvent>,
+    ) -> Arc<Self> {
+        let (internal_tx, internal_rx) = mpsc::channel(config.max_buffer_size);
+        let (heartbeat_tx, heartbeat_rx) = mpsc::channel(1);
+        let socket = Arc::new(Self {
+            id: open.sid,
+            config,
+            open,
+            transport: AtomicU8::new(transport as u8),
+            internal_rx: Mutex::new(PeekableReceiver::new(internal_rx)),
+            internal_tx,
+            event_tx,
+            heartbeat_tx,
+            upgrade: Notify::new(),
+            closed: AtomicBool::new(false),
+            tasks: std::sync::Mutex::new(Vec::new()),
+        });
+        socket.spawn(socket.clone().heartbeat_job(heartbeat_rx));
+        socket
+    }
+
+    /// Spawn a task that is aborted when the connection is closed
+    fn spawn(&self, task: impl std::future::Future<Output = ()> + Send + 'static) {
+        let handle = tokio::spawn(task);
+        let mut tasks = self.tasks.lock().unwrap();
+        if self.closed.load(Ordering::SeqCst) {
+            handle.abort();
+        } else {
+            tasks.push(handle);
+        }
+    }
+
+    /// The server sends a ping every `ping_interval`,
+    /// if no ping is received within `ping_interval + ping_timeout` the connection is closed.
+    async fn heartbeat_job(self: Arc<Self>, mut heartbeat_rx: mpsc::Receiver<()>) {
+        let timeout = Duration::from_millis(self.open.ping_interval + self.open.ping_timeout);
+        while let Ok(Some(())) = tokio::time::timeout(timeout, heartbeat_rx.recv()).await {}
+        #[cfg(feature = "tracing")]
+        tracing::debug!("[sid={}] heartbeat timeout", self.id);
+        self.close(DisconnectReason::HeartbeatTimeout);
+    }
+
+    /// Handle a packet received from the server.
+    ///
+    /// Returns false if the transport should stop reading packets
+    fn on_packet(&self, packet: Packet) -> bool {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("[sid={}] received packet: {:?}", self.id, packet);
+        match packet {
+            Packet::Ping => {
+                self.heartbeat_tx.try_send(()).ok();
+                self.internal_tx.try_send(Packet::Pong).ok();
+            }
+            Packet::Message(msg) => {
+                self.event_tx.send(ClientEvent::Message(msg)).ok();
+            }
+            Packet::Binary(data) | Packet::BinaryV3(data) => {
+                self.event_tx.send(ClientEvent::Binary(data)).ok();
+            }
+            Packet::Close => {
+                self.close(DisconnectReason::TransportClose);
+                return false;
+            }
+            // A Noop packet is sent to the pending polling request before an upgrade
+            Packet::Noop => return false,
+            _ => (),
+        }
+        true
+    }
+
+    /// Close the connection because of an error
+    fn close_on_error(&self, err: &Error) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("[sid={}] transport error: {:?}", self.id, err);
+        let reason: Option<DisconnectReason> = err.into();
+        self.close(reason.unwrap_or(DisconnectReason::TransportClose));
+    }
+
+    /// Immediately closes the connection, abort all its tasks and notify the user.
+    ///
+    /// Only the first call has an effect
+    fn close(&self, reason: DisconnectReason) {
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!("[sid={}] closing connection: {:?}", self.id, reason);
+        self.event_tx.send(ClientEvent::Close(reason)).ok();
+        let tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        for task in tasks {
+            task.abort();
+        }
+    }
+}
+
+impl std::fmt::Debug for ClientSocket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientSocket")
+            .field("sid", &self.id)
+            .field("config", &self.config)
+            .field("transport", &self.transport)
+            .field("closed", &self.closed)
+            .finish()
+    }
+}
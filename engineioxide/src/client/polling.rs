@@ -0,0 +1,120 @@
+//! The polling transport of the client.
+//!
+//! A reader task sends GET requests to receive packets and a writer task sends POST requests
+//! with the packets waiting in the internal channel. They are both encoded with the v4 payload format.
+
+use std::sync::Arc;
+
+use futures::TryStreamExt;
+use http::{Method, Request, StatusCode};
+use hyper::{client::HttpConnector, Body};
+use tokio::sync::mpsc;
+
+use crate::{
+    errors::Error,
+    packet::Packet,
+    transport::{
+        polling::payload::{v4_decoder, v4_encoder},
+        TransportType,
+    },
+    DisconnectReason,
+};
+
+use super::{ClientConfig, ClientEvent, ClientSocket};
+
+type HttpClient = hyper::Client<HttpConnector>;
+
+/// Open a new session with a polling handshake and spawn the reader and writer tasks
+pub(super) async fn connect(
+    config: ClientConfig,
+    event_tx: mpsc::UnboundedSender<ClientEvent>,
+) -> Result<Arc<ClientSocket>, Error> {
+    let client = HttpClient::new();
+    let url = config.req_url(TransportType::Polling, None);
+    let open = match get(&client, &url, config.max_payload)
+        .await?
+        .into_iter()
+        .next()
+    {
+        Some(Packet::Open(open)) => open,
+        Some(p) => return Err(Error::BadPacket(p)),
+        None => return Err(Error::InvalidPacketLength),
+    };
+
+    let socket = ClientSocket::new(config, open, TransportType::Polling, event_tx);
+    #[cfg(feature = "tracing")]
+    tracing::debug!("[sid={}] new polling connection", socket.id);
+
+    socket.spawn(read_job(socket.clone(), client.clone()));
+    socket.spawn(write_job(socket.clone(), client));
+    Ok(socket)
+}
+
+/// Send GET requests until the transport is closed or upgraded
+async fn read_job(socket: Arc<ClientSocket>, client: HttpClient) {
+    let url = socket
+        .config
+        .req_url(TransportType::Polling, Some(socket.id));
+    loop {
+        match get(&client, &url, socket.config.max_payload).await {
+            Ok(packets) => {
+                for packet in packets {
+                    if !socket.on_packet(packet) {
+                        return;
+                    }
+                }
+            }
+            Err(e) => return socket.close_on_error(&e),
+        }
+    }
+}
+
+/// Send POST requests with the packets waiting in the internal channel
+/// until the transport is closed or upgraded
+async fn write_job(socket: Arc<ClientSocket>, client: HttpClient) {
+    let url = socket
+        .config
+        .req_url(TransportType::Polling, Some(socket.id));
+    let max_payload = socket.open.max_payload;
+    loop {
+        let payload = tokio::select! {
+            biased;
+            _ = socket.upgrade.notified() => break,
+            payload = async { v4_encoder(socket.internal_rx.lock().await, max_payload).await } => payload,
+        };
+        let res = match payload {
+            Ok(payload) => post(&client, &url, payload.data).await,
+            // The internal channel was closed after sending a close packet
+            Err(Error::Aborted) => return socket.close(DisconnectReason::TransportClose),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            return socket.close_on_error(&e);
+        }
+    }
+    #[cfg(feature = "tracing")]
+    tracing::debug!("[sid={}] polling writer stopped for upgrade", socket.id);
+}
+
+/// Send a GET request and decode the received payload
+async fn get(client: &HttpClient, url: &str, max_payload: u64) -> Result<Vec<Packet>, Error> {
+    let res = client.get(url.parse().map_err(http::Error::from)?).await?;
+    if res.status() != StatusCode::OK {
+        return Err(Error::HttpErrorResponse(res.status()));
+    }
+    v4_decoder(res.into_body(), max_payload).try_collect().await
+}
+
+/// Send a POST request with an encoded payload
+async fn post(client: &HttpClient, url: &str, data: Vec<u8>) -> Result<(), Error> {
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(url)
+        .header("Content-Type", "text/plain;charset=UTF-8")
+        .body(Body::from(data))?;
+    let res = client.request(req).await?;
+    if res.status() != StatusCode::OK {
+        return Err(Error::HttpErrorResponse(res.status()));
+    }
+    Ok(())
+}
@@ -0,0 +1,152 @@
+//! The websocket transport of the client.
+//!
+//! It is either used directly or after an upgrade from the polling transport.
+
+use std::sync::{atomic::Ordering, Arc};
+
+use futures::{
+    stream::{SplitSink, SplitStream},
+    SinkExt, StreamExt, TryStreamExt,
+};
+use tokio::{net::TcpStream, sync::mpsc};
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use crate::{errors::Error, packet::Packet, transport::TransportType, DisconnectReason};
+
+use super::{ClientConfig, ClientEvent, ClientSocket};
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Open a new session with a websocket handshake and spawn the reader and writer tasks
+pub(super) async fn connect(
+    config: ClientConfig,
+    event_tx: mpsc::UnboundedSender<ClientEvent>,
+) -> Result<Arc<ClientSocket>, Error> {
+    let url = config.req_url(TransportType::Websocket, None);
+    let (mut ws, _) = tokio_tungstenite::connect_async(url).await?;
+    let open = match recv_packet(&mut ws).await? {
+        Packet::Open(open) => open,
+        p => return Err(Error::BadPacket(p)),
+    };
+
+    let socket = ClientSocket::new(config, open, TransportType::Websocket, event_tx);
+    #[cfg(feature = "tracing")]
+    tracing::debug!("[sid={}] new websocket connection", socket.id);
+
+    spawn_jobs(&socket, ws);
+    Ok(socket)
+}
+
+/// Upgrade a polling session to websocket.
+///
+/// The polling writer is stopped before sending the upgrade packet and
+/// the polling reader stops when it receives the noop packet sent by the server.
+/// See [the server side](crate::transport::ws) for the handshake.
+pub(super) async fn upgrade(socket: &Arc<ClientSocket>) -> Result<(), Error> {
+    let url = socket
+        .config
+        .req_url(TransportType::Websocket, Some(socket.id));
+    let (mut ws, _) = tokio_tungstenite::connect_async(url).await?;
+
+    ws.send(Message::Text(Packet::PingUpgrade.try_into()?))
+        .await?;
+    match recv_packet(&mut ws).await? {
+        Packet::PongUpgrade => (),
+        p => return Err(Error::BadPacket(p)),
+    };
+
+    // Wait for the polling writer to release the internal channel
+    socket.upgrade.notify_one();
+    drop(socket.internal_rx.lock().await);
+
+    ws.send(Message::Text(Packet::Upgrade.try_into()?)).await?;
+    socket
+        .transport
+        .store(TransportType::Websocket as u8, Ordering::Relaxed);
+    #[cfg(feature = "tracing")]
+    tracing::debug!("[sid={}] ws upgraded successful", socket.id);
+
+    spawn_jobs(socket, ws);
+    Ok(())
+}
+
+/// Receive the next text packet from the websocket
+async fn recv_packet(ws: &mut WsStream) -> Result<Packet, Error> {
+    match ws.next().await {
+        Some(Ok(Message::Text(msg))) => Packet::try_from(msg),
+        Some(Err(e)) => Err(e.into()),
+        _ => Err(Error::UpgradeError),
+    }
+}
+
+fn spawn_jobs(socket: &Arc<ClientSocket>, ws: WsStream) {
+    let (tx, rx) = ws.split();
+    socket.spawn(read_job(socket.clone(), rx));
+    socket.spawn(write_job(socket.clone(), tx));
+}
+
+/// Forward all the packets received from the websocket to the [`ClientSocket`]
+async fn read_job(socket: Arc<ClientSocket>, mut rx: SplitStream<WsStream>) {
+    loop {
+        let res = match rx.try_next().await {
+            Ok(Some(Message::Text(msg))) => Packet::try_from(msg).map(|p| socket.on_packet(p)),
+            Ok(Some(Message::Binary(data))) => {
+                socket.event_tx.send(ClientEvent::Binary(data)).ok();
+                Ok(true)
+            }
+            Ok(Some(Message::Close(_))) | Ok(None) => Ok(false),
+            Ok(Some(_)) => Ok(true),
+            Err(e) => Err(e.into()),
+        };
+        match res {
+            Ok(true) => (),
+            Ok(false) => return socket.close(DisconnectReason::TransportClose),
+            Err(e) => return socket.close_on_error(&e),
+        }
+    }
+}
+
+/// Forward all the packets waiting in the internal channel to the websocket
+///
+/// The websocket stream is flushed only when the internal channel is drained
+async fn write_job(socket: Arc<ClientSocket>, mut tx: SplitSink<WsStream, Message>) {
+    let mut internal_rx = socket.internal_rx.lock().await;
+
+    // map a packet to a websocket message
+    // It is declared as a macro rather than a closure to avoid ownership issues
+    macro_rules! map_fn {
+        ($item:ident) => {
+            let res = match $item {
+                Packet::Binary(bin) | Packet::BinaryV3(bin) => tx.feed(Message::Binary(bin)).await,
+                Packet::Close => {
+                    let packet: String = Packet::Close.try_into().unwrap();
+                    tx.send(Message::Text(packet)).await.ok();
+                    tx.send(Message::Close(None)).await.ok();
+                    internal_rx.close();
+                    break;
+                }
+                _ => {
+                    let packet: String = $item.try_into().unwrap();
+                    tx.feed(Message::Text(packet)).await
+                }
+            };
+            if let Err(_e) = res {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("[sid={}] error sending packet: {}", socket.id, _e);
+            }
+        };
+    }
+
+    while let Some(item) = internal_rx.recv().await {
+        map_fn!(item);
+
+        // For every available packet we continue to send until the channel is drained
+        while let Ok(item) = internal_rx.try_recv() {
+            map_fn!(item);
+        }
+
+        tx.flush().await.ok();
+    }
+    drop(internal_rx);
+    socket.close(DisconnectReason::TransportClose);
+}
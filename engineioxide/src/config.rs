@@ -1,7 +1,81 @@
-use std::time::Duration;
+use std::{net::IpAddr, time::Duration};
 
 pub use crate::transport::TransportType;
 
+/// What to do when a message is emitted to a socket whose buffer is full,
+/// for example because the client does not read the messages fast enough.
+/// For a group of messages reserved with [`Socket::reserve`](crate::Socket::reserve), it applies to the whole group.
+///
+/// The awaitable [`Socket::emit_async`](crate::Socket::emit_async) waits for a place in the buffer instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OverflowPolicy {
+    /// Return an error with the message
+    Error,
+    /// Drop the oldest messages waiting in the buffer to make room for the new ones.
+    ///
+    /// A group whose first message was already read by the transport can't be dropped.
+    /// The dropped messages are skipped when the transport reads the buffer,
+    /// if there is still not enough room, the new messages are dropped instead.
+    DropOldest,
+    /// Drop the new message
+    DropNewest,
+    /// Close the connection of the client, with the [`BufferOverflow`](crate::DisconnectReason::BufferOverflow) reason
+    Disconnect,
+}
+
+impl From<u8> for OverflowPolicy {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => OverflowPolicy::DropOldest,
+            2 => OverflowPolicy::DropNewest,
+            3 => OverflowPolicy::Disconnect,
+            _ => OverflowPolicy::Error,
+        }
+    }
+}
+
+/// The parameters of the [permessage-deflate](https://datatracker.ietf.org/doc/html/rfc7692) websocket extension,
+/// negotiated with the clients that support it to compress the websocket messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeflateConfig {
+    /// The messages smaller than this number of bytes are not compressed.
+    /// Defaults to 1024 bytes.
+    pub threshold: usize,
+
+    /// The maximum size of the LZ77 window used to compress the messages sent to the clients, as a power of 2 between 8 and 15.
+    ///
+    /// Below 15, only the messages that fit in the window are compressed and the compression context is not kept between messages.
+    /// Defaults to 15.
+    pub server_max_window_bits: u8,
+
+    /// The maximum size of the LZ77 window that the clients can use to compress their messages, as a power of 2 between 8 and 15.
+    /// It is only requested to the clients that announce they support it.
+    /// Defaults to 15.
+    pub client_max_window_bits: u8,
+
+    /// Reset the compression context after each message sent to the clients,
+    /// it uses less memory per connection but compresses less.
+    /// Defaults to false.
+    pub server_no_context_takeover: bool,
+
+    /// Ask the clients to reset their compression context after each message.
+    /// Defaults to false.
+    pub client_no_context_takeover: bool,
+}
+
+impl Default for DeflateConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 1024,
+            server_max_window_bits: 15,
+            client_max_window_bits: 15,
+            server_no_context_takeover: false,
+            client_no_context_takeover: false,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct EngineIoConfig {
     /// The path to listen for engine.io requests on.
@@ -18,11 +92,17 @@ pub struct EngineIoConfig {
 
     /// The maximum number of packets that can be buffered per connection before being emitted to the client.
     ///
-    /// If the buffer if full the `emit()` method will return an error
+    /// If the buffer if full the `emit()` method applies the [`OverflowPolicy`] of the socket
     ///
     /// Defaults to 128 packets
     pub max_buffer_size: usize,
 
+    /// The policy applied when a message is emitted to a socket whose buffer is full.
+    /// It can be changed for each socket with [`Socket::set_overflow_policy`](crate::Socket::set_overflow_policy).
+    ///
+    /// Defaults to [`OverflowPolicy::Error`]
+    pub overflow_policy: OverflowPolicy,
+
     /// The maximum number of bytes that can be received per http request.
     /// Defaults to 100kb.
     pub max_payload: u64,
@@ -30,6 +110,21 @@ pub struct EngineIoConfig {
     /// Allowed transports on this server
     /// It is represented as a bitfield to allow to combine any number of transports easily
     pub transports: u8,
+
+    /// The ip addresses of the reverse proxies trusted to forward the client address
+    /// in the `Forwarded` or `X-Forwarded-For` headers.
+    ///
+    /// When the remote peer of a connection is one of them, the [`client_ip`](crate::SocketReq::client_ip)
+    /// of the socket is read from these headers.
+    ///
+    /// Defaults to no trusted proxies.
+    pub trusted_proxies: Vec<IpAddr>,
+
+    /// The parameters of the permessage-deflate extension used to compress the websocket messages.
+    /// The messages are compressed only with the clients that support the extension.
+    ///
+    /// Defaults to `None`, the messages are not compressed.
+    pub per_message_deflate: Option<DeflateConfig>,
 }
 
 impl Default for EngineIoConfig {
@@ -39,8 +134,11 @@ impl Default for EngineIoConfig {
             ping_interval: Duration::from_millis(25000),
             ping_timeout: Duration::from_millis(20000),
             max_buffer_size: 128,
+            overflow_policy: OverflowPolicy::Error,
             max_payload: 1e5 as u64, // 100kb
             transports: TransportType::Polling as u8 | TransportType::Websocket as u8,
+            trusted_proxies: Vec::new(),
+            per_message_deflate: None,
         }
     }
 }
@@ -128,6 +226,21 @@ impl EngineIoConfigBuilder {
         self
     }
 
+    /// The policy applied when a message is emitted to a socket whose buffer is full.
+    ///
+    /// Defaults to [`OverflowPolicy::Error`]
+    /// ```
+    /// # use engineioxide::config::{EngineIoConfig, OverflowPolicy};
+    /// let config = EngineIoConfig::builder()
+    ///     .max_buffer_size(64)
+    ///     .overflow_policy(OverflowPolicy::DropOldest)
+    ///     .build();
+    /// ```
+    pub fn overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.config.overflow_policy = policy;
+        self
+    }
+
     /// The maximum number of bytes that can be received per http request.
     /// Defaults to 100kb.
     pub fn max_payload(mut self, max_payload: u64) -> Self {
@@ -150,6 +263,45 @@ impl EngineIoConfigBuilder {
         self
     }
 
+    /// The ip addresses of the reverse proxies trusted to forward the client address
+    /// in the `Forwarded` or `X-Forwarded-For` headers.
+    ///
+    /// Defaults to no trusted proxies.
+    /// ```
+    /// # use engineioxide::config::EngineIoConfig;
+    /// # use std::net::{IpAddr, Ipv4Addr};
+    /// let config = EngineIoConfig::builder()
+    ///     .trusted_proxies([IpAddr::V4(Ipv4Addr::LOCALHOST)])
+    ///     .build();
+    /// ```
+    pub fn trusted_proxies(mut self, proxies: impl IntoIterator<Item = IpAddr>) -> Self {
+        self.config.trusted_proxies = proxies.into_iter().collect();
+        self
+    }
+
+    /// Compress the websocket messages with the permessage-deflate extension,
+    /// for the clients that support it.
+    ///
+    /// The window bits must be between 8 and 15.
+    ///
+    /// Defaults to no compression.
+    /// ```
+    /// # use engineioxide::config::{DeflateConfig, EngineIoConfig};
+    /// let config = EngineIoConfig::builder()
+    ///     .per_message_deflate(DeflateConfig {
+    ///         threshold: 512,
+    ///         server_no_context_takeover: true,
+    ///         ..Default::default()
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn per_message_deflate(mut self, config: DeflateConfig) -> Self {
+        assert!((8..=15).contains(&config.server_max_window_bits));
+        assert!((8..=15).contains(&config.client_max_window_bits));
+        self.config.per_message_deflate = Some(config);
+        self
+    }
+
     /// Build the config
     pub fn build(self) -> EngineIoConfig {
         self.config
@@ -42,9 +42,12 @@ impl<H: EngineIoHandler> EngineIo<H> {
         self: &Arc<Self>,
         protocol: ProtocolVersion,
         transport: TransportType,
-        req: SocketReq,
+        mut req: SocketReq,
         #[cfg(feature = "v3")] supports_binary: bool,
     ) -> Arc<Socket<H::Data>> {
+        if !self.config.trusted_proxies.is_empty() {
+            req.resolve_client_ip(&self.config.trusted_proxies);
+        }
         let engine = self.clone();
         let close_fn = Box::new(move |sid, reason| engine.close_session(sid, reason));
 
@@ -134,6 +137,8 @@ mod tests {
             SocketReq {
                 headers: http::HeaderMap::new(),
                 uri: http::Uri::default(),
+                remote_addr: None,
+                client_ip: None,
             },
             #[cfg(feature = "v3")]
             true,
@@ -153,6 +158,8 @@ mod tests {
             SocketReq {
                 headers: http::HeaderMap::new(),
                 uri: http::Uri::default(),
+                remote_addr: None,
+                client_ip: None,
             },
             #[cfg(feature = "v3")]
             true,
@@ -172,6 +179,8 @@ mod tests {
             SocketReq {
                 headers: http::HeaderMap::new(),
                 uri: http::Uri::default(),
+                remote_addr: None,
+                client_ip: None,
             },
             #[cfg(feature = "v3")]
             true,
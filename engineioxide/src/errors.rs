@@ -21,6 +21,9 @@ pub enum Error {
     WsTransport(#[from] tungstenite::Error),
     #[error("http error: {0:?}")]
     Http(#[from] http::Error),
+    #[cfg(feature = "client")]
+    #[error("http client error: {0:?}")]
+    HttpClient(#[from] hyper::Error),
     #[error("internal channel error: {0:?}")]
     SendChannel(#[from] mpsc::error::TrySendError<Packet>),
     #[error("internal channel error: {0:?}")]
@@ -2,7 +2,10 @@ use crate::body::ResponseBody;
 use crate::errors::Error;
 use bytes::Bytes;
 use futures::ready;
-use http::header::{CONNECTION, CONTENT_LENGTH, CONTENT_TYPE, SEC_WEBSOCKET_ACCEPT, UPGRADE};
+use http::header::{
+    CONNECTION, CONTENT_LENGTH, CONTENT_TYPE, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_EXTENSIONS,
+    UPGRADE,
+};
 use http::{HeaderValue, Response, StatusCode};
 use http_body::{Body, Full};
 use pin_project::pin_project;
@@ -35,16 +38,22 @@ where
     .body(ResponseBody::custom_response(Full::new(body)))
 }
 
-/// Create a response for websocket upgrade
-pub fn ws_response<B>(ws_key: &HeaderValue) -> Result<Response<ResponseBody<B>>, http::Error> {
+/// Create a response for websocket upgrade, with the websocket extensions accepted by the server
+pub fn ws_response<B>(
+    ws_key: &HeaderValue,
+    extensions: Option<HeaderValue>,
+) -> Result<Response<ResponseBody<B>>, http::Error> {
     let derived = derive_accept_key(ws_key.as_bytes());
     let sec = derived.parse::<HeaderValue>().unwrap();
-    Response::builder()
+    let mut res = Response::builder()
         .status(StatusCode::SWITCHING_PROTOCOLS)
         .header(UPGRADE, HeaderValue::from_static("websocket"))
         .header(CONNECTION, HeaderValue::from_static("Upgrade"))
-        .header(SEC_WEBSOCKET_ACCEPT, sec)
-        .body(ResponseBody::empty_response())
+        .header(SEC_WEBSOCKET_ACCEPT, sec);
+    if let Some(extensions) = extensions {
+        res = res.header(SEC_WEBSOCKET_EXTENSIONS, extensions);
+    }
+    res.body(ResponseBody::empty_response())
 }
 
 #[pin_project]
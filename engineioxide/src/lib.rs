@@ -1,14 +1,17 @@
 pub use async_trait::async_trait;
 
 /// A Packet type to use when sending data to the client
-pub use socket::{DisconnectReason, Socket, SocketReq};
+pub use socket::{DisconnectReason, Permit, Socket, SocketReq};
 #[cfg(not(any(feature = "v3", feature = "v4")))]
 compile_error!("At least one protocol version must be enabled");
 
+#[cfg(feature = "client")]
+pub mod client;
 pub mod config;
 pub mod errors;
 pub mod handler;
 pub mod layer;
+pub mod packet;
 pub mod service;
 pub mod sid;
 pub mod socket;
@@ -18,6 +21,5 @@ pub use service::ProtocolVersion;
 mod body;
 mod engine;
 mod futures;
-mod packet;
 mod peekable;
 mod transport;
@@ -1,4 +1,8 @@
+//! Engine.io packets sent and received over the polling and websocket transports.
+use std::sync::Arc;
+
 use base64::{engine::general_purpose, Engine};
+use bytes::Bytes;
 use serde::{de::Error, Deserialize, Serialize};
 
 use crate::config::EngineIoConfig;
@@ -47,15 +51,38 @@ pub enum Packet {
     ///
     /// This is a special packet, excepionally specific to the V3 protocol.
     BinaryV3(Vec<u8>), // Not part of the protocol, used internally
+
+    /// Message packet shared between multiple sockets, for example when broadcasting
+    /// It is serialized like a [`Packet::Message`] and is never received from the client
+    SharedMessage(Arc<str>),
+    /// Binary packet shared between multiple sockets, for example when broadcasting
+    /// It is serialized like a [`Packet::Binary`] and is never received from the client
+    SharedBinary(Bytes),
+    /// Binary packet shared between multiple sockets, for example when broadcasting
+    /// It is serialized like a [`Packet::BinaryV3`] and is never received from the client
+    SharedBinaryV3(Bytes),
 }
 
 impl Packet {
     /// Check if the packet is a binary packet
     pub fn is_binary(&self) -> bool {
-        matches!(self, Packet::Binary(_) | Packet::BinaryV3(_))
+        matches!(
+            self,
+            Packet::Binary(_)
+                | Packet::BinaryV3(_)
+                | Packet::SharedBinary(_)
+                | Packet::SharedBinaryV3(_)
+        )
+    }
+
+    /// Check if the packet is a message packet (text or binary) sent by the user,
+    /// only these packets are limited by the size of the buffer of a socket
+    pub(crate) fn is_message(&self) -> bool {
+        matches!(self, Packet::Message(_) | Packet::SharedMessage(_)) || self.is_binary()
     }
 
     /// If the packet is a message packet (text), it returns the message
+    #[cfg(feature = "client")]
     pub(crate) fn into_message(self) -> String {
         match self {
             Packet::Message(msg) => msg,
@@ -64,6 +91,7 @@ impl Packet {
     }
 
     /// If the packet is a binary packet, it returns the binary data
+    #[cfg(feature = "client")]
     pub(crate) fn into_binary(self) -> Vec<u8> {
         match self {
             Packet::Binary(data) => data,
@@ -72,6 +100,15 @@ impl Packet {
         }
     }
 
+    /// Get the binary data of the packet, owned or shared
+    pub(crate) fn binary_data(&self) -> Option<&[u8]> {
+        match self {
+            Packet::Binary(data) | Packet::BinaryV3(data) => Some(data),
+            Packet::SharedBinary(data) | Packet::SharedBinaryV3(data) => Some(data),
+            _ => None,
+        }
+    }
+
     /// Get the max size the packet could have when serialized
     ///
     ///  If b64 is true, it returns the max size when serialized to base64
@@ -86,16 +123,19 @@ impl Packet {
             Packet::PingUpgrade => 6,
             Packet::PongUpgrade => 6,
             Packet::Message(msg) => 1 + msg.len(),
+            Packet::SharedMessage(msg) => 1 + msg.len(),
             Packet::Upgrade => 1,
             Packet::Noop => 1,
-            Packet::Binary(data) => {
+            Packet::Binary(_) | Packet::SharedBinary(_) => {
+                let data = self.binary_data().unwrap();
                 if b64 {
                     1 + ((data.len() as f64) / 3.).ceil() as usize * 4
                 } else {
                     1 + data.len()
                 }
             }
-            Packet::BinaryV3(data) => {
+            Packet::BinaryV3(_) | Packet::SharedBinaryV3(_) => {
+                let data = self.binary_data().unwrap();
                 if b64 {
                     2 + ((data.len() as f64) / 3.).ceil() as usize * 4
                 } else {
@@ -120,10 +160,15 @@ impl TryInto<String> for Packet {
             Packet::PingUpgrade => "2probe".to_string(),
             Packet::PongUpgrade => "3probe".to_string(),
             Packet::Message(msg) => "4".to_string() + &msg,
+            Packet::SharedMessage(msg) => "4".to_string() + &msg,
             Packet::Upgrade => "5".to_string(),
             Packet::Noop => "6".to_string(),
             Packet::Binary(data) => "b".to_string() + &general_purpose::STANDARD.encode(data),
             Packet::BinaryV3(data) => "b4".to_string() + &general_purpose::STANDARD.encode(data),
+            Packet::SharedBinary(data) => "b".to_string() + &general_purpose::STANDARD.encode(data),
+            Packet::SharedBinaryV3(data) => {
+                "b4".to_string() + &general_purpose::STANDARD.encode(data)
+            }
         };
         Ok(res)
     }
@@ -181,11 +226,11 @@ impl TryFrom<String> for Packet {
 #[derive(Debug, Serialize, Deserialize, PartialEq, PartialOrd)]
 #[serde(rename_all = "camelCase")]
 pub struct OpenPacket {
-    sid: Sid,
-    upgrades: Vec<String>,
-    ping_interval: u64,
-    ping_timeout: u64,
-    max_payload: u64,
+    pub(crate) sid: Sid,
+    pub(crate) upgrades: Vec<String>,
+    pub(crate) ping_interval: u64,
+    pub(crate) ping_timeout: u64,
+    pub(crate) max_payload: u64,
 }
 
 impl OpenPacket {
@@ -285,6 +330,23 @@ mod tests {
         assert_eq!(packet, Packet::BinaryV3(vec![1, 2, 3]));
     }
 
+    #[test]
+    fn test_shared_packets() {
+        let packet = Packet::SharedMessage("hello".into());
+        let packet_str: String = packet.try_into().unwrap();
+        assert_eq!(packet_str, "4hello");
+
+        let packet = Packet::SharedBinary(Bytes::from_static(&[1, 2, 3]));
+        assert!(packet.is_binary());
+        assert_eq!(packet.get_size_hint(true), 5);
+        let packet_str: String = packet.try_into().unwrap();
+        assert_eq!(packet_str, "bAQID");
+
+        let packet = Packet::SharedBinaryV3(Bytes::from_static(&[1, 2, 3]));
+        let packet_str: String = packet.try_into().unwrap();
+        assert_eq!(packet_str, "b4AQID");
+    }
+
     #[test]
     fn test_packet_get_size_hint() {
         // Max serialized packet
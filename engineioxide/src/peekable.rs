@@ -1,42 +1,251 @@
-use tokio::sync::mpsc::{error::TryRecvError, Receiver};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::{
+    mpsc::{error::TryRecvError, Receiver},
+    Semaphore,
+};
+
+use crate::packet::Packet;
+
+/// The number of message packets that can be buffered for a socket, shared between the socket and its [`PeekableReceiver`]
+///
+/// The message packets are buffered in groups that are sent or dropped together,
+/// for example a socket.io packet followed by its binary attachments.
+///
+/// The control packets (ping, close...) are not counted so they are never blocked by a full buffer
+#[derive(Debug)]
+pub struct MessageBuffer {
+    /// A permit for each message packet that can still be buffered
+    pub permits: Semaphore,
+    /// The number of message packets that can be buffered
+    size: usize,
+    /// The groups of message packets waiting in the channel, the oldest first
+    groups: Mutex<VecDeque<Group>>,
+}
+
+/// A group of message packets waiting in the channel
+#[derive(Debug)]
+struct Group {
+    len: usize,
+    /// The number of message packets of the group already received
+    received: usize,
+    /// If the group was dropped to make room for a new one,
+    /// its message packets are discarded by the receiver instead of being sent
+    dropped: bool,
+    /// If the message packets of the group can be compressed by the websocket transport
+    compress: bool,
+}
+
+impl MessageBuffer {
+    pub fn new(size: usize) -> Self {
+        Self {
+            permits: Semaphore::new(size),
+            size,
+            groups: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// The number of message packets that can be buffered
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Take `count` places in the buffer if they are available
+    pub(crate) fn try_acquire(&self, count: usize) -> bool {
+        u32::try_from(count)
+            .ok()
+            .and_then(|count| self.permits.try_acquire_many(count).ok())
+            .map(|permit| permit.forget())
+            .is_some()
+    }
+
+    /// Register a group of `len` message packets that will be sent to the channel
+    pub(crate) fn push_group(&self, len: usize) {
+        if len > 0 {
+            self.groups.lock().unwrap().push_back(Group {
+                len,
+                received: 0,
+                dropped: false,
+                compress: true,
+            });
+        }
+    }
+
+    /// Set if the message packets of the last group can be compressed by the websocket transport
+    pub(crate) fn compress_last_group(&self, compress: bool) {
+        if let Some(group) = self.groups.lock().unwrap().back_mut() {
+            group.compress = compress;
+        }
+    }
+
+    /// Remove the `unsent` places reserved for the last group that were not used
+    pub(crate) fn truncate_last_group(&self, unsent: usize) {
+        let mut groups = self.groups.lock().unwrap();
+        if let Some(group) = groups.back_mut() {
+            group.len -= unsent;
+            if group.received == group.len {
+                groups.pop_back();
+            }
+        }
+        drop(groups);
+        self.permits.add_permits(unsent);
+    }
+
+    /// Take `count` places in the buffer by dropping the oldest groups that the receiver did not start to read.
+    /// Returns `false` without dropping anything if it is not possible.
+    pub(crate) fn drop_oldest(&self, count: usize) -> bool {
+        let mut groups = self.groups.lock().unwrap();
+        let mut freed = 0;
+        let mut oldest = Vec::new();
+        for (i, group) in groups.iter().enumerate() {
+            if freed + self.permits.available_permits() >= count {
+                break;
+            }
+            if !group.dropped && group.received == 0 {
+                oldest.push(i);
+                freed += group.len;
+            }
+        }
+        // The places of the dropped groups are taken over, the missing ones are taken from the buffer
+        if freed < count && !self.try_acquire(count - freed) {
+            return false;
+        }
+        for i in oldest {
+            groups[i].dropped = true;
+        }
+        self.permits.add_permits(freed.saturating_sub(count));
+        true
+    }
+
+    /// Release the place of a message packet leaving the buffer.
+    /// Returns `None` if the packet was dropped and should be discarded,
+    /// otherwise if it can be compressed.
+    fn release(&self, packet: &Packet) -> Option<bool> {
+        if !packet.is_message() {
+            return Some(true);
+        }
+        let mut groups = self.groups.lock().unwrap();
+        let (dropped, compress) = match groups.front_mut() {
+            Some(group) => {
+                group.received += 1;
+                let group_state = (group.dropped, group.compress);
+                if group.received == group.len {
+                    groups.pop_front();
+                }
+                group_state
+            }
+            None => (false, true),
+        };
+        drop(groups);
+        if dropped {
+            None
+        } else {
+            self.permits.add_permits(1);
+            Some(compress)
+        }
+    }
+}
 
 /// Peekable receiver for polling transport
 /// It is a thin wrapper around a [`Receiver`](tokio::sync::mpsc::Receiver) that allows to peek the next packet without consuming it
 ///
 /// Its main goal is to be able to peek the next packet without consuming it to calculate the
 /// packet length when using polling transport to check if it fits according to the max_payload setting
+///
+/// If it has a [`MessageBuffer`], the message packets received free their place in the buffer
+/// and the dropped message packets are skipped.
 #[derive(Debug)]
-pub struct PeekableReceiver<T> {
-    rx: Receiver<T>,
-    next: Option<T>,
+pub struct PeekableReceiver {
+    rx: Receiver<Packet>,
+    /// The peeked packet and if it can be compressed
+    next: Option<(Packet, bool)>,
+    buffer: Option<Arc<MessageBuffer>>,
+    /// If the last packet received can be compressed
+    compress: bool,
 }
-impl<T> PeekableReceiver<T> {
-    pub fn new(rx: Receiver<T>) -> Self {
-        Self { rx, next: None }
+impl PeekableReceiver {
+    #[cfg(any(feature = "client", test))]
+    pub fn new(rx: Receiver<Packet>) -> Self {
+        Self {
+            rx,
+            next: None,
+            buffer: None,
+            compress: true,
+        }
     }
-    pub fn peek(&mut self) -> Option<&T> {
-        if self.next.is_none() {
-            self.next = self.rx.try_recv().ok();
+    pub fn with_buffer(rx: Receiver<Packet>, buffer: Arc<MessageBuffer>) -> Self {
+        Self {
+            rx,
+            next: None,
+            buffer: Some(buffer),
+            compress: true,
         }
-        self.next.as_ref()
     }
-    pub async fn recv(&mut self) -> Option<T> {
-        if self.next.is_none() {
-            self.rx.recv().await
-        } else {
-            self.next.take()
+    /// Check if a packet received from the channel should be yielded and if it can be compressed
+    fn accept(&self, packet: &Packet) -> Option<bool> {
+        self.buffer
+            .as_ref()
+            .map_or(Some(true), |b| b.release(packet))
+    }
+    fn try_recv_inner(&mut self) -> Result<(Packet, bool), TryRecvError> {
+        loop {
+            let packet = self.rx.try_recv()?;
+            if let Some(compress) = self.accept(&packet) {
+                return Ok((packet, compress));
+            }
         }
     }
-    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+    /// Yield a packet and remember if it can be compressed
+    fn yield_packet(&mut self, (packet, compress): (Packet, bool)) -> Packet {
+        self.compress = compress;
+        packet
+    }
+    pub fn peek(&mut self) -> Option<&Packet> {
         if self.next.is_none() {
-            self.rx.try_recv()
-        } else {
-            Ok(self.next.take().unwrap())
+            self.next = self.try_recv_inner().ok();
+        }
+        self.next.as_ref().map(|(packet, _)| packet)
+    }
+    pub async fn recv(&mut self) -> Option<Packet> {
+        if let Some(next) = self.next.take() {
+            return Some(self.yield_packet(next));
         }
+        loop {
+            let packet = self.rx.recv().await?;
+            if let Some(compress) = self.accept(&packet) {
+                return Some(self.yield_packet((packet, compress)));
+            }
+        }
+    }
+    pub fn try_recv(&mut self) -> Result<Packet, TryRecvError> {
+        let next = match self.next.take() {
+            Some(next) => next,
+            None => self.try_recv_inner()?,
+        };
+        Ok(self.yield_packet(next))
+    }
+    /// If the last packet received can be compressed by the websocket transport
+    pub fn compress(&self) -> bool {
+        self.compress
     }
 
+    /// Close the channel, the senders waiting for a place in the buffer are notified
     pub fn close(&mut self) {
-        self.rx.close()
+        self.rx.close();
+        if let Some(buffer) = &self.buffer {
+            buffer.permits.close();
+        }
+    }
+}
+
+impl Drop for PeekableReceiver {
+    fn drop(&mut self) {
+        if let Some(buffer) = &self.buffer {
+            buffer.permits.close();
+        }
     }
 }
 
@@ -44,6 +253,81 @@ impl<T> PeekableReceiver<T> {
 mod tests {
     use tokio::sync::Mutex;
 
+    #[tokio::test]
+    async fn dropped_groups() {
+        use super::{MessageBuffer, PeekableReceiver};
+        use crate::packet::Packet;
+        use std::sync::Arc;
+        use tokio::sync::mpsc::channel;
+
+        let (tx, rx) = channel(10);
+        let buffer = Arc::new(MessageBuffer::new(3));
+        let mut rx = PeekableReceiver::with_buffer(rx, buffer.clone());
+
+        assert!(buffer.try_acquire(2));
+        buffer.push_group(2);
+        tx.send(Packet::Message("1".into())).await.unwrap();
+        tx.send(Packet::Binary(vec![1])).await.unwrap();
+        assert!(buffer.try_acquire(1));
+        buffer.push_group(1);
+        tx.send(Packet::Message("2".into())).await.unwrap();
+        tx.send(Packet::Ping).await.unwrap();
+
+        // The receiver started to read the first group, so the second one is dropped instead
+        assert_eq!(rx.recv().await, Some(Packet::Message("1".into())));
+        assert!(!buffer.try_acquire(2));
+        assert!(buffer.drop_oldest(2));
+        assert_eq!(buffer.permits.available_permits(), 0);
+        buffer.push_group(2);
+        tx.send(Packet::Message("3".into())).await.unwrap();
+        tx.send(Packet::Binary(vec![3])).await.unwrap();
+
+        assert_eq!(rx.recv().await, Some(Packet::Binary(vec![1])));
+        assert_eq!(buffer.permits.available_permits(), 1);
+        assert_eq!(rx.peek(), Some(&Packet::Ping));
+        assert_eq!(rx.recv().await, Some(Packet::Ping));
+        assert_eq!(rx.recv().await, Some(Packet::Message("3".into())));
+        assert_eq!(rx.recv().await, Some(Packet::Binary(vec![3])));
+        assert_eq!(buffer.permits.available_permits(), 3);
+
+        rx.close();
+        assert!(buffer.permits.acquire().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn uncompressed_groups() {
+        use super::{MessageBuffer, PeekableReceiver};
+        use crate::packet::Packet;
+        use std::sync::Arc;
+        use tokio::sync::mpsc::channel;
+
+        let (tx, rx) = channel(10);
+        let buffer = Arc::new(MessageBuffer::new(3));
+        let mut rx = PeekableReceiver::with_buffer(rx, buffer.clone());
+
+        assert!(buffer.try_acquire(2));
+        buffer.push_group(2);
+        buffer.compress_last_group(false);
+        tx.send(Packet::Message("1".into())).await.unwrap();
+        tx.send(Packet::Binary(vec![1])).await.unwrap();
+        tx.send(Packet::Ping).await.unwrap();
+        assert!(buffer.try_acquire(1));
+        buffer.push_group(1);
+        tx.send(Packet::Message("2".into())).await.unwrap();
+
+        assert_eq!(rx.recv().await, Some(Packet::Message("1".into())));
+        assert!(!rx.compress());
+        // The flag follows the peeked packet
+        assert_eq!(rx.peek(), Some(&Packet::Binary(vec![1])));
+        assert!(!rx.compress());
+        assert_eq!(rx.try_recv(), Ok(Packet::Binary(vec![1])));
+        assert!(!rx.compress());
+        assert_eq!(rx.recv().await, Some(Packet::Ping));
+        assert!(rx.compress());
+        assert_eq!(rx.recv().await, Some(Packet::Message("2".into())));
+        assert!(rx.compress());
+    }
+
     #[tokio::test]
     async fn peek() {
         use super::PeekableReceiver;
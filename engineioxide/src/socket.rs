@@ -1,16 +1,21 @@
 use std::{
+    net::{IpAddr, SocketAddr},
     sync::{
-        atomic::{AtomicU8, Ordering},
+        atomic::{AtomicBool, AtomicU8, Ordering},
         Arc,
     },
     time::Duration,
 };
 
+use bytes::Bytes;
 use http::{request::Parts, Uri};
 use tokio::{
     sync::{
         mpsc::{self},
-        mpsc::{error::TrySendError, Receiver},
+        mpsc::{
+            error::{SendTimeoutError, TrySendError},
+            Receiver,
+        },
         Mutex,
     },
     task::JoinHandle,
@@ -18,7 +23,10 @@ use tokio::{
 use tokio_tungstenite::tungstenite;
 
 use crate::{
-    config::EngineIoConfig, errors::Error, packet::Packet, peekable::PeekableReceiver,
+    config::{EngineIoConfig, OverflowPolicy},
+    errors::Error,
+    packet::Packet,
+    peekable::{MessageBuffer, PeekableReceiver},
     service::ProtocolVersion,
 };
 use crate::{sid::Sid, transport::TransportType};
@@ -31,23 +39,140 @@ pub struct SocketReq {
 
     /// Request headers
     pub headers: http::HeaderMap,
+
+    /// The address of the remote peer of the connection.
+    ///
+    /// It is read from the request extensions, where it should be inserted as a [`SocketAddr`]
+    /// (e.g. from the [`AddrStream`](https://docs.rs/hyper/0.14/hyper/server/conn/struct.AddrStream.html)
+    /// of a hyper server) or as an axum `ConnectInfo<SocketAddr>` with the `axum` feature flag.
+    /// It is `None` if no address was provided.
+    pub remote_addr: Option<SocketAddr>,
+
+    /// The ip address of the client.
+    ///
+    /// If the remote peer is one of the [`trusted_proxies`](crate::config::EngineIoConfig::trusted_proxies),
+    /// it is the client address forwarded in the `Forwarded` or `X-Forwarded-For` headers.
+    /// Otherwise it is the ip of the [`remote_addr`](SocketReq::remote_addr).
+    pub client_ip: Option<IpAddr>,
+}
+
+impl SocketReq {
+    /// Resolve the [`client_ip`](SocketReq::client_ip) from the forwarding headers
+    /// if the remote peer is one of the trusted proxies.
+    ///
+    /// The forwarding chain is walked from the closest hop, skipping the trusted proxies.
+    /// If an address of the chain cannot be parsed, the client ip is left unchanged.
+    pub(crate) fn resolve_client_ip(&mut self, trusted_proxies: &[IpAddr]) {
+        let is_trusted = |ip: &IpAddr| trusted_proxies.contains(ip);
+        match self.remote_addr {
+            Some(addr) if is_trusted(&addr.ip()) => (),
+            _ => return,
+        }
+        let chain = match forwarded_chain(&self.headers) {
+            Some(chain) => chain,
+            None => return,
+        };
+
+        let mut client_ip = None;
+        for ip in chain.into_iter().rev() {
+            match ip {
+                Some(ip) if is_trusted(&ip) => client_ip = Some(ip),
+                Some(ip) => {
+                    client_ip = Some(ip);
+                    break;
+                }
+                None => return,
+            }
+        }
+        if client_ip.is_some() {
+            self.client_ip = client_ip;
+        }
+    }
+}
+
+/// Get the remote address of the connection from the request extensions.
+fn remote_addr(extensions: &http::Extensions) -> Option<SocketAddr> {
+    #[cfg(feature = "axum")]
+    if let Some(axum::extract::ConnectInfo(addr)) =
+        extensions.get::<axum::extract::ConnectInfo<SocketAddr>>()
+    {
+        return Some(*addr);
+    }
+    extensions.get::<SocketAddr>().copied()
+}
+
+/// Get the list of the forwarded addresses, from the client to the closest proxy.
+///
+/// The `Forwarded` header takes precedence over the `X-Forwarded-For` header.
+/// An address that cannot be parsed (e.g. an obfuscated identifier) is `None`.
+fn forwarded_chain(headers: &http::HeaderMap) -> Option<Vec<Option<IpAddr>>> {
+    let forwarded = headers.get_all(http::header::FORWARDED);
+    if forwarded.iter().next().is_some() {
+        let mut chain = Vec::new();
+        for value in forwarded {
+            for element in value.to_str().ok()?.split(',') {
+                let node = element.split(';').find_map(|pair| {
+                    let (key, value) = pair.split_once('=')?;
+                    key.trim()
+                        .eq_ignore_ascii_case("for")
+                        .then(|| value.trim().trim_matches('"'))
+                });
+                if let Some(node) = node {
+                    chain.push(parse_node(node));
+                }
+            }
+        }
+        return Some(chain);
+    }
+
+    let forwarded_for = headers.get_all("x-forwarded-for");
+    if forwarded_for.iter().next().is_some() {
+        let mut chain = Vec::new();
+        for value in forwarded_for {
+            for node in value.to_str().ok()?.split(',') {
+                chain.push(parse_node(node.trim()));
+            }
+        }
+        return Some(chain);
+    }
+    None
+}
+
+/// Parse a forwarded node: an ip address optionally followed by a port,
+/// with ipv6 addresses possibly enclosed in brackets.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    node.parse::<IpAddr>()
+        .or_else(|_| node.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .ok()
+        .or_else(|| {
+            node.strip_prefix('[')?
+                .strip_suffix(']')?
+                .parse::<IpAddr>()
+                .ok()
+        })
 }
 
 /// Convert a `Parts` struct to a `SocketReq` by cloning the fields.
 impl From<&Parts> for SocketReq {
     fn from(parts: &Parts) -> Self {
+        let remote_addr = remote_addr(&parts.extensions);
         Self {
             uri: parts.uri.clone(),
             headers: parts.headers.clone(),
+            remote_addr,
+            client_ip: remote_addr.map(|addr| addr.ip()),
         }
     }
 }
 /// Convert a `Parts` struct to a `SocketReq` by moving the fields.
 impl From<Parts> for SocketReq {
     fn from(parts: Parts) -> Self {
+        let remote_addr = remote_addr(&parts.extensions);
         Self {
             uri: parts.uri,
             headers: parts.headers,
+            remote_addr,
+            client_ip: remote_addr.map(|addr| addr.ip()),
         }
     }
 }
@@ -68,6 +193,9 @@ pub enum DisconnectReason {
     HeartbeatTimeout,
     /// The server is being closed
     ClosingServer,
+    /// The buffer of the socket was full with the [`OverflowPolicy::Disconnect`] policy,
+    /// the client did not read the messages fast enough
+    BufferOverflow,
 }
 
 /// Convert an [`Error`] to a [`DisconnectReason`] if possible
@@ -79,6 +207,8 @@ impl From<&Error> for Option<DisconnectReason> {
         match err {
             WsTransport(tungstenite::Error::ConnectionClosed) => None,
             WsTransport(_) | Io(_) => Some(DisconnectReason::TransportError),
+            #[cfg(feature = "client")]
+            HttpClient(_) => Some(DisconnectReason::TransportError),
             BadPacket(_) | Serialize(_) | Base64(_) | StrUtf8(_) | PayloadTooLarge
             | InvalidPacketLength => Some(DisconnectReason::PacketParsingError),
             HeartbeatTimeout => Some(DisconnectReason::HeartbeatTimeout),
@@ -107,6 +237,8 @@ where
     /// It is represented as a bitfield to allow the use of an [`AtomicU8`] so it can be shared between threads
     /// without any mutex
     transport: AtomicU8,
+    /// True while a polling request is waiting for packets to send to the client
+    pub(crate) poll_pending: AtomicBool,
 
     /// Channel to receive [`Packet`] from the connection
     ///
@@ -120,10 +252,17 @@ where
     /// * From the [encoder](crate::service::encoder) if the transport is polling
     /// * From the fn [`on_ws_req_init`](crate::engine::EngineIo) if the transport is websocket
     /// * Automatically via the [`close_session fn`](crate::engine::EngineIo::close_session) as a fallback. Because with polling transport, if the client is not currently polling then the encoder will never be able to close the channel
-    pub(crate) internal_rx: Mutex<PeekableReceiver<Packet>>,
+    pub(crate) internal_rx: Mutex<PeekableReceiver>,
 
     /// Channel to send [Packet] to the internal connection
     internal_tx: mpsc::Sender<Packet>,
+    /// The places left for message packets in the internal channel, shared with `internal_rx`
+    buffer: Arc<MessageBuffer>,
+    /// Held while a group of message packets is reserved and sent,
+    /// so that the groups are not interleaved and are sent in the order they are registered in the buffer
+    send_lock: std::sync::Mutex<()>,
+    /// The [`OverflowPolicy`] applied when the buffer is full, stored as a `u8`
+    overflow_policy: AtomicU8,
 
     /// Internal channel to receive Pong [`Packets`](Packet) (v4 protocol) or Ping (v3 protocol) in the heartbeat job
     /// which is running in a separate task
@@ -159,16 +298,20 @@ where
         close_fn: Box<dyn Fn(Sid, DisconnectReason) + Send + Sync>,
         #[cfg(feature = "v3")] supports_binary: bool,
     ) -> Self {
-        let (internal_tx, internal_rx) = mpsc::channel(config.max_buffer_size);
+        let (internal_tx, internal_rx, buffer) = buffer_channel(config.max_buffer_size);
         let (heartbeat_tx, heartbeat_rx) = mpsc::channel(1);
 
         Self {
             id: Sid::new(),
             protocol,
             transport: AtomicU8::new(transport as u8),
+            poll_pending: AtomicBool::new(false),
 
-            internal_rx: Mutex::new(PeekableReceiver::new(internal_rx)),
+            internal_rx: Mutex::new(internal_rx),
             internal_tx,
+            buffer,
+            send_lock: std::sync::Mutex::new(()),
+            overflow_policy: AtomicU8::new(config.overflow_policy as u8),
 
             heartbeat_rx: Mutex::new(heartbeat_rx),
             heartbeat_tx,
@@ -198,6 +341,110 @@ where
         Ok(())
     }
 
+    /// Reserves a place in the internal channel for each of the `count` message packets of a group,
+    /// so that they are either all sent or not at all
+    fn reserve_slots(
+        &self,
+        count: usize,
+    ) -> Result<Vec<mpsc::Permit<'_, Packet>>, TrySendError<()>> {
+        (0..count).map(|_| self.internal_tx.try_reserve()).collect()
+    }
+
+    /// Reserves `count` places in the buffer to emit a group of messages that are sent or dropped together,
+    /// for example a socket.io packet followed by its binary attachments.
+    ///
+    /// If the buffer is full, the [`OverflowPolicy`] of the socket is applied to the whole group.
+    /// When the group is dropped, the returned [`Permit`] has no place and the messages emitted with it are dropped.
+    ///
+    /// ⚠️ If the buffer is full with the [`OverflowPolicy::Error`] or [`OverflowPolicy::Disconnect`] policy,
+    /// or if the socket is disconnected, an error is returned
+    pub fn reserve(&self, count: usize) -> Result<Permit<'_, D>, TrySendError<()>> {
+        let lock = self.send_lock.lock().unwrap();
+        let policy = self.overflow_policy();
+        let reserved = match self.reserve_slots(count) {
+            Ok(slots) if self.buffer.try_acquire(count) => Some(slots),
+            Ok(slots) if policy == OverflowPolicy::DropOldest && self.buffer.drop_oldest(count) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(
+                    "[sid={}] buffer full, dropping the oldest messages",
+                    self.id
+                );
+                Some(slots)
+            }
+            // The channel may also be full of dropped messages waiting to be skipped by the receiver
+            Ok(_) | Err(TrySendError::Full(())) => None,
+            Err(e) => return Err(e),
+        };
+        if let Some(slots) = reserved {
+            self.buffer.push_group(count);
+            return Ok(Permit::new(self, slots, lock));
+        }
+        drop(lock);
+
+        match policy {
+            OverflowPolicy::Error => Err(TrySendError::Full(())),
+            OverflowPolicy::DropNewest | OverflowPolicy::DropOldest => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("[sid={}] buffer full, dropping the new messages", self.id);
+                Ok(Permit::new(self, Vec::new(), None))
+            }
+            OverflowPolicy::Disconnect => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("[sid={}] buffer full, closing the socket", self.id);
+                self.close(DisconnectReason::BufferOverflow);
+                Err(TrySendError::Full(()))
+            }
+        }
+    }
+
+    /// Reserves `count` places in the buffer to emit a group of messages that are sent together,
+    /// waiting for the places if the buffer is full.
+    ///
+    /// If the `timeout` elapses before the places are available, a [`SendTimeoutError::Timeout`] is returned.
+    /// A group larger than the buffer can never be sent, a [`SendTimeoutError::Timeout`] is returned right away.
+    pub async fn reserve_async(
+        &self,
+        count: usize,
+        timeout: Option<Duration>,
+    ) -> Result<Permit<'_, D>, SendTimeoutError<()>> {
+        let places = match u32::try_from(count) {
+            Ok(places) if count <= self.buffer.size() => places,
+            _ => return Err(SendTimeoutError::Timeout(())),
+        };
+        let acquire = self.buffer.permits.acquire_many(places);
+        let places = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, acquire).await {
+                Ok(places) => places,
+                Err(_) => return Err(SendTimeoutError::Timeout(())),
+            },
+            None => acquire.await,
+        };
+        // The semaphore is closed with the internal channel
+        let places = places.map_err(|_| SendTimeoutError::Closed(()))?;
+
+        let lock = self.send_lock.lock().unwrap();
+        // The channel has room for the control packets and the dropped messages on top of the buffer
+        let slots = self.reserve_slots(count).map_err(|e| match e {
+            TrySendError::Full(()) => SendTimeoutError::Timeout(()),
+            TrySendError::Closed(()) => SendTimeoutError::Closed(()),
+        })?;
+        places.forget();
+        self.buffer.push_group(count);
+        Ok(Permit::new(self, slots, lock))
+    }
+
+    /// Returns the [`OverflowPolicy`] applied when a message is emitted while the buffer is full
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow_policy.load(Ordering::Relaxed).into()
+    }
+
+    /// Sets the [`OverflowPolicy`] applied when a message is emitted while the buffer of this socket is full.
+    ///
+    /// It defaults to the [`overflow_policy`](crate::config::EngineIoConfig::overflow_policy) of the config.
+    pub fn set_overflow_policy(&self, policy: OverflowPolicy) {
+        self.overflow_policy.store(policy as u8, Ordering::Relaxed);
+    }
+
     /// Spawn the heartbeat job
     ///
     /// Keep a handle to the job so that it can be aborted when the socket is closed
@@ -307,6 +554,16 @@ where
         }
     }
 
+    /// Returns the current [`TransportType`] of the [`Socket`],
+    /// it changes from polling to websocket when the connection is upgraded
+    pub fn transport_type(&self) -> TransportType {
+        if self.is_ws() {
+            TransportType::Websocket
+        } else {
+            TransportType::Polling
+        }
+    }
+
     /// Returns true if the [`Socket`] has a websocket [`TransportType`]
     pub(crate) fn is_ws(&self) -> bool {
         self.transport.load(Ordering::Relaxed) == TransportType::Websocket as u8
@@ -329,12 +586,18 @@ where
     ///
     /// If the transport is in polling mode, the message is buffered and sent as a text frame to the next polling request.
     ///
-    /// ⚠️ If the buffer is full or the socket is disconnected, an error will be returned with the original data
+    /// If the buffer is full, the [`OverflowPolicy`] of the socket is applied.
+    ///
+    /// ⚠️ If the buffer is full with the [`OverflowPolicy::Error`] or [`OverflowPolicy::Disconnect`] policy,
+    /// or if the socket is disconnected, an error will be returned with the original data
     pub fn emit(&self, msg: String) -> Result<(), TrySendError<String>> {
-        self.send(Packet::Message(msg)).map_err(|e| match e {
-            TrySendError::Full(p) => TrySendError::Full(p.into_message()),
-            TrySendError::Closed(p) => TrySendError::Closed(p.into_message()),
-        })
+        match self.reserve(1) {
+            Ok(mut permit) => {
+                permit.emit(msg);
+                Ok(())
+            }
+            Err(e) => Err(with_data(e, msg)),
+        }
     }
 
     /// Immediately closes the socket and the underlying connection.
@@ -358,20 +621,237 @@ where
     ///
     /// If the transport is in polling mode, the message is buffered and sent as a text frame **encoded in base64** to the next polling request.
     ///
-    /// ⚠️ If the buffer is full or the socket is disconnected, an error will be returned with the original data
+    /// If the buffer is full, the [`OverflowPolicy`] of the socket is applied.
+    ///
+    /// ⚠️ If the buffer is full with the [`OverflowPolicy::Error`] or [`OverflowPolicy::Disconnect`] policy,
+    /// or if the socket is disconnected, an error will be returned with the original data
     pub fn emit_binary(&self, data: Vec<u8>) -> Result<(), TrySendError<Vec<u8>>> {
-        if self.protocol == ProtocolVersion::V3 {
+        match self.reserve(1) {
+            Ok(mut permit) => {
+                permit.emit_binary(data);
+                Ok(())
+            }
+            Err(e) => Err(with_data(e, data)),
+        }
+    }
+
+    /// Emits a message shared with other sockets to the client, without copying it.
+    ///
+    /// It behaves like [`Socket::emit`], the message is only copied when it is written to the transport.
+    ///
+    /// ⚠️ If the buffer is full with the [`OverflowPolicy::Error`] or [`OverflowPolicy::Disconnect`] policy,
+    /// or if the socket is disconnected, an error will be returned with the original data
+    pub fn emit_shared(&self, msg: Arc<str>) -> Result<(), TrySendError<Arc<str>>> {
+        match self.reserve(1) {
+            Ok(mut permit) => {
+                permit.emit_shared(msg);
+                Ok(())
+            }
+            Err(e) => Err(with_data(e, msg)),
+        }
+    }
+
+    /// Emits a binary message shared with other sockets to the client, without copying it.
+    ///
+    /// It behaves like [`Socket::emit_binary`], the data is only copied when it is written to the transport.
+    ///
+    /// ⚠️ If the buffer is full with the [`OverflowPolicy::Error`] or [`OverflowPolicy::Disconnect`] policy,
+    /// or if the socket is disconnected, an error will be returned with the original data
+    pub fn emit_shared_binary(&self, data: Bytes) -> Result<(), TrySendError<Bytes>> {
+        match self.reserve(1) {
+            Ok(mut permit) => {
+                permit.emit_shared_binary(data);
+                Ok(())
+            }
+            Err(e) => Err(with_data(e, data)),
+        }
+    }
+
+    /// Emits a message to the client, waiting for a place in the buffer if it is full.
+    ///
+    /// If the `timeout` elapses before a place is available, a [`SendTimeoutError::Timeout`] is returned with the original data.
+    /// Without timeout, it waits until the client reads the buffered messages or the socket is closed.
+    pub async fn emit_async(
+        &self,
+        msg: String,
+        timeout: Option<Duration>,
+    ) -> Result<(), SendTimeoutError<String>> {
+        match self.reserve_async(1, timeout).await {
+            Ok(mut permit) => {
+                permit.emit(msg);
+                Ok(())
+            }
+            Err(e) => Err(with_data_timeout(e, msg)),
+        }
+    }
+
+    /// Emits a binary message to the client, waiting for a place in the buffer if it is full.
+    ///
+    /// If the `timeout` elapses before a place is available, a [`SendTimeoutError::Timeout`] is returned with the original data.
+    /// Without timeout, it waits until the client reads the buffered messages or the socket is closed.
+    pub async fn emit_binary_async(
+        &self,
+        data: Vec<u8>,
+        timeout: Option<Duration>,
+    ) -> Result<(), SendTimeoutError<Vec<u8>>> {
+        match self.reserve_async(1, timeout).await {
+            Ok(mut permit) => {
+                permit.emit_binary(data);
+                Ok(())
+            }
+            Err(e) => Err(with_data_timeout(e, data)),
+        }
+    }
+
+    /// Returns true if a message emitted now would be written to the transport without waiting:
+    /// the socket is upgraded to websocket or a polling request is pending, and its buffer is not full.
+    pub fn is_writable(&self) -> bool {
+        (self.is_ws() || self.poll_pending.load(Ordering::Relaxed))
+            && self.buffer.permits.available_permits() > 0
+    }
+
+    /// Reserves `count` places in the buffer to emit volatile messages, that are dropped rather than
+    /// buffered or returned as errors when the transport is not writable.
+    ///
+    /// Returns `None` if the transport is not [writable](Socket::is_writable) or if there is not
+    /// enough places in the buffer, so that the messages of a [`Permit`] are either all sent or all dropped.
+    pub fn try_reserve_volatile(&self, count: usize) -> Option<Permit<'_, D>> {
+        if !self.is_ws() && !self.poll_pending.load(Ordering::Relaxed) {
+            return None;
+        }
+        let lock = self.send_lock.lock().unwrap();
+        let slots = self.reserve_slots(count).ok()?;
+        if !self.buffer.try_acquire(count) {
+            return None;
+        }
+        self.buffer.push_group(count);
+        Some(Permit::new(self, slots, lock))
+    }
+}
+
+/// Put the data of an emitted message back in the error of its [`Socket::reserve`] call
+fn with_data<T>(err: TrySendError<()>, data: T) -> TrySendError<T> {
+    match err {
+        TrySendError::Full(()) => TrySendError::Full(data),
+        TrySendError::Closed(()) => TrySendError::Closed(data),
+    }
+}
+
+/// Put the data of an emitted message back in the error of its [`Socket::reserve_async`] call
+fn with_data_timeout<T>(err: SendTimeoutError<()>, data: T) -> SendTimeoutError<T> {
+    match err {
+        SendTimeoutError::Timeout(()) => SendTimeoutError::Timeout(data),
+        SendTimeoutError::Closed(()) => SendTimeoutError::Closed(data),
+    }
+}
+
+/// Places reserved in the buffer of a [`Socket`] for a group of messages,
+/// with [`Socket::reserve`], [`Socket::reserve_async`] or [`Socket::try_reserve_volatile`].
+///
+/// Each emitted message takes one place, a message emitted once all the places are taken is dropped.
+/// The places left are released when the permit is dropped.
+/// Other groups can't be emitted to the socket until then, so the permit should be dropped right after use.
+pub struct Permit<'a, D>
+where
+    D: Default + Send + Sync + 'static,
+{
+    socket: &'a Socket<D>,
+    slots: Vec<mpsc::Permit<'a, Packet>>,
+    _lock: Option<std::sync::MutexGuard<'a, ()>>,
+}
+
+impl<'a, D> Permit<'a, D>
+where
+    D: Default + Send + Sync + 'static,
+{
+    fn new(
+        socket: &'a Socket<D>,
+        slots: Vec<mpsc::Permit<'a, Packet>>,
+        lock: impl Into<Option<std::sync::MutexGuard<'a, ()>>>,
+    ) -> Self {
+        Self {
+            socket,
+            slots,
+            _lock: lock.into(),
+        }
+    }
+
+    /// Sets if the messages of this permit can be compressed when the websocket transport
+    /// negotiated the permessage-deflate extension. They can be compressed by default.
+    pub fn compress(&mut self, compress: bool) {
+        if !self.slots.is_empty() {
+            self.socket.buffer.compress_last_group(compress);
+        }
+    }
+
+    /// Emits a message to the client, see [`Socket::emit`]
+    pub fn emit(&mut self, msg: String) {
+        self.send(Packet::Message(msg))
+    }
+
+    /// Emits a binary message to the client, see [`Socket::emit_binary`]
+    pub fn emit_binary(&mut self, data: Vec<u8>) {
+        if self.socket.protocol == ProtocolVersion::V3 {
             self.send(Packet::BinaryV3(data))
         } else {
             self.send(Packet::Binary(data))
         }
-        .map_err(|e| match e {
-            TrySendError::Full(p) => TrySendError::Full(p.into_binary()),
-            TrySendError::Closed(p) => TrySendError::Closed(p.into_binary()),
-        })
+    }
+
+    /// Emits a message shared with other sockets to the client, see [`Socket::emit_shared`]
+    pub fn emit_shared(&mut self, msg: Arc<str>) {
+        self.send(Packet::SharedMessage(msg))
+    }
+
+    /// Emits a binary message shared with other sockets to the client, see [`Socket::emit_shared_binary`]
+    pub fn emit_shared_binary(&mut self, data: Bytes) {
+        if self.socket.protocol == ProtocolVersion::V3 {
+            self.send(Packet::SharedBinaryV3(data))
+        } else {
+            self.send(Packet::SharedBinary(data))
+        }
+    }
+
+    fn send(&mut self, packet: Packet) {
+        if let Some(slot) = self.slots.pop() {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("[sid={}] sending packet: {:?}", self.socket.id, packet);
+            slot.send(packet);
+        } else {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                "[sid={}] no place reserved, dropping message",
+                self.socket.id
+            );
+        }
     }
 }
 
+impl<'a, D> Drop for Permit<'a, D>
+where
+    D: Default + Send + Sync + 'static,
+{
+    fn drop(&mut self) {
+        if !self.slots.is_empty() {
+            self.socket.buffer.truncate_last_group(self.slots.len());
+        }
+    }
+}
+
+/// Create the internal channel of a socket, with a buffer of `size` message packets.
+///
+/// The channel itself is twice as large so that the control packets and the dropped messages
+/// waiting to be skipped by the receiver never block the buffer.
+fn buffer_channel(size: usize) -> (mpsc::Sender<Packet>, PeekableReceiver, Arc<MessageBuffer>) {
+    let (tx, rx) = mpsc::channel(size.saturating_mul(2));
+    let buffer = Arc::new(MessageBuffer::new(size));
+    (
+        tx,
+        PeekableReceiver::with_buffer(rx, buffer.clone()),
+        buffer,
+    )
+}
+
 impl<D: Default + Send + Sync + 'static> std::fmt::Debug for Socket<D> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Socket")
@@ -408,16 +888,20 @@ where
         sid: Sid,
         close_fn: Box<dyn Fn(Sid, DisconnectReason) + Send + Sync>,
     ) -> Socket<D> {
-        let (internal_tx, internal_rx) = mpsc::channel(200);
+        let (internal_tx, internal_rx, buffer) = buffer_channel(200);
         let (heartbeat_tx, heartbeat_rx) = mpsc::channel(1);
 
         Self {
             id: sid,
             protocol: ProtocolVersion::V4,
             transport: AtomicU8::new(TransportType::Websocket as u8),
+            poll_pending: AtomicBool::new(false),
 
-            internal_rx: Mutex::new(PeekableReceiver::new(internal_rx)),
+            internal_rx: Mutex::new(internal_rx),
             internal_tx,
+            buffer,
+            send_lock: std::sync::Mutex::new(()),
+            overflow_policy: AtomicU8::new(OverflowPolicy::Error as u8),
 
             heartbeat_rx: Mutex::new(heartbeat_rx),
             heartbeat_tx,
@@ -428,6 +912,8 @@ where
             req_data: SocketReq {
                 headers: http::HeaderMap::new(),
                 uri: Uri::default(),
+                remote_addr: None,
+                client_ip: None,
             }
             .into(),
 
@@ -435,4 +921,282 @@ where
             supports_binary: true,
         }
     }
+
+    /// Create a dummy socket connected to an in-memory transport instead of a http or websocket connection.
+    ///
+    /// The packets sent to the client are forwarded to the returned receiver until a [`Packet::Close`] is sent,
+    /// then the socket is closed.
+    pub fn new_dummy_piped(
+        sid: Sid,
+        close_fn: Box<dyn Fn(Sid, DisconnectReason) + Send + Sync>,
+        buffer_size: usize,
+    ) -> (Arc<Socket<D>>, Receiver<Packet>) {
+        let (tx, rx) = mpsc::channel(buffer_size);
+        let socket = Arc::new(Socket::new_dummy(sid, close_fn));
+
+        let sock = socket.clone();
+        tokio::spawn(async move {
+            let mut internal_rx = sock.internal_rx.lock().await;
+            while let Some(packet) = internal_rx.recv().await {
+                let close = packet == Packet::Close;
+                if tx.send(packet).await.is_err() || close {
+                    break;
+                }
+            }
+            internal_rx.close();
+        });
+        (socket, rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn socket_req(peer: &str, headers: &[(&'static str, &str)]) -> SocketReq {
+        let mut req = http::Request::builder();
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        let mut parts = req.body(()).unwrap().into_parts().0;
+        parts.extensions.insert(peer.parse::<SocketAddr>().unwrap());
+        SocketReq::from(parts)
+    }
+
+    #[test]
+    fn client_ip_untrusted_peer() {
+        let mut req = socket_req("192.0.2.1:1234", &[("x-forwarded-for", "203.0.113.7")]);
+        req.resolve_client_ip(&["10.0.0.1".parse().unwrap()]);
+        assert_eq!(req.remote_addr, Some("192.0.2.1:1234".parse().unwrap()));
+        assert_eq!(req.client_ip, Some("192.0.2.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn client_ip_forwarded_chain() {
+        let proxies = ["10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap()];
+
+        let mut req = socket_req(
+            "10.0.0.1:1234",
+            &[("x-forwarded-for", "198.51.100.2, 203.0.113.7, 10.0.0.2")],
+        );
+        req.resolve_client_ip(&proxies);
+        assert_eq!(req.client_ip, Some("203.0.113.7".parse().unwrap()));
+
+        let mut req = socket_req(
+            "10.0.0.1:1234",
+            &[("forwarded", "for=192.0.2.43:4711, for=\"[2001:db8::1]\"")],
+        );
+        req.resolve_client_ip(&proxies);
+        assert_eq!(req.client_ip, Some("2001:db8::1".parse().unwrap()));
+
+        // Only trusted proxies: the farthest one is the client
+        let mut req = socket_req("10.0.0.1:1234", &[("x-forwarded-for", "10.0.0.2")]);
+        req.resolve_client_ip(&proxies);
+        assert_eq!(req.client_ip, Some("10.0.0.2".parse().unwrap()));
+
+        // An obfuscated node cannot be resolved: the peer address is kept
+        let mut req = socket_req(
+            "10.0.0.1:1234",
+            &[("forwarded", "for=_hidden, for=10.0.0.2")],
+        );
+        req.resolve_client_ip(&proxies);
+        assert_eq!(req.client_ip, Some("10.0.0.1".parse().unwrap()));
+    }
+
+    /// Create a socket with a buffer of 2 messages, the reasons it is closed with are sent to the returned channel
+    fn socket(policy: OverflowPolicy) -> (Socket<()>, std::sync::mpsc::Receiver<DisconnectReason>) {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let tx = std::sync::Mutex::new(tx);
+        let config = EngineIoConfig::builder()
+            .max_buffer_size(2)
+            .overflow_policy(policy)
+            .build();
+        let socket = Socket::new(
+            ProtocolVersion::V4,
+            TransportType::Polling,
+            &config,
+            socket_req("127.0.0.1:1234", &[]),
+            Box::new(move |_, reason| tx.lock().unwrap().send(reason).unwrap()),
+            #[cfg(feature = "v3")]
+            true,
+        );
+        (socket, rx)
+    }
+
+    async fn messages(socket: &Socket<()>) -> Vec<Packet> {
+        let mut rx = socket.internal_rx.lock().await;
+        std::iter::from_fn(|| rx.try_recv().ok()).collect()
+    }
+
+    #[tokio::test]
+    async fn overflow_error() {
+        let (socket, _) = socket(OverflowPolicy::Error);
+        socket.emit("1".into()).unwrap();
+        socket.emit_binary(vec![2]).unwrap();
+        assert!(matches!(socket.emit("3".into()), Err(TrySendError::Full(msg)) if msg == "3"));
+        // The control packets are not limited by the buffer
+        socket.send(Packet::Ping).unwrap();
+        assert_eq!(
+            messages(&socket).await,
+            [
+                Packet::Message("1".into()),
+                Packet::Binary(vec![2]),
+                Packet::Ping
+            ]
+        );
+        socket.emit("4".into()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn overflow_drop_oldest() {
+        let (socket, _) = socket(OverflowPolicy::DropOldest);
+        for i in 1..=4 {
+            socket.emit(i.to_string()).unwrap();
+        }
+        assert_eq!(
+            messages(&socket).await,
+            [Packet::Message("3".into()), Packet::Message("4".into())]
+        );
+
+        // Without any read, at most `max_buffer_size` messages are dropped, then the new ones are dropped
+        for i in 5..=10 {
+            socket.emit(i.to_string()).unwrap();
+        }
+        assert_eq!(
+            messages(&socket).await,
+            [Packet::Message("7".into()), Packet::Message("8".into())]
+        );
+        assert_eq!(socket.buffer.permits.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn overflow_drop_newest() {
+        let (socket, _) = socket(OverflowPolicy::DropNewest);
+        for i in 1..=5 {
+            socket.emit(i.to_string()).unwrap();
+        }
+        assert_eq!(
+            messages(&socket).await,
+            [Packet::Message("1".into()), Packet::Message("2".into())]
+        );
+    }
+
+    #[tokio::test]
+    async fn overflow_disconnect() {
+        let (socket, closed) = socket(OverflowPolicy::Error);
+        socket.set_overflow_policy(OverflowPolicy::Disconnect);
+        assert_eq!(socket.overflow_policy(), OverflowPolicy::Disconnect);
+        socket.emit("1".into()).unwrap();
+        socket.emit("2".into()).unwrap();
+        assert!(socket.emit("3".into()).is_err());
+        assert_eq!(closed.try_recv(), Ok(DisconnectReason::BufferOverflow));
+        assert_eq!(messages(&socket).await.last(), Some(&Packet::Close));
+    }
+
+    /// Emit a message and a binary message as a group of 2 to a socket holding one message
+    async fn overflow_group(policy: OverflowPolicy) -> (Result<(), TrySendError<()>>, Vec<Packet>) {
+        let (socket, _closed) = socket(policy);
+        socket.emit("1".into()).unwrap();
+        let res = socket.reserve(2).map(|mut permit| {
+            permit.emit("2".into());
+            permit.emit_binary(vec![2]);
+        });
+        (res, messages(&socket).await)
+    }
+
+    #[tokio::test]
+    async fn overflow_groups() {
+        let (res, msgs) = overflow_group(OverflowPolicy::Error).await;
+        assert!(matches!(res, Err(TrySendError::Full(()))));
+        assert_eq!(msgs, [Packet::Message("1".into())]);
+
+        let (res, msgs) = overflow_group(OverflowPolicy::DropNewest).await;
+        assert!(res.is_ok());
+        assert_eq!(msgs, [Packet::Message("1".into())]);
+
+        let (res, msgs) = overflow_group(OverflowPolicy::DropOldest).await;
+        assert!(res.is_ok());
+        assert_eq!(msgs, [Packet::Message("2".into()), Packet::Binary(vec![2])]);
+
+        let (res, msgs) = overflow_group(OverflowPolicy::Disconnect).await;
+        assert!(matches!(res, Err(TrySendError::Full(()))));
+        assert_eq!(msgs, [Packet::Message("1".into()), Packet::Close]);
+    }
+
+    #[tokio::test]
+    async fn overflow_group_larger_than_buffer() {
+        let (socket, _) = socket(OverflowPolicy::DropOldest);
+        socket.emit("1".into()).unwrap();
+        // The oldest messages are not dropped for a group that can't fit anyway
+        socket.reserve(3).unwrap().emit("2".into());
+        assert!(matches!(
+            socket.reserve_async(3, None).await,
+            Err(SendTimeoutError::Timeout(()))
+        ));
+        assert_eq!(messages(&socket).await, [Packet::Message("1".into())]);
+        assert_eq!(socket.buffer.permits.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn emit_async() {
+        let (socket, _) = socket(OverflowPolicy::Error);
+        let socket = Arc::new(socket);
+        socket.emit_async("1".into(), None).await.unwrap();
+        socket.emit_async("2".into(), None).await.unwrap();
+        let timeout = Some(Duration::from_millis(10));
+        assert!(matches!(
+            socket.emit_async("3".into(), timeout).await,
+            Err(SendTimeoutError::Timeout(msg)) if msg == "3"
+        ));
+
+        // The pending emit completes once the client reads a message
+        let sock = socket.clone();
+        let pending = tokio::spawn(async move { sock.emit_async("3".into(), None).await });
+        tokio::task::yield_now().await;
+        let first = socket.internal_rx.lock().await.recv().await;
+        assert_eq!(first, Some(Packet::Message("1".into())));
+        pending.await.unwrap().unwrap();
+
+        socket.internal_rx.lock().await.close();
+        assert!(matches!(
+            socket.emit_async("4".into(), None).await,
+            Err(SendTimeoutError::Closed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn volatile_emit() {
+        let (socket, _) = socket(OverflowPolicy::Error);
+        // A polling socket without pending request is not writable
+        assert!(!socket.is_writable());
+        assert!(socket.try_reserve_volatile(1).is_none());
+
+        socket.poll_pending.store(true, Ordering::Relaxed);
+        assert!(socket.is_writable());
+        // Not enough places for the three messages
+        assert!(socket.try_reserve_volatile(3).is_none());
+        {
+            let mut permit = socket.try_reserve_volatile(2).unwrap();
+            assert!(!socket.is_writable());
+            permit.emit("1".into());
+        }
+        // The unused place is released with the permit
+        let mut permit = socket.try_reserve_volatile(1).unwrap();
+        permit.emit_shared_binary(Bytes::from_static(&[2]));
+        permit.emit("3".into());
+        drop(permit);
+        assert!(!socket.is_writable());
+        assert!(socket.try_reserve_volatile(1).is_none());
+
+        assert_eq!(
+            messages(&socket).await,
+            [
+                Packet::Message("1".into()),
+                Packet::SharedBinary(Bytes::from_static(&[2]))
+            ]
+        );
+        socket.upgrade_to_websocket();
+        socket.poll_pending.store(false, Ordering::Relaxed);
+        assert!(socket.is_writable());
+    }
 }
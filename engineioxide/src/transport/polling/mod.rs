@@ -1,5 +1,5 @@
 //! The polling transport module handles polling, post and init requests
-use std::sync::Arc;
+use std::sync::{atomic::Ordering, Arc};
 
 use futures::StreamExt;
 use http::{Request, Response, StatusCode};
@@ -14,13 +14,14 @@ use crate::{
     packet::{OpenPacket, Packet},
     service::ProtocolVersion,
     sid::Sid,
+    socket::Socket,
     transport::polling::payload::Payload,
     DisconnectReason, SocketReq,
 };
 
 use super::TransportType;
 
-mod payload;
+pub(crate) mod payload;
 
 pub fn open_req<H, B, R>(
     engine: Arc<EngineIo<H>>,
@@ -100,6 +101,9 @@ where
 
     let max_payload = engine.config.max_payload;
 
+    // Volatile messages can be sent while the request waits for packets
+    let _pending = PendingPoll::new(&socket);
+
     #[cfg(feature = "v3")]
     let Payload { data, has_binary } =
         payload::encoder(rx, protocol, socket.supports_binary, max_payload).await?;
@@ -111,6 +115,20 @@ where
     Ok(http_response(StatusCode::OK, data, has_binary)?)
 }
 
+/// Marks a socket as having a pending polling request until it is dropped
+struct PendingPoll<'a, D: Default + Send + Sync + 'static>(&'a Socket<D>);
+impl<'a, D: Default + Send + Sync + 'static> PendingPoll<'a, D> {
+    fn new(socket: &'a Socket<D>) -> Self {
+        socket.poll_pending.store(true, Ordering::Relaxed);
+        Self(socket)
+    }
+}
+impl<'a, D: Default + Send + Sync + 'static> Drop for PendingPoll<'a, D> {
+    fn drop(&mut self) {
+        self.0.poll_pending.store(false, Ordering::Relaxed);
+    }
+}
+
 /// Handle http polling post request
 ///
 /// Split the body into packets and send them to the internal socket
@@ -24,7 +24,7 @@ use crate::{
 /// * `max_payload` - The maximum payload length
 /// * `b64` - If binary packets should be encoded in base64
 fn try_recv_packet(
-    rx: &mut MutexGuard<'_, PeekableReceiver<Packet>>,
+    rx: &mut MutexGuard<'_, PeekableReceiver>,
     payload_len: usize,
     max_payload: u64,
     b64: bool,
@@ -53,7 +53,7 @@ fn try_recv_packet(
 
 /// Same as [`try_recv_packet`]
 /// but wait for a new packet if there is no packet in the buffer
-async fn recv_packet(rx: &mut MutexGuard<'_, PeekableReceiver<Packet>>) -> Result<Packet, Error> {
+async fn recv_packet(rx: &mut MutexGuard<'_, PeekableReceiver>) -> Result<Packet, Error> {
     let packet = rx.recv().await.ok_or(Error::Aborted)?;
     if packet == Packet::Close {
         #[cfg(feature = "tracing")]
@@ -70,7 +70,7 @@ async fn recv_packet(rx: &mut MutexGuard<'_, PeekableReceiver<Packet>>) -> Resul
 /// [engine.io v4 protocol](https://socket.io/fr/docs/v4/engine-io-protocol/#http-long-polling-1)
 #[cfg(feature = "v4")]
 pub async fn v4_encoder(
-    mut rx: MutexGuard<'_, PeekableReceiver<Packet>>,
+    mut rx: MutexGuard<'_, PeekableReceiver>,
     max_payload: u64,
 ) -> Result<Payload, Error> {
     use crate::transport::polling::payload::PACKET_SEPARATOR_V4;
@@ -108,7 +108,8 @@ pub async fn v4_encoder(
 pub fn v3_bin_packet_encoder(packet: Packet, data: &mut Vec<u8>) -> Result<(), Error> {
     use crate::transport::polling::payload::BINARY_PACKET_SEPARATOR_V3;
     match packet {
-        Packet::BinaryV3(bin) => {
+        Packet::BinaryV3(_) | Packet::SharedBinaryV3(_) => {
+            let bin = packet.binary_data().unwrap();
             data.push(0x1);
 
             let len = (bin.len() + 1).to_string();
@@ -117,7 +118,7 @@ pub fn v3_bin_packet_encoder(packet: Packet, data: &mut Vec<u8>) -> Result<(), E
             }
             data.push(BINARY_PACKET_SEPARATOR_V3); // separator
             data.push(0x04); // message packet type
-            data.extend_from_slice(&bin); // raw data
+            data.extend_from_slice(bin); // raw data
         }
         packet => {
             let packet: String = packet.try_into()?;
@@ -154,7 +155,7 @@ pub fn v3_string_packet_encoder(packet: Packet, data: &mut Vec<u8>) -> Result<()
 /// according to the [engine.io v3 protocol](https://github.com/socketio/engine.io-protocol/tree/v3#payload)
 #[cfg(feature = "v3")]
 pub async fn v3_binary_encoder(
-    mut rx: MutexGuard<'_, PeekableReceiver<Packet>>,
+    mut rx: MutexGuard<'_, PeekableReceiver>,
     max_payload: u64,
 ) -> Result<Payload, Error> {
     let mut data: Vec<u8> = Vec::new();
@@ -196,7 +197,7 @@ pub async fn v3_binary_encoder(
         let packet = recv_packet(&mut rx).await?;
 
         match packet {
-            Packet::BinaryV3(_) | Packet::Binary(_) => {
+            packet if packet.is_binary() => {
                 v3_bin_packet_encoder(packet, &mut data)?;
                 has_binary = true;
             }
@@ -215,7 +216,7 @@ pub async fn v3_binary_encoder(
 /// [engine.io v3 protocol](https://github.com/socketio/engine.io-protocol/tree/v3#payload)
 #[cfg(feature = "v3")]
 pub async fn v3_string_encoder(
-    mut rx: MutexGuard<'_, PeekableReceiver<Packet>>,
+    mut rx: MutexGuard<'_, PeekableReceiver>,
     max_payload: u64,
 ) -> Result<Payload, Error> {
     let mut data: Vec<u8> = Vec::new();
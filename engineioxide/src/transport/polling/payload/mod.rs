@@ -9,6 +9,9 @@ mod buf;
 mod decoder;
 mod encoder;
 
+#[cfg(feature = "client")]
+pub(crate) use {decoder::v4_decoder, encoder::v4_encoder};
+
 #[cfg(feature = "v4")]
 const PACKET_SEPARATOR_V4: u8 = b'\x1e';
 #[cfg(feature = "v3")]
@@ -77,7 +80,7 @@ impl Payload {
 }
 
 pub async fn encoder(
-    rx: MutexGuard<'_, PeekableReceiver<Packet>>,
+    rx: MutexGuard<'_, PeekableReceiver>,
     #[allow(unused_variables)] protocol: ProtocolVersion,
     #[cfg(feature = "v3")] supports_binary: bool,
     max_payload: u64,
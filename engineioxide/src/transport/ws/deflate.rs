@@ -0,0 +1,674 @@
+//! The [permessage-deflate](https://datatracker.ietf.org/doc/html/rfc7692) websocket extension
+//!
+//! The extension is negotiated with [`DeflateParams::negotiate`] from the `Sec-WebSocket-Extensions` request header.
+//!
+//! Tungstenite rejects the frames with the RSV1 bit used by the extension, so the compressed messages
+//! received are decompressed by the [`DeflateStream`] before reaching the websocket parser,
+//! and the messages sent are compressed by a [`Deflater`] into frames with the RSV1 bit set.
+
+use std::{
+    cmp, io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::{BufMut, BytesMut};
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use futures::ready;
+use http::{header::SEC_WEBSOCKET_EXTENSIONS, HeaderMap, HeaderValue};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_tungstenite::tungstenite::{
+    protocol::frame::{
+        coding::{Data, OpCode},
+        Frame,
+    },
+    Message,
+};
+
+use crate::config::DeflateConfig;
+
+/// The name of the extension in the `Sec-WebSocket-Extensions` headers
+const EXTENSION: &str = "permessage-deflate";
+
+/// The bytes removed from the end of each compressed message and added back before decompressing it
+const DEFLATE_TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// The maximum size of a decompressed message, the default max message size of tungstenite
+const MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+/// The maximum payload size of the frames of a decompressed message, below the default max frame size of tungstenite
+const MAX_FRAME_SIZE: usize = 1 << 20;
+
+/// The permessage-deflate parameters agreed with a client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeflateParams {
+    /// The messages smaller than this number of bytes are not compressed
+    threshold: usize,
+    server_max_window_bits: u8,
+    /// Only sent back if the client offered it
+    client_max_window_bits: Option<u8>,
+    server_no_context_takeover: bool,
+    client_no_context_takeover: bool,
+}
+
+impl DeflateParams {
+    /// Negotiate the extension with the offers of the `Sec-WebSocket-Extensions` request headers.
+    ///
+    /// The first valid offer is accepted, returns `None` if the client does not support the extension.
+    pub fn negotiate(config: &DeflateConfig, headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get_all(SEC_WEBSOCKET_EXTENSIONS)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .find_map(|offer| Self::accept(config, offer))
+    }
+
+    /// Accept an extension offer, returns `None` if it is not a valid permessage-deflate offer
+    fn accept(config: &DeflateConfig, offer: &str) -> Option<Self> {
+        let mut params = offer.split(';').map(str::trim);
+        if params.next()? != EXTENSION {
+            return None;
+        }
+        let mut server_max_window_bits = None;
+        let mut client_max_window_bits = None;
+        let mut server_no_context_takeover = false;
+        let mut client_no_context_takeover = false;
+        for param in params {
+            let (name, value) = match param.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (param, None),
+            };
+            match (name, value) {
+                ("server_no_context_takeover", None) if !server_no_context_takeover => {
+                    server_no_context_takeover = true
+                }
+                ("client_no_context_takeover", None) if !client_no_context_takeover => {
+                    client_no_context_takeover = true
+                }
+                ("server_max_window_bits", Some(bits)) if server_max_window_bits.is_none() => {
+                    server_max_window_bits = Some(parse_window_bits(bits)?)
+                }
+                ("client_max_window_bits", bits) if client_max_window_bits.is_none() => {
+                    client_max_window_bits = Some(bits.map_or(Some(15), parse_window_bits)?)
+                }
+                _ => return None,
+            }
+        }
+
+        Some(Self {
+            threshold: config.threshold,
+            server_max_window_bits: server_max_window_bits
+                .map_or(config.server_max_window_bits, |bits| {
+                    cmp::min(bits, config.server_max_window_bits)
+                }),
+            client_max_window_bits: client_max_window_bits
+                .map(|bits| cmp::min(bits, config.client_max_window_bits)),
+            server_no_context_takeover: server_no_context_takeover
+                || config.server_no_context_takeover,
+            client_no_context_takeover: client_no_context_takeover
+                || config.client_no_context_takeover,
+        })
+    }
+
+    /// The `Sec-WebSocket-Extensions` response header accepting the extension
+    pub fn header(&self) -> HeaderValue {
+        let mut header = EXTENSION.to_string();
+        if self.server_no_context_takeover {
+            header.push_str("; server_no_context_takeover");
+        }
+        if self.client_no_context_takeover {
+            header.push_str("; client_no_context_takeover");
+        }
+        if self.server_max_window_bits < 15 {
+            header.push_str(&format!(
+                "; server_max_window_bits={}",
+                self.server_max_window_bits
+            ));
+        }
+        match self.client_max_window_bits {
+            Some(bits) if bits < 15 => {
+                header.push_str(&format!("; client_max_window_bits={}", bits))
+            }
+            _ => (),
+        }
+        HeaderValue::from_str(&header).unwrap()
+    }
+
+    /// Create the [`Deflater`] compressing the messages sent to the client
+    pub fn deflater(&self) -> Deflater {
+        Deflater {
+            compress: Compress::new(Compression::default(), false),
+            threshold: self.threshold,
+            window_bits: self.server_max_window_bits,
+            reset: self.server_no_context_takeover || self.server_max_window_bits < 15,
+        }
+    }
+}
+
+/// Parse a window bits parameter, it must be a number between 8 and 15 without leading zeros
+fn parse_window_bits(bits: &str) -> Option<u8> {
+    bits.parse::<u8>()
+        .ok()
+        .filter(|b| (8..=15).contains(b) && b.to_string() == bits)
+}
+
+/// Compress the messages sent to a client
+#[derive(Debug)]
+pub struct Deflater {
+    compress: Compress,
+    threshold: usize,
+    window_bits: u8,
+    /// Reset the compression context after each message
+    reset: bool,
+}
+
+impl Deflater {
+    /// Compress a text or binary message into a frame with the RSV1 bit set.
+    ///
+    /// The messages smaller than the threshold are returned as is. When the window is reduced, the context
+    /// is reset after each message and the messages larger than the window are returned as is too.
+    pub fn compress(&mut self, msg: Message) -> Message {
+        let opcode = match msg {
+            Message::Text(_) => OpCode::Data(Data::Text),
+            Message::Binary(_) => OpCode::Data(Data::Binary),
+            msg => return msg,
+        };
+        let len = msg.len();
+        if len < self.threshold || (self.window_bits < 15 && len > 1 << self.window_bits) {
+            return msg;
+        }
+        let data = msg.into_data();
+        match self.deflate(&data) {
+            Ok(compressed) => {
+                let mut frame = Frame::message(compressed, opcode, true);
+                frame.header_mut().rsv1 = true;
+                Message::Frame(frame)
+            }
+            Err(_e) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("error compressing message: {}", _e);
+                // The client did not receive anything from this message,
+                // so a new context is still in sync with its own
+                self.compress.reset();
+                match opcode {
+                    OpCode::Data(Data::Text) => Message::Text(String::from_utf8(data).unwrap()),
+                    _ => Message::Binary(data),
+                }
+            }
+        }
+    }
+
+    /// Compress a message payload and remove the tail of the sync flush
+    fn deflate(&mut self, mut data: &[u8]) -> Result<Vec<u8>, flate2::CompressError> {
+        let mut out = Vec::with_capacity(data.len() / 2 + 16);
+        loop {
+            let total_in = self.compress.total_in();
+            self.compress
+                .compress_vec(data, &mut out, FlushCompress::Sync)?;
+            data = &data[(self.compress.total_in() - total_in) as usize..];
+            // The flush is complete once the input is consumed without filling the output
+            if data.is_empty() && out.len() < out.capacity() {
+                break;
+            }
+            out.reserve(cmp::max(out.capacity() / 2, 64));
+        }
+        if out.ends_with(&DEFLATE_TAIL) {
+            out.truncate(out.len() - DEFLATE_TAIL.len());
+        }
+        if self.reset {
+            self.compress.reset();
+        }
+        Ok(out)
+    }
+}
+
+/// A stream that decompresses the compressed messages received before they are read by the websocket parser.
+///
+/// The frames of a compressed message are replaced with the frames of the decompressed message,
+/// all the other frames are read as is. Writes are forwarded to the inner stream.
+#[derive(Debug)]
+pub struct DeflateStream<S> {
+    inner: S,
+    /// `None` if the extension was not negotiated, the stream is then a simple passthrough
+    inflater: Option<Inflater>,
+}
+
+impl<S> DeflateStream<S> {
+    pub fn new(inner: S, params: Option<&DeflateParams>) -> Self {
+        Self {
+            inner,
+            inflater: params.map(|_| Inflater::new()),
+        }
+    }
+}
+
+/// The header of a websocket frame
+#[derive(Debug)]
+struct FrameHeader {
+    fin: bool,
+    rsv1: bool,
+    opcode: u8,
+    mask: Option<[u8; 4]>,
+    /// The length of the header itself
+    len: usize,
+    payload_len: u64,
+}
+
+impl FrameHeader {
+    /// Parse a frame header, returns `None` if the buffer does not contain the whole header yet
+    fn parse(buf: &[u8]) -> Option<Self> {
+        let (first, second) = (*buf.first()?, *buf.get(1)?);
+        let masked = second & 0x80 != 0;
+        let (payload_len, mut len) = match second & 0x7f {
+            126 => (
+                u16::from_be_bytes(buf.get(2..4)?.try_into().unwrap()) as u64,
+                4,
+            ),
+            127 => (u64::from_be_bytes(buf.get(2..10)?.try_into().unwrap()), 10),
+            payload_len => (payload_len as u64, 2),
+        };
+        let mask = if masked {
+            let mask = buf.get(len..len + 4)?.try_into().unwrap();
+            len += 4;
+            Some(mask)
+        } else {
+            None
+        };
+        Some(Self {
+            fin: first & 0x80 != 0,
+            rsv1: first & 0x40 != 0,
+            opcode: first & 0x0f,
+            mask,
+            len,
+            payload_len,
+        })
+    }
+}
+
+/// The decompression state of a [`DeflateStream`]
+#[derive(Debug)]
+struct Inflater {
+    decompress: Decompress,
+    /// The bytes read from the inner stream and not processed yet
+    read_buf: BytesMut,
+    /// The bytes ready to be read by the websocket parser
+    out: BytesMut,
+    /// The number of bytes of an uncompressed frame payload still to be read as is
+    passthrough: u64,
+    /// The opcode and the decompressed payload of a compressed message whose last frame is not received yet
+    message: Option<(u8, Vec<u8>)>,
+}
+
+impl Inflater {
+    fn new() -> Self {
+        Self {
+            decompress: Decompress::new(false),
+            read_buf: BytesMut::new(),
+            out: BytesMut::new(),
+            passthrough: 0,
+            message: None,
+        }
+    }
+
+    /// Process the bytes of the read buffer, returns `false` if more bytes are needed to make progress
+    fn process(&mut self) -> io::Result<bool> {
+        if self.passthrough > 0 {
+            if self.read_buf.is_empty() {
+                return Ok(false);
+            }
+            let len = cmp::min(self.passthrough, self.read_buf.len() as u64) as usize;
+            self.out.extend_from_slice(&self.read_buf.split_to(len));
+            self.passthrough -= len as u64;
+            return Ok(true);
+        }
+        let header = match FrameHeader::parse(&self.read_buf) {
+            Some(header) => header,
+            None => return Ok(false),
+        };
+        let is_control = header.opcode & 0x08 != 0;
+        let compressed = header.rsv1 || (header.opcode == 0 && self.message.is_some());
+        if is_control || !compressed {
+            self.out
+                .extend_from_slice(&self.read_buf.split_to(header.len));
+            self.passthrough = header.payload_len;
+            return Ok(true);
+        }
+        if header.rsv1 && (header.opcode == 0 || self.message.is_some()) {
+            return Err(invalid_data("unexpected compressed frame"));
+        }
+        if header.payload_len > MAX_MESSAGE_SIZE as u64 {
+            return Err(invalid_data("compressed frame too large"));
+        }
+        let frame_len = header.len + header.payload_len as usize;
+        if self.read_buf.len() < frame_len {
+            self.read_buf.reserve(frame_len - self.read_buf.len());
+            return Ok(false);
+        }
+        let mut payload = self.read_buf.split_to(frame_len).split_off(header.len);
+        if let Some(mask) = header.mask {
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte ^= mask[i % 4];
+            }
+        }
+        let (opcode, data) = self
+            .message
+            .get_or_insert_with(|| (header.opcode, Vec::new()));
+        inflate(&mut self.decompress, &payload, data)?;
+        if header.fin {
+            inflate(&mut self.decompress, &DEFLATE_TAIL, data)?;
+            write_frames(&mut self.out, *opcode, data);
+            self.message = None;
+        }
+        Ok(true)
+    }
+}
+
+/// Decompress the `input` bytes at the end of `out`
+fn inflate(decompress: &mut Decompress, mut input: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+    loop {
+        if out.len() == out.capacity() {
+            out.reserve(cmp::max(input.len() * 2, 1024));
+        }
+        let (total_in, total_out) = (decompress.total_in(), decompress.total_out());
+        let status = decompress
+            .decompress_vec(input, out, FlushDecompress::Sync)
+            .map_err(|e| invalid_data(e.to_string()))?;
+        input = &input[(decompress.total_in() - total_in) as usize..];
+        let progress = decompress.total_out() > total_out || decompress.total_in() > total_in;
+        if out.len() > MAX_MESSAGE_SIZE {
+            return Err(invalid_data("decompressed message too large"));
+        }
+        if status == Status::StreamEnd {
+            // The client ended its deflate stream, the next message starts a new one
+            decompress.reset(false);
+        } else if (input.is_empty() && out.len() < out.capacity()) || !progress {
+            return Ok(());
+        }
+    }
+}
+
+/// Write a decompressed message as masked frames, with a zero mask key that leaves the payload unchanged
+fn write_frames(out: &mut BytesMut, mut opcode: u8, mut data: &[u8]) {
+    loop {
+        let len = cmp::min(data.len(), MAX_FRAME_SIZE);
+        let fin = len == data.len();
+        out.put_u8(if fin { 0x80 } else { 0 } | opcode);
+        match len {
+            0..=125 => out.put_u8(0x80 | len as u8),
+            126..=0xffff => {
+                out.put_u8(0x80 | 126);
+                out.put_u16(len as u16);
+            }
+            _ => {
+                out.put_u8(0x80 | 127);
+                out.put_u64(len as u64);
+            }
+        }
+        out.put_slice(&[0; 4]);
+        out.put_slice(&data[..len]);
+        data = &data[len..];
+        opcode = 0;
+        if fin {
+            break;
+        }
+    }
+}
+
+fn invalid_data(msg: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for DeflateStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        let inflater = match this.inflater.as_mut() {
+            Some(inflater) => inflater,
+            None => return Pin::new(&mut this.inner).poll_read(cx, buf),
+        };
+        loop {
+            if !inflater.out.is_empty() {
+                let len = cmp::min(buf.remaining(), inflater.out.len());
+                buf.put_slice(&inflater.out.split_to(len));
+                return Poll::Ready(Ok(()));
+            }
+            if inflater.process()? {
+                continue;
+            }
+            let mut chunk = [0; 8192];
+            let mut chunk = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk))?;
+            if chunk.filled().is_empty() {
+                // The stream is closed, the incomplete frame left is read as is
+                let rest = inflater.read_buf.split();
+                if rest.is_empty() {
+                    return Poll::Ready(Ok(()));
+                }
+                inflater.out.unsplit(rest);
+                continue;
+            }
+            inflater.read_buf.extend_from_slice(chunk.filled());
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for DeflateStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn negotiate(config: &DeflateConfig, offers: &[&'static str]) -> Option<DeflateParams> {
+        let mut headers = HeaderMap::new();
+        for offer in offers {
+            headers.append(SEC_WEBSOCKET_EXTENSIONS, HeaderValue::from_static(offer));
+        }
+        DeflateParams::negotiate(config, &headers)
+    }
+
+    #[test]
+    fn negotiation() {
+        let config = DeflateConfig::default();
+        assert_eq!(negotiate(&config, &[]), None);
+        assert_eq!(negotiate(&config, &["x-webkit-deflate-frame"]), None);
+
+        let params = negotiate(&config, &["permessage-deflate; client_max_window_bits"]).unwrap();
+        assert_eq!(params.header(), "permessage-deflate");
+
+        // Invalid offers are skipped
+        for offer in [
+            "permessage-deflate; server_max_window_bits",
+            "permessage-deflate; server_max_window_bits=7",
+            "permessage-deflate; client_max_window_bits=010",
+            "permessage-deflate; server_no_context_takeover=1",
+            "permessage-deflate; server_no_context_takeover; server_no_context_takeover",
+            "permessage-deflate; unknown",
+        ] {
+            assert_eq!(negotiate(&config, &[offer]), None, "{offer}");
+        }
+
+        let params = negotiate(
+            &config,
+            &[
+                "foo, permessage-deflate; server_max_window_bits=16",
+                "permessage-deflate; server_no_context_takeover; server_max_window_bits=\"10\"",
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            params.header(),
+            "permessage-deflate; server_no_context_takeover; server_max_window_bits=10"
+        );
+    }
+
+    #[test]
+    fn negotiation_config() {
+        let config = DeflateConfig {
+            server_max_window_bits: 12,
+            client_max_window_bits: 9,
+            server_no_context_takeover: true,
+            client_no_context_takeover: true,
+            ..Default::default()
+        };
+        let params = negotiate(&config, &["permessage-deflate"]).unwrap();
+        assert_eq!(
+            params.header(),
+            "permessage-deflate; server_no_context_takeover; client_no_context_takeover; server_max_window_bits=12"
+        );
+
+        let offer = "permessage-deflate; server_max_window_bits=14; client_max_window_bits=10";
+        let params = negotiate(&config, &[offer]).unwrap();
+        assert_eq!(
+            params.header(),
+            "permessage-deflate; server_no_context_takeover; client_no_context_takeover; server_max_window_bits=12; client_max_window_bits=9"
+        );
+    }
+
+    /// Read the frames given by the inflater to the websocket parser
+    fn frames(mut buf: &[u8]) -> Vec<(bool, u8, Vec<u8>)> {
+        let mut frames = Vec::new();
+        while let Some(header) = FrameHeader::parse(buf) {
+            assert!(!header.rsv1);
+            let end = header.len + header.payload_len as usize;
+            let mut payload = buf[header.len..end].to_vec();
+            if let Some(mask) = header.mask {
+                for (i, byte) in payload.iter_mut().enumerate() {
+                    *byte ^= mask[i % 4];
+                }
+            }
+            frames.push((header.fin, header.opcode, payload));
+            buf = &buf[end..];
+        }
+        assert!(buf.is_empty());
+        frames
+    }
+
+    fn inflate_frames(inflater: &mut Inflater, bytes: &[u8]) -> Vec<(bool, u8, Vec<u8>)> {
+        // The bytes are received one by one to check that partial frames are waited for
+        for byte in bytes {
+            inflater.read_buf.put_u8(*byte);
+            while inflater.process().unwrap() {}
+        }
+        frames(&inflater.out.split())
+    }
+
+    #[test]
+    fn deflate_inflate() {
+        let config = DeflateConfig {
+            threshold: 10,
+            ..Default::default()
+        };
+        let mut deflater = negotiate(&config, &["permessage-deflate"])
+            .unwrap()
+            .deflater();
+        let mut inflater = Inflater::new();
+
+        let msg = "hello world ".repeat(50);
+        for _ in 0..2 {
+            let frame = match deflater.compress(Message::Text(msg.clone())) {
+                Message::Frame(frame) => frame,
+                msg => panic!("message not compressed: {msg:?}"),
+            };
+            assert!(frame.header().rsv1);
+            let mut bytes = Vec::new();
+            frame.format(&mut bytes).unwrap();
+            assert_eq!(
+                inflate_frames(&mut inflater, &bytes),
+                [(true, 1, msg.clone().into_bytes())]
+            );
+        }
+
+        assert_eq!(
+            deflater.compress(Message::Text("small".into())),
+            Message::Text("small".into())
+        );
+        assert_eq!(
+            deflater.compress(Message::Ping(vec![1; 20])),
+            Message::Ping(vec![1; 20])
+        );
+
+        // The uncompressed and control frames are left as is
+        let mut bytes = Vec::new();
+        Frame::message(b"uncompressed".to_vec(), OpCode::Data(Data::Binary), true)
+            .format(&mut bytes)
+            .unwrap();
+        assert_eq!(
+            inflate_frames(&mut inflater, &bytes),
+            [(true, 2, b"uncompressed".to_vec())]
+        );
+        let mut bytes = Vec::new();
+        Frame::ping(vec![1, 2]).format(&mut bytes).unwrap();
+        assert_eq!(
+            inflate_frames(&mut inflater, &bytes),
+            [(true, 9, vec![1, 2])]
+        );
+    }
+
+    #[test]
+    fn inflate_large_message() {
+        let mut compress = Compress::new(Compression::default(), false);
+        let data = vec![7; 3 * MAX_FRAME_SIZE];
+        let mut compressed = Vec::with_capacity(data.len());
+        compress
+            .compress_vec(&data, &mut compressed, FlushCompress::Sync)
+            .unwrap();
+        compressed.truncate(compressed.len() - DEFLATE_TAIL.len());
+
+        let mut frame = Frame::message(compressed, OpCode::Data(Data::Binary), true);
+        frame.header_mut().rsv1 = true;
+        let mut bytes = Vec::new();
+        frame.format(&mut bytes).unwrap();
+        let mut inflater = Inflater::new();
+        inflater.read_buf.extend_from_slice(&bytes);
+        while inflater.process().unwrap() {}
+
+        let frames = frames(&inflater.out);
+        let lens: Vec<_> = frames
+            .iter()
+            .map(|(fin, opcode, payload)| (*fin, *opcode, payload.len()))
+            .collect();
+        assert_eq!(
+            lens,
+            [
+                (false, 2, MAX_FRAME_SIZE),
+                (false, 0, MAX_FRAME_SIZE),
+                (true, 0, MAX_FRAME_SIZE)
+            ]
+        );
+        assert!(frames
+            .iter()
+            .all(|(_, _, payload)| payload.iter().all(|b| *b == 7)));
+    }
+}
@@ -32,6 +32,13 @@ use crate::{
     DisconnectReason, Socket, SocketReq,
 };
 
+use self::deflate::{DeflateParams, DeflateStream, Deflater};
+
+mod deflate;
+
+/// A websocket stream, that decompresses the messages received if the permessage-deflate extension is negotiated
+type WsStream = WebSocketStream<DeflateStream<Upgraded>>;
+
 /// Upgrade a websocket request to create a websocket connection.
 ///
 /// If a sid is provided in the query it means that is is upgraded from an existing HTTP polling request. In this case
@@ -49,11 +56,17 @@ pub fn new_req<R, B, H: EngineIoHandler>(
         .ok_or(Error::HttpErrorResponse(StatusCode::BAD_REQUEST))?
         .clone();
     let req_data = SocketReq::from(&parts);
+    let deflate = engine
+        .config
+        .per_message_deflate
+        .as_ref()
+        .and_then(|config| DeflateParams::negotiate(config, &parts.headers));
+    let extensions = deflate.as_ref().map(DeflateParams::header);
 
     let req = Request::from_parts(parts, ());
     tokio::spawn(async move {
         match hyper::upgrade::on(req).await {
-            Ok(conn) => match on_init(engine, conn, protocol, sid, req_data).await {
+            Ok(conn) => match on_init(engine, conn, protocol, sid, req_data, deflate).await {
                 Ok(_) => {
                     #[cfg(feature = "tracing")]
                     tracing::debug!("ws closed")
@@ -70,7 +83,7 @@ pub fn new_req<R, B, H: EngineIoHandler>(
         }
     });
 
-    Ok(ws_response(&ws_key)?)
+    Ok(ws_response(&ws_key, extensions)?)
 }
 
 /// Handle a websocket connection upgrade
@@ -78,14 +91,21 @@ pub fn new_req<R, B, H: EngineIoHandler>(
 /// Sends an open packet if it is not an upgrade from a polling request
 ///
 /// Read packets from the websocket and handle them, it will block until the connection is closed
+///
+/// The messages are compressed and decompressed if the permessage-deflate extension was negotiated
 async fn on_init<H: EngineIoHandler>(
     engine: Arc<EngineIo<H>>,
     conn: Upgraded,
     protocol: ProtocolVersion,
     sid: Option<Sid>,
     req_data: SocketReq,
+    deflate: Option<DeflateParams>,
 ) -> Result<(), Error> {
-    let ws_init = move || WebSocketStream::from_raw_socket(conn, Role::Server, None);
+    let deflater = deflate.as_ref().map(DeflateParams::deflater);
+    let ws_init = move || {
+        let conn = DeflateStream::new(conn, deflate.as_ref());
+        WebSocketStream::from_raw_socket(conn, Role::Server, None)
+    };
     let (socket, ws) = if let Some(sid) = sid {
         match engine.get_socket(sid) {
             None => return Err(Error::UnknownSessionID(sid)),
@@ -114,7 +134,7 @@ async fn on_init<H: EngineIoHandler>(
         (socket, ws)
     };
     let (tx, rx) = ws.split();
-    let rx_handle = forward_to_socket::<H>(socket.clone(), tx);
+    let rx_handle = forward_to_socket::<H>(socket.clone(), tx, deflater);
 
     engine.handler.on_connect(socket.clone());
 
@@ -134,7 +154,7 @@ async fn on_init<H: EngineIoHandler>(
 /// Forwards all packets received from a websocket to a EngineIo [`Socket`]
 async fn forward_to_handler<H: EngineIoHandler>(
     engine: &Arc<EngineIo<H>>,
-    mut rx: SplitStream<WebSocketStream<Upgraded>>,
+    mut rx: SplitStream<WsStream>,
     socket: &Arc<Socket<H::Data>>,
 ) -> Result<(), Error> {
     while let Some(msg) = rx.try_next().await? {
@@ -170,9 +190,12 @@ async fn forward_to_handler<H: EngineIoHandler>(
 /// Forwards all packets waiting to be sent to the websocket
 ///
 /// The websocket stream is flushed only when the internal channel is drained
+///
+/// With a [`Deflater`], the messages are compressed unless they were emitted with compression disabled
 fn forward_to_socket<H: EngineIoHandler>(
     socket: Arc<Socket<H::Data>>,
-    mut tx: SplitSink<WebSocketStream<Upgraded>, Message>,
+    mut tx: SplitSink<WsStream, Message>,
+    mut deflater: Option<Deflater>,
 ) -> JoinHandle<()> {
     // Pipe between websocket and internal socket channel
     tokio::spawn(async move {
@@ -182,9 +205,10 @@ fn forward_to_socket<H: EngineIoHandler>(
         // It is declared as a macro rather than a closure to avoid ownership issues
         macro_rules! map_fn {
             ($item:ident) => {
-                let res = match $item {
-                    Packet::Binary(bin) | Packet::BinaryV3(bin) => {
-                        tx.feed(Message::Binary(bin)).await
+                let msg = match $item {
+                    Packet::Binary(bin) | Packet::BinaryV3(bin) => Some(Message::Binary(bin)),
+                    Packet::SharedBinary(bin) | Packet::SharedBinaryV3(bin) => {
+                        Some(Message::Binary(bin.to_vec()))
                     }
                     Packet::Close => {
                         tx.send(Message::Close(None)).await.ok();
@@ -194,15 +218,21 @@ fn forward_to_socket<H: EngineIoHandler>(
                     // A Noop Packet maybe sent by the server to upgrade from a polling connection
                     // In the case that the packet was not poll in time it will remain in the buffer and therefore
                     // it should be discarded here
-                    Packet::Noop => Ok(()),
+                    Packet::Noop => None,
                     _ => {
                         let packet: String = $item.try_into().unwrap();
-                        tx.feed(Message::Text(packet)).await
+                        Some(Message::Text(packet))
                     }
                 };
-                if let Err(_e) = res {
-                    #[cfg(feature = "tracing")]
-                    tracing::debug!("[sid={}] error sending packet: {}", socket.id, _e);
+                if let Some(msg) = msg {
+                    let msg = match deflater.as_mut() {
+                        Some(deflater) if internal_rx.compress() => deflater.compress(msg),
+                        _ => msg,
+                    };
+                    if let Err(_e) = tx.feed(msg).await {
+                        #[cfg(feature = "tracing")]
+                        tracing::debug!("[sid={}] error sending packet: {}", socket.id, _e);
+                    }
                 }
             };
         }
@@ -220,11 +250,7 @@ fn forward_to_socket<H: EngineIoHandler>(
     })
 }
 /// Send a Engine.IO [`OpenPacket`] to initiate a websocket connection
-async fn init_handshake(
-    sid: Sid,
-    ws: &mut WebSocketStream<Upgraded>,
-    config: &EngineIoConfig,
-) -> Result<(), Error> {
+async fn init_handshake(sid: Sid, ws: &mut WsStream, config: &EngineIoConfig) -> Result<(), Error> {
     let packet = Packet::Open(OpenPacket::new(TransportType::Websocket, sid, config));
     ws.send(Message::Text(packet.try_into()?)).await?;
     Ok(())
@@ -257,7 +283,7 @@ async fn init_handshake(
 async fn upgrade_handshake<H: EngineIoHandler>(
     protocol: ProtocolVersion,
     socket: &Arc<Socket<H::Data>>,
-    ws: &mut WebSocketStream<Upgraded>,
+    ws: &mut WsStream,
 ) -> Result<(), Error> {
     #[cfg(feature = "tracing")]
     tracing::debug!("websocket connection upgrade");
@@ -0,0 +1,274 @@
+//! Tests for the permessage-deflate websocket extension, with a raw websocket client
+//! that compresses and decompresses the messages itself.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+};
+
+use engineioxide::{
+    config::{DeflateConfig, EngineIoConfig},
+    handler::EngineIoHandler,
+    service::EngineIoService,
+    socket::{DisconnectReason, Socket},
+};
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
+use futures::{SinkExt, StreamExt};
+use hyper::Server;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use tokio_tungstenite::tungstenite::Message;
+
+const TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+#[derive(Debug, Clone)]
+struct MyHandler;
+
+#[engineioxide::async_trait]
+impl EngineIoHandler for MyHandler {
+    type Data = ();
+
+    fn on_connect(&self, socket: Arc<Socket<()>>) {
+        println!("socket connect {}", socket.id);
+    }
+    fn on_disconnect(&self, socket: Arc<Socket<()>>, reason: DisconnectReason) {
+        println!("socket disconnect {}: {:?}", socket.id, reason);
+    }
+
+    fn on_message(&self, msg: String, socket: Arc<Socket<()>>) {
+        // The messages starting with "raw" are sent back without compression
+        if msg.starts_with("raw") {
+            let mut permit = socket.reserve(1).unwrap();
+            permit.compress(false);
+            permit.emit(msg);
+        } else {
+            socket.emit(msg).ok();
+        }
+    }
+
+    fn on_binary(&self, data: Vec<u8>, socket: Arc<Socket<()>>) {
+        socket.emit_binary(data).ok();
+    }
+}
+
+fn create_server(port: u16, deflate: DeflateConfig) {
+    let config = EngineIoConfig::builder()
+        .per_message_deflate(deflate)
+        .build();
+    let addr = &SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+    let svc = EngineIoService::with_config(MyHandler, config);
+    tokio::spawn(Server::bind(addr).serve(svc.into_make_service()));
+}
+
+/// Open a websocket connection offering the given extensions, returns the stream and the response head
+async fn connect(port: u16, extensions: &str) -> (TcpStream, String) {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let req = format!(
+        "GET /engine.io/?EIO=4&transport=websocket HTTP/1.1\r\n\
+        Host: 127.0.0.1:{port}\r\n\
+        Upgrade: websocket\r\n\
+        Connection: Upgrade\r\n\
+        Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+        Sec-WebSocket-Version: 13\r\n\
+        Sec-WebSocket-Extensions: {extensions}\r\n\r\n"
+    );
+    stream.write_all(req.as_bytes()).await.unwrap();
+
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(stream.read_u8().await.unwrap());
+    }
+    let head = String::from_utf8(head).unwrap().to_lowercase();
+    assert!(head.starts_with("http/1.1 101"), "{head}");
+    (stream, head)
+}
+
+/// Read a frame sent by the server, returns its RSV1 bit, its opcode and its payload
+async fn read_frame(stream: &mut TcpStream) -> (bool, u8, Vec<u8>) {
+    let (first, second) = (
+        stream.read_u8().await.unwrap(),
+        stream.read_u8().await.unwrap(),
+    );
+    assert_eq!(first & 0x80, 0x80, "the server messages are not fragmented");
+    assert_eq!(second & 0x80, 0, "the server frames are not masked");
+    let len = match second & 0x7f {
+        126 => stream.read_u16().await.unwrap() as usize,
+        127 => stream.read_u64().await.unwrap() as usize,
+        len => len as usize,
+    };
+    let mut payload = vec![0; len];
+    stream.read_exact(&mut payload).await.unwrap();
+    (first & 0x40 != 0, first & 0x0f, payload)
+}
+
+/// Write a masked frame, with the RSV1 bit set if the payload is compressed
+async fn write_frame(stream: &mut TcpStream, first: u8, payload: &[u8]) {
+    let mut frame = vec![first];
+    match payload.len() {
+        len @ 0..=125 => frame.push(0x80 | len as u8),
+        len @ 126..=0xffff => {
+            frame.push(0x80 | 126);
+            frame.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(0x80 | 127);
+            frame.extend((len as u64).to_be_bytes());
+        }
+    }
+    let mask = [0x12, 0x34, 0x56, 0x78];
+    frame.extend(mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    stream.write_all(&frame).await.unwrap();
+}
+
+fn deflate(compress: &mut Compress, data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 64);
+    compress
+        .compress_vec(data, &mut out, FlushCompress::Sync)
+        .unwrap();
+    assert!(out.ends_with(&TAIL));
+    out.truncate(out.len() - TAIL.len());
+    out
+}
+
+fn inflate(decompress: &mut Decompress, data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(1 << 16);
+    decompress
+        .decompress_vec(&[data, &TAIL].concat(), &mut out, FlushDecompress::Sync)
+        .unwrap();
+    out
+}
+
+#[tokio::test]
+pub async fn compressed_messages() {
+    create_server(
+        3200,
+        DeflateConfig {
+            threshold: 64,
+            ..Default::default()
+        },
+    );
+    let (mut stream, head) = connect(3200, "permessage-deflate; client_max_window_bits").await;
+    assert!(head.contains("\r\nsec-websocket-extensions: permessage-deflate\r\n"));
+
+    let (rsv1, opcode, open) = read_frame(&mut stream).await;
+    assert!(!rsv1);
+    assert_eq!(opcode, 1);
+    assert!(open.starts_with(b"0{"));
+
+    let mut compress = Compress::new(Compression::default(), false);
+    let mut decompress = Decompress::new(false);
+    let msg = format!("4{}", "hello world ".repeat(100));
+    // The context is kept between the messages in both directions
+    for _ in 0..2 {
+        write_frame(
+            &mut stream,
+            0x80 | 0x40 | 1,
+            &deflate(&mut compress, msg.as_bytes()),
+        )
+        .await;
+        let (rsv1, opcode, payload) = read_frame(&mut stream).await;
+        assert!(rsv1);
+        assert_eq!(opcode, 1);
+        assert!(payload.len() < msg.len() / 10);
+        assert_eq!(inflate(&mut decompress, &payload), msg.as_bytes());
+    }
+
+    // A compressed message split in several frames
+    let compressed = deflate(&mut compress, msg.as_bytes());
+    let (start, end) = compressed.split_at(compressed.len() / 2);
+    write_frame(&mut stream, 0x40 | 1, start).await;
+    write_frame(&mut stream, 0x80, end).await;
+    let (rsv1, _, payload) = read_frame(&mut stream).await;
+    assert!(rsv1);
+    assert_eq!(inflate(&mut decompress, &payload), msg.as_bytes());
+
+    // Binary messages are compressed too
+    let bin = vec![1; 1024];
+    write_frame(&mut stream, 0x80 | 0x40 | 2, &deflate(&mut compress, &bin)).await;
+    let (rsv1, opcode, payload) = read_frame(&mut stream).await;
+    assert!(rsv1);
+    assert_eq!(opcode, 2);
+    assert_eq!(inflate(&mut decompress, &payload), bin);
+
+    // The messages below the threshold and the uncompressed messages received are accepted as well
+    write_frame(&mut stream, 0x80 | 1, b"4small").await;
+    assert_eq!(
+        read_frame(&mut stream).await,
+        (false, 1, b"4small".to_vec())
+    );
+
+    // A message emitted with compression disabled
+    let raw = format!("4raw{}", "hello world ".repeat(100));
+    write_frame(&mut stream, 0x80 | 1, raw.as_bytes()).await;
+    assert_eq!(read_frame(&mut stream).await, (false, 1, raw.into_bytes()));
+
+    // The context of the server is still in sync
+    write_frame(&mut stream, 0x80 | 1, msg.as_bytes()).await;
+    let (rsv1, _, payload) = read_frame(&mut stream).await;
+    assert!(rsv1);
+    assert_eq!(inflate(&mut decompress, &payload), msg.as_bytes());
+}
+
+#[tokio::test]
+pub async fn negotiated_params() {
+    create_server(
+        3201,
+        DeflateConfig {
+            threshold: 64,
+            client_no_context_takeover: true,
+            client_max_window_bits: 12,
+            ..Default::default()
+        },
+    );
+    let (mut stream, head) = connect(
+        3201,
+        "x-webkit-deflate-frame, permessage-deflate; server_max_window_bits=16, \
+        permessage-deflate; server_no_context_takeover; server_max_window_bits=10; client_max_window_bits",
+    )
+    .await;
+    assert!(head.contains(
+        "\r\nsec-websocket-extensions: permessage-deflate; server_no_context_takeover; \
+        client_no_context_takeover; server_max_window_bits=10; client_max_window_bits=12\r\n"
+    ));
+    read_frame(&mut stream).await;
+
+    let msg = format!("4{}", "hello world ".repeat(80));
+    for _ in 0..2 {
+        write_frame(&mut stream, 0x80 | 1, msg.as_bytes()).await;
+        let (rsv1, _, payload) = read_frame(&mut stream).await;
+        assert!(rsv1);
+        // Without context takeover, each message can be decompressed on its own
+        assert_eq!(
+            inflate(&mut Decompress::new(false), &payload),
+            msg.as_bytes()
+        );
+    }
+
+    // The messages larger than the window are not compressed
+    let large = format!("4{}", "hello world ".repeat(100));
+    write_frame(&mut stream, 0x80 | 1, large.as_bytes()).await;
+    assert_eq!(
+        read_frame(&mut stream).await,
+        (false, 1, large.into_bytes())
+    );
+}
+
+#[tokio::test]
+pub async fn not_negotiated() {
+    create_server(3202, DeflateConfig::default());
+    let (mut ws, res) = tokio_tungstenite::connect_async(
+        "ws://127.0.0.1:3202/engine.io/?EIO=4&transport=websocket",
+    )
+    .await
+    .unwrap();
+    assert!(res.headers().get("Sec-WebSocket-Extensions").is_none());
+    ws.next().await.unwrap().unwrap();
+
+    // The client would reject a compressed message
+    let msg = format!("4{}", "hello world ".repeat(200));
+    ws.send(Message::Text(msg.clone())).await.unwrap();
+    assert_eq!(ws.next().await.unwrap().unwrap(), Message::Text(msg));
+}
@@ -1,6 +1,9 @@
 use hyper::Server;
 use serde_json::Value;
-use socketioxide::SocketIo;
+use socketioxide::{
+    extract::{AckSender, Bin, Data, SocketRef},
+    SocketIo,
+};
 use tracing::info;
 use tracing_subscriber::FmtSubscriber;
 
@@ -9,29 +12,41 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::subscriber::set_global_default(FmtSubscriber::default())?;
 
     let (service, io) = SocketIo::new_svc();
-    io.ns("/", |socket, auth: Value| async move {
-        info!("Socket.IO connected: {:?} {:?}", socket.ns(), socket.id);
-        socket.emit("auth", auth).ok();
-
-        socket.on("message", |socket, data: Value, bin, _| async move {
-            info!("Received event: {:?} {:?}", data, bin);
-            socket.bin(bin).emit("message-back", data).ok();
-        });
-
-        socket.on("message-with-ack", |_, data: Value, bin, ack| async move {
-            info!("Received event: {:?} {:?}", data, bin);
-            ack.bin(bin).send(data).ok();
-        });
-
-        socket.on_disconnect(|socket, reason| async move {
-            info!("Socket.IO disconnected: {} {}", socket.id, reason);
-        });
-    });
-
-    io.ns("/custom", |socket, auth: Value| async move {
-        info!("Socket.IO connected on: {:?} {:?}", socket.ns(), socket.id);
-        socket.emit("auth", auth).ok();
-    });
+    io.ns(
+        "/",
+        |socket: SocketRef, Data(auth): Data<Value>| async move {
+            info!("Socket.IO connected: {:?} {:?}", socket.ns(), socket.id);
+            socket.emit("auth", auth).ok();
+
+            socket.on(
+                "message",
+                |socket: SocketRef, Data(data): Data<Value>, Bin(bin)| async move {
+                    info!("Received event: {:?} {:?}", data, bin);
+                    socket.bin(bin).emit("message-back", data).await.ok();
+                },
+            );
+
+            socket.on(
+                "message-with-ack",
+                |Data(data): Data<Value>, Bin(bin), ack: AckSender| async move {
+                    info!("Received event: {:?} {:?}", data, bin);
+                    ack.bin(bin).send(data).ok();
+                },
+            );
+
+            socket.on_disconnect(|socket, reason| async move {
+                info!("Socket.IO disconnected: {} {}", socket.id, reason);
+            });
+        },
+    );
+
+    io.ns(
+        "/custom",
+        |socket: SocketRef, Data(auth): Data<Value>| async move {
+            info!("Socket.IO connected on: {:?} {:?}", socket.ns(), socket.id);
+            socket.emit("auth", auth).ok();
+        },
+    );
     info!("Starting server");
 
     Server::bind(&"127.0.0.1:3000".parse().unwrap())
@@ -0,0 +1,340 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+use engineioxide::{
+    client::{ClientConfig as EngineConfig, ClientEvent, EngineClient},
+    config::TransportType,
+};
+use socketioxide::{
+    packet::Packet,
+    parser::{DefaultParser, EncodedPacket, Parser, ParserState},
+    DisconnectReason,
+};
+use tokio::sync::mpsc::{error::TrySendError, UnboundedReceiver};
+
+use crate::{
+    errors::Error,
+    socket::{SocketBuilder, SocketInner},
+};
+
+/// Configuration of a [`Client`]
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// The url of the server, e.g. `http://localhost:3000`
+    pub url: String,
+
+    /// The path of the socket.io endpoint of the server.
+    /// Defaults to `/socket.io/`.
+    pub path: String,
+
+    /// Allowed transports, represented as a bitfield.
+    /// Defaults to polling upgraded to websocket.
+    pub transports: u8,
+
+    /// Whether the client should reconnect when the connection is lost.
+    /// Defaults to true.
+    pub reconnection: bool,
+
+    /// The number of reconnection attempts before giving up.
+    /// Defaults to 5.
+    pub reconnection_attempts: usize,
+
+    /// The delay before each reconnection attempt.
+    /// Defaults to 1 second.
+    pub reconnection_delay: Duration,
+
+    /// The amount of time the client will wait for an acknowledgement from the server.
+    /// Defaults to 5 seconds.
+    pub ack_timeout: Duration,
+
+    /// The parser used to encode and decode the packets, it must be the same as the server one.
+    /// Defaults to the [`DefaultParser`].
+    pub parser: Arc<dyn Parser>,
+}
+
+impl ClientConfig {
+    fn engine_config(&self) -> EngineConfig {
+        let url = format!("{}{}", self.url.trim_end_matches('/'), self.path);
+        let mut config = EngineConfig::builder(url).build();
+        config.transports = self.transports;
+        config
+    }
+}
+
+/// A builder to create a [`Client`] with custom configuration
+pub struct ClientBuilder {
+    config: ClientConfig,
+}
+
+impl ClientBuilder {
+    /// Create a new [`ClientBuilder`] with the default configuration
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            config: ClientConfig {
+                url: url.into(),
+                path: "/socket.io/".to_string(),
+                transports: TransportType::Polling as u8 | TransportType::Websocket as u8,
+                reconnection: true,
+                reconnection_attempts: 5,
+                reconnection_delay: Duration::from_secs(1),
+                ack_timeout: Duration::from_secs(5),
+                parser: Arc::new(DefaultParser),
+            },
+        }
+    }
+
+    /// The path of the socket.io endpoint of the server.
+    /// Defaults to `/socket.io/`.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.config.path = path.into();
+        self
+    }
+
+    /// Allowed transports for this client
+    ///
+    /// The `transports` array should have a size of 1 or 2
+    ///
+    /// Defaults to :
+    /// `[TransportType::Polling, TransportType::Websocket]`
+    pub fn transports<const N: usize>(mut self, transports: [TransportType; N]) -> Self {
+        assert!(N > 0 && N <= 2);
+        self.config.transports = 0;
+        for transport in transports {
+            self.config.transports |= transport as u8;
+        }
+        self
+    }
+
+    /// Whether the client should reconnect when the connection is lost.
+    /// Defaults to true.
+    pub fn reconnection(mut self, reconnection: bool) -> Self {
+        self.config.reconnection = reconnection;
+        self
+    }
+
+    /// The number of reconnection attempts before giving up.
+    /// Defaults to 5.
+    pub fn reconnection_attempts(mut self, attempts: usize) -> Self {
+        self.config.reconnection_attempts = attempts;
+        self
+    }
+
+    /// The delay before each reconnection attempt.
+    /// Defaults to 1 second.
+    pub fn reconnection_delay(mut self, delay: Duration) -> Self {
+        self.config.reconnection_delay = delay;
+        self
+    }
+
+    /// The amount of time the client will wait for an acknowledgement from the server.
+    /// Defaults to 5 seconds.
+    pub fn ack_timeout(mut self, ack_timeout: Duration) -> Self {
+        self.config.ack_timeout = ack_timeout;
+        self
+    }
+
+    /// Use a custom [`Parser`] to encode and decode the packets, it must be the same as the server one.
+    pub fn with_parser<P: Parser>(mut self, parser: P) -> Self {
+        self.config.parser = Arc::new(parser);
+        self
+    }
+
+    /// Open the connection to the server
+    pub async fn connect(self) -> Result<Client, Error> {
+        let (engine, rx) = EngineClient::connect(self.config.engine_config()).await?;
+        let inner = Arc::new(ClientInner {
+            config: self.config,
+            engine: RwLock::new(Some(engine)),
+            sockets: RwLock::new(HashMap::new()),
+            closed: AtomicBool::new(false),
+        });
+        tokio::spawn(inner.clone().run(rx));
+        Ok(Client { inner })
+    }
+}
+
+/// A connection to a socket.io server, shared by the [`Socket`](crate::Socket)s
+/// connected to each namespace.
+#[derive(Debug, Clone)]
+pub struct Client {
+    inner: Arc<ClientInner>,
+}
+
+impl Client {
+    /// Create a new [`ClientBuilder`] to connect to the server at the given url
+    pub fn builder(url: impl Into<String>) -> ClientBuilder {
+        ClientBuilder::new(url)
+    }
+
+    /// Connect to the server with the default configuration
+    pub async fn connect(url: impl Into<String>) -> Result<Self, Error> {
+        ClientBuilder::new(url).connect().await
+    }
+
+    /// Create a [`SocketBuilder`] to connect to a namespace.
+    ///
+    /// Event handlers should be registered on the builder so that no event
+    /// emitted by the server on connection is missed.
+    pub fn socket(&self, ns: impl Into<String>) -> SocketBuilder {
+        SocketBuilder::new(self.inner.clone(), ns.into())
+    }
+
+    /// The transport currently used by the client, if it is connected
+    pub fn transport(&self) -> Option<TransportType> {
+        let engine = self.inner.engine.read().unwrap();
+        engine.as_ref().map(|engine| engine.transport())
+    }
+
+    /// Disconnect all the sockets and close the connection, the client will not reconnect
+    pub fn close(&self) {
+        self.inner.closed.store(true, Ordering::SeqCst);
+        let sockets: Vec<_> = self
+            .inner
+            .sockets
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect();
+        for socket in sockets {
+            socket.disconnect().ok();
+        }
+        if let Some(engine) = self.inner.engine.write().unwrap().take() {
+            engine.close();
+        }
+    }
+}
+
+pub(crate) struct ClientInner {
+    pub(crate) config: ClientConfig,
+    engine: RwLock<Option<EngineClient>>,
+    pub(crate) sockets: RwLock<HashMap<String, Arc<SocketInner>>>,
+    closed: AtomicBool,
+}
+
+impl ClientInner {
+    /// Encode a packet and send it to the server
+    pub(crate) fn send(&self, packet: Packet) -> Result<(), Error> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("sending packet: {:?}", packet);
+        let engine = self.engine.read().unwrap().clone();
+        let engine = engine.ok_or(Error::Disconnected)?;
+        match self.config.parser.encode(packet)? {
+            EncodedPacket::Text(msg, bin) => {
+                engine.emit(msg).map_err(send_error)?;
+                for bin in bin {
+                    engine.emit_binary(bin).map_err(send_error)?;
+                }
+            }
+            EncodedPacket::Binary(data) => engine.emit_binary(data).map_err(send_error)?,
+        }
+        Ok(())
+    }
+
+    /// Receive the engine.io events and reconnect when the connection is lost
+    async fn run(self: Arc<Self>, mut rx: UnboundedReceiver<ClientEvent>) {
+        loop {
+            let reason = self.forward_to_sockets(&mut rx).await;
+            self.engine.write().unwrap().take();
+            let sockets: Vec<_> = self.sockets.read().unwrap().values().cloned().collect();
+            for socket in sockets {
+                socket.on_transport_close(reason.clone());
+            }
+
+            if !self.config.reconnection || self.closed.load(Ordering::SeqCst) {
+                break;
+            }
+            match self.reconnect().await {
+                Some(new_rx) => rx = new_rx,
+                None => break,
+            }
+        }
+        self.sockets.write().unwrap().clear();
+    }
+
+    /// Decode the messages received from the server and forward the packets to the sockets,
+    /// until the connection is closed
+    async fn forward_to_sockets(
+        &self,
+        rx: &mut UnboundedReceiver<ClientEvent>,
+    ) -> DisconnectReason {
+        let state = ParserState::default();
+        loop {
+            let res = match rx.recv().await {
+                Some(ClientEvent::Message(msg)) => self.config.parser.decode_str(&state, msg),
+                Some(ClientEvent::Binary(data)) => self.config.parser.decode_bin(&state, data),
+                Some(ClientEvent::Close(reason)) => return reason.into(),
+                None => return DisconnectReason::TransportClose,
+            };
+            match res {
+                Ok(Some(packet)) => self.on_packet(packet),
+                Ok(None) => (),
+                Err(_e) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!("error decoding packet: {:?}", _e);
+                }
+            }
+        }
+    }
+
+    fn on_packet(&self, packet: Packet) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("received packet: {:?}", packet);
+        let socket = self.sockets.read().unwrap().get(&packet.ns).cloned();
+        if let Some(socket) = socket {
+            socket.on_packet(packet.inner);
+        }
+    }
+
+    /// Try to reconnect to the server and to reconnect all the sockets to their namespace.
+    ///
+    /// Returns the receiver of the new connection or `None` if all the attempts failed
+    async fn reconnect(&self) -> Option<UnboundedReceiver<ClientEvent>> {
+        for _attempt in 1..=self.config.reconnection_attempts {
+            tokio::time::sleep(self.config.reconnection_delay).await;
+            if self.closed.load(Ordering::SeqCst) {
+                return None;
+            }
+            #[cfg(feature = "tracing")]
+            tracing::debug!("reconnection attempt {}", _attempt);
+            match EngineClient::connect(self.config.engine_config()).await {
+                Ok((engine, rx)) => {
+                    self.engine.write().unwrap().replace(engine);
+                    let sockets: Vec<_> = self.sockets.read().unwrap().values().cloned().collect();
+                    for socket in sockets {
+                        socket.send_connect().ok();
+                    }
+                    return Some(rx);
+                }
+                Err(_e) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!("reconnection error: {:?}", _e);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl std::fmt::Debug for ClientInner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Client")
+            .field("config", &self.config)
+            .field("engine", &self.engine)
+            .field("closed", &self.closed)
+            .finish()
+    }
+}
+
+fn send_error<T>(err: TrySendError<T>) -> Error {
+    match err {
+        TrySendError::Full(_) => Error::InternalChannelFull,
+        TrySendError::Closed(_) => Error::Disconnected,
+    }
+}
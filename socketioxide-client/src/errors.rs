@@ -0,0 +1,38 @@
+use engineioxide::errors::Error as EIoError;
+use serde_json::Value;
+use socketioxide::ParserError;
+
+/// Error type for the socket.io client
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("engine.io error: {0}")]
+    EngineIo(Box<EIoError>),
+
+    #[error("error encoding or decoding packet: {0}")]
+    Parser(#[from] ParserError),
+
+    #[error("error serializing or deserializing data: {0:?}")]
+    Serialize(#[from] serde_json::Error),
+
+    /// The server refused the connection to the namespace, for example from a middleware
+    #[error("connection refused by the server: {message}")]
+    ConnectRefused {
+        message: String,
+        data: Option<Value>,
+    },
+
+    #[error("socket is not connected")]
+    Disconnected,
+
+    #[error("internal channel full, the server is not reading fast enough")]
+    InternalChannelFull,
+
+    #[error("ack timeout")]
+    AckTimeout,
+}
+
+impl From<EIoError> for Error {
+    fn from(err: EIoError) -> Self {
+        Error::EngineIo(Box::new(err))
+    }
+}
@@ -0,0 +1,41 @@
+//! Socketioxide-client is a socket.io client implementation in rust,
+//! built on the [`engineioxide`] client and the [`socketioxide`] packets and parsers.
+//!
+//! ## Features :
+//! * Polling and websocket transports, with the upgrade from polling to websocket
+//! * Namespaces
+//! * Acknowledgements in both directions
+//! * Binary payloads
+//! * Reconnection, with connection state recovery if it is enabled on the server
+//!
+//! TLS connections and the socket.io v4 protocol are not supported.
+//!
+//! ## Example :
+//! ```no_run
+//! # use socketioxide_client::Client;
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = Client::connect("http://localhost:3000").await.unwrap();
+//! let socket = client
+//!     .socket("/")
+//!     .on("message", |socket, msg| {
+//!         let data: String = msg.data().unwrap();
+//!         socket.emit("message-back", data).ok();
+//!     })
+//!     .connect()
+//!     .await
+//!     .unwrap();
+//!
+//! let (res, _) = socket.emit_with_ack::<String>("hello", "world").await.unwrap();
+//! println!("ack: {res}");
+//! # }
+//! ```
+
+pub use client::{Client, ClientBuilder, ClientConfig};
+pub use engineioxide::config::TransportType;
+pub use errors::Error;
+pub use socket::{AckSender, BinEmitter, Message, Socket, SocketBuilder};
+
+mod client;
+mod errors;
+mod socket;
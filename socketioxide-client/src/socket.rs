@@ -0,0 +1,469 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicI64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+};
+
+use engineioxide::sid::Sid;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use socketioxide::{
+    handler::AckResponse,
+    packet::{BinaryPacket, ConnectPacket, Packet, PacketData},
+    DisconnectReason,
+};
+use tokio::sync::oneshot;
+
+use crate::{client::ClientInner, errors::Error};
+
+type EventHandler = Arc<dyn Fn(Socket, Message) + Send + Sync>;
+type ConnectHandler = Arc<dyn Fn(Socket) + Send + Sync>;
+type DisconnectHandler = Arc<dyn Fn(Socket, DisconnectReason) + Send + Sync>;
+
+/// A builder to connect a [`Socket`] to a namespace, created with [`Client::socket`](crate::Client::socket).
+pub struct SocketBuilder {
+    client: Arc<ClientInner>,
+    ns: String,
+    auth: Option<Value>,
+    handlers: HashMap<String, EventHandler>,
+    connect_handler: Option<ConnectHandler>,
+    disconnect_handler: Option<DisconnectHandler>,
+}
+
+impl SocketBuilder {
+    pub(crate) fn new(client: Arc<ClientInner>, ns: String) -> Self {
+        Self {
+            client,
+            ns,
+            auth: None,
+            handlers: HashMap::new(),
+            connect_handler: None,
+            disconnect_handler: None,
+        }
+    }
+
+    /// The auth payload sent with the connect packet, it can be read by the server connect handler.
+    pub fn auth(mut self, auth: impl Serialize) -> Result<Self, Error> {
+        self.auth = Some(serde_json::to_value(auth)?);
+        Ok(self)
+    }
+
+    /// Register a handler for an event, see [`Socket::on`]
+    pub fn on(
+        mut self,
+        event: impl Into<String>,
+        handler: impl Fn(Socket, Message) + Send + Sync + 'static,
+    ) -> Self {
+        self.handlers.insert(event.into(), Arc::new(handler));
+        self
+    }
+
+    /// Register a handler called each time the socket is connected or reconnected to the namespace
+    pub fn on_connect(mut self, handler: impl Fn(Socket) + Send + Sync + 'static) -> Self {
+        self.connect_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Register a handler called each time the socket is disconnected from the namespace,
+    /// it may be called several times if the client reconnects.
+    pub fn on_disconnect(
+        mut self,
+        handler: impl Fn(Socket, DisconnectReason) + Send + Sync + 'static,
+    ) -> Self {
+        self.disconnect_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Send the connect packet and wait for the server to accept or refuse the connection
+    pub async fn connect(self) -> Result<Socket, Error> {
+        let (tx, rx) = oneshot::channel();
+        let socket = Arc::new(SocketInner {
+            ns: self.ns,
+            client: self.client,
+            auth: self.auth,
+            id: RwLock::new(None),
+            pid: RwLock::new(None),
+            connected: AtomicBool::new(false),
+            handlers: RwLock::new(self.handlers),
+            connect_handler: self.connect_handler,
+            disconnect_handler: self.disconnect_handler,
+            connect_tx: Mutex::new(Some(tx)),
+            acks: Mutex::new(HashMap::new()),
+            ack_counter: AtomicI64::new(0),
+        });
+        let sockets = &socket.client.sockets;
+        sockets
+            .write()
+            .unwrap()
+            .insert(socket.ns.clone(), socket.clone());
+        if let Err(e) = socket.send_connect() {
+            sockets.write().unwrap().remove(&socket.ns);
+            return Err(e);
+        }
+        rx.await.map_err(|_| Error::Disconnected)??;
+        Ok(Socket { inner: socket })
+    }
+}
+
+/// A socket connected to a namespace of the server
+#[derive(Debug, Clone)]
+pub struct Socket {
+    inner: Arc<SocketInner>,
+}
+
+impl Socket {
+    /// The id of the socket given by the server, `None` if the socket was never connected
+    pub fn id(&self) -> Option<Sid> {
+        *self.inner.id.read().unwrap()
+    }
+
+    /// The namespace of the socket
+    pub fn ns(&self) -> &str {
+        &self.inner.ns
+    }
+
+    pub fn connected(&self) -> bool {
+        self.inner.connected.load(Ordering::SeqCst)
+    }
+
+    /// Register a handler for an event, it replaces any handler previously registered for this event.
+    ///
+    /// The handler is called from the task reading the connection,
+    /// long running work or awaiting acknowledgements should be done in a spawned task.
+    ///
+    /// #### Example
+    /// ```no_run
+    /// # use socketioxide_client::Client;
+    /// # async fn doc(client: Client) {
+    /// let socket = client.socket("/").connect().await.unwrap();
+    /// socket.on("ping", |socket, msg| {
+    ///     let data: String = msg.data().unwrap();
+    ///     socket.emit("pong", data).ok();
+    /// });
+    /// # }
+    /// ```
+    pub fn on(
+        &self,
+        event: impl Into<String>,
+        handler: impl Fn(Socket, Message) + Send + Sync + 'static,
+    ) {
+        let mut handlers = self.inner.handlers.write().unwrap();
+        handlers.insert(event.into(), Arc::new(handler));
+    }
+
+    /// Emit an event to the server.
+    ///
+    /// Like with the server, a tuple or an array is sent as multiple arguments,
+    /// any other value is sent as a single argument.
+    pub fn emit(&self, event: impl Into<String>, data: impl Serialize) -> Result<(), Error> {
+        self.inner.emit(event.into(), data, vec![], None)
+    }
+
+    /// Emit an event to the server and wait for the acknowledgement.
+    ///
+    /// It returns [`Error::AckTimeout`] if the server does not answer
+    /// within the [`ack_timeout`](crate::ClientBuilder::ack_timeout).
+    pub async fn emit_with_ack<V: DeserializeOwned>(
+        &self,
+        event: impl Into<String>,
+        data: impl Serialize,
+    ) -> Result<AckResponse<V>, Error> {
+        self.inner.emit_with_ack(event.into(), data, vec![]).await
+    }
+
+    /// Add binary payloads to the next emitted event
+    pub fn bin(&self, bin: Vec<Vec<u8>>) -> BinEmitter<'_> {
+        BinEmitter { socket: self, bin }
+    }
+
+    /// Disconnect the socket from the namespace, it will not be reconnected.
+    pub fn disconnect(&self) -> Result<(), Error> {
+        self.inner.disconnect()
+    }
+}
+
+/// An emitter sending an event with binary payloads, created with [`Socket::bin`]
+pub struct BinEmitter<'a> {
+    socket: &'a Socket,
+    bin: Vec<Vec<u8>>,
+}
+
+impl BinEmitter<'_> {
+    /// Emit an event with binary payloads to the server
+    pub fn emit(self, event: impl Into<String>, data: impl Serialize) -> Result<(), Error> {
+        self.socket.inner.emit(event.into(), data, self.bin, None)
+    }
+
+    /// Emit an event with binary payloads to the server and wait for the acknowledgement
+    pub async fn emit_with_ack<V: DeserializeOwned>(
+        self,
+        event: impl Into<String>,
+        data: impl Serialize,
+    ) -> Result<AckResponse<V>, Error> {
+        self.socket
+            .inner
+            .emit_with_ack(event.into(), data, self.bin)
+            .await
+    }
+}
+
+/// An event received from the server
+#[derive(Debug)]
+pub struct Message {
+    /// The name of the event
+    pub event: String,
+    /// The binary payloads of the event
+    pub bin: Vec<Vec<u8>>,
+    /// The ack sender, if the server waits for an acknowledgement
+    pub ack: Option<AckSender>,
+    data: Value,
+}
+
+impl Message {
+    /// Deserialize the arguments of the event.
+    ///
+    /// Like the server [`Data`](socketioxide::extract::Data) extractor, a single argument is
+    /// deserialized on its own and multiple arguments are deserialized as a tuple or a vec.
+    pub fn data<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        from_args(self.data.clone())
+    }
+}
+
+/// Send an acknowledgement to an event received from the server
+#[derive(Debug)]
+pub struct AckSender {
+    socket: Arc<SocketInner>,
+    ack_id: i64,
+    binary: Vec<Vec<u8>>,
+}
+
+impl AckSender {
+    /// Add binary payloads to the ack response
+    pub fn bin(mut self, bin: Vec<Vec<u8>>) -> Self {
+        self.binary = bin;
+        self
+    }
+
+    /// Send the ack response to the server
+    pub fn send(self, data: impl Serialize) -> Result<(), Error> {
+        let ns = self.socket.ns.clone();
+        let data = serde_json::to_value(data)?;
+        let packet = if self.binary.is_empty() {
+            Packet::ack(ns, data, self.ack_id)
+        } else {
+            Packet::bin_ack(ns, data, self.binary, self.ack_id)
+        };
+        self.socket.client.send(packet)
+    }
+}
+
+pub(crate) struct SocketInner {
+    ns: String,
+    pub(crate) client: Arc<ClientInner>,
+    auth: Option<Value>,
+    id: RwLock<Option<Sid>>,
+    /// The private session id sent back on reconnection to recover the socket state
+    pid: RwLock<Option<Sid>>,
+    connected: AtomicBool,
+
+    handlers: RwLock<HashMap<String, EventHandler>>,
+    connect_handler: Option<ConnectHandler>,
+    disconnect_handler: Option<DisconnectHandler>,
+
+    /// Notified when the first connection is accepted or refused
+    connect_tx: Mutex<Option<oneshot::Sender<Result<(), Error>>>>,
+    acks: Mutex<HashMap<i64, oneshot::Sender<AckResponse<Value>>>>,
+    ack_counter: AtomicI64,
+}
+
+impl SocketInner {
+    /// Send the connect packet with the auth payload and the private session id if there is one
+    pub(crate) fn send_connect(&self) -> Result<(), Error> {
+        let mut auth = self.auth.clone();
+        if let Some(pid) = *self.pid.read().unwrap() {
+            match auth {
+                Some(Value::Object(ref mut auth)) => {
+                    auth.insert("pid".into(), pid.to_string().into());
+                }
+                None => auth = Some(serde_json::json!({ "pid": pid })),
+                Some(_) => (),
+            }
+        }
+        let auth = auth.map(|auth| serde_json::to_string(&auth)).transpose()?;
+        let packet = Packet {
+            inner: PacketData::Connect(auth),
+            ns: self.ns.clone(),
+        };
+        self.client.send(packet)
+    }
+
+    fn emit(
+        &self,
+        event: String,
+        data: impl Serialize,
+        bin: Vec<Vec<u8>>,
+        ack_id: Option<i64>,
+    ) -> Result<(), Error> {
+        if !self.connected.load(Ordering::SeqCst) {
+            return Err(Error::Disconnected);
+        }
+        let data = serde_json::to_value(data)?;
+        let inner = if bin.is_empty() {
+            PacketData::Event(event, data, ack_id)
+        } else {
+            PacketData::BinaryEvent(event, BinaryPacket::outgoing(data, bin), ack_id)
+        };
+        self.client.send(Packet {
+            inner,
+            ns: self.ns.clone(),
+        })
+    }
+
+    async fn emit_with_ack<V: DeserializeOwned>(
+        &self,
+        event: String,
+        data: impl Serialize,
+        bin: Vec<Vec<u8>>,
+    ) -> Result<AckResponse<V>, Error> {
+        let ack_id = self.ack_counter.fetch_add(1, Ordering::SeqCst) + 1;
+        let (tx, rx) = oneshot::channel();
+        self.acks.lock().unwrap().insert(ack_id, tx);
+        if let Err(e) = self.emit(event, data, bin, Some(ack_id)) {
+            self.acks.lock().unwrap().remove(&ack_id);
+            return Err(e);
+        }
+        match tokio::time::timeout(self.client.config.ack_timeout, rx).await {
+            Ok(Ok((data, bin))) => Ok((from_args(data)?, bin)),
+            Ok(Err(_)) => Err(Error::Disconnected),
+            Err(_) => {
+                self.acks.lock().unwrap().remove(&ack_id);
+                Err(Error::AckTimeout)
+            }
+        }
+    }
+
+    pub(crate) fn disconnect(self: &Arc<Self>) -> Result<(), Error> {
+        let res = self.client.send(Packet::disconnect(self.ns.clone()));
+        self.close(DisconnectReason::ClientNSDisconnect);
+        res
+    }
+
+    /// Remove the socket from the client and call the disconnect handler
+    fn close(self: &Arc<Self>, reason: DisconnectReason) {
+        let mut sockets = self.client.sockets.write().unwrap();
+        if sockets
+            .get(&self.ns)
+            .map_or(false, |s| Arc::ptr_eq(s, self))
+        {
+            sockets.remove(&self.ns);
+        }
+        drop(sockets);
+        self.on_transport_close(reason);
+    }
+
+    /// Called when the connection is lost, the socket is kept to be reconnected
+    pub(crate) fn on_transport_close(self: &Arc<Self>, reason: DisconnectReason) {
+        self.acks.lock().unwrap().clear();
+        if self.connected.swap(false, Ordering::SeqCst) {
+            if let Some(handler) = &self.disconnect_handler {
+                handler(self.socket(), reason);
+            }
+        }
+    }
+
+    pub(crate) fn on_packet(self: &Arc<Self>, packet: PacketData) {
+        match packet {
+            PacketData::Connect(data) => {
+                let data: Option<ConnectPacket> = data.and_then(|d| serde_json::from_str(&d).ok());
+                if let Some(data) = data {
+                    self.id.write().unwrap().replace(data.sid);
+                    *self.pid.write().unwrap() = data.pid;
+                }
+                self.connected.store(true, Ordering::SeqCst);
+                if let Some(tx) = self.connect_tx.lock().unwrap().take() {
+                    tx.send(Ok(())).ok();
+                }
+                if let Some(handler) = &self.connect_handler {
+                    handler(self.socket());
+                }
+            }
+            PacketData::ConnectError(err) => {
+                self.close(DisconnectReason::ServerNSDisconnect);
+                if let Some(tx) = self.connect_tx.lock().unwrap().take() {
+                    let err = Error::ConnectRefused {
+                        message: err.message,
+                        data: err.data,
+                    };
+                    tx.send(Err(err)).ok();
+                }
+            }
+            PacketData::Disconnect => self.close(DisconnectReason::ServerNSDisconnect),
+            PacketData::Event(event, data, ack_id) => self.on_event(event, data, vec![], ack_id),
+            PacketData::BinaryEvent(event, packet, ack_id) => {
+                self.on_event(event, packet.data, packet.bin, ack_id)
+            }
+            PacketData::EventAck(data, ack_id) => self.on_ack(data, vec![], ack_id),
+            PacketData::BinaryAck(packet, ack_id) => self.on_ack(packet.data, packet.bin, ack_id),
+        }
+    }
+
+    fn on_event(
+        self: &Arc<Self>,
+        event: String,
+        data: Value,
+        bin: Vec<Vec<u8>>,
+        ack_id: Option<i64>,
+    ) {
+        let handler = self.handlers.read().unwrap().get(&event).cloned();
+        if let Some(handler) = handler {
+            let ack = ack_id.map(|ack_id| AckSender {
+                socket: self.clone(),
+                ack_id,
+                binary: vec![],
+            });
+            let msg = Message {
+                event,
+                bin,
+                ack,
+                data,
+            };
+            handler(self.socket(), msg);
+        }
+    }
+
+    fn on_ack(&self, data: Value, bin: Vec<Vec<u8>>, ack_id: i64) {
+        if let Some(tx) = self.acks.lock().unwrap().remove(&ack_id) {
+            tx.send((data, bin)).ok();
+        }
+    }
+
+    fn socket(self: &Arc<Self>) -> Socket {
+        Socket {
+            inner: self.clone(),
+        }
+    }
+}
+
+impl std::fmt::Debug for SocketInner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Socket")
+            .field("ns", &self.ns)
+            .field("id", &self.id)
+            .field("connected", &self.connected)
+            .finish()
+    }
+}
+
+/// Deserialize the arguments of an event or an ack with the same rules as the server:
+/// a single argument is deserialized on its own, falling back to the array of arguments.
+fn from_args<T: DeserializeOwned>(args: Value) -> Result<T, serde_json::Error> {
+    match args {
+        Value::Array(args) if args.len() <= 1 => {
+            let arg = args.first().unwrap_or(&Value::Null);
+            T::deserialize(arg).or_else(|_| T::deserialize(Value::Array(args)))
+        }
+        args => T::deserialize(args),
+    }
+}
@@ -0,0 +1,272 @@
+//! Tests for the socket.io client against a socketioxide server.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use hyper::Server;
+use serde_json::{json, Value};
+use socketioxide::{
+    extract::{AckSender, Bin, Data, SocketRef},
+    ConnectError, DisconnectReason, SocketIo,
+};
+use socketioxide_client::{Client, Error, Message, TransportType};
+use tokio::sync::mpsc;
+
+fn create_server(port: u16) -> SocketIo {
+    let (svc, io) = SocketIo::builder()
+        .ping_interval(Duration::from_millis(300))
+        .ping_timeout(Duration::from_millis(200))
+        .build_svc();
+
+    let addr = &SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+    let server = Server::bind(addr).serve(svc.into_make_service());
+    tokio::spawn(server);
+
+    io.ns("/", |socket: SocketRef| async move {
+        socket.on(
+            "echo",
+            |socket: SocketRef, Data(data): Data<Value>, Bin(bin)| async move {
+                socket.bin(bin).emit("echo", data).await.ok();
+            },
+        );
+        socket.on(
+            "echo-ack",
+            |Data(data): Data<Value>, Bin(bin), ack: AckSender| async move {
+                ack.bin(bin).send(data).ok();
+            },
+        );
+    });
+    io
+}
+
+async fn recv<T>(rx: &mut mpsc::UnboundedReceiver<T>) -> T {
+    tokio::time::timeout(Duration::from_millis(500), rx.recv())
+        .await
+        .expect("timeout waiting for a message")
+        .unwrap()
+}
+
+/// Connect a socket to the main namespace and forward the echo events to a channel
+async fn echo_socket(
+    client: &Client,
+) -> (
+    socketioxide_client::Socket,
+    mpsc::UnboundedReceiver<Message>,
+) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let socket = client
+        .socket("/")
+        .on("echo", move |_, msg| tx.send(msg).unwrap())
+        .connect()
+        .await
+        .unwrap();
+    (socket, rx)
+}
+
+#[tokio::test]
+pub async fn polling_transport() {
+    create_server(3200);
+    let client = Client::builder("http://127.0.0.1:3200")
+        .transports([TransportType::Polling])
+        .connect()
+        .await
+        .unwrap();
+    let (socket, mut rx) = echo_socket(&client).await;
+    assert_eq!(client.transport(), Some(TransportType::Polling));
+    assert!(socket.id().is_some());
+
+    socket.emit("echo", ("foo", 1)).unwrap();
+    let msg = recv(&mut rx).await;
+    assert_eq!(msg.data::<(String, u32)>().unwrap(), ("foo".into(), 1));
+
+    // The connection is kept alive by answering the server pings
+    tokio::time::sleep(Duration::from_millis(800)).await;
+    socket.emit("echo", "bar").unwrap();
+    assert_eq!(recv(&mut rx).await.data::<String>().unwrap(), "bar");
+}
+
+#[tokio::test]
+pub async fn websocket_transport() {
+    create_server(3201);
+    let client = Client::builder("http://127.0.0.1:3201")
+        .transports([TransportType::Websocket])
+        .connect()
+        .await
+        .unwrap();
+    let (socket, mut rx) = echo_socket(&client).await;
+    assert_eq!(client.transport(), Some(TransportType::Websocket));
+
+    socket.emit("echo", json!({ "foo": "bar" })).unwrap();
+    let msg = recv(&mut rx).await;
+    assert_eq!(msg.data::<Value>().unwrap(), json!({ "foo": "bar" }));
+
+    tokio::time::sleep(Duration::from_millis(800)).await;
+    socket.emit("echo", "bar").unwrap();
+    assert_eq!(recv(&mut rx).await.data::<String>().unwrap(), "bar");
+}
+
+#[tokio::test]
+pub async fn upgrade_transport() {
+    create_server(3202);
+    let client = Client::connect("http://127.0.0.1:3202").await.unwrap();
+    assert_eq!(client.transport(), Some(TransportType::Websocket));
+    let (socket, mut rx) = echo_socket(&client).await;
+
+    for i in 0..10 {
+        socket.emit("echo", i).unwrap();
+    }
+    for i in 0..10 {
+        assert_eq!(recv(&mut rx).await.data::<u32>().unwrap(), i);
+    }
+}
+
+#[tokio::test]
+pub async fn namespaces() {
+    let io = create_server(3203);
+    io.ns(
+        "/admin",
+        |socket: SocketRef, Data(auth): Data<Value>| async move {
+            socket.emit("welcome", auth["name"].clone()).ok();
+        },
+    )
+    .with_middleware(|Data(auth): Data<Value>| async move {
+        match auth["token"].as_str() {
+            Some("secret") => Ok(()),
+            _ => Err(ConnectError::new("unauthorized").with_data(json!({ "code": 401 }))),
+        }
+    });
+    let client = Client::connect("http://127.0.0.1:3203").await.unwrap();
+
+    let res = client
+        .socket("/admin")
+        .auth(json!({ "token": "wrong" }))
+        .unwrap()
+        .connect()
+        .await;
+    match res {
+        Err(Error::ConnectRefused { message, data }) => {
+            assert_eq!(message, "unauthorized");
+            assert_eq!(data, Some(json!({ "code": 401 })));
+        }
+        res => panic!("unexpected connect result: {:?}", res),
+    }
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let admin = client
+        .socket("/admin")
+        .auth(json!({ "token": "secret", "name": "foo" }))
+        .unwrap()
+        .on("welcome", move |_, msg| tx.send(msg).unwrap())
+        .connect()
+        .await
+        .unwrap();
+    assert_eq!(recv(&mut rx).await.data::<String>().unwrap(), "foo");
+
+    // Both namespaces share the same connection
+    let (socket, mut rx) = echo_socket(&client).await;
+    socket.emit("echo", "bar").unwrap();
+    assert_eq!(recv(&mut rx).await.data::<String>().unwrap(), "bar");
+    assert!(admin.connected());
+    assert_eq!(admin.id(), socket.id());
+}
+
+#[tokio::test]
+pub async fn acks() {
+    let io = create_server(3204);
+    io.ns("/ack", |socket: SocketRef| async move {
+        let res = socket
+            .emit_with_ack::<(String, u32)>("ask", "question")
+            .await;
+        let (data, _) = res.unwrap();
+        socket.emit("answer", data).ok();
+    });
+    let client = Client::connect("http://127.0.0.1:3204").await.unwrap();
+
+    let (socket, _) = echo_socket(&client).await;
+    let (data, bin) = socket
+        .emit_with_ack::<(String, u32)>("echo-ack", ("foo", 2))
+        .await
+        .unwrap();
+    assert_eq!(data, ("foo".into(), 2));
+    assert!(bin.is_empty());
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    client
+        .socket("/ack")
+        .on("ask", |_, msg| {
+            assert_eq!(msg.data::<String>().unwrap(), "question");
+            msg.ack.unwrap().send(("response", 42)).unwrap();
+        })
+        .on("answer", move |_, msg| tx.send(msg).unwrap())
+        .connect()
+        .await
+        .unwrap();
+    let msg = recv(&mut rx).await;
+    assert_eq!(
+        msg.data::<(String, u32)>().unwrap(),
+        ("response".into(), 42)
+    );
+}
+
+#[tokio::test]
+pub async fn binary() {
+    create_server(3205);
+    let client = Client::connect("http://127.0.0.1:3205").await.unwrap();
+    let (socket, mut rx) = echo_socket(&client).await;
+
+    let bin = vec![vec![1, 2, 3], vec![4, 5, 6]];
+    socket.bin(bin.clone()).emit("echo", "foo").unwrap();
+    let msg = recv(&mut rx).await;
+    assert_eq!(msg.data::<String>().unwrap(), "foo");
+    assert_eq!(msg.bin, bin);
+
+    let (data, ack_bin) = socket
+        .bin(bin.clone())
+        .emit_with_ack::<String>("echo-ack", "bar")
+        .await
+        .unwrap();
+    assert_eq!(data, "bar");
+    assert_eq!(ack_bin, bin);
+}
+
+#[tokio::test]
+pub async fn reconnection() {
+    let io = create_server(3206);
+    let client = Client::builder("http://127.0.0.1:3206")
+        .reconnection_delay(Duration::from_millis(50))
+        .connect()
+        .await
+        .unwrap();
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let (disconnect_tx, mut disconnect_rx) = mpsc::unbounded_channel();
+    let socket = client
+        .socket("/")
+        .on_connect(move |socket| tx.send(socket.id().unwrap()).unwrap())
+        .on_disconnect(move |_, reason| disconnect_tx.send(reason).unwrap())
+        .connect()
+        .await
+        .unwrap();
+    let sid = recv(&mut rx).await;
+
+    // Close all the connections, the namespaces are kept so the client can reconnect
+    io.close().await;
+    assert_eq!(
+        recv(&mut disconnect_rx).await,
+        DisconnectReason::TransportClose
+    );
+    assert!(!socket.connected());
+
+    let new_sid = recv(&mut rx).await;
+    assert_ne!(sid, new_sid);
+    assert!(socket.connected());
+
+    client.close();
+    assert_eq!(
+        recv(&mut disconnect_rx).await,
+        DisconnectReason::ClientNSDisconnect
+    );
+    assert!(matches!(socket.emit("echo", ()), Err(Error::Disconnected)));
+}
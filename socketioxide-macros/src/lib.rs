@@ -0,0 +1,405 @@
+//! Derive macros to declare the events of a [socketioxide](https://docs.rs/socketioxide) server
+//! with typed data and ack responses, instead of free-form event names.
+//!
+//! They are re-exported by socketioxide with the `macros` feature.
+//!
+//! Each variant of the derived enum is an event:
+//! * a unit variant is an event without data,
+//! * a variant with a single field is an event whose data is this field,
+//! * a variant with multiple fields is an event with multiple arguments, its data is the tuple of the fields.
+//!
+//! The variants accept a `#[socketio(...)]` attribute with the following options:
+//! * `rename = "name"`: the name of the event, the snake case name of the variant by default.
+//! * `ack = Type`: the type of the ack response of the event.
+//!
+//! The generated methods are only wrappers over the string-based api
+//! ([`Socket::on`], [`Socket::emit`], [`Operators::emit`], ...), so both can be used together.
+//!
+//! #### Example
+//! ```
+//! use serde::{Deserialize, Serialize};
+//! use socketioxide::{extract::SocketRef, ClientEvents, ServerEvents, SocketIo};
+//!
+//! #[derive(Debug, Serialize, Deserialize)]
+//! struct Message {
+//!     room: String,
+//!     text: String,
+//! }
+//!
+//! /// The events sent by the clients
+//! #[derive(ClientEvents)]
+//! enum ClientEvent {
+//!     #[socketio(ack = Vec<String>)]
+//!     Join(String),
+//!     #[socketio(rename = "new message")]
+//!     NewMessage(Message),
+//!     Ping,
+//! }
+//!
+//! /// The events sent by the server
+//! #[derive(ServerEvents)]
+//! enum ServerEvent {
+//!     #[socketio(rename = "new message")]
+//!     NewMessage(Message),
+//!     Pong,
+//! }
+//!
+//! let (_, io) = SocketIo::new_svc();
+//! io.ns("/", |socket: SocketRef| async move {
+//!     socket.on_join(|socket, room, ack| async move {
+//!         socket.join(room).await.ok();
+//!         ack.send(socket.rooms().await.unwrap()).ok();
+//!     });
+//!     socket.on_new_message(|socket, msg| async move {
+//!         socket.within(msg.room.clone()).emit_new_message(msg).await.ok();
+//!     });
+//!     socket.on_ping(|socket| async move {
+//!         socket.emit_pong().ok();
+//!     });
+//! });
+//! ```
+//!
+//! [`Socket::on`]: https://docs.rs/socketioxide/latest/socketioxide/struct.Socket.html#method.on
+//! [`Socket::emit`]: https://docs.rs/socketioxide/latest/socketioxide/struct.Socket.html#method.emit
+//! [`Operators::emit`]: https://docs.rs/socketioxide/latest/socketioxide/struct.Operators.html#method.emit
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, LitStr, Type};
+
+/// Derive the events sent by the clients to the server.
+///
+/// It generates a `{Enum}Handlers` trait implemented for `Socket`, with an `on_{variant}` method per event
+/// to register a handler for this event.
+/// The handler is called with the socket, the data of the event if it has one,
+/// and a `TypedAckSender` if the event has an ack type.
+/// Like with `Socket::on`, it is not called if the data can't be deserialized.
+#[proc_macro_derive(ClientEvents, attributes(socketio))]
+pub fn derive_client_events(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, client_events).into()
+}
+
+/// Derive the events sent by the server to the clients.
+///
+/// It generates an `emit_{variant}` method per event in two traits:
+/// * `{Enum}Emitter`, implemented for `Socket` to emit the event to this socket.
+/// * `{Enum}Broadcaster`, implemented for `Operators` to broadcast the event to the selected sockets.
+///
+/// If the event has an ack type, the method waits for the ack response(s) of this type,
+/// like `Socket::emit_with_ack` and `Operators::emit_with_ack`.
+#[proc_macro_derive(ServerEvents, attributes(socketio))]
+pub fn derive_server_events(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input, server_events).into()
+}
+
+/// An event declared by a variant of the derived enum
+struct Event {
+    /// The name of the event
+    name: String,
+    /// The variant of the event
+    variant: Ident,
+    /// The snake case name of the variant, used to name the generated methods
+    ident: Ident,
+    /// The type of the data of the event, `None` if it has no data
+    data: Option<Type>,
+    /// The bindings of the fields of the variant
+    bindings: Vec<Ident>,
+    /// The type of the ack response of the event, `None` if it has no ack
+    ack: Option<Type>,
+}
+
+impl Event {
+    /// The variant with its fields bound, usable as a pattern and as an expression
+    fn variant(&self, enum_ident: &Ident) -> TokenStream2 {
+        let variant = &self.variant;
+        let bindings = &self.bindings;
+        match bindings.len() {
+            0 => quote!(#enum_ident::#variant),
+            _ => quote!(#enum_ident::#variant(#(#bindings),*)),
+        }
+    }
+
+    /// The data of the event built from the bindings of the fields, usable as a pattern and as an expression
+    fn data(&self) -> TokenStream2 {
+        let bindings = &self.bindings;
+        match bindings.len() {
+            1 => quote!(#(#bindings)*),
+            _ => quote!((#(#bindings),*)),
+        }
+    }
+
+    /// The arguments sent for the event, a tuple is sent as multiple arguments
+    fn args(&self) -> TokenStream2 {
+        let bindings = &self.bindings;
+        match bindings.len() {
+            // An empty array is sent without any argument
+            0 => quote!([(); 0]),
+            _ => quote!((#(#bindings,)*)),
+        }
+    }
+}
+
+fn expand(
+    input: &DeriveInput,
+    generate: fn(&DeriveInput, &[Event]) -> TokenStream2,
+) -> TokenStream2 {
+    match parse_events(input) {
+        Ok(events) => generate(input, &events),
+        Err(err) => err.to_compile_error(),
+    }
+}
+
+fn parse_events(input: &DeriveInput) -> syn::Result<Vec<Event>> {
+    let variants = match &input.data {
+        Data::Enum(data) => &data.variants,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "events can only be derived for an enum",
+            ))
+        }
+    };
+    variants
+        .iter()
+        .map(|variant| {
+            let snake_case = to_snake_case(&variant.ident.to_string());
+            let mut name = snake_case.clone();
+            let mut ack = None;
+            for attr in variant
+                .attrs
+                .iter()
+                .filter(|a| a.path().is_ident("socketio"))
+            {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("rename") {
+                        name = meta.value()?.parse::<LitStr>()?.value();
+                    } else if meta.path.is_ident("ack") {
+                        ack = Some(meta.value()?.parse::<Type>()?);
+                    } else {
+                        return Err(meta.error("expected `rename` or `ack`"));
+                    }
+                    Ok(())
+                })?;
+            }
+
+            let (data, bindings) =
+                match &variant.fields {
+                    Fields::Unit => (None, vec![]),
+                    Fields::Unnamed(fields) if fields.unnamed.len() == 1 => (
+                        Some(fields.unnamed[0].ty.clone()),
+                        vec![format_ident!("data")],
+                    ),
+                    Fields::Unnamed(fields) => {
+                        let types = fields.unnamed.iter().map(|f| &f.ty);
+                        let bindings = (0..fields.unnamed.len())
+                            .map(|i| format_ident!("data{}", i))
+                            .collect();
+                        (Some(syn::parse_quote!((#(#types),*))), bindings)
+                    }
+                    Fields::Named(fields) => return Err(syn::Error::new_spanned(
+                        fields,
+                        "an event can't have named fields, use a tuple variant or a struct as data",
+                    )),
+                };
+
+            Ok(Event {
+                name,
+                variant: variant.ident.clone(),
+                ident: Ident::new(&snake_case, variant.ident.span()),
+                data,
+                bindings,
+                ack,
+            })
+        })
+        .collect()
+}
+
+// The generated methods convert the data of an event to its variant and back,
+// so that the events that are handled or emitted are not reported as never constructed,
+// the other ones are reported like any unused enum variant.
+
+fn client_events(input: &DeriveInput, events: &[Event]) -> TokenStream2 {
+    let vis = &input.vis;
+    let enum_ident = &input.ident;
+    let trait_ident = format_ident!("{}Handlers", enum_ident);
+    let trait_doc = format!(
+        "Register typed handlers for the events of [`{}`]",
+        enum_ident
+    );
+
+    let mut methods = Vec::with_capacity(events.len());
+    let mut impls = Vec::with_capacity(events.len());
+    for event in events {
+        let name = &event.name;
+        let method = format_ident!("on_{}", event.ident);
+        let doc = format!("Register a handler for the `{}` event", name);
+        let variant = event.variant(enum_ident);
+
+        let mut args = vec![quote!(::socketioxide::extract::SocketRef<A>)];
+        let mut params = vec![quote!(socket: ::socketioxide::extract::SocketRef<A>)];
+        let mut values = vec![quote!(socket)];
+        if let Some(data_ty) = &event.data {
+            let data = event.data();
+            args.push(quote!(#data_ty));
+            params.push(quote! {
+                ::socketioxide::extract::Data(#data): ::socketioxide::extract::Data<#data_ty>
+            });
+            values.push(data);
+        }
+        if let Some(ack) = &event.ack {
+            args.push(quote!(::socketioxide::extract::TypedAckSender<#ack, A>));
+            params.push(quote!(ack: ::socketioxide::extract::TypedAckSender<#ack, A>));
+            values.push(quote!(ack));
+        }
+
+        let signature = quote! {
+            fn #method<F, Fut>(&self, handler: F)
+            where
+                F: Fn(#(#args),*) -> Fut + Send + Sync + 'static,
+                Fut: ::std::future::Future<Output = ()> + Send + 'static
+        };
+        methods.push(quote! {
+            #[doc = #doc]
+            #signature;
+        });
+        impls.push(quote! {
+            #signature
+            {
+                self.on(#name, move |#(#params),*| match #variant {
+                    #variant => handler(#(#values),*),
+                    _ => unreachable!(),
+                });
+            }
+        });
+    }
+
+    quote! {
+        #[doc = #trait_doc]
+        #[allow(dead_code)]
+        #vis trait #trait_ident<A: ::socketioxide::adapter::Adapter> {
+            #(#methods)*
+        }
+
+        #[allow(unreachable_patterns)]
+        impl<A: ::socketioxide::adapter::Adapter> #trait_ident<A> for ::socketioxide::Socket<A> {
+            #(#impls)*
+        }
+    }
+}
+
+fn server_events(input: &DeriveInput, events: &[Event]) -> TokenStream2 {
+    let vis = &input.vis;
+    let enum_ident = &input.ident;
+    let emitter_ident = format_ident!("{}Emitter", enum_ident);
+    let broadcaster_ident = format_ident!("{}Broadcaster", enum_ident);
+    let emitter_doc = format!("Emit the events of [`{}`] to a socket", enum_ident);
+    let broadcaster_doc = format!(
+        "Broadcast the events of [`{}`] to the selected sockets",
+        enum_ident
+    );
+
+    let mut emitter_methods = Vec::with_capacity(events.len());
+    let mut emitter_impls = Vec::with_capacity(events.len());
+    let mut broadcaster_methods = Vec::with_capacity(events.len());
+    let mut broadcaster_impls = Vec::with_capacity(events.len());
+    for event in events {
+        let name = &event.name;
+        let method = format_ident!("emit_{}", event.ident);
+        let doc = format!("Emit the `{}` event", name);
+        let variant = event.variant(enum_ident);
+        let data = event.data();
+        let args = event.args();
+        let (param, param_pat) = match &event.data {
+            Some(data_ty) => (quote!(, data: #data_ty), quote!(, #data: #data_ty)),
+            None => (quote!(), quote!()),
+        };
+
+        let (emitter_ret, emitter_emit, broadcaster_ret, broadcaster_emit) = match &event.ack {
+            None => (
+                quote!(Result<(), ::socketioxide::SendError>),
+                quote!(self.emit(#name, #args)),
+                quote!(Result<(), ::socketioxide::BroadcastError>),
+                quote!(self.emit(#name, #args).await),
+            ),
+            Some(ack) => (
+                quote!(Result<::socketioxide::handler::AckResponse<#ack>, ::socketioxide::AckError>),
+                quote!(self.emit_with_ack::<#ack>(#name, #args).await),
+                quote!(Result<::socketioxide::handler::AckStream<#ack>, ::socketioxide::BroadcastError>),
+                quote!(self.emit_with_ack::<#ack>(#name, #args).await),
+            ),
+        };
+        let emitter_async = event.ack.as_ref().map(|_| quote!(async));
+
+        emitter_methods.push(quote! {
+            #[doc = #doc]
+            #emitter_async fn #method(&self #param) -> #emitter_ret;
+        });
+        emitter_impls.push(quote! {
+            #emitter_async fn #method(&self #param_pat) -> #emitter_ret {
+                match #variant {
+                    #variant => #emitter_emit,
+                    _ => unreachable!(),
+                }
+            }
+        });
+        broadcaster_methods.push(quote! {
+            #[doc = #doc]
+            async fn #method(self #param) -> #broadcaster_ret;
+        });
+        broadcaster_impls.push(quote! {
+            async fn #method(self #param_pat) -> #broadcaster_ret {
+                match #variant {
+                    #variant => #broadcaster_emit,
+                    _ => unreachable!(),
+                }
+            }
+        });
+    }
+
+    quote! {
+        #[doc = #emitter_doc]
+        #[allow(dead_code)]
+        #[::socketioxide::async_trait]
+        #vis trait #emitter_ident {
+            #(#emitter_methods)*
+        }
+
+        #[allow(unreachable_patterns)]
+        #[::socketioxide::async_trait]
+        impl<A: ::socketioxide::adapter::Adapter> #emitter_ident for ::socketioxide::Socket<A> {
+            #(#emitter_impls)*
+        }
+
+        #[doc = #broadcaster_doc]
+        #[allow(dead_code)]
+        #[::socketioxide::async_trait]
+        #vis trait #broadcaster_ident {
+            #(#broadcaster_methods)*
+        }
+
+        #[allow(unreachable_patterns)]
+        #[::socketioxide::async_trait]
+        impl<A: ::socketioxide::adapter::Adapter> #broadcaster_ident for ::socketioxide::Operators<A> {
+            #(#broadcaster_impls)*
+        }
+    }
+}
+
+/// Convert a `PascalCase` variant name to `snake_case`
+fn to_snake_case(ident: &str) -> String {
+    let mut snake_case = String::with_capacity(ident.len() + 4);
+    let chars: Vec<char> = ident.chars().collect();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).map_or(false, |c| c.is_lowercase());
+            if prev.is_lowercase() || prev.is_numeric() || (prev.is_uppercase() && next_lower) {
+                snake_case.push('_');
+            }
+        }
+        snake_case.extend(c.to_lowercase());
+    }
+    snake_case
+}
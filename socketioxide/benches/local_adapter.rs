@@ -0,0 +1,163 @@
+//! Benchmarks of the room management of the [`LocalAdapter`](socketioxide::adapter::LocalAdapter),
+//! with the in-memory test harness.
+//!
+//! Run with `cargo bench -p socketioxide --features test-utils --bench local_adapter`.
+use std::time::{Duration, Instant};
+
+use socketioxide::{
+    adapter::{LocalAdapter, RoomEvent},
+    extract::{Data, SocketRef},
+    testing::{TestClient, TestServer},
+    Sid, SocketIo,
+};
+use tokio::{runtime::Runtime, sync::mpsc};
+
+/// Create a server where the sockets join the rooms sent in their auth payload,
+/// with a channel receiving the rooms deleted
+fn server() -> (
+    TestServer<LocalAdapter>,
+    SocketIo,
+    mpsc::UnboundedReceiver<()>,
+) {
+    let (server, io) = SocketIo::builder().build_test();
+    io.ns(
+        "/",
+        |socket: SocketRef, Data(rooms): Data<Vec<String>>| async move {
+            socket.join(rooms).await.ok();
+        },
+    );
+    let (tx, rx) = mpsc::unbounded_channel();
+    io.of("/").unwrap().on_room_event(move |event| {
+        if let RoomEvent::Delete(_) = event {
+            tx.send(()).ok();
+        }
+    });
+    (server, io, rx)
+}
+
+async fn connect(
+    server: &TestServer<LocalAdapter>,
+    rooms: Vec<String>,
+) -> (TestClient<LocalAdapter>, Sid) {
+    let mut client = server.new_client();
+    let sid = client.connect("/", rooms).await.unwrap();
+    (client, sid)
+}
+
+/// Disconnect sockets in a few rooms each, while the server has many other rooms
+fn disconnect_storm(rt: &Runtime, rooms: usize, sockets: usize) -> Duration {
+    rt.block_on(async {
+        let (server, _io, mut deleted) = server();
+        let mut fillers = Vec::new();
+        for i in 0..100 {
+            let filler_rooms = (0..rooms / 100)
+                .map(|j| format!("filler-{i}-{j}"))
+                .collect();
+            fillers.push(connect(&server, filler_rooms).await);
+        }
+        let mut clients = Vec::new();
+        for i in 0..sockets {
+            let rooms = (0..3).map(|j| format!("room-{i}-{j}")).collect();
+            clients.push(connect(&server, rooms).await.0);
+        }
+
+        let start = Instant::now();
+        for client in &clients {
+            client.disconnect("/");
+        }
+        // Each socket deletes its 3 rooms and its private room
+        for _ in 0..sockets * 4 {
+            deleted.recv().await.unwrap();
+        }
+        start.elapsed()
+    })
+}
+
+/// Concurrent tasks making sockets join and leave rooms
+fn concurrent_join_leave(rt: &Runtime, tasks: usize, iterations: usize) -> Duration {
+    rt.block_on(async {
+        let (server, io, _) = server();
+        let mut clients = Vec::new();
+        for _ in 0..tasks {
+            clients.push(connect(&server, Vec::new()).await);
+        }
+
+        let start = Instant::now();
+        let handles: Vec<_> = clients
+            .iter()
+            .enumerate()
+            .map(|(i, (_, sid))| {
+                let (io, sid) = (io.clone(), *sid);
+                tokio::spawn(async move {
+                    for j in 0..iterations {
+                        let room = format!("room-{i}-{}", j % 10);
+                        io.within(sid).join(room.clone()).await.unwrap();
+                        io.within(sid).leave(room).await.unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        start.elapsed()
+    })
+}
+
+/// Concurrent tasks making sockets join and leave rooms while as many tasks broadcast to these rooms
+fn concurrent_join_broadcast(rt: &Runtime, tasks: usize, iterations: usize) -> Duration {
+    rt.block_on(async {
+        let (server, io, _) = server();
+        let mut clients = Vec::new();
+        for _ in 0..tasks {
+            clients.push(connect(&server, Vec::new()).await);
+        }
+
+        let start = Instant::now();
+        let mut handles = Vec::new();
+        for (i, (_, sid)) in clients.iter().enumerate() {
+            let (join_io, broadcast_io, sid) = (io.clone(), io.clone(), *sid);
+            handles.push(tokio::spawn(async move {
+                for j in 0..iterations {
+                    let room = format!("room-{}", (i + j) % 100);
+                    join_io.within(sid).join(room.clone()).await.unwrap();
+                    join_io.within(sid).leave(room).await.unwrap();
+                }
+            }));
+            handles.push(tokio::spawn(async move {
+                for j in 0..iterations {
+                    // The buffers of the clients are full after a while, the errors are ignored
+                    broadcast_io
+                        .to(format!("room-{}", (i + j) % 100))
+                        .emit("msg", ())
+                        .await
+                        .ok();
+                }
+            }));
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        start.elapsed()
+    })
+}
+
+fn main() {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    for rooms in [1_000, 10_000, 100_000] {
+        let duration = disconnect_storm(&rt, rooms, 1_000);
+        println!("disconnect 1000 sockets with {rooms} other rooms: {duration:?}");
+    }
+    for tasks in [1, 4, 16] {
+        let duration = concurrent_join_leave(&rt, tasks, 10_000);
+        println!("{tasks} tasks joining and leaving 10000 rooms: {duration:?}");
+    }
+    for tasks in [1, 4, 16] {
+        let duration = concurrent_join_broadcast(&rt, tasks, 10_000);
+        println!("{tasks} tasks joining 10000 rooms and {tasks} tasks broadcasting: {duration:?}");
+    }
+}
@@ -2,34 +2,63 @@
 //! When a socket joins or leaves a room, the adapter is responsible for updating the state.
 //! The default adapter is the [`LocalAdapter`], which stores the state in memory.
 //! Other adapters can be made to share the state between multiple servers.
+//!
+//! With the `redis` feature, the [`RedisAdapter`] shares the state through redis pub/sub.
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     convert::Infallible,
-    sync::{Arc, RwLock, Weak},
-    time::Duration,
+    sync::{Arc, Mutex, MutexGuard, Weak},
+    time::{Duration, Instant},
 };
 
+use self::rooms::RoomIndex;
+
 use engineioxide::sid::Sid;
 use futures::{
-    stream::{self, BoxStream},
+    future,
+    stream::{BoxStream, FuturesUnordered},
     StreamExt,
 };
-use itertools::Itertools;
 use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+#[cfg(feature = "extensions")]
+use crate::extensions::Extensions;
 
 use crate::{
+    async_trait,
     errors::{AckError, AdapterError, BroadcastError},
-    handler::AckResponse,
+    handler::{from_args, AckResponse},
     ns::Namespace,
     operators::RoomParam,
-    packet::Packet,
+    packet::{Packet, PacketData},
     socket::Socket,
 };
 
+#[cfg(feature = "redis")]
+mod redis;
+mod rooms;
+#[cfg(feature = "redis")]
+pub use self::redis::{RedisAdapter, RedisAdapterConfig, RedisAdapterError};
+
 /// A room identifier
 pub type Room = String;
 
+/// A change of the rooms of a namespace on this server,
+/// reported to the handlers registered with [`Operators::on_room_event`](crate::Operators::on_room_event).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoomEvent {
+    /// A room was created, it is reported before the first socket joins it
+    Create(Room),
+    /// A socket joined a room
+    Join(Room, Sid),
+    /// A socket left a room
+    Leave(Room, Sid),
+    /// A room was deleted, it is reported after the last socket left it
+    Delete(Room),
+}
+
 /// Flags that can be used to modify the behavior of the broadcast methods.
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub enum BroadcastFlags {
@@ -39,6 +68,10 @@ pub enum BroadcastFlags {
     Broadcast,
     /// Add a custom timeout to the ack callback
     Timeout(Duration),
+    /// Drop the packet for the sockets whose transport is not currently writable
+    Volatile,
+    /// Do not compress the packet when the websocket transport negotiated the permessage-deflate extension
+    NoCompress,
 }
 
 /// Options that can be used to modify the behavior of the broadcast methods.
@@ -64,7 +97,12 @@ impl BroadcastOptions {
     }
 }
 
-//TODO: Make an AsyncAdapter trait
+/// An adapter is responsible for managing the state of the server (rooms, sockets, etc.).
+///
+/// Every method is asynchronous so that an adapter can rely on an external service
+/// (broker, database, etc.) without blocking the tokio worker threads.
+/// Use the [`async_trait`](crate::async_trait) macro to implement it.
+#[async_trait]
 pub trait Adapter: std::fmt::Debug + Send + Sync + 'static {
     type Error: std::error::Error + Into<AdapterError> + Send + 'static;
 
@@ -74,62 +112,129 @@ pub trait Adapter: std::fmt::Debug + Send + Sync + 'static {
         Self: Sized;
 
     /// Initialize the adapter.
-    fn init(&self) -> Result<(), Self::Error>;
+    async fn init(&self) -> Result<(), Self::Error>;
     /// Close the adapter.
-    fn close(&self) -> Result<(), Self::Error>;
+    async fn close(&self) -> Result<(), Self::Error>;
 
     /// Return the number of servers.
-    fn server_count(&self) -> Result<u16, Self::Error>;
+    async fn server_count(&self) -> Result<u16, Self::Error>;
 
     /// Add the socket to all the rooms.
-    fn add_all(&self, sid: Sid, rooms: impl RoomParam) -> Result<(), Self::Error>;
+    async fn add_all(&self, sid: Sid, rooms: impl RoomParam) -> Result<(), Self::Error>;
+    /// Add the socket to all the rooms without awaiting, if the adapter can do it synchronously.
+    ///
+    /// Returns `false` if it cannot, then [`Adapter::add_all`] is called instead.
+    /// It is used to connect the sockets right away, the default implementation always returns `false`.
+    fn try_add_all_now(&self, _sid: Sid, _rooms: impl RoomParam) -> bool {
+        false
+    }
     /// Remove the socket from the rooms.
-    fn del(&self, sid: Sid, rooms: impl RoomParam) -> Result<(), Self::Error>;
+    async fn del(&self, sid: Sid, rooms: impl RoomParam) -> Result<(), Self::Error>;
     /// Remove the socket from all the rooms.
-    fn del_all(&self, sid: Sid) -> Result<(), Self::Error>;
+    async fn del_all(&self, sid: Sid) -> Result<(), Self::Error>;
 
     /// Broadcast the packet to the sockets that match the [`BroadcastOptions`].
-    fn broadcast(&self, packet: Packet, opts: BroadcastOptions) -> Result<(), BroadcastError>;
+    async fn broadcast(&self, packet: Packet, opts: BroadcastOptions)
+        -> Result<(), BroadcastError>;
 
     /// Broadcast the packet to the sockets that match the [`BroadcastOptions`] and return a stream of ack responses.
-    fn broadcast_with_ack<V: DeserializeOwned>(
+    async fn broadcast_with_ack<V: DeserializeOwned>(
         &self,
         packet: Packet,
         opts: BroadcastOptions,
     ) -> Result<BoxStream<'static, Result<AckResponse<V>, AckError>>, BroadcastError>;
 
     /// Return the sockets ids that match the [`BroadcastOptions`].
-    fn sockets(&self, rooms: impl RoomParam) -> Result<Vec<Sid>, Self::Error>;
+    async fn sockets(&self, rooms: impl RoomParam) -> Result<Vec<Sid>, Self::Error>;
 
     /// Return the rooms of the socket.
-    fn socket_rooms(&self, sid: Sid) -> Result<Vec<Room>, Self::Error>;
+    async fn socket_rooms(&self, sid: Sid) -> Result<Vec<Room>, Self::Error>;
 
     /// Return the sockets that match the [`BroadcastOptions`].
-    fn fetch_sockets(&self, opts: BroadcastOptions) -> Result<Vec<Arc<Socket<Self>>>, Self::Error>
+    async fn fetch_sockets(
+        &self,
+        opts: BroadcastOptions,
+    ) -> Result<Vec<Arc<Socket<Self>>>, Self::Error>
     where
         Self: Sized;
 
     /// Add the sockets that match the [`BroadcastOptions`] to the rooms.
-    fn add_sockets(&self, opts: BroadcastOptions, rooms: impl RoomParam)
-        -> Result<(), Self::Error>;
+    async fn add_sockets(
+        &self,
+        opts: BroadcastOptions,
+        rooms: impl RoomParam,
+    ) -> Result<(), Self::Error>;
     /// Remove the sockets that match the [`BroadcastOptions`] from the rooms.
-    fn del_sockets(&self, opts: BroadcastOptions, rooms: impl RoomParam)
-        -> Result<(), Self::Error>;
+    async fn del_sockets(
+        &self,
+        opts: BroadcastOptions,
+        rooms: impl RoomParam,
+    ) -> Result<(), Self::Error>;
     /// Disconnect the sockets that match the [`BroadcastOptions`].
-    fn disconnect_socket(&self, opts: BroadcastOptions) -> Result<(), BroadcastError>;
+    async fn disconnect_socket(&self, opts: BroadcastOptions) -> Result<(), BroadcastError>;
+
+    /// Emit an event to the other servers of the cluster.
+    ///
+    /// The `data` is the serialized argument of the event, an array is sent as multiple arguments.
+    async fn server_side_emit(&self, event: String, data: Value) -> Result<(), Self::Error>;
+    /// Emit an event to the other servers of the cluster and return a stream of their ack responses.
+    async fn server_side_emit_with_ack(
+        &self,
+        event: String,
+        data: Value,
+    ) -> Result<BoxStream<'static, Result<Value, AckError>>, Self::Error>;
+
+    /// Keep the [`Session`] of a disconnected socket so that it can be recovered later.
+    async fn persist_session(&self, session: Session) -> Result<(), Self::Error>;
+    /// Take the [`Session`] with the given private id if it exists and has not expired,
+    /// with the packets broadcast after the `offset` of the last packet received by the client.
+    ///
+    /// The session cannot be recovered if the packet of the `offset` is unknown.
+    async fn restore_session(
+        &self,
+        pid: Sid,
+        offset: Option<&str>,
+    ) -> Result<Option<Session>, Self::Error>;
+}
+
+/// The state of a disconnected socket, kept during the
+/// [`max_disconnection_duration`](crate::SocketIoConfig::max_disconnection_duration) so that it can be recovered.
+#[derive(Debug)]
+pub struct Session {
+    /// The id of the socket.
+    pub sid: Sid,
+    /// The private id of the session, only known by the client.
+    pub pid: Sid,
+    /// The rooms of the socket.
+    pub rooms: Vec<Room>,
+    /// The extensions of the socket.
+    #[cfg(feature = "extensions")]
+    pub extensions: Extensions,
+    /// The packets broadcast to the socket after the last packet received by the client,
+    /// set when the session is restored.
+    pub missed_packets: Vec<Packet>,
+    /// When the socket was disconnected.
+    pub disconnected_at: Instant,
+}
 
-    //TODO: implement
-    // fn server_side_emit(&self, packet: Packet, opts: BroadcastOptions) -> Result<u64, Error>;
-    // fn persist_session(&self, sid: i64);
-    // fn restore_session(&self, sid: i64) -> Session;
+impl Session {
+    /// Check if a packet broadcast with the given [`BroadcastOptions`] was sent to the socket of this session,
+    /// with the same rules as [`LocalState::apply_opts`].
+    fn matches(&self, opts: &BroadcastOptions) -> bool {
+        let broadcast = opts.flags.contains(&BroadcastFlags::Broadcast);
+        if opts.rooms.is_empty() && !broadcast {
+            return opts.sid == Some(self.sid);
+        }
+        let in_rooms = opts.rooms.is_empty() || opts.rooms.iter().any(|r| self.rooms.contains(r));
+        in_rooms
+            && !opts.except.iter().any(|r| self.rooms.contains(r))
+            && !(broadcast && opts.sid == Some(self.sid))
+    }
 }
 
 /// The default adapter. store the state in memory.
 #[derive(Debug)]
-pub struct LocalAdapter {
-    rooms: RwLock<HashMap<Room, HashSet<Sid>>>,
-    ns: Weak<Namespace<Self>>,
-}
+pub struct LocalAdapter(LocalState<LocalAdapter>);
 
 impl From<Infallible> for AdapterError {
     fn from(_: Infallible) -> AdapterError {
@@ -137,67 +242,340 @@ impl From<Infallible> for AdapterError {
     }
 }
 
+#[async_trait]
 impl Adapter for LocalAdapter {
     type Error = Infallible;
 
     fn new(ns: Weak<Namespace<Self>>) -> Self {
-        Self {
-            rooms: HashMap::new().into(),
-            ns,
-        }
+        Self(LocalState::new(ns))
     }
 
-    fn init(&self) -> Result<(), Infallible> {
+    async fn init(&self) -> Result<(), Infallible> {
         Ok(())
     }
 
-    fn close(&self) -> Result<(), Infallible> {
+    async fn close(&self) -> Result<(), Infallible> {
         #[cfg(feature = "tracing")]
-        tracing::debug!("closing local adapter: {}", self.ns.upgrade().unwrap().path);
-        let mut rooms = self.rooms.write().unwrap();
-        rooms.clear();
-        rooms.shrink_to_fit();
+        tracing::debug!(
+            "closing local adapter: {}",
+            self.0.ns.upgrade().unwrap().path
+        );
+        self.0.clear();
         Ok(())
     }
 
-    fn server_count(&self) -> Result<u16, Infallible> {
+    async fn server_count(&self) -> Result<u16, Infallible> {
         Ok(1)
     }
 
-    fn add_all(&self, sid: Sid, rooms: impl RoomParam) -> Result<(), Infallible> {
-        let mut rooms_map = self.rooms.write().unwrap();
-        for room in rooms.into_room_iter() {
-            rooms_map.entry(room).or_default().insert(sid);
-        }
+    async fn add_all(&self, sid: Sid, rooms: impl RoomParam) -> Result<(), Infallible> {
+        self.0.add_all(sid, rooms);
         Ok(())
     }
 
-    fn del(&self, sid: Sid, rooms: impl RoomParam) -> Result<(), Infallible> {
-        let mut rooms_map = self.rooms.write().unwrap();
-        for room in rooms.into_room_iter() {
-            if let Some(room) = rooms_map.get_mut(&room) {
-                room.remove(&sid);
+    fn try_add_all_now(&self, sid: Sid, rooms: impl RoomParam) -> bool {
+        self.0.add_all(sid, rooms);
+        true
+    }
+
+    async fn del(&self, sid: Sid, rooms: impl RoomParam) -> Result<(), Infallible> {
+        self.0.del(sid, rooms);
+        Ok(())
+    }
+
+    async fn del_all(&self, sid: Sid) -> Result<(), Infallible> {
+        self.0.del_all(sid);
+        Ok(())
+    }
+
+    async fn broadcast(
+        &self,
+        packet: Packet,
+        opts: BroadcastOptions,
+    ) -> Result<(), BroadcastError> {
+        self.0.broadcast(packet, opts)
+    }
+
+    async fn broadcast_with_ack<V: DeserializeOwned>(
+        &self,
+        packet: Packet,
+        opts: BroadcastOptions,
+    ) -> Result<BoxStream<'static, Result<AckResponse<V>, AckError>>, BroadcastError> {
+        Ok(deserialize_acks(self.0.broadcast_with_ack(packet, opts)))
+    }
+
+    async fn sockets(&self, rooms: impl RoomParam) -> Result<Vec<Sid>, Infallible> {
+        Ok(self.0.sockets(rooms))
+    }
+
+    async fn socket_rooms(&self, sid: Sid) -> Result<Vec<String>, Infallible> {
+        Ok(self.0.socket_rooms(sid))
+    }
+
+    async fn fetch_sockets(
+        &self,
+        opts: BroadcastOptions,
+    ) -> Result<Vec<Arc<Socket<LocalAdapter>>>, Infallible> {
+        Ok(self.0.apply_opts(opts))
+    }
+
+    async fn add_sockets(
+        &self,
+        opts: BroadcastOptions,
+        rooms: impl RoomParam,
+    ) -> Result<(), Infallible> {
+        self.0.add_sockets(opts, rooms);
+        Ok(())
+    }
+
+    async fn del_sockets(
+        &self,
+        opts: BroadcastOptions,
+        rooms: impl RoomParam,
+    ) -> Result<(), Infallible> {
+        self.0.del_sockets(opts, rooms);
+        Ok(())
+    }
+
+    async fn disconnect_socket(&self, opts: BroadcastOptions) -> Result<(), BroadcastError> {
+        self.0.disconnect_socket(opts).await
+    }
+
+    /// The session is kept in memory until it is restored or expires.
+    async fn persist_session(&self, session: Session) -> Result<(), Infallible> {
+        self.0.persist_session(session);
+        Ok(())
+    }
+
+    async fn restore_session(
+        &self,
+        pid: Sid,
+        offset: Option<&str>,
+    ) -> Result<Option<Session>, Infallible> {
+        Ok(self.0.restore_session(pid, offset))
+    }
+
+    /// There is no other server, so the event is not sent anywhere.
+    async fn server_side_emit(&self, _: String, _: Value) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    /// There is no other server, so the stream is always empty.
+    async fn server_side_emit_with_ack(
+        &self,
+        _: String,
+        _: Value,
+    ) -> Result<BoxStream<'static, Result<Value, AckError>>, Infallible> {
+        Ok(futures::stream::empty().boxed())
+    }
+}
+
+/// Deserialize the arguments of each ack response of the stream to `V` with [`from_args`].
+pub(crate) fn deserialize_acks<V: DeserializeOwned>(
+    acks: BoxStream<'static, Result<AckResponse<Value>, AckError>>,
+) -> BoxStream<'static, Result<AckResponse<V>, AckError>> {
+    #[allow(clippy::result_large_err)]
+    let acks = acks.map(|ack| {
+        let (args, bin) = ack?;
+        Ok((from_args(args)?, bin))
+    });
+    acks.boxed()
+}
+
+/// The in-memory state of the sockets connected to this node.
+///
+/// It is the whole implementation of the [`LocalAdapter`] and it can be reused
+/// by other adapters to manage the sockets connected to the current server.
+/// An event packet broadcast while connection state recovery is enabled,
+/// kept during the [`max_disconnection_duration`](crate::SocketIoConfig::max_disconnection_duration).
+#[derive(Debug)]
+struct BufferedPacket {
+    /// The offset appended to the arguments of the event, sent back by the client when it reconnects.
+    offset: String,
+    packet: Packet,
+    opts: BroadcastOptions,
+    emitted_at: Instant,
+}
+
+#[derive(Debug)]
+pub(crate) struct LocalState<A: Adapter> {
+    rooms: RoomIndex,
+    /// The sessions of the disconnected sockets, by private id.
+    sessions: Mutex<HashMap<Sid, Session>>,
+    /// The event packets broadcast recently, in the order of their offsets.
+    packets: Mutex<VecDeque<BufferedPacket>>,
+    ns: Weak<Namespace<A>>,
+}
+
+impl<A: Adapter> LocalState<A> {
+    pub fn new(ns: Weak<Namespace<A>>) -> Self {
+        Self {
+            rooms: RoomIndex::new(),
+            sessions: HashMap::new().into(),
+            packets: VecDeque::new().into(),
+            ns,
+        }
+    }
+
+    /// Remove all the rooms, the sessions and the buffered packets.
+    pub fn clear(&self) {
+        self.rooms.clear();
+        self.sessions.lock().unwrap().clear();
+        self.packets.lock().unwrap().clear();
+    }
+
+    pub fn persist_session(&self, session: Session) {
+        let max_duration = match self.ns.upgrade() {
+            Some(ns) => ns.config.max_disconnection_duration,
+            None => return,
+        };
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(max_duration) = max_duration {
+            sessions.retain(|_, s| s.disconnected_at.elapsed() < max_duration);
+            sessions.insert(session.pid, session);
+        }
+    }
+
+    pub fn restore_session(&self, pid: Sid, offset: Option<&str>) -> Option<Session> {
+        let ns = self.ns.upgrade()?;
+        let max_duration = ns.config.max_disconnection_duration?;
+        let mut session = self.sessions.lock().unwrap().remove(&pid)?;
+        if session.disconnected_at.elapsed() >= max_duration {
+            return None;
+        }
+
+        let packets = self.packets.lock().unwrap();
+        let index = packets
+            .iter()
+            .position(|p| Some(p.offset.as_str()) == offset)?;
+        session.missed_packets = packets
+            .iter()
+            .skip(index + 1)
+            .filter(|p| session.matches(&p.opts))
+            .map(|p| p.packet.clone())
+            .collect();
+        (session.missed_packets.len() <= ns.config.max_missed_packets).then_some(session)
+    }
+
+    /// Append a new offset to the arguments of an event packet without ack
+    /// and keep it for the sockets that could be disconnected, if connection state recovery is enabled.
+    ///
+    /// The buffered packets are returned locked, so that the packet is sent in the order of the offsets.
+    fn buffer_packet(
+        &self,
+        packet: &mut Packet,
+        opts: &BroadcastOptions,
+    ) -> Option<MutexGuard<'_, VecDeque<BufferedPacket>>> {
+        let max_duration = self.ns.upgrade()?.config.max_disconnection_duration?;
+        let offset = Sid::new().to_string();
+        match &mut packet.inner {
+            PacketData::Event(_, data, None) => push_arg(data, offset.clone().into()),
+            PacketData::BinaryEvent(_, bin, None) => push_arg(&mut bin.data, offset.clone().into()),
+            _ => return None,
+        }
+
+        let mut packets = self.packets.lock().unwrap();
+        while let Some(p) = packets.front() {
+            if p.emitted_at.elapsed() < max_duration {
+                break;
             }
+            packets.pop_front();
         }
-        Ok(())
+        packets.push_back(BufferedPacket {
+            offset,
+            packet: packet.clone(),
+            opts: opts.clone(),
+            emitted_at: Instant::now(),
+        });
+        Some(packets)
     }
 
-    fn del_all(&self, sid: Sid) -> Result<(), Infallible> {
-        let mut rooms_map = self.rooms.write().unwrap();
-        for room in rooms_map.values_mut() {
-            room.remove(&sid);
+    /// Add the socket to the given rooms, the rooms that do not exist yet are created.
+    pub fn add_all(&self, sid: Sid, rooms: impl RoomParam) {
+        let ns = self.room_events_ns();
+        let mut events = Vec::new();
+        let events_ref = ns.as_ref().map(|_| &mut events);
+        self.rooms.add(sid, rooms.into_room_iter(), events_ref);
+        if let Some(ns) = ns {
+            ns.on_room_events(&events);
         }
-        Ok(())
     }
 
-    fn broadcast(&self, packet: Packet, opts: BroadcastOptions) -> Result<(), BroadcastError> {
+    /// Remove the socket from the given rooms, the rooms left empty are deleted.
+    pub fn del(&self, sid: Sid, rooms: impl RoomParam) {
+        let ns = self.room_events_ns();
+        let mut events = Vec::new();
+        let events_ref = ns.as_ref().map(|_| &mut events);
+        self.rooms.del(sid, rooms.into_room_iter(), events_ref);
+        if let Some(ns) = ns {
+            ns.on_room_events(&events);
+        }
+    }
+
+    /// Remove the socket from all its rooms, the rooms left empty are deleted.
+    pub fn del_all(&self, sid: Sid) {
+        let ns = self.room_events_ns();
+        let mut events = Vec::new();
+        let events_ref = ns.as_ref().map(|_| &mut events);
+        self.rooms.del_all(sid, events_ref);
+        if let Some(ns) = ns {
+            ns.on_room_events(&events);
+        }
+    }
+
+    /// Get the namespace if it has room event handlers, the room events are only tracked in this case.
+    fn room_events_ns(&self) -> Option<Arc<Namespace<A>>> {
+        self.ns.upgrade().filter(|ns| ns.has_room_event_handlers())
+    }
+
+    /// Check if at least one socket or session of this node is in the given room.
+    #[cfg(feature = "redis")]
+    pub fn has_room(&self, room: &str) -> bool {
+        self.rooms.has_room(room)
+            || self
+                .sessions
+                .lock()
+                .unwrap()
+                .values()
+                .any(|session| session.rooms.iter().any(|r| r == room))
+    }
+
+    /// Return all the rooms with at least one socket of this node.
+    #[cfg(feature = "redis")]
+    pub fn rooms(&self) -> Vec<Room> {
+        self.rooms.rooms()
+    }
+
+    pub fn broadcast(
+        &self,
+        mut packet: Packet,
+        opts: BroadcastOptions,
+    ) -> Result<(), BroadcastError> {
+        // Volatile packets are not kept for the disconnected sessions
+        let volatile = opts.flags.contains(&BroadcastFlags::Volatile);
+        let compress = !opts.flags.contains(&BroadcastFlags::NoCompress);
+        let _packets = if volatile {
+            None
+        } else {
+            self.buffer_packet(&mut packet, &opts)
+        };
         let sockets = self.apply_opts(opts);
 
         #[cfg(feature = "tracing")]
         tracing::debug!("broadcasting packet to {} sockets", sockets.len());
+        let encoded = match sockets.first() {
+            Some(socket) => socket.encode_shared(packet.clone()).map_err(|e| vec![e])?,
+            None => return Ok(()),
+        };
+        if volatile {
+            for socket in sockets {
+                socket.send_volatile(&packet, &encoded, compress);
+            }
+            return Ok(());
+        }
         let errors: Vec<_> = sockets
             .into_iter()
-            .filter_map(|socket| socket.send(packet.clone()).err())
+            .filter_map(|socket| socket.send_shared(&packet, &encoded, compress).err())
             .collect();
         if errors.is_empty() {
             Ok(())
@@ -206,107 +584,112 @@ impl Adapter for LocalAdapter {
         }
     }
 
-    fn broadcast_with_ack<V: DeserializeOwned>(
+    /// Broadcast the packet and return a stream of the ack responses, with their arguments not deserialized yet.
+    pub fn broadcast_with_ack(
         &self,
         packet: Packet,
         opts: BroadcastOptions,
-    ) -> Result<BoxStream<'static, Result<AckResponse<V>, AckError>>, BroadcastError> {
+    ) -> BoxStream<'static, Result<AckResponse<Value>, AckError>> {
         let duration = opts.flags.iter().find_map(|flag| match flag {
             BroadcastFlags::Timeout(duration) => Some(*duration),
             _ => None,
         });
-        let sockets = self.apply_opts(opts);
+        let compress = !opts.flags.contains(&BroadcastFlags::NoCompress);
+        Self::send_with_ack(self.apply_opts(opts), packet, duration, compress)
+    }
+
+    /// Send the packet to every given socket and return a stream of their ack responses,
+    /// with their arguments not deserialized yet.
+    /// `compress` tells if the websocket transport can compress the packet.
+    pub fn send_with_ack(
+        sockets: Vec<Arc<Socket<A>>>,
+        packet: Packet,
+        duration: Option<Duration>,
+        compress: bool,
+    ) -> BoxStream<'static, Result<AckResponse<Value>, AckError>> {
         #[cfg(feature = "tracing")]
         tracing::debug!(
             "broadcasting packet to {} sockets: {:?}",
             sockets.len(),
             sockets.iter().map(|s| s.id).collect::<Vec<_>>()
         );
-        let count = sockets.len();
-        let ack_futs = sockets.into_iter().map(move |socket| {
-            let packet = packet.clone();
-            async move { socket.clone().send_with_ack(packet, duration).await }
-        });
-        Ok(stream::iter(ack_futs).buffer_unordered(count).boxed())
+        sockets
+            .into_iter()
+            .map(move |socket| {
+                let packet = packet.clone();
+                async move {
+                    socket
+                        .clone()
+                        .send_with_ack(packet, duration, compress)
+                        .await
+                }
+            })
+            .collect::<FuturesUnordered<_>>()
+            .boxed()
     }
 
-    fn sockets(&self, rooms: impl RoomParam) -> Result<Vec<Sid>, Infallible> {
+    pub fn sockets(&self, rooms: impl RoomParam) -> Vec<Sid> {
         let mut opts = BroadcastOptions::new(None);
         opts.rooms.extend(rooms.into_room_iter());
-        Ok(self
-            .apply_opts(opts)
+        self.apply_opts(opts)
             .into_iter()
             .map(|socket| socket.id)
-            .collect())
+            .collect()
     }
 
-    //TODO: make this operation O(1)
-    fn socket_rooms(&self, sid: Sid) -> Result<Vec<String>, Infallible> {
-        let rooms_map = self.rooms.read().unwrap();
-        Ok(rooms_map
-            .iter()
-            .filter(|(_, sockets)| sockets.contains(&sid))
-            .map(|(room, _)| room.clone())
-            .collect())
-    }
-
-    fn fetch_sockets(
-        &self,
-        opts: BroadcastOptions,
-    ) -> Result<Vec<Arc<Socket<LocalAdapter>>>, Infallible> {
-        Ok(self.apply_opts(opts))
+    pub fn socket_rooms(&self, sid: Sid) -> Vec<Room> {
+        self.rooms.socket_rooms(sid)
     }
 
-    fn add_sockets(&self, opts: BroadcastOptions, rooms: impl RoomParam) -> Result<(), Infallible> {
+    pub fn add_sockets(&self, opts: BroadcastOptions, rooms: impl RoomParam) {
         let rooms: Vec<Room> = rooms.into_room_iter().collect();
         for socket in self.apply_opts(opts) {
-            self.add_all(socket.id, rooms.clone()).unwrap();
+            self.add_all(socket.id, rooms.clone());
         }
-        Ok(())
     }
 
-    fn del_sockets(&self, opts: BroadcastOptions, rooms: impl RoomParam) -> Result<(), Infallible> {
+    pub fn del_sockets(&self, opts: BroadcastOptions, rooms: impl RoomParam) {
         let rooms: Vec<Room> = rooms.into_room_iter().collect();
         for socket in self.apply_opts(opts) {
-            self.del(socket.id, rooms.clone()).unwrap();
+            self.del(socket.id, rooms.clone());
         }
-        Ok(())
     }
 
-    fn disconnect_socket(&self, opts: BroadcastOptions) -> Result<(), BroadcastError> {
-        let errors: Vec<_> = self
-            .apply_opts(opts)
-            .into_iter()
-            .filter_map(|socket| socket.disconnect().err())
-            .collect();
+    pub async fn disconnect_socket(&self, opts: BroadcastOptions) -> Result<(), BroadcastError> {
+        let errors: Vec<_> = future::join_all(
+            self.apply_opts(opts)
+                .into_iter()
+                .map(|socket| socket.disconnect()),
+        )
+        .await
+        .into_iter()
+        .filter_map(|res| res.err())
+        .collect();
         if errors.is_empty() {
             Ok(())
         } else {
             Err(errors.into())
         }
     }
-}
 
-impl LocalAdapter {
     /// Apply the given `opts` and return the sockets that match.
-    fn apply_opts(&self, opts: BroadcastOptions) -> Vec<Arc<Socket<Self>>> {
+    pub fn apply_opts(&self, opts: BroadcastOptions) -> Vec<Arc<Socket<A>>> {
         let rooms = opts.rooms;
 
         let except = self.get_except_sids(&opts.except);
         let ns = self.ns.upgrade().unwrap();
         if !rooms.is_empty() {
-            let rooms_map = self.rooms.read().unwrap();
-            rooms
-                .iter()
-                .filter_map(|room| rooms_map.get(room))
-                .flatten()
-                .unique()
+            let mut sids = HashSet::new();
+            for room in &rooms {
+                self.rooms.extend_sockets(room, &mut sids);
+            }
+            sids.into_iter()
                 .filter(|sid| {
-                    !except.contains(*sid)
+                    !except.contains(sid)
                         && (!opts.flags.contains(&BroadcastFlags::Broadcast)
-                            || opts.sid.map(|s| s != **sid).unwrap_or_default())
+                            || opts.sid.map(|s| s != *sid).unwrap_or(true))
                 })
-                .filter_map(|sid| ns.get_socket(*sid).ok())
+                .filter_map(|sid| ns.get_socket(sid).ok())
                 .collect()
         } else if opts.flags.contains(&BroadcastFlags::Broadcast) {
             let sockets = ns.get_sockets();
@@ -323,16 +706,21 @@ impl LocalAdapter {
 
     fn get_except_sids(&self, except: &Vec<Room>) -> HashSet<Sid> {
         let mut except_sids = HashSet::new();
-        let rooms_map = self.rooms.read().unwrap();
         for room in except {
-            if let Some(sockets) = rooms_map.get(room) {
-                except_sids.extend(sockets);
-            }
+            self.rooms.extend_sockets(room, &mut except_sids);
         }
         except_sids
     }
 }
 
+/// Push an argument to the arguments of an event, a single argument is turned into an array.
+fn push_arg(data: &mut Value, arg: Value) {
+    match data {
+        Value::Array(args) => args.push(arg),
+        data => *data = Value::Array(vec![data.take(), arg]),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -341,7 +729,7 @@ mod test {
     async fn test_server_count() {
         let ns = Namespace::new_dummy([]);
         let adapter = LocalAdapter::new(Arc::downgrade(&ns));
-        assert_eq!(adapter.server_count().unwrap(), 1);
+        assert_eq!(adapter.server_count().await.unwrap(), 1);
     }
 
     #[tokio::test]
@@ -349,8 +737,8 @@ mod test {
         let socket = Sid::new();
         let ns = Namespace::new_dummy([socket]);
         let adapter = LocalAdapter::new(Arc::downgrade(&ns));
-        adapter.add_all(socket, ["room1", "room2"]).unwrap();
-        let rooms_map = adapter.rooms.read().unwrap();
+        adapter.add_all(socket, ["room1", "room2"]).await.unwrap();
+        let rooms_map = adapter.0.rooms.snapshot();
         assert_eq!(rooms_map.len(), 2);
         assert_eq!(rooms_map.get("room1").unwrap().len(), 1);
         assert_eq!(rooms_map.get("room2").unwrap().len(), 1);
@@ -361,11 +749,11 @@ mod test {
         let socket = Sid::new();
         let ns = Namespace::new_dummy([socket]);
         let adapter = LocalAdapter::new(Arc::downgrade(&ns));
-        adapter.add_all(socket, ["room1", "room2"]).unwrap();
-        adapter.del(socket, "room1").unwrap();
-        let rooms_map = adapter.rooms.read().unwrap();
-        assert_eq!(rooms_map.len(), 2);
-        assert_eq!(rooms_map.get("room1").unwrap().len(), 0);
+        adapter.add_all(socket, ["room1", "room2"]).await.unwrap();
+        adapter.del(socket, "room1").await.unwrap();
+        let rooms_map = adapter.0.rooms.snapshot();
+        assert_eq!(rooms_map.len(), 1);
+        assert!(!rooms_map.contains_key("room1"));
         assert_eq!(rooms_map.get("room2").unwrap().len(), 1);
     }
 
@@ -374,12 +762,10 @@ mod test {
         let socket = Sid::new();
         let ns = Namespace::new_dummy([socket]);
         let adapter = LocalAdapter::new(Arc::downgrade(&ns));
-        adapter.add_all(socket, ["room1", "room2"]).unwrap();
-        adapter.del_all(socket).unwrap();
-        let rooms_map = adapter.rooms.read().unwrap();
-        assert_eq!(rooms_map.len(), 2);
-        assert_eq!(rooms_map.get("room1").unwrap().len(), 0);
-        assert_eq!(rooms_map.get("room2").unwrap().len(), 0);
+        adapter.add_all(socket, ["room1", "room2"]).await.unwrap();
+        adapter.del_all(socket).await.unwrap();
+        let rooms_map = adapter.0.rooms.snapshot();
+        assert!(rooms_map.is_empty());
     }
 
     #[tokio::test]
@@ -389,19 +775,21 @@ mod test {
         let sid3 = Sid::new();
         let ns = Namespace::new_dummy([sid1, sid2, sid3]);
         let adapter = LocalAdapter::new(Arc::downgrade(&ns));
-        adapter.add_all(sid1, ["room1", "room2"]).unwrap();
-        adapter.add_all(sid2, ["room1"]).unwrap();
-        adapter.add_all(sid3, ["room2"]).unwrap();
+        adapter.add_all(sid1, ["room1", "room2"]).await.unwrap();
+        adapter.add_all(sid2, ["room1"]).await.unwrap();
+        adapter.add_all(sid3, ["room2"]).await.unwrap();
         assert!(adapter
             .socket_rooms(sid1)
+            .await
             .unwrap()
             .contains(&"room1".into()));
         assert!(adapter
             .socket_rooms(sid1)
+            .await
             .unwrap()
             .contains(&"room2".into()));
-        assert_eq!(adapter.socket_rooms(sid2).unwrap(), ["room1"]);
-        assert_eq!(adapter.socket_rooms(sid3).unwrap(), ["room2"]);
+        assert_eq!(adapter.socket_rooms(sid2).await.unwrap(), ["room1"]);
+        assert_eq!(adapter.socket_rooms(sid3).await.unwrap(), ["room2"]);
     }
 
     #[tokio::test]
@@ -409,12 +797,12 @@ mod test {
         let socket = Sid::new();
         let ns = Namespace::new_dummy([socket]);
         let adapter = LocalAdapter::new(Arc::downgrade(&ns));
-        adapter.add_all(socket, ["room1"]).unwrap();
+        adapter.add_all(socket, ["room1"]).await.unwrap();
 
         let mut opts = BroadcastOptions::new(Some(socket));
         opts.rooms = vec!["room1".to_string()];
-        adapter.add_sockets(opts, "room2").unwrap();
-        let rooms_map = adapter.rooms.read().unwrap();
+        adapter.add_sockets(opts, "room2").await.unwrap();
+        let rooms_map = adapter.0.rooms.snapshot();
 
         assert_eq!(rooms_map.len(), 2);
         assert!(rooms_map.get("room1").unwrap().contains(&socket));
@@ -426,14 +814,14 @@ mod test {
         let socket = Sid::new();
         let ns = Namespace::new_dummy([socket]);
         let adapter = LocalAdapter::new(Arc::downgrade(&ns));
-        adapter.add_all(socket, ["room1"]).unwrap();
+        adapter.add_all(socket, ["room1"]).await.unwrap();
 
         let mut opts = BroadcastOptions::new(Some(socket));
         opts.rooms = vec!["room1".to_string()];
-        adapter.add_sockets(opts, "room2").unwrap();
+        adapter.add_sockets(opts, "room2").await.unwrap();
 
         {
-            let rooms_map = adapter.rooms.read().unwrap();
+            let rooms_map = adapter.0.rooms.snapshot();
 
             assert_eq!(rooms_map.len(), 2);
             assert!(rooms_map.get("room1").unwrap().contains(&socket));
@@ -442,14 +830,14 @@ mod test {
 
         let mut opts = BroadcastOptions::new(Some(socket));
         opts.rooms = vec!["room1".to_string()];
-        adapter.del_sockets(opts, "room2").unwrap();
+        adapter.del_sockets(opts, "room2").await.unwrap();
 
         {
-            let rooms_map = adapter.rooms.read().unwrap();
+            let rooms_map = adapter.0.rooms.snapshot();
 
-            assert_eq!(rooms_map.len(), 2);
+            assert_eq!(rooms_map.len(), 1);
             assert!(rooms_map.get("room1").unwrap().contains(&socket));
-            assert!(rooms_map.get("room2").unwrap().is_empty());
+            assert!(!rooms_map.contains_key("room2"));
         }
     }
 
@@ -460,21 +848,21 @@ mod test {
         let socket2 = Sid::new();
         let ns = Namespace::new_dummy([socket0, socket1, socket2]);
         let adapter = LocalAdapter::new(Arc::downgrade(&ns));
-        adapter.add_all(socket0, ["room1", "room2"]).unwrap();
-        adapter.add_all(socket1, ["room1", "room3"]).unwrap();
-        adapter.add_all(socket2, ["room2", "room3"]).unwrap();
+        adapter.add_all(socket0, ["room1", "room2"]).await.unwrap();
+        adapter.add_all(socket1, ["room1", "room3"]).await.unwrap();
+        adapter.add_all(socket2, ["room2", "room3"]).await.unwrap();
 
-        let sockets = adapter.sockets("room1").unwrap();
+        let sockets = adapter.sockets("room1").await.unwrap();
         assert_eq!(sockets.len(), 2);
         assert!(sockets.contains(&socket0));
         assert!(sockets.contains(&socket1));
 
-        let sockets = adapter.sockets("room2").unwrap();
+        let sockets = adapter.sockets("room2").await.unwrap();
         assert_eq!(sockets.len(), 2);
         assert!(sockets.contains(&socket0));
         assert!(sockets.contains(&socket2));
 
-        let sockets = adapter.sockets("room3").unwrap();
+        let sockets = adapter.sockets("room3").await.unwrap();
         assert_eq!(sockets.len(), 2);
         assert!(sockets.contains(&socket1));
         assert!(sockets.contains(&socket2));
@@ -489,17 +877,20 @@ mod test {
         let adapter = LocalAdapter::new(Arc::downgrade(&ns));
         adapter
             .add_all(socket0, ["room1", "room2", "room4"])
+            .await
             .unwrap();
         adapter
             .add_all(socket1, ["room1", "room3", "room5"])
+            .await
             .unwrap();
         adapter
             .add_all(socket2, ["room2", "room3", "room6"])
+            .await
             .unwrap();
 
         let mut opts = BroadcastOptions::new(Some(socket0));
         opts.rooms = vec!["room5".to_string()];
-        match adapter.disconnect_socket(opts) {
+        match adapter.disconnect_socket(opts).await {
             // todo it returns Ok, in previous commits it also returns Ok
             Err(BroadcastError::SendError(_)) | Ok(_) => {}
             e => panic!(
@@ -508,7 +899,7 @@ mod test {
             ),
         }
 
-        let sockets = adapter.sockets("room2").unwrap();
+        let sockets = adapter.sockets("room2").await.unwrap();
         assert_eq!(sockets.len(), 2);
         assert!(sockets.contains(&socket2));
         assert!(sockets.contains(&socket0));
@@ -521,35 +912,70 @@ mod test {
         let ns = Namespace::new_dummy([socket0, socket1, socket2]);
         let adapter = LocalAdapter::new(Arc::downgrade(&ns));
         // Add socket 0 to room1 and room2
-        adapter.add_all(socket0, ["room1", "room2"]).unwrap();
+        adapter.add_all(socket0, ["room1", "room2"]).await.unwrap();
         // Add socket 1 to room1 and room3
-        adapter.add_all(socket1, ["room1", "room3"]).unwrap();
+        adapter.add_all(socket1, ["room1", "room3"]).await.unwrap();
         // Add socket 2 to room2 and room3
         adapter
             .add_all(socket2, ["room1", "room2", "room3"])
+            .await
             .unwrap();
 
         // socket 2 is the sender
         let mut opts = BroadcastOptions::new(Some(socket2));
         opts.rooms = vec!["room1".to_string()];
         opts.except = vec!["room2".to_string()];
-        let sockets = adapter.fetch_sockets(opts).unwrap();
+        let sockets = adapter.fetch_sockets(opts).await.unwrap();
         assert_eq!(sockets.len(), 1);
         assert_eq!(sockets[0].id, socket1);
 
         let mut opts = BroadcastOptions::new(Some(socket2));
         opts.flags.insert(BroadcastFlags::Broadcast);
         opts.except = vec!["room2".to_string()];
-        let sockets = adapter.fetch_sockets(opts).unwrap();
+        let sockets = adapter.fetch_sockets(opts).await.unwrap();
         assert_eq!(sockets.len(), 1);
 
         let opts = BroadcastOptions::new(Some(socket2));
-        let sockets = adapter.fetch_sockets(opts).unwrap();
+        let sockets = adapter.fetch_sockets(opts).await.unwrap();
         assert_eq!(sockets.len(), 1);
         assert_eq!(sockets[0].id, socket2);
 
         let opts = BroadcastOptions::new(Some(Sid::new()));
-        let sockets = adapter.fetch_sockets(opts).unwrap();
+        let sockets = adapter.fetch_sockets(opts).await.unwrap();
         assert_eq!(sockets.len(), 0);
     }
+
+    #[test]
+    fn test_session_matches() {
+        let session = Session {
+            sid: Sid::new(),
+            pid: Sid::new(),
+            rooms: vec!["room1".to_string(), "room2".to_string()],
+            #[cfg(feature = "extensions")]
+            extensions: Extensions::new(),
+            missed_packets: Vec::new(),
+            disconnected_at: Instant::now(),
+        };
+
+        let mut opts = BroadcastOptions::new(None);
+        opts.flags.insert(BroadcastFlags::Broadcast);
+        assert!(session.matches(&opts));
+        opts.rooms = vec!["room1".to_string()];
+        assert!(session.matches(&opts));
+        opts.except = vec!["room2".to_string()];
+        assert!(!session.matches(&opts));
+        opts.rooms = vec!["room3".to_string()];
+        opts.except = vec![];
+        assert!(!session.matches(&opts));
+
+        // A packet emitted to a single socket only matches the session of this socket
+        let mut opts = BroadcastOptions::new(Some(Sid::new()));
+        assert!(!session.matches(&opts));
+        opts.sid = Some(session.sid);
+        assert!(session.matches(&opts));
+        // The sender is excluded from its broadcasts
+        opts.flags.insert(BroadcastFlags::Broadcast);
+        opts.rooms = vec!["room1".to_string()];
+        assert!(!session.matches(&opts));
+    }
 }
@@ -0,0 +1,1106 @@
+//! A [`RedisAdapter`] to share the state of the server between multiple nodes through redis pub/sub.
+//!
+//! The messages exchanged on the redis channels follow the format of the official
+//! [`@socket.io/redis-adapter`](https://github.com/socketio/socket.io-redis-adapter) package,
+//! so socketioxide servers and node.js servers can be part of the same cluster.
+//!
+//! #### Example
+//! ```no_run
+//! # use socketioxide::{SocketIo, adapter::{RedisAdapter, RedisAdapterConfig}};
+//! let client = redis::Client::open("redis://127.0.0.1:6379").unwrap();
+//! let (_, io) = SocketIo::builder()
+//!     .redis(RedisAdapterConfig::new(client))
+//!     .build_svc_with_adapter::<RedisAdapter>();
+//! ```
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+
+use engineioxide::sid::Sid;
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use redis::{
+    aio::{MultiplexedConnection, PubSub},
+    AsyncCommands,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use tokio::{
+    sync::{mpsc, oneshot, OnceCell},
+    task::JoinHandle,
+    time::Instant,
+};
+
+use super::{
+    deserialize_acks, Adapter, BroadcastFlags, BroadcastOptions, LocalState, Room, Session,
+};
+use crate::{
+    async_trait,
+    errors::{AckError, AdapterError, BroadcastError},
+    handler::{AckResponse, ServerAckSender},
+    ns::Namespace,
+    operators::RoomParam,
+    packet::{event_args, BinaryPacket, Packet, PacketData},
+    parser::msgpack::{json_to_msgpack, msgpack_to_json},
+    socket::Socket,
+};
+
+/// The request types of the `@socket.io/redis-adapter` protocol.
+mod request_type {
+    pub const SOCKETS: u64 = 0;
+    pub const ALL_ROOMS: u64 = 1;
+    pub const REMOTE_JOIN: u64 = 2;
+    pub const REMOTE_LEAVE: u64 = 3;
+    pub const REMOTE_DISCONNECT: u64 = 4;
+    pub const REMOTE_FETCH: u64 = 5;
+    pub const SERVER_SIDE_EMIT: u64 = 6;
+    pub const BROADCAST: u64 = 7;
+    pub const BROADCAST_CLIENT_COUNT: u64 = 8;
+    pub const BROADCAST_ACK: u64 = 9;
+}
+
+/// Configuration for the [`RedisAdapter`].
+#[derive(Debug, Clone)]
+pub struct RedisAdapterConfig {
+    /// The redis client used to publish and subscribe to the adapter channels.
+    pub client: redis::Client,
+
+    /// The prefix of the redis channels.
+    ///
+    /// Defaults to `socket.io`, the same as `@socket.io/redis-adapter`.
+    pub prefix: String,
+
+    /// The amount of time to wait for the other servers to answer a request.
+    ///
+    /// Defaults to 5 seconds.
+    pub request_timeout: Duration,
+}
+
+impl RedisAdapterConfig {
+    /// Create a new config with the given redis client and the default options.
+    pub fn new(client: redis::Client) -> Self {
+        Self {
+            client,
+            prefix: "socket.io".to_string(),
+            request_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Error type for the [`RedisAdapter`].
+#[derive(Debug, thiserror::Error)]
+pub enum RedisAdapterError {
+    #[error("redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+
+    #[error("error decoding msgpack message: {0}")]
+    Decode(#[from] rmpv::decode::Error),
+
+    #[error("error encoding msgpack message: {0}")]
+    Encode(#[from] rmpv::encode::Error),
+
+    #[error("error serializing json message: {0}")]
+    Serialize(#[from] serde_json::Error),
+
+    /// The [`SocketIoConfig`](crate::SocketIoConfig) does not contain any [`RedisAdapterConfig`].
+    #[error("redis adapter is not configured")]
+    NotConfigured,
+
+    /// The other servers did not answer in time.
+    #[error("timeout reached while waiting for a response")]
+    Timeout,
+}
+
+impl From<RedisAdapterError> for AdapterError {
+    fn from(err: RedisAdapterError) -> Self {
+        AdapterError(Box::new(err))
+    }
+}
+
+/// The names of the redis channels for a namespace.
+#[derive(Debug)]
+struct Channels {
+    broadcast: String,
+    request: String,
+    response: String,
+    specific_response: String,
+}
+
+impl Channels {
+    fn new(prefix: &str, ns: &str, uid: &str) -> Self {
+        let response = format!("{prefix}-response#{ns}#");
+        Self {
+            broadcast: format!("{prefix}#{ns}#"),
+            request: format!("{prefix}-request#{ns}#"),
+            specific_response: format!("{response}{uid}#"),
+            response,
+        }
+    }
+
+    /// The channel to publish a broadcast packet on.
+    /// If the packet targets a single room, a dedicated channel is used so that other servers can filter it early.
+    fn broadcast_channel(&self, opts: &BroadcastOptions) -> String {
+        match opts.rooms.as_slice() {
+            [room] => format!("{}{}#", self.broadcast, room),
+            _ => self.broadcast.clone(),
+        }
+    }
+}
+
+/// The redis connections of the adapter, opened on the first use.
+struct RedisConn {
+    conn: MultiplexedConnection,
+    channels: Channels,
+    ns_path: String,
+    request_timeout: Duration,
+    listener: JoinHandle<()>,
+}
+
+/// A response from another server: the decoded message and its binary attachments.
+type Response = (Value, Vec<Vec<u8>>);
+
+/// An adapter that broadcasts packets to the other servers through redis pub/sub.
+///
+/// The sockets connected to the current server are stored in memory, like with the [`LocalAdapter`](super::LocalAdapter).
+/// Every operation that is not flagged as [`BroadcastFlags::Local`] is also published
+/// to the other servers that share the same redis instance and the same channel prefix.
+///
+/// The sockets returned by [`Operators::sockets`](crate::Operators::sockets) are only the ones
+/// connected to this server, the sockets of the other servers cannot be fetched.
+///
+/// It requires a [`RedisAdapterConfig`] set with [`SocketIoBuilder::redis`](crate::SocketIoBuilder::redis).
+pub struct RedisAdapter {
+    /// The unique id of this server.
+    uid: String,
+    local: LocalState<Self>,
+    ns: Weak<Namespace<Self>>,
+    conn: OnceCell<RedisConn>,
+    requests: Mutex<HashMap<String, mpsc::UnboundedSender<Response>>>,
+}
+
+impl std::fmt::Debug for RedisAdapter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisAdapter")
+            .field("uid", &self.uid)
+            .field("local", &self.local)
+            .field("channels", &self.conn.get().map(|c| &c.channels))
+            .finish()
+    }
+}
+
+#[async_trait]
+impl Adapter for RedisAdapter {
+    type Error = RedisAdapterError;
+
+    fn new(ns: Weak<Namespace<Self>>) -> Self {
+        Self {
+            uid: Sid::new().to_string(),
+            local: LocalState::new(ns.clone()),
+            ns,
+            conn: OnceCell::new(),
+            requests: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn init(&self) -> Result<(), RedisAdapterError> {
+        self.conn().await?;
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<(), RedisAdapterError> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("closing redis adapter");
+        if let Some(conn) = self.conn.get() {
+            conn.listener.abort();
+        }
+        self.local.clear();
+        Ok(())
+    }
+
+    async fn server_count(&self) -> Result<u16, RedisAdapterError> {
+        let conn = self.conn().await?;
+        let (_, count): (String, u16) = redis::cmd("PUBSUB")
+            .arg("NUMSUB")
+            .arg(&conn.channels.request)
+            .query_async(&mut conn.conn.clone())
+            .await?;
+        Ok(count)
+    }
+
+    async fn add_all(&self, sid: Sid, rooms: impl RoomParam) -> Result<(), RedisAdapterError> {
+        self.local.add_all(sid, rooms);
+        Ok(())
+    }
+
+    fn try_add_all_now(&self, sid: Sid, rooms: impl RoomParam) -> bool {
+        self.local.add_all(sid, rooms);
+        true
+    }
+
+    async fn del(&self, sid: Sid, rooms: impl RoomParam) -> Result<(), RedisAdapterError> {
+        self.local.del(sid, rooms);
+        Ok(())
+    }
+
+    async fn del_all(&self, sid: Sid) -> Result<(), RedisAdapterError> {
+        self.local.del_all(sid);
+        Ok(())
+    }
+
+    async fn broadcast(
+        &self,
+        packet: Packet,
+        opts: BroadcastOptions,
+    ) -> Result<(), BroadcastError> {
+        if !opts.flags.contains(&BroadcastFlags::Local) {
+            self.publish_broadcast(&packet, &opts)
+                .await
+                .map_err(AdapterError::from)?;
+        }
+        self.local.broadcast(packet, opts)
+    }
+
+    async fn broadcast_with_ack<V: DeserializeOwned>(
+        &self,
+        packet: Packet,
+        opts: BroadcastOptions,
+    ) -> Result<BoxStream<'static, Result<AckResponse<V>, AckError>>, BroadcastError> {
+        if opts.flags.contains(&BroadcastFlags::Local) {
+            return Ok(deserialize_acks(
+                self.local.broadcast_with_ack(packet, opts),
+            ));
+        }
+        let remote = self
+            .publish_broadcast_with_ack(&packet, &opts)
+            .await
+            .map_err(AdapterError::from)?;
+        let local = self.local.broadcast_with_ack(packet, opts);
+        Ok(deserialize_acks(stream::select(local, remote).boxed()))
+    }
+
+    async fn sockets(&self, rooms: impl RoomParam) -> Result<Vec<Sid>, RedisAdapterError> {
+        let rooms: Vec<Room> = rooms.into_room_iter().collect();
+        let mut sockets = self.local.sockets(rooms.clone());
+        let servers = self.server_count().await?.saturating_sub(1) as usize;
+        if servers == 0 {
+            return Ok(sockets);
+        }
+
+        let request_id = Sid::new().to_string();
+        let request = json!({
+            "type": request_type::SOCKETS,
+            "uid": self.uid,
+            "requestId": request_id,
+            "rooms": rooms,
+        });
+        let responses = self.request(&request_id, request, servers).await;
+        self.requests.lock().unwrap().remove(&request_id);
+
+        for (res, _) in responses? {
+            let ids = res["sockets"].as_array().into_iter().flatten();
+            sockets.extend(ids.filter_map(|id| id.as_str()?.parse::<Sid>().ok()));
+        }
+        Ok(sockets)
+    }
+
+    /// Only the rooms of the sockets connected to this server are known.
+    async fn socket_rooms(&self, sid: Sid) -> Result<Vec<Room>, RedisAdapterError> {
+        Ok(self.local.socket_rooms(sid))
+    }
+
+    /// Only the sockets connected to this server are returned,
+    /// the sockets of the other servers cannot be represented as a [`Socket`].
+    async fn fetch_sockets(
+        &self,
+        opts: BroadcastOptions,
+    ) -> Result<Vec<Arc<Socket<Self>>>, RedisAdapterError> {
+        Ok(self.local.apply_opts(opts))
+    }
+
+    async fn add_sockets(
+        &self,
+        opts: BroadcastOptions,
+        rooms: impl RoomParam,
+    ) -> Result<(), RedisAdapterError> {
+        let rooms: Vec<Room> = rooms.into_room_iter().collect();
+        if !opts.flags.contains(&BroadcastFlags::Local) {
+            let request = json!({
+                "type": request_type::REMOTE_JOIN,
+                "uid": self.uid,
+                "opts": encode_opts(&opts),
+                "rooms": rooms,
+            });
+            self.publish_request(&request).await?;
+        }
+        self.local.add_sockets(opts, rooms);
+        Ok(())
+    }
+
+    async fn del_sockets(
+        &self,
+        opts: BroadcastOptions,
+        rooms: impl RoomParam,
+    ) -> Result<(), RedisAdapterError> {
+        let rooms: Vec<Room> = rooms.into_room_iter().collect();
+        if !opts.flags.contains(&BroadcastFlags::Local) {
+            let request = json!({
+                "type": request_type::REMOTE_LEAVE,
+                "uid": self.uid,
+                "opts": encode_opts(&opts),
+                "rooms": rooms,
+            });
+            self.publish_request(&request).await?;
+        }
+        self.local.del_sockets(opts, rooms);
+        Ok(())
+    }
+
+    async fn disconnect_socket(&self, opts: BroadcastOptions) -> Result<(), BroadcastError> {
+        if !opts.flags.contains(&BroadcastFlags::Local) {
+            let request = json!({
+                "type": request_type::REMOTE_DISCONNECT,
+                "uid": self.uid,
+                "opts": encode_opts(&opts),
+                "close": false,
+            });
+            self.publish_request(&request)
+                .await
+                .map_err(AdapterError::from)?;
+        }
+        self.local.disconnect_socket(opts).await
+    }
+
+    /// The session is only kept on this server, the client must reconnect to the same server to recover it.
+    async fn persist_session(&self, session: Session) -> Result<(), RedisAdapterError> {
+        self.local.persist_session(session);
+        Ok(())
+    }
+
+    async fn restore_session(
+        &self,
+        pid: Sid,
+        offset: Option<&str>,
+    ) -> Result<Option<Session>, RedisAdapterError> {
+        Ok(self.local.restore_session(pid, offset))
+    }
+
+    async fn server_side_emit(&self, event: String, data: Value) -> Result<(), RedisAdapterError> {
+        let request = json!({
+            "type": request_type::SERVER_SIDE_EMIT,
+            "uid": self.uid,
+            "data": event_args(&event, &data),
+        });
+        self.publish_request(&request).await
+    }
+
+    async fn server_side_emit_with_ack(
+        &self,
+        event: String,
+        data: Value,
+    ) -> Result<BoxStream<'static, Result<Value, AckError>>, RedisAdapterError> {
+        let servers = self.server_count().await?.saturating_sub(1) as usize;
+        if servers == 0 {
+            return Ok(stream::empty().boxed());
+        }
+        let timeout = self.conn().await?.request_timeout;
+
+        let request_id = Sid::new().to_string();
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.requests.lock().unwrap().insert(request_id.clone(), tx);
+        let request = json!({
+            "type": request_type::SERVER_SIDE_EMIT,
+            "uid": self.uid,
+            "requestId": request_id,
+            "data": event_args(&event, &data),
+        });
+        self.publish_request(&request).await?;
+
+        let ns = self.ns.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(timeout).await;
+            if let Some(ns) = ns.upgrade() {
+                ns.adapter.requests.lock().unwrap().remove(&request_id);
+            }
+        });
+
+        // Each server answers once, a single timeout error is yielded if some of them did not.
+        let deadline = Instant::now() + timeout;
+        let acks = stream::unfold((rx, servers), move |(mut rx, servers)| async move {
+            if servers == 0 {
+                return None;
+            }
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some((mut res, _))) => Some((Ok(res["data"].take()), (rx, servers - 1))),
+                Ok(None) => None,
+                Err(elapsed) => Some((Err(elapsed.into()), (rx, 0))),
+            }
+        });
+        Ok(acks.boxed())
+    }
+}
+
+impl RedisAdapter {
+    /// Get the redis connections, open them and subscribe to the adapter channels if it is the first call.
+    async fn conn(&self) -> Result<&RedisConn, RedisAdapterError> {
+        self.conn
+            .get_or_try_init(|| async {
+                let ns = self.ns.upgrade().ok_or(RedisAdapterError::NotConfigured)?;
+                let config = ns
+                    .config
+                    .redis
+                    .clone()
+                    .ok_or(RedisAdapterError::NotConfigured)?;
+                let channels = Channels::new(&config.prefix, &ns.path, &self.uid);
+
+                let conn = config.client.get_multiplexed_tokio_connection().await?;
+                let mut pubsub = config.client.get_async_connection().await?.into_pubsub();
+                // Each channel is subscribed separately so that every subscription
+                // confirmation is read before the messages start to be consumed.
+                pubsub
+                    .psubscribe(format!("{}*", channels.broadcast))
+                    .await?;
+                pubsub.subscribe(&channels.request).await?;
+                pubsub.subscribe(&channels.response).await?;
+                pubsub.subscribe(&channels.specific_response).await?;
+
+                #[cfg(feature = "tracing")]
+                tracing::debug!("redis adapter subscribed to {:?}", channels);
+
+                let listener = Self::spawn_listener(self.ns.clone(), pubsub);
+
+                Ok(RedisConn {
+                    conn,
+                    channels,
+                    ns_path: ns.path.clone(),
+                    request_timeout: config.request_timeout,
+                    listener,
+                })
+            })
+            .await
+    }
+
+    /// Spawn the task that dispatches the messages received on the adapter channels.
+    /// It stops when the namespace is dropped.
+    fn spawn_listener(ns: Weak<Namespace<Self>>, pubsub: PubSub) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut messages = pubsub.into_on_message();
+            while let Some(msg) = messages.next().await {
+                let ns = match ns.upgrade() {
+                    Some(ns) => ns,
+                    None => break,
+                };
+                let channel = msg.get_channel_name();
+                if let Err(_e) = ns
+                    .adapter
+                    .on_message(channel, msg.get_payload_bytes())
+                    .await
+                {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!("error handling message on {channel}: {_e}");
+                }
+            }
+        })
+    }
+
+    async fn publish(&self, channel: &str, msg: Vec<u8>) -> Result<(), RedisAdapterError> {
+        let mut conn = self.conn().await?.conn.clone();
+        conn.publish::<_, _, ()>(channel, msg).await?;
+        Ok(())
+    }
+
+    async fn publish_request(&self, request: &Value) -> Result<(), RedisAdapterError> {
+        let channel = &self.conn().await?.channels.request;
+        self.publish(channel, serde_json::to_vec(request)?).await
+    }
+
+    async fn publish_response(&self, response: Vec<u8>) -> Result<(), RedisAdapterError> {
+        let channel = &self.conn().await?.channels.response;
+        self.publish(channel, response).await
+    }
+
+    /// Publish the packet to the other servers.
+    async fn publish_broadcast(
+        &self,
+        packet: &Packet,
+        opts: &BroadcastOptions,
+    ) -> Result<(), RedisAdapterError> {
+        let channel = self.conn().await?.channels.broadcast_channel(opts);
+        let (packet, bin) = encode_packet(packet);
+        let msg = json!([self.uid, packet, encode_opts(opts)]);
+        self.publish(&channel, encode_msgpack(msg, &bin)?).await
+    }
+
+    /// Publish the packet to the other servers and return a stream of the acks they relay.
+    ///
+    /// The stream ends once every server has answered with its number of clients and all of them acknowledged,
+    /// or after the ack timeout, in which case a single [`AckError::AckTimeoutError`] is yielded.
+    async fn publish_broadcast_with_ack(
+        &self,
+        packet: &Packet,
+        opts: &BroadcastOptions,
+    ) -> Result<BoxStream<'static, Result<AckResponse<Value>, AckError>>, RedisAdapterError> {
+        let servers = self.server_count().await?.saturating_sub(1) as usize;
+        if servers == 0 {
+            return Ok(stream::empty().boxed());
+        }
+        let timeout = opts
+            .flags
+            .iter()
+            .find_map(|flag| match flag {
+                BroadcastFlags::Timeout(timeout) => Some(*timeout),
+                _ => None,
+            })
+            .or_else(|| self.ns.upgrade().map(|ns| ns.config.ack_timeout))
+            .unwrap_or_default();
+
+        let request_id = Sid::new().to_string();
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.requests.lock().unwrap().insert(request_id.clone(), tx);
+
+        let (packet, bin) = encode_packet(packet);
+        let mut opts = encode_opts(opts);
+        opts["flags"]["timeout"] = json!(timeout.as_millis() as u64);
+        let request = json!({
+            "type": request_type::BROADCAST,
+            "uid": self.uid,
+            "requestId": request_id,
+            "packet": packet,
+            "opts": opts,
+        });
+        let channel = &self.conn().await?.channels.request;
+        self.publish(channel, encode_msgpack(request, &bin)?)
+            .await?;
+
+        // There is no way to know if the other servers received every ack,
+        // so the request is simply forgotten after the timeout.
+        let ns = self.ns.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(timeout).await;
+            if let Some(ns) = ns.upgrade() {
+                ns.adapter.requests.lock().unwrap().remove(&request_id);
+            }
+        });
+
+        let state = RemoteAcks {
+            rx,
+            deadline: Instant::now() + timeout,
+            servers,
+            expected: 0,
+            received: 0,
+        };
+        Ok(stream::unfold(state, RemoteAcks::next).boxed())
+    }
+
+    /// Publish a request to the other servers and wait for `count` responses.
+    async fn request(
+        &self,
+        request_id: &str,
+        request: Value,
+        count: usize,
+    ) -> Result<Vec<Response>, RedisAdapterError> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        self.requests
+            .lock()
+            .unwrap()
+            .insert(request_id.to_string(), tx);
+        self.publish_request(&request).await?;
+
+        let timeout = self.conn().await?.request_timeout;
+        let mut responses = Vec::with_capacity(count);
+        tokio::time::timeout(timeout, async {
+            while responses.len() < count {
+                match rx.recv().await {
+                    Some(res) => responses.push(res),
+                    None => break,
+                }
+            }
+        })
+        .await
+        .map_err(|_| RedisAdapterError::Timeout)?;
+        Ok(responses)
+    }
+
+    /// Dispatch a message received on one of the adapter channels.
+    async fn on_message(&self, channel: &str, payload: &[u8]) -> Result<(), RedisAdapterError> {
+        let channels = &self.conn().await?.channels;
+        if let Some(room) = channel.strip_prefix(channels.broadcast.as_str()) {
+            let room = room.strip_suffix('#').unwrap_or_default();
+            if room.is_empty() || self.local.has_room(room) {
+                self.on_broadcast(payload).await?;
+            }
+        } else if channel.starts_with(channels.response.as_str()) {
+            self.on_response(payload)?;
+        } else if channel == channels.request {
+            self.on_request(payload).await?;
+        }
+        Ok(())
+    }
+
+    /// Broadcast a packet published by another server to the local sockets.
+    async fn on_broadcast(&self, payload: &[u8]) -> Result<(), RedisAdapterError> {
+        let (msg, bin) = decode_message(payload)?;
+        let conn = self.conn().await?;
+        let (uid, packet, opts) = match msg {
+            Value::Array(mut msg) if msg.len() == 3 => {
+                let opts = msg.pop().unwrap();
+                let packet = msg.pop().unwrap();
+                (msg.pop().unwrap(), packet, opts)
+            }
+            _ => return Ok(()),
+        };
+        if uid.as_str() == Some(&self.uid) {
+            return Ok(());
+        }
+        if let Some(packet) = decode_packet(packet, bin, &conn.ns_path) {
+            if let Err(_e) = self.local.broadcast(packet, decode_opts(&opts)) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("error broadcasting remote packet: {_e}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Forward the response of another server to the pending request.
+    fn on_response(&self, payload: &[u8]) -> Result<(), RedisAdapterError> {
+        let (res, bin) = decode_message(payload)?;
+        let requests = self.requests.lock().unwrap();
+        if let Some(tx) = res["requestId"].as_str().and_then(|id| requests.get(id)) {
+            tx.send((res, bin)).ok();
+        }
+        Ok(())
+    }
+
+    /// Handle a request of another server.
+    async fn on_request(&self, payload: &[u8]) -> Result<(), RedisAdapterError> {
+        let (req, bin) = decode_message(payload)?;
+        if req["uid"].as_str() == Some(&self.uid) {
+            return Ok(());
+        }
+        let request_id = req["requestId"].clone();
+        let rooms: Vec<Room> = serde_json::from_value(req["rooms"].clone()).unwrap_or_default();
+        let has_opts = req["opts"].is_object();
+
+        match req["type"].as_u64() {
+            Some(request_type::SOCKETS) => {
+                let sockets = self.local.sockets(rooms);
+                let res = json!({ "requestId": request_id, "sockets": sockets });
+                self.publish_response(serde_json::to_vec(&res)?).await?;
+            }
+            Some(request_type::ALL_ROOMS) => {
+                let res = json!({ "requestId": request_id, "rooms": self.local.rooms() });
+                self.publish_response(serde_json::to_vec(&res)?).await?;
+            }
+            Some(request_type::REMOTE_JOIN) if has_opts => {
+                self.local.add_sockets(decode_opts(&req["opts"]), rooms);
+            }
+            Some(request_type::REMOTE_LEAVE) if has_opts => {
+                self.local.del_sockets(decode_opts(&req["opts"]), rooms);
+            }
+            Some(request_type::REMOTE_DISCONNECT) if has_opts => {
+                self.local
+                    .disconnect_socket(decode_opts(&req["opts"]))
+                    .await
+                    .ok();
+            }
+            Some(
+                ty @ (request_type::REMOTE_JOIN
+                | request_type::REMOTE_LEAVE
+                | request_type::REMOTE_DISCONNECT),
+            ) => {
+                let socket = req["sid"]
+                    .as_str()
+                    .and_then(|sid| sid.parse::<Sid>().ok())
+                    .and_then(|sid| self.ns.upgrade()?.get_socket(sid).ok());
+                let socket = match socket {
+                    Some(socket) => socket,
+                    None => return Ok(()),
+                };
+                let room = req["room"].as_str().unwrap_or_default().to_string();
+                match ty {
+                    request_type::REMOTE_JOIN => self.local.add_all(socket.id, room),
+                    request_type::REMOTE_LEAVE => self.local.del(socket.id, room),
+                    _ => {
+                        socket.disconnect().await.ok();
+                    }
+                }
+                let res = json!({ "requestId": request_id });
+                self.publish_response(serde_json::to_vec(&res)?).await?;
+            }
+            Some(request_type::REMOTE_FETCH) => {
+                let sockets = self
+                    .local
+                    .apply_opts(decode_opts(&req["opts"]))
+                    .into_iter()
+                    .map(|socket| {
+                        json!({
+                            "id": socket.id,
+                            "handshake": {},
+                            "rooms": self.local.socket_rooms(socket.id),
+                            "data": null,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                let res = json!({ "requestId": request_id, "sockets": sockets });
+                self.publish_response(serde_json::to_vec(&res)?).await?;
+            }
+            Some(request_type::BROADCAST) => {
+                let ns = match self.ns.upgrade() {
+                    Some(ns) => ns,
+                    None => return Ok(()),
+                };
+                let packet = match decode_packet(req["packet"].clone(), bin, &ns.path) {
+                    Some(packet) => packet,
+                    None => return Ok(()),
+                };
+                let opts = decode_opts(&req["opts"]);
+                let timeout = opts.flags.iter().find_map(|flag| match flag {
+                    BroadcastFlags::Timeout(timeout) => Some(*timeout),
+                    _ => None,
+                });
+                let compress = !opts.flags.contains(&BroadcastFlags::NoCompress);
+                let sockets = self.local.apply_opts(opts);
+                let res = json!({
+                    "type": request_type::BROADCAST_CLIENT_COUNT,
+                    "requestId": request_id,
+                    "clientCount": sockets.len(),
+                });
+                self.publish_response(encode_msgpack(res, &[])?).await?;
+
+                let mut acks = LocalState::send_with_ack(sockets, packet, timeout, compress);
+                let conn = self.conn().await?;
+                let (mut conn, channel) = (conn.conn.clone(), conn.channels.response.clone());
+                tokio::spawn(async move {
+                    while let Some(ack) = acks.next().await {
+                        let (data, bin) = match ack {
+                            Ok(ack) => ack,
+                            Err(_) => continue,
+                        };
+                        // Only the first argument of the ack is relayed, like socket.io does
+                        let packet = match data {
+                            Value::Array(data) if !data.is_empty() => {
+                                data.into_iter().next().unwrap()
+                            }
+                            _ if !bin.is_empty() => json!({ "_placeholder": true, "num": 0 }),
+                            _ => Value::Null,
+                        };
+                        let res = json!({
+                            "type": request_type::BROADCAST_ACK,
+                            "requestId": request_id,
+                            "packet": packet,
+                        });
+                        let res = match encode_msgpack(res, &bin) {
+                            Ok(res) => res,
+                            Err(_) => continue,
+                        };
+                        if let Err(_e) = conn.publish::<_, _, ()>(&channel, res).await {
+                            #[cfg(feature = "tracing")]
+                            tracing::debug!("error publishing ack response: {_e}");
+                        }
+                    }
+                });
+            }
+            Some(request_type::SERVER_SIDE_EMIT) => {
+                let ns = match self.ns.upgrade() {
+                    Some(ns) => ns,
+                    None => return Ok(()),
+                };
+                let (event, args) = match req["data"].as_array().map(Vec::as_slice) {
+                    Some([Value::String(event), args @ ..]) => (event, Value::from(args)),
+                    _ => return Ok(()),
+                };
+                if request_id.is_null() {
+                    ns.recv_server_event(event, args, ServerAckSender::new(None))?;
+                    return Ok(());
+                }
+
+                let (tx, rx) = oneshot::channel();
+                ns.recv_server_event(event, args, ServerAckSender::new(Some(tx)))?;
+                let conn = self.conn().await?;
+                let (mut conn, channel) = (conn.conn.clone(), conn.channels.response.clone());
+                tokio::spawn(async move {
+                    // The handler may drop the ack sender without answering
+                    let data = match rx.await {
+                        Ok(data) => data,
+                        Err(_) => return,
+                    };
+                    let res = json!({
+                        "type": request_type::SERVER_SIDE_EMIT,
+                        "requestId": request_id,
+                        "data": data,
+                    });
+                    let res = match serde_json::to_vec(&res) {
+                        Ok(res) => res,
+                        Err(_) => return,
+                    };
+                    if let Err(_e) = conn.publish::<_, _, ()>(&channel, res).await {
+                        #[cfg(feature = "tracing")]
+                        tracing::debug!("error publishing server side ack: {_e}");
+                    }
+                });
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+/// The state of the stream of acks relayed by the other servers.
+struct RemoteAcks {
+    rx: mpsc::UnboundedReceiver<Response>,
+    deadline: Instant,
+    /// The number of servers that did not send their client count yet.
+    servers: usize,
+    /// The number of acks expected from the servers that sent their client count.
+    expected: usize,
+    received: usize,
+}
+
+impl RemoteAcks {
+    async fn next(mut self) -> Option<(Result<AckResponse<Value>, AckError>, Self)> {
+        while self.servers > 0 || self.received < self.expected {
+            let (res, bin) = match tokio::time::timeout_at(self.deadline, self.rx.recv()).await {
+                Ok(Some(res)) => res,
+                Ok(None) => return None,
+                Err(elapsed) => {
+                    self.servers = 0;
+                    self.expected = self.received;
+                    return Some((Err(elapsed.into()), self));
+                }
+            };
+            match res["type"].as_u64() {
+                Some(request_type::BROADCAST_CLIENT_COUNT) => {
+                    self.servers = self.servers.saturating_sub(1);
+                    self.expected += res["clientCount"].as_u64().unwrap_or_default() as usize;
+                }
+                Some(request_type::BROADCAST_ACK) => {
+                    self.received += 1;
+                    let packet = BinaryPacket::incoming(json!([res["packet"]]));
+                    return Some((Ok((packet.data, bin)), self));
+                }
+                _ => (),
+            }
+        }
+        None
+    }
+}
+
+/// Encode the options the same way as `@socket.io/redis-adapter`.
+fn encode_opts(opts: &BroadcastOptions) -> Value {
+    let mut except = opts.except.clone();
+    let mut flags = serde_json::Map::new();
+    for flag in &opts.flags {
+        match flag {
+            BroadcastFlags::Local => flags.insert("local".into(), true.into()),
+            BroadcastFlags::Broadcast => flags.insert("broadcast".into(), true.into()),
+            BroadcastFlags::Timeout(timeout) => {
+                flags.insert("timeout".into(), (timeout.as_millis() as u64).into())
+            }
+            BroadcastFlags::Volatile => flags.insert("volatile".into(), true.into()),
+            BroadcastFlags::NoCompress => flags.insert("compress".into(), false.into()),
+        };
+    }
+    // socket.io sockets are always in the room of their own id,
+    // so the sender is excluded through the `except` rooms.
+    if let (true, Some(sid)) = (opts.flags.contains(&BroadcastFlags::Broadcast), opts.sid) {
+        except.push(sid.to_string());
+    }
+    json!({ "rooms": opts.rooms, "except": except, "flags": flags })
+}
+
+/// Decode options sent by another server.
+/// There is no sender on this server so the packet is broadcast to every socket matching the rooms.
+fn decode_opts(opts: &Value) -> BroadcastOptions {
+    let mut res = BroadcastOptions::new(None);
+    res.flags.insert(BroadcastFlags::Broadcast);
+    res.rooms = serde_json::from_value(opts["rooms"].clone()).unwrap_or_default();
+    res.except = serde_json::from_value(opts["except"].clone()).unwrap_or_default();
+    if let Some(timeout) = opts["flags"]["timeout"].as_u64() {
+        res.flags
+            .insert(BroadcastFlags::Timeout(Duration::from_millis(timeout)));
+    }
+    if opts["flags"]["volatile"].as_bool() == Some(true) {
+        res.flags.insert(BroadcastFlags::Volatile);
+    }
+    if opts["flags"]["compress"].as_bool() == Some(false) {
+        res.flags.insert(BroadcastFlags::NoCompress);
+    }
+    res
+}
+
+/// Encode a packet as a `socket.io-parser` packet object.
+/// The binary attachments are returned alongside and referenced by placeholders.
+fn encode_packet(packet: &Packet) -> (Value, Vec<Vec<u8>>) {
+    let (ty, data, id, bin) = match &packet.inner {
+        PacketData::Event(event, data, id) => (2, event_args(event, data), *id, vec![]),
+        PacketData::BinaryEvent(event, packet, id) => {
+            (2, event_args(event, &packet.data), *id, packet.bin.clone())
+        }
+        PacketData::Disconnect => (1, Value::Null, None, vec![]),
+        PacketData::EventAck(data, id) => (3, data.clone(), Some(*id), vec![]),
+        PacketData::BinaryAck(packet, id) => {
+            (3, packet.data.clone(), Some(*id), packet.bin.clone())
+        }
+        PacketData::Connect(data) => {
+            let data = data.as_deref().and_then(|d| serde_json::from_str(d).ok());
+            (0, data.unwrap_or_default(), None, vec![])
+        }
+        PacketData::ConnectError(err) => (4, json!(err), None, vec![]),
+    };
+    let mut res = json!({ "type": ty, "nsp": packet.ns });
+    if !data.is_null() {
+        res["data"] = data;
+    }
+    if let Some(id) = id {
+        res["id"] = id.into();
+    }
+    (res, bin)
+}
+
+/// Decode a `socket.io-parser` event packet object sent by another server.
+fn decode_packet(mut packet: Value, bin: Vec<Vec<u8>>, ns: &str) -> Option<Packet> {
+    if packet["nsp"].as_str().unwrap_or("/") != ns {
+        return None;
+    }
+    let id = packet["id"].as_i64();
+    let mut data = match packet["data"].take() {
+        Value::Array(data) if !data.is_empty() => data,
+        _ => return None,
+    };
+    let event = match data.remove(0) {
+        Value::String(event) => event,
+        _ => return None,
+    };
+    let data = Value::Array(data);
+    let inner = match packet["type"].as_u64()? {
+        2 | 5 if bin.is_empty() => PacketData::Event(event, data, id),
+        2 | 5 => {
+            // The placeholders are removed and added back in the order of the attachments
+            let data = BinaryPacket::incoming(data).data;
+            PacketData::BinaryEvent(event, BinaryPacket::outgoing(data, bin), id)
+        }
+        _ => return None,
+    };
+    Some(Packet {
+        inner,
+        ns: ns.to_string(),
+    })
+}
+
+/// Decode a message published by another server, either JSON or msgpack encoded.
+/// Binary values are extracted and replaced by placeholders.
+fn decode_message(payload: &[u8]) -> Result<(Value, Vec<Vec<u8>>), RedisAdapterError> {
+    if payload.first() == Some(&b'{') {
+        return Ok((serde_json::from_slice(payload)?, vec![]));
+    }
+    let value = rmpv::decode::read_value(&mut &payload[..])?;
+    let mut bin = Vec::new();
+    let value = msgpack_to_json(value, &mut bin);
+    Ok((value, bin))
+}
+
+/// Encode a message with msgpack, replacing the placeholders with their binary attachment.
+fn encode_msgpack(value: Value, bin: &[Vec<u8>]) -> Result<Vec<u8>, RedisAdapterError> {
+    let mut buf = Vec::new();
+    rmpv::encode::write_value(&mut buf, &json_to_msgpack(value, bin))?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn channels() {
+        let channels = Channels::new("socket.io", "/admin", "abc");
+        assert_eq!(channels.broadcast, "socket.io#/admin#");
+        assert_eq!(channels.request, "socket.io-request#/admin#");
+        assert_eq!(channels.response, "socket.io-response#/admin#");
+        assert_eq!(channels.specific_response, "socket.io-response#/admin#abc#");
+
+        let mut opts = BroadcastOptions::new(None);
+        assert_eq!(channels.broadcast_channel(&opts), "socket.io#/admin#");
+        opts.rooms = vec!["room1".into()];
+        assert_eq!(channels.broadcast_channel(&opts), "socket.io#/admin#room1#");
+        opts.rooms = vec!["room1".into(), "room2".into()];
+        assert_eq!(channels.broadcast_channel(&opts), "socket.io#/admin#");
+    }
+
+    #[test]
+    fn encode_event_packet() {
+        let packet = Packet::event("/".into(), "event".into(), json!({ "foo": "bar" }));
+        let (packet, bin) = encode_packet(&packet);
+        assert_eq!(
+            packet,
+            json!({ "type": 2, "nsp": "/", "data": ["event", { "foo": "bar" }] })
+        );
+        assert!(bin.is_empty());
+    }
+
+    #[test]
+    fn binary_packet_roundtrip() {
+        let packet = Packet::bin_event(
+            "/".into(),
+            "event".into(),
+            json!(["foo"]),
+            vec![vec![1, 2, 3]],
+        );
+        let (value, bin) = encode_packet(&packet);
+        let msg = encode_msgpack(json!(["uid", value, {}]), &bin).unwrap();
+
+        // The binary attachment is sent as a msgpack binary value and not as a placeholder
+        let raw = rmpv::decode::read_value(&mut &msg[..]).unwrap();
+        assert_eq!(raw[1]["data"][2], rmpv::Value::Binary(vec![1, 2, 3]));
+
+        let (msg, bin) = decode_message(&msg).unwrap();
+        let decoded = decode_packet(msg[1].clone(), bin, "/").unwrap();
+        assert_eq!(decoded, packet);
+    }
+
+    #[test]
+    fn decode_packet_other_ns() {
+        let packet = json!({ "type": 2, "nsp": "/admin", "data": ["event"] });
+        assert!(decode_packet(packet, vec![], "/").is_none());
+    }
+
+    #[test]
+    fn opts_roundtrip() {
+        let sid = Sid::new();
+        let mut opts = BroadcastOptions::new(Some(sid));
+        opts.rooms = vec!["room1".into()];
+        opts.except = vec!["room2".into()];
+        opts.flags.insert(BroadcastFlags::Broadcast);
+        opts.flags
+            .insert(BroadcastFlags::Timeout(Duration::from_millis(1500)));
+        opts.flags.insert(BroadcastFlags::Volatile);
+        opts.flags.insert(BroadcastFlags::NoCompress);
+
+        let value = encode_opts(&opts);
+        assert_eq!(value["rooms"], json!(["room1"]));
+        assert_eq!(value["except"], json!(["room2", sid.to_string()]));
+        assert_eq!(
+            value["flags"],
+            json!({ "broadcast": true, "timeout": 1500, "volatile": true, "compress": false })
+        );
+
+        let decoded = decode_opts(&value);
+        assert_eq!(decoded.rooms, opts.rooms);
+        assert_eq!(decoded.except, ["room2".to_string(), sid.to_string()]);
+        assert!(decoded.sid.is_none());
+        assert!(decoded
+            .flags
+            .contains(&BroadcastFlags::Timeout(Duration::from_millis(1500))));
+        assert!(decoded.flags.contains(&BroadcastFlags::Volatile));
+        assert!(decoded.flags.contains(&BroadcastFlags::NoCompress));
+    }
+
+    #[test]
+    fn decode_json_message() {
+        let (msg, bin) = decode_message(br#"{"type":0,"requestId":"abc"}"#).unwrap();
+        assert_eq!(msg["requestId"], "abc");
+        assert!(bin.is_empty());
+    }
+}
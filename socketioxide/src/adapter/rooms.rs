@@ -0,0 +1,301 @@
+//! The rooms of the sockets connected to the current server, indexed by room and by socket.
+
+use std::{
+    collections::{hash_map::Entry, hash_map::RandomState, HashMap, HashSet},
+    hash::{BuildHasher, Hash, Hasher},
+    sync::RwLock,
+};
+
+use engineioxide::sid::Sid;
+
+use super::{Room, RoomEvent};
+
+/// The number of shards of each map, it must be a power of two.
+const SHARDS: usize = 16;
+
+type Shard<K, V> = RwLock<HashMap<K, HashSet<V>>>;
+
+/// The sockets of each room and the rooms of each socket.
+///
+/// Both maps are sharded to reduce the lock contention when sockets join and leave rooms concurrently.
+/// A change always locks the shard of the socket first and then the shard of each room one at a time,
+/// so the rooms of a socket stay consistent with the sockets of its rooms.
+/// The rooms left empty are deleted.
+#[derive(Debug)]
+pub(crate) struct RoomIndex {
+    hasher: RandomState,
+    /// The sockets of each room, sharded by room
+    rooms: Box<[Shard<Room, Sid>]>,
+    /// The rooms of each socket, sharded by socket id
+    sockets: Box<[Shard<Sid, Room>]>,
+}
+
+impl RoomIndex {
+    pub fn new() -> Self {
+        Self {
+            hasher: RandomState::new(),
+            rooms: (0..SHARDS).map(|_| RwLock::default()).collect(),
+            sockets: (0..SHARDS).map(|_| RwLock::default()).collect(),
+        }
+    }
+
+    fn shard_index(&self, key: &(impl Hash + ?Sized)) -> usize {
+        let mut hasher = self.hasher.build_hasher();
+        key.hash(&mut hasher);
+        hasher.finish() as usize & (SHARDS - 1)
+    }
+
+    fn room_shard(&self, room: &str) -> &Shard<Room, Sid> {
+        &self.rooms[self.shard_index(room)]
+    }
+
+    fn socket_shard(&self, sid: Sid) -> &Shard<Sid, Room> {
+        &self.sockets[self.shard_index(&sid)]
+    }
+
+    /// Add the socket to the given rooms.
+    /// The [`RoomEvent`]s of the changes are pushed to `events` if it is `Some`.
+    pub fn add(
+        &self,
+        sid: Sid,
+        rooms: impl Iterator<Item = Room>,
+        mut events: Option<&mut Vec<RoomEvent>>,
+    ) {
+        let mut sockets = self.socket_shard(sid).write().unwrap();
+        let socket_rooms = sockets.entry(sid).or_default();
+        for room in rooms {
+            if socket_rooms.contains(&room) {
+                continue;
+            }
+            let mut rooms_map = self.room_shard(&room).write().unwrap();
+            match rooms_map.entry(room.clone()) {
+                Entry::Occupied(mut entry) => {
+                    entry.get_mut().insert(sid);
+                }
+                Entry::Vacant(entry) => {
+                    if let Some(events) = events.as_deref_mut() {
+                        events.push(RoomEvent::Create(room.clone()));
+                    }
+                    entry.insert(HashSet::from([sid]));
+                }
+            }
+            drop(rooms_map);
+            if let Some(events) = events.as_deref_mut() {
+                events.push(RoomEvent::Join(room.clone(), sid));
+            }
+            socket_rooms.insert(room);
+        }
+        if socket_rooms.is_empty() {
+            sockets.remove(&sid);
+        }
+    }
+
+    /// Remove the socket from the given rooms.
+    /// The [`RoomEvent`]s of the changes are pushed to `events` if it is `Some`.
+    pub fn del(
+        &self,
+        sid: Sid,
+        rooms: impl Iterator<Item = Room>,
+        mut events: Option<&mut Vec<RoomEvent>>,
+    ) {
+        let mut sockets = self.socket_shard(sid).write().unwrap();
+        let socket_rooms = match sockets.get_mut(&sid) {
+            Some(socket_rooms) => socket_rooms,
+            None => return,
+        };
+        for room in rooms {
+            if socket_rooms.remove(&room) {
+                self.leave_room(sid, room, events.as_deref_mut());
+            }
+        }
+        if socket_rooms.is_empty() {
+            sockets.remove(&sid);
+        }
+    }
+
+    /// Remove the socket from all its rooms.
+    /// The [`RoomEvent`]s of the changes are pushed to `events` if it is `Some`.
+    pub fn del_all(&self, sid: Sid, mut events: Option<&mut Vec<RoomEvent>>) {
+        let mut sockets = self.socket_shard(sid).write().unwrap();
+        if let Some(socket_rooms) = sockets.remove(&sid) {
+            for room in socket_rooms {
+                self.leave_room(sid, room, events.as_deref_mut());
+            }
+        }
+    }
+
+    /// Remove the socket from the sockets of the room and delete the room if it is empty.
+    /// The shard of the socket must be locked by the caller.
+    fn leave_room(&self, sid: Sid, room: Room, events: Option<&mut Vec<RoomEvent>>) {
+        let mut rooms_map = self.room_shard(&room).write().unwrap();
+        let empty = match rooms_map.get_mut(&room) {
+            Some(sockets) => sockets.remove(&sid) && sockets.is_empty(),
+            None => false,
+        };
+        if empty {
+            rooms_map.remove(&room);
+        }
+        drop(rooms_map);
+        if let Some(events) = events {
+            events.push(RoomEvent::Leave(room.clone(), sid));
+            if empty {
+                events.push(RoomEvent::Delete(room));
+            }
+        }
+    }
+
+    /// Get the rooms of the socket.
+    pub fn socket_rooms(&self, sid: Sid) -> Vec<Room> {
+        self.socket_shard(sid)
+            .read()
+            .unwrap()
+            .get(&sid)
+            .map(|rooms| rooms.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Add the sockets of the room to `sids`.
+    pub fn extend_sockets(&self, room: &str, sids: &mut HashSet<Sid>) {
+        if let Some(sockets) = self.room_shard(room).read().unwrap().get(room) {
+            sids.extend(sockets);
+        }
+    }
+
+    /// Check if at least one socket is in the room.
+    #[cfg(feature = "redis")]
+    pub fn has_room(&self, room: &str) -> bool {
+        self.room_shard(room).read().unwrap().contains_key(room)
+    }
+
+    /// Get all the rooms with at least one socket.
+    #[cfg(feature = "redis")]
+    pub fn rooms(&self) -> Vec<Room> {
+        self.rooms
+            .iter()
+            .flat_map(|shard| shard.read().unwrap().keys().cloned().collect::<Vec<_>>())
+            .collect()
+    }
+
+    /// Remove all the rooms.
+    pub fn clear(&self) {
+        for shard in self.sockets.iter() {
+            let mut sockets = shard.write().unwrap();
+            sockets.clear();
+            sockets.shrink_to_fit();
+        }
+        for shard in self.rooms.iter() {
+            let mut rooms = shard.write().unwrap();
+            rooms.clear();
+            rooms.shrink_to_fit();
+        }
+    }
+
+    /// Get a copy of the sockets of each room.
+    #[cfg(test)]
+    pub fn snapshot(&self) -> HashMap<Room, HashSet<Sid>> {
+        self.rooms
+            .iter()
+            .flat_map(|shard| shard.read().unwrap().clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Check that the rooms of each socket match the sockets of each room
+    fn assert_consistent(index: &RoomIndex) {
+        let rooms = index.snapshot();
+        let mut sockets: HashMap<Sid, HashSet<Room>> = HashMap::new();
+        for shard in index.sockets.iter() {
+            sockets.extend(shard.read().unwrap().clone());
+        }
+        for (room, sids) in &rooms {
+            assert!(!sids.is_empty(), "empty room {room}");
+            for sid in sids {
+                assert!(sockets[sid].contains(room));
+            }
+        }
+        for (sid, socket_rooms) in &sockets {
+            assert!(!socket_rooms.is_empty());
+            for room in socket_rooms {
+                assert!(rooms[room].contains(sid));
+            }
+        }
+    }
+
+    fn rooms<'a>(rooms: &'a [&'a str]) -> impl Iterator<Item = Room> + 'a {
+        rooms.iter().map(|r| r.to_string())
+    }
+
+    #[test]
+    fn add_del() {
+        let index = RoomIndex::new();
+        let (sid1, sid2) = (Sid::new(), Sid::new());
+        index.add(sid1, rooms(&["room1", "room2"]), None);
+        index.add(sid2, rooms(&["room2", "room3"]), None);
+        assert_consistent(&index);
+
+        let mut socket_rooms = index.socket_rooms(sid1);
+        socket_rooms.sort();
+        assert_eq!(socket_rooms, ["room1", "room2"]);
+
+        index.del(sid1, rooms(&["room2", "room4"]), None);
+        assert_consistent(&index);
+        assert_eq!(index.socket_rooms(sid1), ["room1"]);
+        assert_eq!(index.snapshot()["room2"], HashSet::from([sid2]));
+
+        index.del_all(sid2, None);
+        assert_consistent(&index);
+        assert!(index.socket_rooms(sid2).is_empty());
+        assert_eq!(index.snapshot().len(), 1);
+    }
+
+    #[test]
+    fn events() {
+        let index = RoomIndex::new();
+        let (sid1, sid2) = (Sid::new(), Sid::new());
+        let mut events = Vec::new();
+        index.add(sid1, rooms(&["room1", "room1"]), Some(&mut events));
+        index.add(sid2, rooms(&["room1"]), Some(&mut events));
+        index.del(sid1, rooms(&["room1", "room2"]), Some(&mut events));
+        index.del_all(sid2, Some(&mut events));
+        assert_eq!(
+            events,
+            [
+                RoomEvent::Create("room1".into()),
+                RoomEvent::Join("room1".into(), sid1),
+                RoomEvent::Join("room1".into(), sid2),
+                RoomEvent::Leave("room1".into(), sid1),
+                RoomEvent::Leave("room1".into(), sid2),
+                RoomEvent::Delete("room1".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn concurrent_changes() {
+        let index = std::sync::Arc::new(RoomIndex::new());
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let index = index.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        let sid = Sid::new();
+                        let rooms = (0..10).map(|j| format!("room{}", (i + j) % 12));
+                        index.add(sid, rooms.clone(), None);
+                        index.del(sid, rooms.take(5), None);
+                        if i % 2 == 0 {
+                            index.del_all(sid, None);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_consistent(&index);
+    }
+}
@@ -0,0 +1,587 @@
+//! Instrumentation for the [Socket.IO Admin UI](https://socket.io/docs/v4/admin-ui/), available with the `admin-ui` feature.
+//!
+//! The server registers an admin namespace (`/admin` by default) that the official
+//! [`@socket.io/admin-ui`](https://github.com/socketio/socket.io-admin-ui) dashboard connects to.
+//! It reports the server stats, the namespaces, the sockets with their handshake and their rooms,
+//! the room membership changes and the events, and it applies the join, leave and disconnect
+//! actions of the dashboard to the sockets with the [`Operators`] API.
+//!
+//! #### Example
+//! ```
+//! # use socketioxide::{SocketIo, admin::AdminUiConfig};
+//! // The password is a bcrypt hash, like with the javascript server
+//! let hash = "$2b$10$heqvAkYMez.Va6Et2uXInOnkCT6/uQj1brkrbyG3LpopDklcq7ZOS";
+//! let (layer, io) = SocketIo::builder()
+//!     .admin_ui(AdminUiConfig::new("admin", hash))
+//!     .build_layer();
+//! ```
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use engineioxide::{config::TransportType, sid::Sid};
+use serde_json::{json, Map, Value};
+use tokio::sync::mpsc;
+
+use crate::{
+    adapter::{Adapter, Room},
+    client::Client,
+    extract::{Data, SocketRef},
+    operators::Operators,
+    packet::{event_args, Packet, PacketData},
+    ConnectError, DisconnectReason, Socket,
+};
+
+/// The credentials required to connect to the admin namespace.
+#[derive(Debug, Clone)]
+pub struct AdminUiAuth {
+    /// The username to connect to the admin namespace.
+    pub username: String,
+
+    /// The [bcrypt](https://en.wikipedia.org/wiki/Bcrypt) hash of the password to connect to the admin namespace.
+    pub password: String,
+}
+
+/// The amount of details reported to the admin UI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AdminUiMode {
+    /// Report the details of every socket, room and event.
+    #[default]
+    Development,
+
+    /// Only report the server stats and the aggregated events, to reduce the overhead of the instrumentation.
+    Production,
+}
+
+/// Configuration of the admin UI instrumentation,
+/// set with [`SocketIoBuilder::admin_ui`](crate::SocketIoBuilder::admin_ui).
+#[derive(Debug, Clone)]
+pub struct AdminUiConfig {
+    /// The credentials required to connect to the admin namespace.
+    /// If it is `None`, anyone can connect to the admin namespace.
+    pub auth: Option<AdminUiAuth>,
+
+    /// The name of the admin namespace.
+    ///
+    /// Defaults to `/admin`.
+    pub namespace: String,
+
+    /// Whether the admin actions (join, leave and disconnect) are disabled.
+    ///
+    /// Defaults to `false`.
+    pub readonly: bool,
+
+    /// The id of this server displayed in the admin UI.
+    ///
+    /// Defaults to `<hostname>#<pid>`.
+    pub server_id: Option<String>,
+
+    /// The amount of details reported to the admin UI.
+    ///
+    /// Defaults to [`AdminUiMode::Development`].
+    pub mode: AdminUiMode,
+
+    /// The interval at which the server stats are sent to the admin UI.
+    ///
+    /// Defaults to 2 seconds.
+    pub server_stats_interval: Duration,
+}
+
+impl AdminUiConfig {
+    /// Create a new config with the default options, requiring the given credentials.
+    /// The password is a bcrypt hash, like with the javascript server.
+    pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            auth: Some(AdminUiAuth {
+                username: username.into(),
+                password: password.into(),
+            }),
+            ..Self::without_auth()
+        }
+    }
+
+    /// Create a new config with the default options, without any authentication.
+    pub fn without_auth() -> Self {
+        Self {
+            auth: None,
+            namespace: "/admin".to_string(),
+            readonly: false,
+            server_id: None,
+            mode: AdminUiMode::default(),
+            server_stats_interval: Duration::from_secs(2),
+        }
+    }
+}
+
+/// An event sent to the admin namespace, with its arguments
+type AdminEvent = (&'static str, Vec<Value>);
+
+/// The key of an aggregated event: its timestamp rounded to the second, its type and its subtype
+type AggregateKey = (u64, &'static str, Option<String>);
+
+/// The state of the admin UI instrumentation, notified by the server of the connections, rooms and events.
+///
+/// The events are forwarded to the admin namespace by a single task, so that they are received in order.
+#[derive(Debug)]
+pub(crate) struct AdminUi {
+    config: AdminUiConfig,
+    server_id: String,
+    hostname: String,
+    started_at: Instant,
+    /// Set once the task forwarding the events to the admin namespace is running,
+    /// the events are dropped before
+    running: AtomicBool,
+    tx: mpsc::UnboundedSender<AdminEvent>,
+    rx: Mutex<Option<mpsc::UnboundedReceiver<AdminEvent>>>,
+    clients_count: AtomicUsize,
+    aggregated_events: Mutex<HashMap<AggregateKey, u64>>,
+}
+
+impl AdminUi {
+    pub(crate) fn new(config: AdminUiConfig) -> Self {
+        let hostname = gethostname::gethostname().to_string_lossy().into_owned();
+        let server_id = config
+            .server_id
+            .clone()
+            .unwrap_or_else(|| format!("{}#{}", hostname, std::process::id()));
+        let (tx, rx) = mpsc::unbounded_channel();
+        Self {
+            config,
+            server_id,
+            hostname,
+            started_at: Instant::now(),
+            running: AtomicBool::new(false),
+            tx,
+            rx: Mutex::new(Some(rx)),
+            clients_count: AtomicUsize::new(0),
+            aggregated_events: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Register the admin namespace and start the instrumentation if a runtime is available,
+    /// otherwise it is started when the first admin connects.
+    pub(crate) fn register<A: Adapter>(self: &Arc<Self>, client: &Arc<Client<A>>) {
+        let admin = self.clone();
+        let weak_client = Arc::downgrade(client);
+        let ns = client.add_ns(
+            self.config.namespace.clone(),
+            move |socket: SocketRef<A>| {
+                let admin = admin.clone();
+                let client = weak_client.clone();
+                async move { admin.on_admin_connect(socket, client).await }
+            },
+        );
+
+        if let Some(auth) = self.config.auth.clone() {
+            ns.add_middleware(move |Data(data): Data<Value>| {
+                let auth = auth.clone();
+                async move { authenticate(&auth, &data).await }
+            });
+        }
+
+        if tokio::runtime::Handle::try_current().is_ok() {
+            self.start(Arc::downgrade(client));
+        }
+    }
+
+    /// Spawn the task forwarding the events and the server stats to the admin namespace, if it is not running yet
+    fn start<A: Adapter>(self: &Arc<Self>, client: Weak<Client<A>>) {
+        let rx = match self.rx.lock().unwrap().take() {
+            Some(rx) => rx,
+            None => return,
+        };
+        self.running.store(true, Ordering::Release);
+        tokio::spawn(self.clone().run(client, rx));
+    }
+
+    async fn run<A: Adapter>(
+        self: Arc<Self>,
+        client: Weak<Client<A>>,
+        mut rx: mpsc::UnboundedReceiver<AdminEvent>,
+    ) {
+        let mut interval = tokio::time::interval(self.config.server_stats_interval);
+        loop {
+            let (event, args) = tokio::select! {
+                Some(event) = rx.recv() => event,
+                _ = interval.tick() => match client.upgrade() {
+                    Some(client) => ("server_stats", vec![self.server_stats(&client)]),
+                    None => break,
+                },
+            };
+            let ns = match client.upgrade() {
+                Some(client) => client.get_ns(&self.config.namespace),
+                None => break,
+            };
+            if let Some(ns) = ns {
+                if let Err(_e) = Operators::new(ns, None).broadcast().emit(event, args).await {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!("error sending admin event {}: {}", event, _e);
+                }
+            }
+        }
+    }
+
+    async fn on_admin_connect<A: Adapter>(
+        self: Arc<Self>,
+        socket: SocketRef<A>,
+        client: Weak<Client<A>>,
+    ) {
+        self.start(client.clone());
+
+        let dev = self.config.mode == AdminUiMode::Development;
+        let mut features = if dev { vec!["ALL_EVENTS"] } else { vec![] };
+        features.push("AGGREGATED_EVENTS");
+        if !self.config.readonly {
+            features.extend([
+                "JOIN",
+                "LEAVE",
+                "DISCONNECT",
+                "MJOIN",
+                "MLEAVE",
+                "MDISCONNECT",
+            ]);
+        }
+        socket
+            .emit("config", json!({ "supportedFeatures": features }))
+            .ok();
+
+        if !self.config.readonly {
+            register_actions(&socket, client.clone());
+        }
+
+        if let (true, Some(client)) = (dev, client.upgrade()) {
+            let sockets = self.all_sockets(&client).await;
+            socket.emit("all_sockets", (sockets,)).ok();
+        }
+    }
+
+    /// Serialize all the sockets connected to this server, except the admin ones
+    async fn all_sockets<A: Adapter>(&self, client: &Client<A>) -> Vec<Value> {
+        let mut sockets = Vec::new();
+        for ns in client.all_ns() {
+            if self.is_admin(&ns.path) {
+                continue;
+            }
+            for socket in ns.get_sockets() {
+                let rooms = ns.adapter.socket_rooms(socket.id).await.unwrap_or_default();
+                sockets.push(serialize_socket(&socket, rooms));
+            }
+        }
+        sockets
+    }
+
+    fn server_stats<A: Adapter>(&self, client: &Client<A>) -> Value {
+        let namespaces = client.all_ns();
+        let mut polling_clients = HashMap::new();
+        for socket in namespaces.iter().flat_map(|ns| ns.get_sockets()) {
+            let polling = socket.transport_type() == TransportType::Polling;
+            polling_clients.insert(socket.esocket.id, polling);
+        }
+        let aggregated_events: Vec<_> = self
+            .aggregated_events
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|((timestamp, kind, sub_type), count)| {
+                json!({ "timestamp": timestamp, "type": kind, "subType": sub_type, "count": count })
+            })
+            .collect();
+        let namespaces: Vec<_> = namespaces
+            .iter()
+            .map(|ns| json!({ "name": ns.path, "socketsCount": ns.get_sockets().len() }))
+            .collect();
+
+        json!({
+            "serverId": self.server_id,
+            "hostname": self.hostname,
+            "pid": std::process::id(),
+            "uptime": self.started_at.elapsed().as_secs_f64(),
+            "clientsCount": self.clients_count.load(Ordering::Relaxed),
+            "pollingClientsCount": polling_clients.values().filter(|polling| **polling).count(),
+            "aggregatedEvents": aggregated_events,
+            "namespaces": namespaces,
+        })
+    }
+
+    fn is_admin(&self, ns: &str) -> bool {
+        ns == self.config.namespace
+    }
+
+    /// Whether the details of the sockets, rooms and events are reported for this namespace
+    fn reports(&self, ns: &str) -> bool {
+        self.config.mode == AdminUiMode::Development && !self.is_admin(ns)
+    }
+
+    fn emit(&self, event: &'static str, args: Vec<Value>) {
+        if self.running.load(Ordering::Acquire) {
+            self.tx.send((event, args)).ok();
+        }
+    }
+
+    /// Count an event in the aggregated events of the current second
+    fn aggregate(&self, kind: &'static str, sub_type: Option<&str>) {
+        let timestamp = now() / 1000 * 1000;
+        let key = (timestamp, kind, sub_type.map(str::to_string));
+        *self
+            .aggregated_events
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_default() += 1;
+    }
+
+    pub(crate) fn on_raw_connection(&self) {
+        self.clients_count.fetch_add(1, Ordering::Relaxed);
+        self.aggregate("rawConnection", None);
+    }
+
+    pub(crate) fn on_raw_disconnection(&self) {
+        self.clients_count.fetch_sub(1, Ordering::Relaxed);
+        self.aggregate("rawDisconnection", None);
+    }
+
+    pub(crate) fn on_connect<A: Adapter>(&self, socket: &Socket<A>) {
+        if self.is_admin(socket.ns()) {
+            return;
+        }
+        self.aggregate("connection", Some(socket.ns()));
+        if self.reports(socket.ns()) {
+            let socket = serialize_socket(socket, vec![socket.id.to_string()]);
+            self.emit("socket_connected", vec![socket, now().into()]);
+        }
+    }
+
+    pub(crate) fn on_disconnect(&self, ns: &str, sid: Sid, reason: &DisconnectReason) {
+        if self.is_admin(ns) {
+            return;
+        }
+        self.aggregate("disconnection", Some(ns));
+        if self.reports(ns) {
+            let args = vec![
+                ns.into(),
+                json!(sid),
+                reason_str(reason).into(),
+                now().into(),
+            ];
+            self.emit("socket_disconnected", args);
+        }
+    }
+
+    pub(crate) fn on_packet_received(&self, ns: &str) {
+        if !self.is_admin(ns) {
+            self.aggregate("packetsIn", None);
+        }
+    }
+
+    pub(crate) fn on_event_received(&self, ns: &str, sid: Sid, event: &str, data: &Value) {
+        if self.reports(ns) {
+            let args = vec![ns.into(), json!(sid), event_args(event, data), now().into()];
+            self.emit("event_received", args);
+        }
+    }
+
+    pub(crate) fn on_packet_sent<A: Adapter>(&self, socket: &Socket<A>, packet: &Packet) {
+        if self.is_admin(socket.ns()) {
+            return;
+        }
+        self.aggregate("packetsOut", None);
+        if !self.reports(socket.ns()) {
+            return;
+        }
+        let args = match &packet.inner {
+            PacketData::Event(e, data, _) => event_args(e, data),
+            PacketData::BinaryEvent(e, bin, _) => event_args(e, &bin.data),
+            _ => return,
+        };
+        let args = vec![
+            socket.ns().as_str().into(),
+            json!(socket.id),
+            args,
+            now().into(),
+        ];
+        self.emit("event_sent", args);
+    }
+
+    pub(crate) fn on_rooms_joined(&self, ns: &str, sid: Sid, rooms: &[Room]) {
+        self.on_rooms(ns, sid, rooms, "room_joined");
+    }
+
+    pub(crate) fn on_rooms_left(&self, ns: &str, sid: Sid, rooms: &[Room]) {
+        self.on_rooms(ns, sid, rooms, "room_left");
+    }
+
+    fn on_rooms(&self, ns: &str, sid: Sid, rooms: &[Room], event: &'static str) {
+        if self.reports(ns) {
+            for room in rooms {
+                self.emit(
+                    event,
+                    vec![ns.into(), room.as_str().into(), json!(sid), now().into()],
+                );
+            }
+        }
+    }
+}
+
+/// Check the credentials sent in the auth payload of the admin socket
+async fn authenticate(auth: &AdminUiAuth, data: &Value) -> Result<(), ConnectError> {
+    let username = data["username"].as_str();
+    let password = data["password"].as_str().unwrap_or_default().to_string();
+    let hash = auth.password.clone();
+    let valid = username == Some(auth.username.as_str())
+        && tokio::task::spawn_blocking(move || bcrypt::verify(password, &hash))
+            .await
+            .map_or(false, |res| res.unwrap_or(false));
+    match valid {
+        true => Ok(()),
+        false => Err(ConnectError::new("Authentication error")),
+    }
+}
+
+/// Register the handlers of the admin actions, applied to the sockets of a namespace
+/// selected by a socket id or by a room
+fn register_actions<A: Adapter>(socket: &SocketRef<A>, client: Weak<Client<A>>) {
+    let join_client = client.clone();
+    socket.on(
+        "_join",
+        move |Data((ns, room, filter)): Data<(String, Room, String)>| {
+            let client = join_client.clone();
+            async move {
+                match select(&client, &ns, &filter) {
+                    Some(Selection::Socket(socket)) => socket.join(room).await.map_err(Into::into),
+                    Some(Selection::Room(ops)) => ops.join(room).await.map_err(Into::into),
+                    None => Ok(()),
+                }
+                .unwrap_or_else(log_action_error)
+            }
+        },
+    );
+    let leave_client = client.clone();
+    socket.on(
+        "_leave",
+        move |Data((ns, room, filter)): Data<(String, Room, String)>| {
+            let client = leave_client.clone();
+            async move {
+                match select(&client, &ns, &filter) {
+                    Some(Selection::Socket(socket)) => socket.leave(room).await.map_err(Into::into),
+                    Some(Selection::Room(ops)) => ops.leave(room).await.map_err(Into::into),
+                    None => Ok(()),
+                }
+                .unwrap_or_else(log_action_error)
+            }
+        },
+    );
+    socket.on(
+        "_disconnect",
+        move |Data((ns, close, filter)): Data<(String, bool, String)>| {
+            let client = client.clone();
+            async move { disconnect(&client, &ns, close, &filter).await }
+        },
+    );
+}
+
+/// The sockets targeted by an admin action
+enum Selection<A: Adapter> {
+    Socket(Arc<Socket<A>>),
+    Room(Operators<A>),
+}
+
+/// Select the socket with the given id in the namespace, or the sockets of the room with this name
+fn select<A: Adapter>(client: &Weak<Client<A>>, ns: &str, filter: &str) -> Option<Selection<A>> {
+    let ns = client.upgrade()?.get_ns(ns)?;
+    let socket = filter.parse().ok().and_then(|sid| ns.get_socket(sid).ok());
+    match socket {
+        Some(socket) => Some(Selection::Socket(socket)),
+        None => Some(Selection::Room(
+            Operators::new(ns, None).within(filter.to_string()),
+        )),
+    }
+}
+
+/// Disconnect the selected sockets from their namespace, or close their underlying connection
+async fn disconnect<A: Adapter>(client: &Weak<Client<A>>, ns: &str, close: bool, filter: &str) {
+    let sockets = match select(client, ns, filter) {
+        Some(Selection::Socket(socket)) => vec![socket],
+        Some(Selection::Room(ops)) if close => ops.sockets().await.unwrap_or_default(),
+        Some(Selection::Room(ops)) => {
+            if let Err(_e) = ops.disconnect().await {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("error applying admin action: {}", _e);
+            }
+            return;
+        }
+        None => return,
+    };
+    for socket in sockets {
+        if close {
+            socket.close_underlying_transport().await;
+        } else if let Err(_e) = socket.disconnect().await {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("error applying admin action: {}", _e);
+        }
+    }
+}
+
+fn log_action_error(_e: crate::errors::AdapterError) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!("error applying admin action: {}", _e);
+}
+
+/// Serialize a socket like the javascript server does, with its handshake and its rooms
+fn serialize_socket<A: Adapter>(socket: &Socket<A>, rooms: Vec<Room>) -> Value {
+    let handshake = socket.handshake();
+    let headers: Map<String, Value> = handshake
+        .headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.into())))
+        .collect();
+    let issued = handshake
+        .issued
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64);
+    let transport: &'static str = handshake.transport.into();
+
+    json!({
+        "id": socket.id,
+        "clientId": socket.esocket.id,
+        "transport": transport,
+        "nsp": socket.ns(),
+        "data": {},
+        "handshake": {
+            "headers": headers,
+            "url": handshake.url.to_string(),
+            "address": handshake.address.map(|ip| ip.to_string()),
+            "query": handshake.query,
+            "auth": handshake.auth,
+            "issued": issued,
+            "xdomain": handshake.xdomain,
+        },
+        "rooms": rooms,
+    })
+}
+
+/// The disconnect reasons of the javascript server, displayed by the admin UI
+fn reason_str(reason: &DisconnectReason) -> &'static str {
+    use DisconnectReason::*;
+    match reason {
+        TransportClose => "transport close",
+        MultipleHttpPollingError | TransportError => "transport error",
+        PacketParsingError => "parse error",
+        HeartbeatTimeout => "ping timeout",
+        ClientNSDisconnect => "client namespace disconnect",
+        ServerNSDisconnect => "server namespace disconnect",
+        ClosingServer => "server shutting down",
+        BufferOverflow => "forced close",
+    }
+}
+
+/// The current time in milliseconds since the unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
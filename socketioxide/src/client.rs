@@ -3,25 +3,27 @@ use std::sync::{Arc, Mutex, RwLock};
 
 use engineioxide::handler::EngineIoHandler;
 use engineioxide::socket::{DisconnectReason as EIoDisconnectReason, Socket as EIoSocket};
-use futures::{Future, TryFutureExt};
-use serde::de::DeserializeOwned;
+use futures::TryFutureExt;
 
 use engineioxide::sid::Sid;
 use tokio::sync::oneshot;
 
 use crate::adapter::Adapter;
+use crate::ProtocolVersion;
 use crate::{
-    errors::Error,
-    ns::Namespace,
+    errors::{Error, ParserError, SendError},
+    handler::ConnectHandler,
+    ns::{DynNamespace, Namespace, NsPattern},
     packet::{Packet, PacketData},
+    parser::ParserState,
     SocketIoConfig,
 };
-use crate::{ProtocolVersion, Socket};
 
 #[derive(Debug)]
 pub struct Client<A: Adapter> {
     pub(crate) config: Arc<SocketIoConfig>,
     ns: RwLock<HashMap<String, Arc<Namespace<A>>>>,
+    dyn_ns: RwLock<Vec<Arc<DynNamespace<A>>>>,
 }
 
 impl<A: Adapter> Client<A> {
@@ -29,28 +31,7 @@ impl<A: Adapter> Client<A> {
         Self {
             config,
             ns: RwLock::new(HashMap::new()),
-        }
-    }
-
-    /// Apply an incoming binary payload to a partial binary packet waiting to be filled with all the payloads
-    ///
-    /// Returns true if the packet is complete and should be processed
-    fn apply_payload_on_packet(&self, data: Vec<u8>, socket: &EIoSocket<SocketData>) -> bool {
-        #[cfg(feature = "tracing")]
-        tracing::debug!("[sid={}] applying payload on packet", socket.id);
-        if let Some(ref mut packet) = *socket.data.partial_bin_packet.lock().unwrap() {
-            match packet.inner {
-                PacketData::BinaryEvent(_, ref mut bin, _)
-                | PacketData::BinaryAck(ref mut bin, _) => {
-                    bin.add_payload(data);
-                    bin.is_complete()
-                }
-                _ => unreachable!("partial_bin_packet should only be set for binary packets"),
-            }
-        } else {
-            #[cfg(feature = "tracing")]
-            tracing::debug!("[sid={}] socket received unexpected bin data", socket.id);
-            false
+            dyn_ns: RwLock::new(Vec::new()),
         }
     }
 
@@ -65,8 +46,8 @@ impl<A: Adapter> Client<A> {
         tracing::debug!("auth: {:?}", auth);
 
         let sid = esocket.id;
-        if let Some(ns) = self.get_ns(&ns_path) {
-            ns.connect(sid, esocket.clone(), auth, self.config.clone())?;
+        if let Some(ns) = self.get_or_create_ns(&ns_path) {
+            ns.connect(sid, esocket.clone(), auth);
 
             // cancel the connect timeout task for v5
             #[cfg(feature = "v5")]
@@ -83,8 +64,8 @@ impl<A: Adapter> Client<A> {
             esocket.close(EIoDisconnectReason::TransportClose);
             Ok(())
         } else {
-            let packet = Packet::invalid_namespace(ns_path).try_into().unwrap();
-            if let Err(_e) = esocket.emit(packet) {
+            let packet = Packet::invalid_namespace(ns_path);
+            if let Err(_e) = self.send_packet(esocket, packet) {
                 #[cfg(feature = "tracing")]
                 tracing::error!("error while sending invalid namespace packet: {}", _e);
             }
@@ -92,19 +73,23 @@ impl<A: Adapter> Client<A> {
         }
     }
 
-    /// Cache-in the socket data until all the binary payloads are received
-    fn sock_recv_bin_packet(&self, socket: &EIoSocket<SocketData>, packet: Packet) {
-        socket
-            .data
-            .partial_bin_packet
-            .lock()
-            .unwrap()
-            .replace(packet);
+    /// Send a packet to an engine.io socket that is not connected to a namespace
+    fn send_packet(
+        &self,
+        esocket: &EIoSocket<SocketData>,
+        packet: Packet,
+    ) -> Result<(), SendError> {
+        self.config.parser.encode(packet)?.send(esocket, true)
     }
 
     /// Propagate a packet to a its target namespace
-    fn sock_propagate_packet(&self, packet: Packet, sid: Sid) -> Result<(), Error> {
+    fn sock_propagate_packet(
+        &self,
+        packet: Packet,
+        socket: &EIoSocket<SocketData>,
+    ) -> Result<(), Error> {
         if let Some(ns) = self.get_ns(&packet.ns) {
+            let sid = socket.data.socket_id(&ns.path, socket.id);
             ns.recv(sid, packet.inner)
         } else {
             #[cfg(feature = "tracing")]
@@ -113,6 +98,43 @@ impl<A: Adapter> Client<A> {
         }
     }
 
+    /// Handle the result of the decoding of a message received from a socket
+    ///
+    /// A complete packet is propagated to its namespace, a decoding error closes the socket
+    fn on_decoded(
+        &self,
+        res: Result<Option<Packet>, ParserError>,
+        socket: &Arc<EIoSocket<SocketData>>,
+    ) {
+        let res: Result<(), Error> = match res {
+            Ok(Some(packet)) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("Packet: {:?}", packet);
+                #[cfg(feature = "admin-ui")]
+                if let Some(admin_ui) = &self.config.admin_ui {
+                    admin_ui.on_packet_received(&packet.ns);
+                }
+                match packet.inner {
+                    PacketData::Connect(auth) => self.sock_connect(auth, packet.ns, socket),
+                    _ => self.sock_propagate_packet(packet, socket),
+                }
+            }
+            Ok(None) => Ok(()),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(ref err) = res {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                "error while processing packet to socket {}: {}",
+                socket.id,
+                err
+            );
+            if let Some(reason) = err.into() {
+                socket.close(reason);
+            }
+        }
+    }
+
     /// Spawn a task that will close the socket if it is not connected to a namespace
     /// after the [`SocketIoConfig::connect_timeout`] duration
     #[cfg(feature = "v5")]
@@ -132,16 +154,48 @@ impl<A: Adapter> Client<A> {
     }
 
     /// Add a new namespace handler
-    pub fn add_ns<C, F, V>(&self, path: String, callback: C)
+    pub fn add_ns<H, T>(&self, path: String, handler: H) -> Arc<Namespace<A>>
     where
-        C: Fn(Arc<Socket<A>>, V) -> F + Send + Sync + 'static,
-        F: Future<Output = ()> + Send + 'static,
-        V: DeserializeOwned + Send + Sync + 'static,
+        H: ConnectHandler<A, T>,
+        T: Send + Sync + 'static,
     {
         #[cfg(feature = "tracing")]
         tracing::debug!("adding namespace {}", path);
-        let ns = Namespace::new(path.clone(), callback);
-        self.ns.write().unwrap().insert(path, ns);
+        let ns = Namespace::new(path.clone(), handler, self.config.clone());
+        Self::init_adapter(&ns);
+        self.ns.write().unwrap().insert(path, ns.clone());
+        ns
+    }
+
+    /// Add a new dynamic namespace handler, the namespaces matching the pattern are created lazily
+    /// when a socket connects to them
+    pub fn add_dyn_ns<H, T>(&self, pattern: NsPattern, handler: H) -> Arc<DynNamespace<A>>
+    where
+        H: ConnectHandler<A, T>,
+        T: Send + Sync + 'static,
+    {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("adding dynamic namespace {}", pattern.as_str());
+        let dyn_ns = DynNamespace::new(pattern, handler, self.config.clone());
+        self.dyn_ns.write().unwrap().push(dyn_ns.clone());
+        dyn_ns
+    }
+
+    /// The adapter may need to connect to an external service, so it is initialized in the background.
+    fn init_adapter(ns: &Arc<Namespace<A>>) {
+        if let Ok(rt) = tokio::runtime::Handle::try_current() {
+            let ns = ns.clone();
+            rt.spawn(async move {
+                if let Err(_e) = ns.adapter.init().await {
+                    #[cfg(feature = "tracing")]
+                    tracing::error!(
+                        "error initializing adapter of namespace {}: {}",
+                        ns.path,
+                        _e
+                    );
+                }
+            });
+        }
     }
 
     /// Delete a namespace handler
@@ -151,8 +205,51 @@ impl<A: Adapter> Client<A> {
         self.ns.write().unwrap().remove(path);
     }
 
+    /// Delete a dynamic namespace handler, the namespaces it created are deleted too
+    pub fn delete_dyn_ns(&self, pattern: &str) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("deleting dynamic namespace {}", pattern);
+        self.dyn_ns
+            .write()
+            .unwrap()
+            .retain(|ns| ns.pattern() != pattern);
+    }
+
+    /// Get a namespace by its path, either a static namespace
+    /// or a namespace already created by a dynamic namespace
     pub fn get_ns(&self, path: &str) -> Option<Arc<Namespace<A>>> {
-        self.ns.read().unwrap().get(path).cloned()
+        if let Some(ns) = self.ns.read().unwrap().get(path) {
+            return Some(ns.clone());
+        }
+        self.dyn_ns
+            .read()
+            .unwrap()
+            .iter()
+            .find_map(|dyn_ns| dyn_ns.get_child(path))
+    }
+
+    /// Get a namespace by its path, or create it from the first dynamic namespace matching the path
+    fn get_or_create_ns(&self, path: &str) -> Option<Arc<Namespace<A>>> {
+        if let Some(ns) = self.ns.read().unwrap().get(path) {
+            return Some(ns.clone());
+        }
+        let dyn_ns = self.dyn_ns.read().unwrap().clone();
+        let (ns, created) = dyn_ns
+            .iter()
+            .find_map(|dyn_ns| dyn_ns.get_or_create_child(path))?;
+        if created {
+            Self::init_adapter(&ns);
+        }
+        Some(ns)
+    }
+
+    /// Get all the namespaces, including the ones created by dynamic namespaces
+    pub(crate) fn all_ns(&self) -> Vec<Arc<Namespace<A>>> {
+        let mut ns: Vec<_> = self.ns.read().unwrap().values().cloned().collect();
+        for dyn_ns in self.dyn_ns.read().unwrap().iter() {
+            ns.extend(dyn_ns.children());
+        }
+        ns
     }
 
     /// Close all engine.io connections and all clients
@@ -160,8 +257,8 @@ impl<A: Adapter> Client<A> {
     pub(crate) async fn close(&self) {
         #[cfg(feature = "tracing")]
         tracing::debug!("closing all namespaces");
-        let ns = self.ns.read().unwrap().clone();
-        futures::future::join_all(ns.values().map(|ns| ns.close())).await;
+        let ns = self.all_ns();
+        futures::future::join_all(ns.iter().map(|ns| ns.close())).await;
         #[cfg(feature = "tracing")]
         tracing::debug!("all namespaces closed");
     }
@@ -169,13 +266,25 @@ impl<A: Adapter> Client<A> {
 
 #[derive(Debug, Default)]
 pub struct SocketData {
-    /// Partial binary packet that is being received
-    /// Stored here until all the binary payloads are received
-    pub partial_bin_packet: Mutex<Option<Packet>>,
+    /// The decoding state of the parser, e.g. a binary packet waiting for its attachments
+    pub parser_state: ParserState,
 
     /// Channel used to notify the socket that it has been connected to a namespace
     #[cfg(feature = "v5")]
     pub connect_recv_tx: Mutex<Option<oneshot::Sender<()>>>,
+
+    /// The ids of the sockets recovered from a previous session through this connection, by namespace
+    ///
+    /// A recovered socket keeps its previous id instead of the engine.io socket id
+    pub recovered_sids: Mutex<HashMap<String, Sid>>,
+}
+
+impl SocketData {
+    /// Get the id of the socket connected to the given namespace through the engine.io socket `sid`
+    fn socket_id(&self, ns: &str, sid: Sid) -> Sid {
+        let recovered_sids = self.recovered_sids.lock().unwrap();
+        recovered_sids.get(ns).copied().unwrap_or(sid)
+    }
 }
 
 #[engineioxide::async_trait]
@@ -187,6 +296,11 @@ impl<A: Adapter> EngineIoHandler for Client<A> {
         #[cfg(feature = "tracing")]
         tracing::debug!("eio socket connect");
 
+        #[cfg(feature = "admin-ui")]
+        if let Some(admin_ui) = &self.config.admin_ui {
+            admin_ui.on_raw_connection();
+        }
+
         let protocol: ProtocolVersion = socket.protocol.into();
 
         // Connecting the client to the default namespace is mandatory if the SocketIO protocol is v4.
@@ -208,82 +322,59 @@ impl<A: Adapter> EngineIoHandler for Client<A> {
     fn on_disconnect(&self, socket: Arc<EIoSocket<SocketData>>, reason: EIoDisconnectReason) {
         #[cfg(feature = "tracing")]
         tracing::debug!("eio socket disconnected");
-        let _res: Result<Vec<_>, _> = self
-            .ns
-            .read()
-            .unwrap()
-            .values()
-            .filter_map(|ns| ns.get_socket(socket.id).ok())
+
+        #[cfg(feature = "admin-ui")]
+        if let Some(admin_ui) = &self.config.admin_ui {
+            admin_ui.on_raw_disconnection();
+        }
+
+        let close_futs: Vec<_> = self
+            .all_ns()
+            .iter()
+            .filter_map(|ns| {
+                ns.get_socket(socket.data.socket_id(&ns.path, socket.id))
+                    .ok()
+            })
             .map(|s| s.close(reason.clone().into()))
             .collect();
 
-        #[cfg(feature = "tracing")]
-        match _res {
-            Ok(vec) => {
-                tracing::debug!("disconnect handle spawned for {} namespaces", vec.len())
-            }
-            Err(_e) => {
-                tracing::debug!("error while disconnecting socket: {}", _e)
+        tokio::spawn(async move {
+            let _res: Result<Vec<_>, _> = futures::future::join_all(close_futs)
+                .await
+                .into_iter()
+                .collect();
+
+            #[cfg(feature = "tracing")]
+            match _res {
+                Ok(vec) => {
+                    tracing::debug!("disconnect handle spawned for {} namespaces", vec.len())
+                }
+                Err(_e) => {
+                    tracing::debug!("error while disconnecting socket: {}", _e)
+                }
             }
-        }
+        });
     }
 
     fn on_message(&self, msg: String, socket: Arc<EIoSocket<SocketData>>) {
         #[cfg(feature = "tracing")]
         tracing::debug!("Received message: {:?}", msg);
-        let packet = match Packet::try_from(msg) {
-            Ok(packet) => packet,
-            Err(_e) => {
-                #[cfg(feature = "tracing")]
-                tracing::debug!("socket serialization error: {}", _e);
-                socket.close(EIoDisconnectReason::PacketParsingError);
-                return;
-            }
-        };
-        #[cfg(feature = "tracing")]
-        tracing::debug!("Packet: {:?}", packet);
-
-        let res: Result<(), Error> = match packet.inner {
-            PacketData::Connect(auth) => self
-                .sock_connect(auth, packet.ns, &socket)
-                .map_err(Into::into),
-            PacketData::BinaryEvent(_, _, _) | PacketData::BinaryAck(_, _) => {
-                self.sock_recv_bin_packet(&socket, packet);
-                Ok(())
-            }
-            _ => self.sock_propagate_packet(packet, socket.id),
-        };
-        if let Err(ref err) = res {
-            #[cfg(feature = "tracing")]
-            tracing::debug!(
-                "error while processing packet to socket {}: {}",
-                socket.id,
-                err
-            );
-            if let Some(reason) = err.into() {
-                socket.close(reason);
-            }
-        }
+        let res = self
+            .config
+            .parser
+            .decode_str(&socket.data.parser_state, msg);
+        self.on_decoded(res, &socket);
     }
 
-    /// When a binary payload is received from a socket, it is applied to the partial binary packet
+    /// When a binary message is received from a socket, it is decoded by the parser,
+    /// with the default parser it is applied to the partial binary packet
     ///
     /// If the packet is complete, it is propagated to the namespace
     fn on_binary(&self, data: Vec<u8>, socket: Arc<EIoSocket<SocketData>>) {
-        if self.apply_payload_on_packet(data, &socket) {
-            if let Some(packet) = socket.data.partial_bin_packet.lock().unwrap().take() {
-                if let Err(ref err) = self.sock_propagate_packet(packet, socket.id) {
-                    #[cfg(feature = "tracing")]
-                    tracing::debug!(
-                        "error while propagating packet to socket {}: {}",
-                        socket.id,
-                        err
-                    );
-                    if let Some(reason) = err.into() {
-                        socket.close(reason);
-                    }
-                }
-            }
-        }
+        let res = self
+            .config
+            .parser
+            .decode_bin(&socket.data.parser_state, data);
+        self.on_decoded(res, &socket);
     }
 }
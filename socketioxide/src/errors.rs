@@ -1,10 +1,14 @@
 use crate::{adapter::Adapter, Socket};
 use engineioxide::{sid::Sid, socket::DisconnectReason as EIoDisconnectReason};
+use serde_json::Value;
 use std::{
     fmt::{Debug, Display},
     sync::Arc,
 };
-use tokio::sync::{mpsc::error::TrySendError, oneshot};
+use tokio::sync::{
+    mpsc::error::{SendTimeoutError, TrySendError},
+    oneshot,
+};
 
 /// Error type for socketio
 #[derive(thiserror::Error, Debug)]
@@ -12,11 +16,8 @@ pub enum Error {
     #[error("error serializing json packet: {0:?}")]
     SerializeError(#[from] serde_json::Error),
 
-    #[error("invalid packet type")]
-    InvalidPacketType,
-
-    #[error("invalid event name")]
-    InvalidEventName,
+    #[error("parser error: {0}")]
+    Parser(#[from] ParserError),
 
     #[error("invalid namespace")]
     InvalidNamespace,
@@ -41,14 +42,37 @@ impl From<&Error> for Option<EIoDisconnectReason> {
         match value {
             Error::SocketGone(_) => Some(TransportClose),
             Error::EngineIoError(ref e) => e.into(),
-            Error::SerializeError(_) | Error::InvalidPacketType | Error::InvalidEventName => {
-                Some(PacketParsingError)
-            }
+            Error::SerializeError(_) | Error::Parser(_) => Some(PacketParsingError),
             Error::Adapter(_) | Error::InvalidNamespace => None,
         }
     }
 }
 
+/// Error type for the [`Parser`](crate::parser::Parser) trait, returned when a packet cannot be encoded or decoded
+#[derive(thiserror::Error, Debug)]
+pub enum ParserError {
+    #[error("error serializing json packet: {0:?}")]
+    Serialize(#[from] serde_json::Error),
+
+    #[error("invalid packet type")]
+    InvalidPacketType,
+
+    #[error("invalid event name")]
+    InvalidEventName,
+
+    /// The message type (text or binary) is not supported by the parser
+    #[error("unsupported message type")]
+    UnsupportedMessage,
+
+    #[cfg(feature = "msgpack")]
+    #[error("error decoding msgpack packet: {0}")]
+    MsgPackDecode(#[from] rmpv::decode::Error),
+
+    /// A custom error returned by a user-provided parser
+    #[error("{0}")]
+    Custom(Box<dyn std::error::Error + Send + Sync>),
+}
+
 /// Error type for ack responses
 #[derive(thiserror::Error, Debug)]
 pub enum AckError {
@@ -112,8 +136,20 @@ pub enum SendError {
     #[error("Adapter error: {0}")]
     AdapterError(#[from] AdapterError),
 
+    /// An error occurred while encoding the packet with the parser.
+    #[error("Parser error: {0}")]
+    Parser(#[from] ParserError),
+
     #[error("internal channel full error")]
     InternalChannelFull,
+
+    /// The buffer of the socket was still full when the timeout of an awaitable emit elapsed.
+    #[error("timeout waiting for a place in the internal channel")]
+    Timeout,
+
+    /// The socket was closed while waiting for a place in its buffer.
+    #[error("socket closed")]
+    SocketClosed,
 }
 
 impl<T> From<TrySendError<T>> for SendError {
@@ -125,6 +161,15 @@ impl<T> From<TrySendError<T>> for SendError {
     }
 }
 
+impl<T> From<SendTimeoutError<T>> for SendError {
+    fn from(value: SendTimeoutError<T>) -> Self {
+        match value {
+            SendTimeoutError::Timeout(_) => Self::Timeout,
+            SendTimeoutError::Closed(_) => Self::SocketClosed,
+        }
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum AckSenderError<A: Adapter> {
     #[error("Failed to send ack message")]
@@ -136,6 +181,60 @@ pub enum AckSenderError<A: Adapter> {
     },
 }
 
+/// Error returned by a namespace middleware to refuse the connection of a socket.
+///
+/// It is sent to the client in a `connect_error` packet with its message and its optional data.
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+#[error("{message}")]
+pub struct ConnectError {
+    pub(crate) message: String,
+    pub(crate) data: Option<Value>,
+}
+
+impl ConnectError {
+    /// Create a new [`ConnectError`] with the given message
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    /// Add some data to the error, it is available on the client in the `data` field of the error
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+}
+
+impl From<&str> for ConnectError {
+    fn from(message: &str) -> Self {
+        Self::new(message)
+    }
+}
+impl From<String> for ConnectError {
+    fn from(message: String) -> Self {
+        Self::new(message)
+    }
+}
+
+/// Error returned when a dynamic namespace pattern is invalid.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum NsPatternError {
+    /// The pattern does not start with a `/`
+    #[error("namespace pattern must start with a '/'")]
+    MissingLeadingSlash,
+    /// A `{` is not closed by a `}` or a `}` is not preceded by a `{`
+    #[error("unbalanced brace in namespace pattern")]
+    UnbalancedBrace,
+    /// A parameter has no name, e.g `{}`
+    #[error("empty parameter name in namespace pattern")]
+    EmptyParam,
+    /// Two parameters follow each other without a separator, e.g `{a}{b}`
+    #[error("parameters must be separated by a literal in namespace pattern")]
+    AdjacentParams,
+}
+
 /// Error type for the [`Adapter`] trait.
 #[derive(Debug, thiserror::Error)]
 pub struct AdapterError(#[from] pub Box<dyn std::error::Error + Send>);
@@ -9,6 +9,7 @@
 use dashmap::DashMap;
 use std::fmt;
 use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
 use std::{
     any::{Any, TypeId},
     hash::{BuildHasherDefault, Hasher},
@@ -120,7 +121,9 @@ impl Hasher for IdHasher {
 pub struct Extensions {
     /// The underlying map. It is not wrapped with an option because it would require insert calls to take a mutable reference.
     /// Therefore an anydashmap will be allocated for every socket, even if it is not used.
-    map: AnyDashMap,
+    ///
+    /// It is shared with the session of the socket when it is disconnected, so that it can be recovered.
+    map: Arc<AnyDashMap>,
 }
 
 impl Extensions {
@@ -128,7 +131,14 @@ impl Extensions {
     #[inline]
     pub fn new() -> Extensions {
         Extensions {
-            map: AnyDashMap::default(),
+            map: Arc::new(AnyDashMap::default()),
+        }
+    }
+
+    /// Get a new handle to the same extensions, used to keep them in the session of a disconnected socket.
+    pub(crate) fn share(&self) -> Extensions {
+        Extensions {
+            map: self.map.clone(),
         }
     }
 
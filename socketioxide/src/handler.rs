@@ -1,28 +1,66 @@
+//! Handlers called when a socket connects to a namespace or when it receives a message.
+//!
+//! A handler is an async function or closure taking any number of extractors as arguments,
+//! see the [`extract`] module for the available extractors:
+//! * [`ConnectHandler`] and [`ConnectMiddleware`] are called when a socket connects to a namespace,
+//!   their arguments implement [`FromConnectParts`].
+//! * [`MessageHandler`] is called when a socket receives an event,
+//!   its arguments implement [`FromMessageParts`].
 use std::sync::Arc;
 
-use futures::future::BoxFuture;
+use futures::{future::BoxFuture, stream::BoxStream};
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
+use tokio::sync::oneshot;
 
-use crate::errors::AckSenderError;
-use crate::{adapter::Adapter, errors::Error, packet::Packet, Socket};
+use crate::{
+    adapter::{Adapter, RoomEvent},
+    errors::AckError,
+};
 
-pub type AckResponse<T> = (T, Vec<Vec<u8>>);
+pub(crate) use connect::{BoxedConnectHandler, BoxedConnectMiddleware, MakeErasedConnectHandler};
+pub use connect::{ConnectHandler, ConnectMiddleware, FromConnectParts};
+pub(crate) use message::{BoxedMessageHandler, MakeErasedMessageHandler};
+pub use message::{FromMessageParts, MessageHandler};
 
-pub(crate) type BoxedMessageHandler<A> = Box<dyn MessageCaller<A>>;
-pub(crate) type BoxedNamespaceHandler<A> = Box<dyn NamespaceCaller<A>>;
-pub(crate) trait MessageCaller<A: Adapter>: Send + Sync + 'static {
-    fn call(
-        &self,
-        s: Arc<Socket<A>>,
-        v: Value,
-        p: Vec<Vec<u8>>,
-        ack_id: Option<i64>,
-    ) -> Result<(), Error>;
+/// Call the given macro for every handler arity, from 0 to 16 arguments
+macro_rules! all_the_tuples {
+    ($name:ident) => {
+        $name!([]);
+        $name!([T1]);
+        $name!([T1, T2]);
+        $name!([T1, T2, T3]);
+        $name!([T1, T2, T3, T4]);
+        $name!([T1, T2, T3, T4, T5]);
+        $name!([T1, T2, T3, T4, T5, T6]);
+        $name!([T1, T2, T3, T4, T5, T6, T7]);
+        $name!([T1, T2, T3, T4, T5, T6, T7, T8]);
+        $name!([T1, T2, T3, T4, T5, T6, T7, T8, T9]);
+        $name!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10]);
+        $name!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11]);
+        $name!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12]);
+        $name!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13]);
+        $name!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14]);
+        $name!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15]);
+        $name!([T1, T2, T3, T4, T5, T6, T7, T8, T9, T10, T11, T12, T13, T14, T15, T16]);
+    };
 }
 
-pub(crate) trait NamespaceCaller<A: Adapter>: Send + Sync + 'static {
-    fn call(&self, s: Arc<Socket<A>>, auth: Option<String>) -> Result<(), serde_json::Error>;
+mod connect;
+pub mod extract;
+mod message;
+
+pub type AckResponse<T> = (T, Vec<Vec<u8>>);
+
+/// The stream of the ack responses of the sockets selected by a broadcast,
+/// returned by [`Operators::emit_with_ack`](crate::Operators::emit_with_ack).
+pub type AckStream<T> = BoxStream<'static, Result<AckResponse<T>, AckError>>;
+
+pub(crate) type BoxedServerEventHandler = Box<dyn ServerEventCaller>;
+pub(crate) type BoxedRoomEventHandler = Arc<dyn Fn(&RoomEvent) + Send + Sync + 'static>;
+
+pub(crate) trait ServerEventCaller: Send + Sync + 'static {
+    fn call(&self, v: Value, ack: ServerAckSender) -> Result<(), serde_json::Error>;
 }
 
 pub(crate) struct CallbackHandler<Param, F, A>
@@ -37,13 +75,10 @@ where
 impl<Param, F, A> CallbackHandler<Param, F, A>
 where
     Param: DeserializeOwned + Send + Sync + 'static,
-    F: Fn(Arc<Socket<A>>, Param, Vec<Vec<u8>>, AckSender<A>) -> BoxFuture<'static, ()>
-        + Send
-        + Sync
-        + 'static,
+    F: Fn(Param, ServerAckSender) -> BoxFuture<'static, ()> + Send + Sync + 'static,
     A: Adapter,
 {
-    pub fn boxed_message_handler(handler: F) -> Box<Self> {
+    pub fn boxed_server_handler(handler: F) -> Box<Self> {
         Box::new(Self {
             param: std::marker::PhantomData,
             adapter: std::marker::PhantomData,
@@ -52,120 +87,55 @@ where
     }
 }
 
-impl<Param, F, A> CallbackHandler<Param, F, A>
+impl<Param, F, A> ServerEventCaller for CallbackHandler<Param, F, A>
 where
     Param: DeserializeOwned + Send + Sync + 'static,
-    F: Fn(Arc<Socket<A>>, Param) -> BoxFuture<'static, ()> + Send + Sync + 'static,
+    F: Fn(Param, ServerAckSender) -> BoxFuture<'static, ()> + Send + Sync + 'static,
     A: Adapter,
 {
-    pub fn boxed_ns_handler(handler: F) -> Box<Self> {
-        Box::new(Self {
-            param: std::marker::PhantomData,
-            adapter: std::marker::PhantomData,
-            handler,
-        })
-    }
-}
-
-impl<Param, F, A> MessageCaller<A> for CallbackHandler<Param, F, A>
-where
-    Param: DeserializeOwned + Send + Sync + 'static,
-    F: Fn(Arc<Socket<A>>, Param, Vec<Vec<u8>>, AckSender<A>) -> BoxFuture<'static, ()>
-        + Send
-        + Sync
-        + 'static,
-    A: Adapter,
-{
-    fn call(
-        &self,
-        s: Arc<Socket<A>>,
-        v: Value,
-        p: Vec<Vec<u8>>,
-        ack_id: Option<i64>,
-    ) -> Result<(), Error> {
-        // Unwrap array if it has only one element
-        let v = match v {
-            Value::Array(v) => {
-                if v.len() == 1 {
-                    v.into_iter().next().unwrap_or(Value::Null)
-                } else {
-                    Value::Array(v)
-                }
-            }
-            v => v,
-        };
-        let v: Param = serde_json::from_value(v)?;
-        let owned_socket = s.clone();
-        let fut = (self.handler)(s, v, p, AckSender::new(owned_socket, ack_id));
+    fn call(&self, v: Value, ack: ServerAckSender) -> Result<(), serde_json::Error> {
+        let v: Param = from_args(v)?;
+        let fut = (self.handler)(v, ack);
         tokio::spawn(fut);
         Ok(())
     }
 }
 
-impl<Param, F, A> NamespaceCaller<A> for CallbackHandler<Param, F, A>
-where
-    Param: DeserializeOwned + Send + Sync + 'static,
-    F: Fn(Arc<Socket<A>>, Param) -> BoxFuture<'static, ()> + Send + Sync + 'static,
-    A: Adapter,
-{
-    fn call(&self, s: Arc<Socket<A>>, auth: Option<String>) -> Result<(), serde_json::Error> {
-        let v: Param = serde_json::from_str(&auth.unwrap_or("{}".to_string()))?;
-        let fut = (self.handler)(s, v);
-        tokio::spawn(fut);
-        Ok(())
+/// Deserialize the arguments of an event or of an ack response to `T`.
+///
+/// A single argument is deserialized to `T` first.
+/// If it fails, or if there are zero or multiple arguments, `T` is deserialized from the array of all the arguments,
+/// for example to a tuple or a `Vec<Value>`.
+pub(crate) fn from_args<T: DeserializeOwned>(args: Value) -> Result<T, serde_json::Error> {
+    match args {
+        Value::Array(args) if args.len() == 1 => {
+            T::deserialize(&args[0]).or_else(|_| T::deserialize(Value::Array(args)))
+        }
+        args => T::deserialize(args),
     }
 }
 
-/// AckSender is used to send an ack response to the client.
-/// If the client did not request an ack, it will not send anything.
+/// ServerAckSender is used to answer an event emitted by another server
+/// with [`SocketIo::server_side_emit_with_ack`](crate::SocketIo::server_side_emit_with_ack).
+/// If the other server did not request an ack, it will not send anything.
 #[derive(Debug)]
-pub struct AckSender<A: Adapter> {
-    binary: Vec<Vec<u8>>,
-    socket: Arc<Socket<A>>,
-    ack_id: Option<i64>,
+pub struct ServerAckSender {
+    tx: Option<oneshot::Sender<Value>>,
 }
-impl<A: Adapter> AckSender<A> {
-    pub(crate) fn new(socket: Arc<Socket<A>>, ack_id: Option<i64>) -> Self {
-        Self {
-            binary: vec![],
-            socket,
-            ack_id,
-        }
+impl ServerAckSender {
+    /// Create a new [`ServerAckSender`], used by [`Adapter`] implementations
+    /// to forward an event received from another server to the namespace handlers.
+    ///
+    /// The ack response is sent on the `tx` channel if there is one.
+    pub fn new(tx: Option<oneshot::Sender<Value>>) -> Self {
+        Self { tx }
     }
 
-    /// Add binary data to the ack response.
-    pub fn bin(mut self, bin: Vec<Vec<u8>>) -> Self {
-        self.binary = bin;
-        self
-    }
-
-    /// Send the ack response to the client.
-    pub fn send(self, data: impl Serialize) -> Result<(), AckSenderError<A>> {
-        if let Some(ack_id) = self.ack_id {
-            let ns = self.socket.ns().clone();
-            let data = match serde_json::to_value(&data) {
-                Err(err) => {
-                    return Err(AckSenderError::SendError {
-                        send_error: err.into(),
-                        socket: self.socket,
-                    })
-                }
-                Ok(data) => data,
-            };
-
-            let packet = if self.binary.is_empty() {
-                Packet::ack(ns, data, ack_id)
-            } else {
-                Packet::bin_ack(ns, data, self.binary, ack_id)
-            };
-            self.socket
-                .send(packet)
-                .map_err(|err| AckSenderError::SendError {
-                    send_error: err,
-                    socket: self.socket,
-                })
-        } else {
-            Ok(())
+    /// Send the ack response to the server that emitted the event.
+    pub fn send(self, data: impl Serialize) -> Result<(), serde_json::Error> {
+        if let Some(tx) = self.tx {
+            tx.send(serde_json::to_value(data)?).ok();
         }
+        Ok(())
     }
 }
@@ -0,0 +1,151 @@
+//! Handlers and middlewares called when a socket connects to a namespace,
+//! registered with [`SocketIo::ns`](crate::SocketIo::ns) and [`NsHandle::with_middleware`](crate::NsHandle::with_middleware).
+use std::{future::Future, marker::PhantomData, sync::Arc};
+
+use futures::future::BoxFuture;
+
+use crate::{adapter::Adapter, errors::ConnectError, socket::Socket};
+
+pub(crate) type BoxedConnectHandler<A> = Arc<dyn ErasedConnectHandler<A>>;
+pub(crate) type BoxedConnectMiddleware<A> = Arc<dyn ErasedConnectMiddleware<A>>;
+
+/// A type that can be extracted from the parts of a connection to a namespace:
+/// the connecting socket and the auth data sent by the client.
+pub trait FromConnectParts<A: Adapter>: Sized {
+    /// The error returned if the extraction fails.
+    type Error: std::error::Error + 'static;
+
+    /// Extract the value from the parts of the connection
+    fn from_connect_parts(s: &Arc<Socket<A>>, auth: &Option<String>) -> Result<Self, Self::Error>;
+}
+
+/// A handler called when a socket is connected to a namespace.
+///
+/// It is implemented for any async function or closure whose arguments implement [`FromConnectParts`].
+/// If one of the extractors fails, the handler is not called.
+pub trait ConnectHandler<A: Adapter, T>: Send + Sync + 'static {
+    /// Extract the arguments of the handler and spawn the handler
+    fn call(&self, s: Arc<Socket<A>>, auth: Option<String>);
+}
+
+/// A middleware called before a socket is connected to a namespace.
+///
+/// It is implemented for any async function or closure whose arguments implement [`FromConnectParts`]
+/// and that returns a `Result<(), ConnectError>`.
+/// If one of the extractors fails, the connection is refused with the error of the extractor as message.
+pub trait ConnectMiddleware<A: Adapter, T>: Send + Sync + 'static {
+    /// Extract the arguments of the middleware and return its future
+    fn call(
+        &self,
+        s: Arc<Socket<A>>,
+        auth: &Option<String>,
+    ) -> BoxFuture<'static, Result<(), ConnectError>>;
+}
+
+/// A [`ConnectHandler`] with its arguments type erased so that it can be stored
+pub(crate) trait ErasedConnectHandler<A: Adapter>: Send + Sync + 'static {
+    fn call(&self, s: Arc<Socket<A>>, auth: Option<String>);
+}
+
+/// A [`ConnectMiddleware`] with its arguments type erased so that it can be stored
+pub(crate) trait ErasedConnectMiddleware<A: Adapter>: Send + Sync + 'static {
+    fn call(
+        &self,
+        s: Arc<Socket<A>>,
+        auth: &Option<String>,
+    ) -> BoxFuture<'static, Result<(), ConnectError>>;
+}
+
+pub(crate) struct MakeErasedConnectHandler<H, A, T> {
+    handler: H,
+    type_: PhantomData<fn(A, T)>,
+}
+
+impl<H, A, T> MakeErasedConnectHandler<H, A, T> {
+    pub fn new_arc(handler: H) -> Arc<Self> {
+        Arc::new(Self {
+            handler,
+            type_: PhantomData,
+        })
+    }
+}
+
+impl<H, A, T> ErasedConnectHandler<A> for MakeErasedConnectHandler<H, A, T>
+where
+    H: ConnectHandler<A, T>,
+    A: Adapter,
+    T: Send + Sync + 'static,
+{
+    fn call(&self, s: Arc<Socket<A>>, auth: Option<String>) {
+        self.handler.call(s, auth);
+    }
+}
+
+impl<H, A, T> ErasedConnectMiddleware<A> for MakeErasedConnectHandler<H, A, T>
+where
+    H: ConnectMiddleware<A, T>,
+    A: Adapter,
+    T: Send + Sync + 'static,
+{
+    fn call(
+        &self,
+        s: Arc<Socket<A>>,
+        auth: &Option<String>,
+    ) -> BoxFuture<'static, Result<(), ConnectError>> {
+        self.handler.call(s, auth)
+    }
+}
+
+macro_rules! impl_connect_handler {
+    ([$($ty:ident),*]) => {
+        #[allow(non_snake_case, unused_variables)]
+        impl<A, F, Fut, $($ty,)*> ConnectHandler<A, ($($ty,)*)> for F
+        where
+            F: Fn($($ty,)*) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = ()> + Send + 'static,
+            A: Adapter,
+            $($ty: FromConnectParts<A> + Send,)*
+        {
+            fn call(&self, s: Arc<Socket<A>>, auth: Option<String>) {
+                $(
+                    let $ty = match $ty::from_connect_parts(&s, &auth) {
+                        Ok(v) => v,
+                        Err(_e) => {
+                            #[cfg(feature = "tracing")]
+                            tracing::error!("error while extracting data for the connect handler: {}", _e);
+                            return;
+                        }
+                    };
+                )*
+                tokio::spawn((self)($($ty,)*));
+            }
+        }
+
+        #[allow(non_snake_case, unused_variables)]
+        impl<A, F, Fut, $($ty,)*> ConnectMiddleware<A, ($($ty,)*)> for F
+        where
+            F: Fn($($ty,)*) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = Result<(), ConnectError>> + Send + 'static,
+            A: Adapter,
+            $($ty: FromConnectParts<A> + Send,)*
+        {
+            fn call(
+                &self,
+                s: Arc<Socket<A>>,
+                auth: &Option<String>,
+            ) -> BoxFuture<'static, Result<(), ConnectError>> {
+                $(
+                    let $ty = match $ty::from_connect_parts(&s, auth) {
+                        Ok(v) => v,
+                        Err(e) => {
+                            let err = ConnectError::new(e.to_string());
+                            return Box::pin(async move { Err(err) });
+                        }
+                    };
+                )*
+                Box::pin((self)($($ty,)*))
+            }
+        }
+    };
+}
+all_the_tuples!(impl_connect_handler);
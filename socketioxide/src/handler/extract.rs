@@ -0,0 +1,430 @@
+//! Extractors used as arguments of the connect handlers, middlewares and message handlers.
+//!
+//! #### Connect handlers and middlewares
+//! * [`SocketRef`]: the connected socket
+//! * [`Data`]: the auth data sent by the client, deserialized to `T`.
+//!   The handler is not called if the deserialization fails.
+//! * [`TryData`]: the auth data sent by the client, with the result of the deserialization
+//! * [`State`]: a clone of the application state added with [`SocketIoBuilder::with_state`](crate::SocketIoBuilder::with_state)
+//! * [`Extension`]: a clone of an extension of the socket (`extensions` feature)
+//!
+//! #### Message handlers
+//! * [`SocketRef`]: the socket that received the message
+//! * [`Event`]: the name of the event
+//! * [`Data`]: the data of the event, deserialized to `T`.
+//!   An event with multiple arguments can be deserialized to a tuple or a `Vec<Value>`.
+//!   The handler is not called if the deserialization fails.
+//! * [`TryData`]: the data of the event, with the result of the deserialization
+//! * [`Bin`]: the binary payloads of the event
+//! * [`AckSender`]: used to send an ack response to the client
+//! * [`TypedAckSender`]: an [`AckSender`] that only sends an ack response of type `T`
+//! * [`State`]: a clone of the application state added with [`SocketIoBuilder::with_state`](crate::SocketIoBuilder::with_state)
+//! * [`Extension`]: a clone of an extension of the socket (`extensions` feature)
+//!
+//! The data and the binary payloads are taken by their extractor,
+//! so they should be extracted only once per handler.
+//!
+//! #### Example
+//! ```
+//! # use socketioxide::SocketIo;
+//! # use serde_json::Value;
+//! use socketioxide::extract::{AckSender, Bin, Data, SocketRef};
+//!
+//! let (_, io) = SocketIo::new_svc();
+//! io.ns("/", |socket: SocketRef, Data(auth): Data<Value>| async move {
+//!     println!("Socket {} connected with auth {:?}", socket.id, auth);
+//!     socket.on("echo", |Data(data): Data<Value>, Bin(bin), ack: AckSender| async move {
+//!         ack.bin(bin).send(data).ok();
+//!     });
+//! });
+//! ```
+use std::{convert::Infallible, marker::PhantomData, ops::Deref, sync::Arc};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use super::{from_args, FromConnectParts, FromMessageParts};
+use crate::{
+    adapter::{Adapter, LocalAdapter},
+    errors::{AckSenderError, SendError},
+    packet::Packet,
+    socket::Socket,
+};
+
+/// An extractor that returns a reference to the [`Socket`] that connected or received the message.
+///
+/// It derefs to the [`Socket`], so all its methods can be used directly.
+#[derive(Debug)]
+pub struct SocketRef<A: Adapter = LocalAdapter>(Arc<Socket<A>>);
+
+impl<A: Adapter> SocketRef<A> {
+    /// Disconnect the socket from the current namespace,
+    /// see [`Socket::disconnect`].
+    pub async fn disconnect(self) -> Result<(), SendError> {
+        self.0.disconnect().await
+    }
+}
+
+impl<A: Adapter> Clone for SocketRef<A> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<A: Adapter> Deref for SocketRef<A> {
+    type Target = Socket<A>;
+
+    fn deref(&self) -> &Socket<A> {
+        &self.0
+    }
+}
+
+impl<A: Adapter> From<Arc<Socket<A>>> for SocketRef<A> {
+    fn from(socket: Arc<Socket<A>>) -> Self {
+        Self(socket)
+    }
+}
+
+impl<A: Adapter> FromConnectParts<A> for SocketRef<A> {
+    type Error = Infallible;
+    fn from_connect_parts(s: &Arc<Socket<A>>, _: &Option<String>) -> Result<Self, Infallible> {
+        Ok(SocketRef(s.clone()))
+    }
+}
+
+impl<A: Adapter> FromMessageParts<A> for SocketRef<A> {
+    type Error = Infallible;
+    fn from_message_parts(
+        s: &Arc<Socket<A>>,
+        _: &str,
+        _: &mut Value,
+        _: &mut Vec<Vec<u8>>,
+        _: &Option<i64>,
+    ) -> Result<Self, Infallible> {
+        Ok(SocketRef(s.clone()))
+    }
+}
+
+/// An extractor that deserializes the data of the message or the auth data of the connection to `T`.
+///
+/// A message with a single argument is deserialized from this argument.
+/// A message with multiple arguments, or whose single argument can't be deserialized to `T`,
+/// is deserialized from the array of all its arguments, for example to a tuple or a `Vec<Value>`.
+///
+/// If the deserialization fails, the handler is not called.
+/// Use [`TryData`] to handle the deserialization error.
+#[derive(Debug)]
+pub struct Data<T>(pub T);
+
+impl<T: DeserializeOwned, A: Adapter> FromConnectParts<A> for Data<T> {
+    type Error = serde_json::Error;
+    fn from_connect_parts(_: &Arc<Socket<A>>, auth: &Option<String>) -> Result<Self, Self::Error> {
+        let auth = auth.as_deref().unwrap_or("{}");
+        serde_json::from_str(auth).map(Data)
+    }
+}
+
+impl<T: DeserializeOwned, A: Adapter> FromMessageParts<A> for Data<T> {
+    type Error = serde_json::Error;
+    fn from_message_parts(
+        _: &Arc<Socket<A>>,
+        _: &str,
+        v: &mut Value,
+        _: &mut Vec<Vec<u8>>,
+        _: &Option<i64>,
+    ) -> Result<Self, Self::Error> {
+        from_args(v.take()).map(Data)
+    }
+}
+
+/// An extractor that tries to deserialize the data of the message or the auth data of the connection to `T`,
+/// and returns the result of the deserialization. The handler is always called.
+#[derive(Debug)]
+pub struct TryData<T>(pub Result<T, serde_json::Error>);
+
+impl<T: DeserializeOwned, A: Adapter> FromConnectParts<A> for TryData<T> {
+    type Error = Infallible;
+    fn from_connect_parts(_: &Arc<Socket<A>>, auth: &Option<String>) -> Result<Self, Infallible> {
+        let auth = auth.as_deref().unwrap_or("{}");
+        Ok(TryData(serde_json::from_str(auth)))
+    }
+}
+
+impl<T: DeserializeOwned, A: Adapter> FromMessageParts<A> for TryData<T> {
+    type Error = Infallible;
+    fn from_message_parts(
+        _: &Arc<Socket<A>>,
+        _: &str,
+        v: &mut Value,
+        _: &mut Vec<Vec<u8>>,
+        _: &Option<i64>,
+    ) -> Result<Self, Infallible> {
+        Ok(TryData(from_args(v.take())))
+    }
+}
+
+/// An extractor that returns the binary payloads of the message.
+#[derive(Debug)]
+pub struct Bin(pub Vec<Vec<u8>>);
+
+impl<A: Adapter> FromMessageParts<A> for Bin {
+    type Error = Infallible;
+    fn from_message_parts(
+        _: &Arc<Socket<A>>,
+        _: &str,
+        _: &mut Value,
+        p: &mut Vec<Vec<u8>>,
+        _: &Option<i64>,
+    ) -> Result<Self, Infallible> {
+        Ok(Bin(std::mem::take(p)))
+    }
+}
+
+/// An extractor that returns the name of the event,
+/// mostly useful in the handlers registered with [`Socket::on_any`].
+#[derive(Debug)]
+pub struct Event(pub String);
+
+impl<A: Adapter> FromMessageParts<A> for Event {
+    type Error = Infallible;
+    fn from_message_parts(
+        _: &Arc<Socket<A>>,
+        e: &str,
+        _: &mut Value,
+        _: &mut Vec<Vec<u8>>,
+        _: &Option<i64>,
+    ) -> Result<Self, Infallible> {
+        Ok(Event(e.to_string()))
+    }
+}
+
+/// An extractor used to send an ack response to the client.
+/// If the client did not request an ack, it will not send anything.
+#[derive(Debug)]
+pub struct AckSender<A: Adapter = LocalAdapter> {
+    binary: Vec<Vec<u8>>,
+    socket: Arc<Socket<A>>,
+    ack_id: Option<i64>,
+}
+
+impl<A: Adapter> FromMessageParts<A> for AckSender<A> {
+    type Error = Infallible;
+    fn from_message_parts(
+        s: &Arc<Socket<A>>,
+        _: &str,
+        _: &mut Value,
+        _: &mut Vec<Vec<u8>>,
+        ack_id: &Option<i64>,
+    ) -> Result<Self, Infallible> {
+        Ok(AckSender::new(s.clone(), *ack_id))
+    }
+}
+
+impl<A: Adapter> AckSender<A> {
+    pub(crate) fn new(socket: Arc<Socket<A>>, ack_id: Option<i64>) -> Self {
+        Self {
+            binary: vec![],
+            socket,
+            ack_id,
+        }
+    }
+
+    /// Add binary data to the ack response.
+    pub fn bin(mut self, bin: Vec<Vec<u8>>) -> Self {
+        self.binary = bin;
+        self
+    }
+
+    /// Send the ack response to the client.
+    ///
+    /// Like with [`Socket::emit`], a tuple or an array is sent as multiple arguments
+    /// and any other value as a single argument.
+    pub fn send(self, data: impl Serialize) -> Result<(), AckSenderError<A>> {
+        if let Some(ack_id) = self.ack_id {
+            let ns = self.socket.ns().clone();
+            let data = match serde_json::to_value(&data) {
+                Err(err) => {
+                    return Err(AckSenderError::SendError {
+                        send_error: err.into(),
+                        socket: self.socket,
+                    })
+                }
+                Ok(data) => data,
+            };
+
+            let packet = if self.binary.is_empty() {
+                Packet::ack(ns, data, ack_id)
+            } else {
+                Packet::bin_ack(ns, data, self.binary, ack_id)
+            };
+            self.socket
+                .send(packet)
+                .map_err(|err| AckSenderError::SendError {
+                    send_error: err,
+                    socket: self.socket,
+                })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// An extractor used to send an ack response of type `T` to the client.
+/// If the client did not request an ack, it will not send anything.
+///
+/// It is given to the handlers generated by the `ClientEvents` derive macro (`macros` feature)
+/// for the events declared with an ack type.
+#[derive(Debug)]
+pub struct TypedAckSender<T, A: Adapter = LocalAdapter> {
+    sender: AckSender<A>,
+    type_: PhantomData<fn(T)>,
+}
+
+impl<T, A: Adapter> FromMessageParts<A> for TypedAckSender<T, A> {
+    type Error = Infallible;
+    fn from_message_parts(
+        s: &Arc<Socket<A>>,
+        _: &str,
+        _: &mut Value,
+        _: &mut Vec<Vec<u8>>,
+        ack_id: &Option<i64>,
+    ) -> Result<Self, Infallible> {
+        Ok(AckSender::new(s.clone(), *ack_id).into())
+    }
+}
+
+impl<T, A: Adapter> From<AckSender<A>> for TypedAckSender<T, A> {
+    fn from(sender: AckSender<A>) -> Self {
+        Self {
+            sender,
+            type_: PhantomData,
+        }
+    }
+}
+
+impl<T: Serialize, A: Adapter> TypedAckSender<T, A> {
+    /// Add binary data to the ack response.
+    pub fn bin(mut self, bin: Vec<Vec<u8>>) -> Self {
+        self.sender = self.sender.bin(bin);
+        self
+    }
+
+    /// Send the ack response to the client, see [`AckSender::send`].
+    ///
+    /// The data is always sent as a single argument, even if it is a tuple or an array.
+    pub fn send(self, data: T) -> Result<(), AckSenderError<A>> {
+        self.sender.send((data,))
+    }
+}
+
+/// An extractor that returns a clone of the application state of type `S`
+/// added with [`SocketIoBuilder::with_state`](crate::SocketIoBuilder::with_state).
+///
+/// If there is no state of type `S`, a handler is not called
+/// and a middleware refuses the connection.
+#[derive(Debug, Clone)]
+pub struct State<S>(pub S);
+
+/// The error returned when the state requested by a [`State`] extractor does not exist.
+#[derive(Debug, thiserror::Error)]
+#[error("state {0} not found")]
+pub struct StateNotFound(&'static str);
+
+impl<S> State<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    fn extract<A: Adapter>(s: &Arc<Socket<A>>) -> Result<Self, StateNotFound> {
+        s.state::<S>()
+            .map(|state| State(state.clone()))
+            .ok_or(StateNotFound(std::any::type_name::<S>()))
+    }
+}
+
+impl<S, A> FromConnectParts<A> for State<S>
+where
+    S: Clone + Send + Sync + 'static,
+    A: Adapter,
+{
+    type Error = StateNotFound;
+    fn from_connect_parts(s: &Arc<Socket<A>>, _: &Option<String>) -> Result<Self, Self::Error> {
+        Self::extract(s)
+    }
+}
+
+impl<S, A> FromMessageParts<A> for State<S>
+where
+    S: Clone + Send + Sync + 'static,
+    A: Adapter,
+{
+    type Error = StateNotFound;
+    fn from_message_parts(
+        s: &Arc<Socket<A>>,
+        _: &str,
+        _: &mut Value,
+        _: &mut Vec<Vec<u8>>,
+        _: &Option<i64>,
+    ) -> Result<Self, Self::Error> {
+        Self::extract(s)
+    }
+}
+
+#[cfg(feature = "extensions")]
+pub use extension::{Extension, ExtensionNotFound};
+
+#[cfg(feature = "extensions")]
+mod extension {
+    use super::*;
+
+    /// An extractor that returns a clone of an extension of the socket,
+    /// inserted for example by a middleware with [`Socket::extensions`].
+    ///
+    /// If there is no extension of type `T`, a handler is not called
+    /// and a middleware refuses the connection.
+    #[derive(Debug, Clone)]
+    pub struct Extension<T>(pub T);
+
+    /// The error returned when the extension requested by an [`Extension`] extractor does not exist.
+    #[derive(Debug, thiserror::Error)]
+    #[error("extension {0} not found")]
+    pub struct ExtensionNotFound(&'static str);
+
+    fn extract<T, A>(s: &Arc<Socket<A>>) -> Result<Extension<T>, ExtensionNotFound>
+    where
+        T: Clone + Send + Sync + 'static,
+        A: Adapter,
+    {
+        s.extensions
+            .get::<T>()
+            .map(|ext| Extension(ext.clone()))
+            .ok_or(ExtensionNotFound(std::any::type_name::<T>()))
+    }
+
+    impl<T, A> FromConnectParts<A> for Extension<T>
+    where
+        T: Clone + Send + Sync + 'static,
+        A: Adapter,
+    {
+        type Error = ExtensionNotFound;
+        fn from_connect_parts(s: &Arc<Socket<A>>, _: &Option<String>) -> Result<Self, Self::Error> {
+            extract(s)
+        }
+    }
+
+    impl<T, A> FromMessageParts<A> for Extension<T>
+    where
+        T: Clone + Send + Sync + 'static,
+        A: Adapter,
+    {
+        type Error = ExtensionNotFound;
+        fn from_message_parts(
+            s: &Arc<Socket<A>>,
+            _: &str,
+            _: &mut Value,
+            _: &mut Vec<Vec<u8>>,
+            _: &Option<i64>,
+        ) -> Result<Self, Self::Error> {
+            extract(s)
+        }
+    }
+}
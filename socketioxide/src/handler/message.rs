@@ -0,0 +1,109 @@
+//! Handlers called when a socket receives an event, registered with [`Socket::on`] and [`Socket::on_any`].
+use std::{future::Future, marker::PhantomData, sync::Arc};
+
+use serde_json::Value;
+
+use crate::{adapter::Adapter, socket::Socket};
+
+pub(crate) type BoxedMessageHandler<A> = Box<dyn ErasedMessageHandler<A>>;
+
+/// A type that can be extracted from the parts of a message received by a socket:
+/// its socket, the name of the event, its data, its binary payloads and its ack id.
+///
+/// The data and the binary payloads are mutable so that an extractor can take them
+/// instead of cloning them, the next extractors of the handler then get an empty value.
+pub trait FromMessageParts<A: Adapter>: Sized {
+    /// The error returned if the extraction fails, the handler is not called in this case.
+    type Error: std::error::Error + 'static;
+
+    /// Extract the value from the parts of the message
+    fn from_message_parts(
+        s: &Arc<Socket<A>>,
+        e: &str,
+        v: &mut Value,
+        p: &mut Vec<Vec<u8>>,
+        ack_id: &Option<i64>,
+    ) -> Result<Self, Self::Error>;
+}
+
+/// A handler called when a socket receives an event.
+///
+/// It is implemented for any async function or closure whose arguments implement [`FromMessageParts`].
+/// If one of the extractors fails, the handler is not called.
+///
+/// The `T` parameter is the tuple of the arguments of the handler,
+/// it allows to implement this trait for closures with any number of arguments.
+pub trait MessageHandler<A: Adapter, T>: Send + Sync + 'static {
+    /// Extract the arguments of the handler from the message and spawn the handler
+    fn call(&self, s: Arc<Socket<A>>, e: String, v: Value, p: Vec<Vec<u8>>, ack_id: Option<i64>);
+}
+
+/// A [`MessageHandler`] with its arguments type erased so that it can be stored
+pub(crate) trait ErasedMessageHandler<A: Adapter>: Send + Sync + 'static {
+    fn call(&self, s: Arc<Socket<A>>, e: String, v: Value, p: Vec<Vec<u8>>, ack_id: Option<i64>);
+}
+
+pub(crate) struct MakeErasedMessageHandler<H, A, T> {
+    handler: H,
+    type_: PhantomData<fn(A, T)>,
+}
+
+impl<H, A, T> MakeErasedMessageHandler<H, A, T>
+where
+    H: MessageHandler<A, T>,
+    A: Adapter,
+    T: Send + Sync + 'static,
+{
+    pub fn new_boxed(handler: H) -> Box<Self> {
+        Box::new(Self {
+            handler,
+            type_: PhantomData,
+        })
+    }
+}
+
+impl<H, A, T> ErasedMessageHandler<A> for MakeErasedMessageHandler<H, A, T>
+where
+    H: MessageHandler<A, T>,
+    A: Adapter,
+    T: Send + Sync + 'static,
+{
+    fn call(&self, s: Arc<Socket<A>>, e: String, v: Value, p: Vec<Vec<u8>>, ack_id: Option<i64>) {
+        self.handler.call(s, e, v, p, ack_id);
+    }
+}
+
+macro_rules! impl_message_handler {
+    ([$($ty:ident),*]) => {
+        #[allow(non_snake_case, unused_mut, unused_variables)]
+        impl<A, F, Fut, $($ty,)*> MessageHandler<A, ($($ty,)*)> for F
+        where
+            F: Fn($($ty,)*) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = ()> + Send + 'static,
+            A: Adapter,
+            $($ty: FromMessageParts<A> + Send,)*
+        {
+            fn call(
+                &self,
+                s: Arc<Socket<A>>,
+                e: String,
+                mut v: Value,
+                mut p: Vec<Vec<u8>>,
+                ack_id: Option<i64>,
+            ) {
+                $(
+                    let $ty = match $ty::from_message_parts(&s, &e, &mut v, &mut p, &ack_id) {
+                        Ok(v) => v,
+                        Err(_e) => {
+                            #[cfg(feature = "tracing")]
+                            tracing::error!("error while extracting data for event {}: {}", e, _e);
+                            return;
+                        }
+                    };
+                )*
+                tokio::spawn((self)($($ty,)*));
+            }
+        }
+    };
+}
+all_the_tuples!(impl_message_handler);
@@ -1,20 +1,29 @@
-use std::{sync::Arc, time::Duration};
+use std::{net::IpAddr, sync::Arc, time::Duration};
 
 use engineioxide::{
-    config::{EngineIoConfig, EngineIoConfigBuilder, TransportType},
+    config::{DeflateConfig, EngineIoConfig, EngineIoConfigBuilder, OverflowPolicy, TransportType},
     service::NotFoundService,
 };
-use futures::{stream::BoxStream, Future};
+use futures::{stream::BoxStream, Future, StreamExt};
 use serde::de::DeserializeOwned;
 
+#[cfg(feature = "redis")]
+use crate::adapter::RedisAdapterConfig;
+#[cfg(feature = "admin-ui")]
+use crate::admin::{AdminUi, AdminUiConfig};
+#[cfg(feature = "test-utils")]
+use crate::testing::TestServer;
 use crate::{
     adapter::{Adapter, LocalAdapter},
     client::Client,
-    handler::AckResponse,
+    handler::{AckResponse, ConnectHandler, ServerAckSender},
     layer::SocketIoLayer,
+    ns::{DynNsHandle, Namespace, NsHandle, NsPattern},
     operators::{Operators, RoomParam},
+    parser::{DefaultParser, Parser},
     service::SocketIoService,
-    AckError, BroadcastError, Socket,
+    state::StateMap,
+    AckError, BroadcastError, NsPatternError, Socket,
 };
 
 /// Configuration for Socket.IO & Engine.IO
@@ -32,6 +41,46 @@ pub struct SocketIoConfig {
     ///
     /// Defaults to 45 seconds.
     pub connect_timeout: Duration,
+
+    /// The amount of time the state of a disconnected socket is kept so that it can be recovered
+    /// if the client reconnects, with the same id, rooms, extensions and the packets broadcast in the meantime.
+    /// An offset is appended to the arguments of the broadcast events so that the client can tell the last one it received.
+    ///
+    /// Connection state recovery is only available with the v5 protocol and is disabled if it is `None`.
+    ///
+    /// Defaults to `None`.
+    pub max_disconnection_duration: Option<Duration>,
+
+    /// The maximum number of packets buffered for a disconnected socket.
+    /// If more packets are missed, the state of the socket is dropped and it cannot be recovered.
+    ///
+    /// Defaults to 1000.
+    pub max_missed_packets: usize,
+
+    /// Exclude the private room of each socket, named after its id, from the results of [`Socket::rooms`](crate::Socket::rooms).
+    ///
+    /// The sockets still join their private room, so they can be targeted with their id.
+    ///
+    /// Defaults to `false`.
+    pub exclude_sid_room: bool,
+
+    /// The configuration of the [`RedisAdapter`](crate::adapter::RedisAdapter).
+    ///
+    /// It is required when the server is built with the [`RedisAdapter`](crate::adapter::RedisAdapter).
+    #[cfg(feature = "redis")]
+    pub redis: Option<RedisAdapterConfig>,
+
+    /// The parser used to encode and decode the socket.io packets.
+    ///
+    /// Defaults to the [`DefaultParser`].
+    pub parser: Arc<dyn Parser>,
+
+    /// The application state set with [`SocketIoBuilder::with_state`].
+    pub(crate) state: Arc<StateMap>,
+
+    /// The admin UI instrumentation set with [`SocketIoBuilder::admin_ui`].
+    #[cfg(feature = "admin-ui")]
+    pub(crate) admin_ui: Option<Arc<AdminUi>>,
 }
 
 impl Default for SocketIoConfig {
@@ -43,6 +92,15 @@ impl Default for SocketIoConfig {
             },
             ack_timeout: Duration::from_secs(5),
             connect_timeout: Duration::from_secs(45),
+            max_disconnection_duration: None,
+            max_missed_packets: 1000,
+            exclude_sid_room: false,
+            #[cfg(feature = "redis")]
+            redis: None,
+            parser: Arc::new(DefaultParser),
+            state: Arc::default(),
+            #[cfg(feature = "admin-ui")]
+            admin_ui: None,
         }
     }
 }
@@ -56,6 +114,7 @@ pub struct SocketIoBuilder {
     config: SocketIoConfig,
     engine_config_builder: EngineIoConfigBuilder,
     req_path: String,
+    state: StateMap,
 }
 
 impl SocketIoBuilder {
@@ -65,6 +124,7 @@ impl SocketIoBuilder {
             config: SocketIoConfig::default(),
             engine_config_builder: EngineIoConfigBuilder::new(),
             req_path: "/socket.io".to_string(),
+            state: StateMap::default(),
         }
     }
 
@@ -127,6 +187,40 @@ impl SocketIoBuilder {
         self
     }
 
+    /// The policy applied when a message is emitted to a socket whose buffer is full,
+    /// it can be changed for each socket with [`Socket::set_overflow_policy`](crate::Socket::set_overflow_policy).
+    ///
+    /// Defaults to [`OverflowPolicy::Error`](crate::OverflowPolicy::Error)
+    #[inline]
+    pub fn overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.engine_config_builder = self.engine_config_builder.overflow_policy(policy);
+        self
+    }
+
+    /// The ip addresses of the reverse proxies trusted to forward the client address
+    /// in the `Forwarded` or `X-Forwarded-For` headers.
+    ///
+    /// When a connection comes from one of them, the [`address`](crate::Handshake::address)
+    /// of the socket handshake is read from these headers.
+    ///
+    /// Defaults to no trusted proxies.
+    #[inline]
+    pub fn trusted_proxies(mut self, proxies: impl IntoIterator<Item = IpAddr>) -> Self {
+        self.engine_config_builder = self.engine_config_builder.trusted_proxies(proxies);
+        self
+    }
+
+    /// Compress the websocket messages with the permessage-deflate extension,
+    /// for the clients that support it.
+    /// The messages emitted with [`compress(false)`](crate::Operators::compress) are never compressed.
+    ///
+    /// Defaults to no compression.
+    #[inline]
+    pub fn per_message_deflate(mut self, config: DeflateConfig) -> Self {
+        self.engine_config_builder = self.engine_config_builder.per_message_deflate(config);
+        self
+    }
+
     /// The amount of time the server will wait for an acknowledgement from the client before closing the connection.
     ///
     /// Defaults to 5 seconds.
@@ -145,6 +239,103 @@ impl SocketIoBuilder {
         self
     }
 
+    /// Enable connection state recovery: the state of a disconnected socket is kept during this amount of time
+    /// so that it can be recovered if the client reconnects, with the same id, rooms, extensions
+    /// and the packets broadcast in the meantime.
+    /// An offset is appended to the arguments of the broadcast events so that the client can tell the last one it received.
+    ///
+    /// Use [`Socket::recovered`] to know if a socket was recovered.
+    /// It is only available with the v5 protocol.
+    ///
+    /// Disabled by default.
+    #[inline]
+    pub fn max_disconnection_duration(mut self, max_disconnection_duration: Duration) -> Self {
+        self.config.max_disconnection_duration = Some(max_disconnection_duration);
+        self
+    }
+
+    /// The maximum number of packets buffered for a disconnected socket.
+    /// If more packets are missed, the state of the socket is dropped and it cannot be recovered.
+    ///
+    /// Defaults to 1000.
+    #[inline]
+    pub fn max_missed_packets(mut self, max_missed_packets: usize) -> Self {
+        self.config.max_missed_packets = max_missed_packets;
+        self
+    }
+
+    /// Exclude the private room of each socket, named after its id, from the results of [`Socket::rooms`](crate::Socket::rooms).
+    ///
+    /// The sockets still join their private room, so they can be targeted with their id.
+    ///
+    /// Defaults to `false`.
+    #[inline]
+    pub fn exclude_sid_room(mut self, exclude_sid_room: bool) -> Self {
+        self.config.exclude_sid_room = exclude_sid_room;
+        self
+    }
+
+    /// The configuration of the [`RedisAdapter`](crate::adapter::RedisAdapter).
+    ///
+    /// It is required to build the server with the [`RedisAdapter`](crate::adapter::RedisAdapter).
+    #[cfg(feature = "redis")]
+    #[inline]
+    pub fn redis(mut self, config: RedisAdapterConfig) -> Self {
+        self.config.redis = Some(config);
+        self
+    }
+
+    /// The parser used to encode and decode the socket.io packets.
+    ///
+    /// It can be the [`DefaultParser`], the [`MsgPackParser`](crate::parser::MsgPackParser)
+    /// with the `msgpack` feature or any custom [`Parser`].
+    ///
+    /// Defaults to the [`DefaultParser`].
+    #[inline]
+    pub fn with_parser<P: Parser>(mut self, parser: P) -> Self {
+        self.config.parser = Arc::new(parser);
+        self
+    }
+
+    /// Add a typed application state shared by all the handlers of the server.
+    ///
+    /// It can be extracted in the connect and message handlers with the [`State`](crate::extract::State) extractor,
+    /// or accessed from any socket with [`Socket::state`].
+    /// Several states of different types can be added, a state replaces the previous one of the same type.
+    ///
+    /// ```
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+    /// #[derive(Clone, Default)]
+    /// struct Counter(Arc<AtomicUsize>);
+    ///
+    /// async fn on_connect(socket: SocketRef, State(counter): State<Counter>) {
+    ///     counter.0.fetch_add(1, Ordering::Relaxed);
+    ///     socket.on_disconnect(|socket, _| async move {
+    ///         let counter = socket.state::<Counter>().unwrap();
+    ///         counter.0.fetch_sub(1, Ordering::Relaxed);
+    ///     });
+    /// }
+    ///
+    /// let (_, io) = SocketIo::builder().with_state(Counter::default()).build_svc();
+    /// io.ns("/", on_connect);
+    /// ```
+    pub fn with_state<S: Send + Sync + 'static>(mut self, state: S) -> Self {
+        self.state.insert(state);
+        self
+    }
+
+    /// Instrument the server for the [Socket.IO Admin UI](https://socket.io/docs/v4/admin-ui/),
+    /// the admin namespace is registered when the server is built.
+    ///
+    /// See the [`admin`](crate::admin) module for more details.
+    #[cfg(feature = "admin-ui")]
+    #[inline]
+    pub fn admin_ui(mut self, config: AdminUiConfig) -> Self {
+        self.config.admin_ui = Some(Arc::new(AdminUi::new(config)));
+        self
+    }
+
     #[inline]
     pub fn with_config(mut self, config: SocketIoConfig) -> Self {
         self.config = config;
@@ -164,9 +355,10 @@ impl SocketIoBuilder {
     /// The layer can be used as a tower layer
     pub fn build_layer_with_adapter<A: Adapter>(mut self) -> (SocketIoLayer<A>, SocketIo<A>) {
         self.config.engine_config = self.engine_config_builder.req_path(self.req_path).build();
+        self.config.state = Arc::new(self.state);
 
         let (layer, client) = SocketIoLayer::from_config(Arc::new(self.config));
-        (layer, SocketIo(client))
+        (layer, SocketIo::from_client(client))
     }
 
     /// Build a [`SocketIoService`] and a [`SocketIo`] instance
@@ -191,10 +383,11 @@ impl SocketIoBuilder {
         mut self,
     ) -> (SocketIoService<A, NotFoundService>, SocketIo<A>) {
         self.config.engine_config = self.engine_config_builder.req_path(self.req_path).build();
+        self.config.state = Arc::new(self.state);
 
         let (svc, client) =
             SocketIoService::with_config_inner(NotFoundService, Arc::new(self.config));
-        (svc, SocketIo(client))
+        (svc, SocketIo::from_client(client))
     }
 
     /// Build a [`SocketIoService`] and a [`SocketIo`] instance with an inner service
@@ -216,9 +409,30 @@ impl SocketIoBuilder {
         svc: S,
     ) -> (SocketIoService<A, S>, SocketIo<A>) {
         self.config.engine_config = self.engine_config_builder.req_path(self.req_path).build();
+        self.config.state = Arc::new(self.state);
 
         let (svc, client) = SocketIoService::with_config_inner(svc, Arc::new(self.config));
-        (svc, SocketIo(client))
+        (svc, SocketIo::from_client(client))
+    }
+
+    /// Build an in-memory [`TestServer`] and a [`SocketIo`] instance to test the handlers without a network
+    #[cfg(feature = "test-utils")]
+    #[inline(always)]
+    pub fn build_test(self) -> (TestServer<LocalAdapter>, SocketIo<LocalAdapter>) {
+        self.build_test_with_adapter::<LocalAdapter>()
+    }
+
+    /// Build an in-memory [`TestServer`] and a [`SocketIo`] instance with a custom [`Adapter`]
+    #[cfg(feature = "test-utils")]
+    pub fn build_test_with_adapter<A: Adapter>(mut self) -> (TestServer<A>, SocketIo<A>) {
+        self.config.engine_config = self.engine_config_builder.req_path(self.req_path).build();
+        self.config.state = Arc::new(self.state);
+
+        let client = Arc::new(Client::new(Arc::new(self.config)));
+        (
+            TestServer::new(client.clone()),
+            SocketIo::from_client(client),
+        )
     }
 }
 
@@ -274,6 +488,16 @@ impl<A: Adapter> SocketIo<A> {
         SocketIoBuilder::new()
     }
 
+    /// Create a [`SocketIo`] instance from a client built by the [`SocketIoBuilder`]
+    /// and register the admin namespace if the admin UI instrumentation is enabled
+    fn from_client(client: Arc<Client<A>>) -> Self {
+        #[cfg(feature = "admin-ui")]
+        if let Some(admin_ui) = client.config.admin_ui.clone() {
+            admin_ui.register(&client);
+        }
+        SocketIo(client)
+    }
+
     /// Returns a reference to the [`SocketIoConfig`] used by this [`SocketIo`] instance
     #[inline]
     pub fn config(&self) -> &SocketIoConfig {
@@ -282,17 +506,20 @@ impl<A: Adapter> SocketIo<A> {
 
     /// ### Register a connect handler for the given namespace.
     ///
-    /// The data parameter can be typed with anything that implement [serde::Deserialize](https://docs.rs/serde/latest/serde/).
-    /// It corresponds to the auth data sent by the client when connecting to the namespace.
+    /// The handler is an async function or closure taking any number of extractors as arguments,
+    /// see the [`extract`](crate::extract) module for the available extractors.
+    /// The auth data sent by the client when connecting to the namespace can be
+    /// extracted with [`Data`](crate::extract::Data) or [`TryData`](crate::extract::TryData).
+    /// The data can be typed with anything that implement [serde::Deserialize](https://docs.rs/serde/latest/serde/).
     ///
     ///
     /// ### V4 protocol (legacy) note:
-    /// If the v4 protocol is enabled, the auth data parameter **must** be nullable (e.g `Option`, `()` or `Default`), in particular for the root namespace.
+    /// If the v4 protocol is enabled, the auth data **must** be nullable (e.g `Option`, `()` or `Default`), in particular for the root namespace.
     /// If it is not the case your handler may be never called because of a deserialisation error.
     ///
     /// #### Simple example with a closure:
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// # use serde::{Serialize, Deserialize};
     /// #[derive(Debug, Deserialize)]
@@ -306,27 +533,83 @@ impl<A: Adapter> SocketIo<A> {
     /// }
     ///
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, auth: MyAuthData| async move {
+    /// io.ns("/", |socket: SocketRef, Data(auth): Data<MyAuthData>| async move {
     ///     if auth.token.is_empty() {
     ///         println!("Invalid token, disconnecting");
-    ///         socket.disconnect().ok();
+    ///         socket.disconnect().await.ok();
     ///         return;
     ///     }
-    ///     socket.on("test", |socket, data: MyData, _, _| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<MyData>| async move {
     ///         println!("Received a test message {:?}", data);
     ///         socket.emit("test-test", MyData { name: "Test".to_string(), age: 8 }).ok(); // Emit a message to the client
     ///     });
     /// });
     ///
     /// ```
+    ///
+    /// #### Middlewares:
+    /// The returned [`NsHandle`] can be used to add middlewares to the namespace
+    /// with [`NsHandle::with_middleware`]. They are called in order before the connect handler
+    /// and can refuse the connection by returning a [`ConnectError`](crate::ConnectError).
+    /// They take the same extractors as the connect handler.
+    /// ```
+    /// # use socketioxide::{SocketIo, ConnectError, extract::*};
+    /// # use serde_json::{json, Value};
+    /// let (_, io) = SocketIo::new_svc();
+    /// io.ns("/admin", |socket: SocketRef| async move {
+    ///     println!("Admin connected: {}", socket.id);
+    /// })
+    /// .with_middleware(|Data(auth): Data<Value>| async move {
+    ///     match auth["token"].as_str() {
+    ///         Some("secret") => Ok(()),
+    ///         _ => Err(ConnectError::new("unauthorized").with_data(json!({ "code": 401 }))),
+    ///     }
+    /// });
+    /// ```
     #[inline]
-    pub fn ns<C, F, V>(&self, path: impl Into<String>, callback: C)
+    pub fn ns<H, T>(&self, path: impl Into<String>, handler: H) -> NsHandle<A>
     where
-        C: Fn(Arc<Socket<A>>, V) -> F + Send + Sync + 'static,
-        F: Future<Output = ()> + Send + 'static,
-        V: DeserializeOwned + Send + Sync + 'static,
+        H: ConnectHandler<A, T>,
+        T: Send + Sync + 'static,
+    {
+        NsHandle(self.0.add_ns(path.into(), handler))
+    }
+
+    /// ### Register a connect handler for all the namespaces matching a pattern.
+    ///
+    /// The pattern is a namespace path with named parameters between braces, e.g `/tenant-{id}`.
+    /// A parameter matches at least one character and never matches a `/`.
+    ///
+    /// When a client connects to a namespace that is not registered with [`SocketIo::ns`] and that
+    /// matches the pattern, the namespace is created with its own adapter and the connect handler is called.
+    /// The captured parameters are available with [`Socket::ns_param`].
+    ///
+    /// The returned [`DynNsHandle`] can be used to add middlewares to the created namespaces,
+    /// or to remove them once their last socket is disconnected with [`DynNsHandle::with_cleanup`].
+    ///
+    /// If several patterns match a path, the first registered one is used.
+    ///
+    /// ```
+    /// # use socketioxide::{SocketIo, extract::SocketRef};
+    /// let (_, io) = SocketIo::new_svc();
+    /// io.dyn_ns("/tenant-{id}", |socket: SocketRef| async move {
+    ///     let tenant = socket.ns_param("id").unwrap();
+    ///     println!("Socket {} connected to tenant {}", socket.id, tenant);
+    /// })
+    /// .unwrap()
+    /// .with_cleanup();
+    /// ```
+    pub fn dyn_ns<H, T>(
+        &self,
+        pattern: impl Into<String>,
+        handler: H,
+    ) -> Result<DynNsHandle<A>, NsPatternError>
+    where
+        H: ConnectHandler<A, T>,
+        T: Send + Sync + 'static,
     {
-        self.0.add_ns(path.into(), callback);
+        let pattern = NsPattern::parse(pattern)?;
+        Ok(DynNsHandle(self.0.add_dyn_ns(pattern, handler)))
     }
 
     /// Delete the namespace with the given path
@@ -335,6 +618,12 @@ impl<A: Adapter> SocketIo<A> {
         self.0.delete_ns(path.into());
     }
 
+    /// Delete the dynamic namespace registered with the given pattern and the namespaces it created
+    #[inline]
+    pub fn delete_dyn_ns<'a>(&self, pattern: impl Into<&'a str>) {
+        self.0.delete_dyn_ns(pattern.into());
+    }
+
     /// Gracefully closes all the connections and drops every sockets
     ///
     /// Any `on_disconnect` handler will called with [`DisconnectReason::ClosingServer`](crate::DisconnectReason::ClosingServer)
@@ -349,18 +638,21 @@ impl<A: Adapter> SocketIo<A> {
     ///
     /// ## Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # #[tokio::main]
+    /// # async fn main() {
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("custom_ns", |socket, _: ()| async move {
+    /// io.ns("custom_ns", |socket: SocketRef| async move {
     ///     println!("Socket connected on /custom_ns namespace with id: {}", socket.id);
     /// });
     ///
     /// // Later in your code you can select the custom_ns namespace
     /// // and show all sockets connected to it
-    /// let sockets = io.of("custom_ns").unwrap().sockets().unwrap();
+    /// let sockets = io.of("custom_ns").unwrap().sockets().await.unwrap();
     /// for socket in sockets {
     ///    println!("found socket on /custom_ns namespace with id: {}", socket.id);
     /// }
+    /// # }
     #[inline]
     pub fn of<'a>(&self, path: impl Into<&'a str>) -> Option<Operators<A>> {
         self.get_op(path.into())
@@ -375,18 +667,21 @@ impl<A: Adapter> SocketIo<A> {
     ///
     /// ## Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # #[tokio::main]
+    /// # async fn main() {
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
     ///     println!("Socket connected on / namespace with id: {}", socket.id);
     /// });
     ///
     /// // Later in your code you can select all sockets in the room "room1"
     /// // and for example show all sockets connected to it
-    /// let sockets = io.to("room1").sockets().unwrap();
+    /// let sockets = io.to("room1").sockets().await.unwrap();
     /// for socket in sockets {
     ///   println!("found socket on / ns in room1 with id: {}", socket.id);
     /// }
+    /// # }
     #[inline]
     pub fn to(&self, rooms: impl RoomParam) -> Operators<A> {
         self.get_default_op().to(rooms)
@@ -402,18 +697,21 @@ impl<A: Adapter> SocketIo<A> {
     ///
     /// ## Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # #[tokio::main]
+    /// # async fn main() {
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
     ///     println!("Socket connected on / namespace with id: {}", socket.id);
     /// });
     ///
     /// // Later in your code you can select all sockets in the room "room1"
     /// // and for example show all sockets connected to it
-    /// let sockets = io.within("room1").sockets().unwrap();
+    /// let sockets = io.within("room1").sockets().await.unwrap();
     /// for socket in sockets {
     ///   println!("found socket on / ns in room1 with id: {}", socket.id);
     /// }
+    /// # }
     #[inline]
     pub fn within(&self, rooms: impl RoomParam) -> Operators<A> {
         self.get_default_op().within(rooms)
@@ -428,25 +726,28 @@ impl<A: Adapter> SocketIo<A> {
     ///
     /// ## Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # #[tokio::main]
+    /// # async fn main() {
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
     ///     println!("Socket connected on / namespace with id: {}", socket.id);
-    ///     socket.on("register1", |socket, data: (), _, _| async move {
-    ///         socket.join("room1");
+    ///     socket.on("register1", |socket: SocketRef, Data(data): Data<()>| async move {
+    ///         socket.join("room1").await;
     ///     });
-    ///     socket.on("register2", |socket, data: (), _, _| async move {
-    ///         socket.join("room2");
+    ///     socket.on("register2", |socket: SocketRef, Data(data): Data<()>| async move {
+    ///         socket.join("room2").await;
     ///     });
     /// });
     ///
     ///
     /// // Later in your code you can select all sockets in the root namespace that are not in the room1
     /// // and for example show all sockets connected to it
-    /// let sockets = io.except("room1").sockets().unwrap();
+    /// let sockets = io.except("room1").sockets().await.unwrap();
     /// for socket in sockets {
     ///   println!("found socket on / ns in room1 with id: {}", socket.id);
     /// }
+    /// # }
     #[inline]
     pub fn except(&self, rooms: impl RoomParam) -> Operators<A> {
         self.get_default_op().except(rooms)
@@ -462,23 +763,80 @@ impl<A: Adapter> SocketIo<A> {
     ///
     /// ## Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # #[tokio::main]
+    /// # async fn main() {
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
     ///     println!("Socket connected on / namespace with id: {}", socket.id);
     /// });
     ///
     /// // Later in your code you can select all sockets in the local node and on the root namespace
     /// // and for example show all sockets connected to it
-    /// let sockets = io.local().sockets().unwrap();
+    /// let sockets = io.local().sockets().await.unwrap();
     /// for socket in sockets {
     ///   println!("found socket on / ns in room1 with id: {}", socket.id);
     /// }
+    /// # }
     #[inline]
     pub fn local(&self) -> Operators<A> {
         self.get_default_op().local()
     }
 
+    /// Drop the message for the sockets whose transport is not currently writable
+    /// rather than buffering it or returning an error.
+    ///
+    /// Alias for `io.of("/").unwrap().volatile()`
+    ///
+    /// ## Panics
+    /// If the **default namespace "/" is not found** this fn will panic!
+    ///
+    /// ## Example
+    /// ```
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (_, io) = SocketIo::new_svc();
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     println!("Socket connected on / namespace with id: {}", socket.id);
+    /// });
+    ///
+    /// // Later in your code you can emit positions to the room1 room,
+    /// // the clients that are not ready to receive them will miss some of them
+    /// io.to("room1").volatile().emit("position", [12, 34]).await;
+    /// # }
+    #[inline]
+    pub fn volatile(&self) -> Operators<A> {
+        self.get_default_op().volatile()
+    }
+
+    /// Set if the message can be compressed, for the clients whose websocket transport negotiated
+    /// the permessage-deflate extension. The messages can be compressed by default.
+    ///
+    /// Alias for `io.of("/").unwrap().compress(compress)`
+    ///
+    /// ## Panics
+    /// If the **default namespace "/" is not found** this fn will panic!
+    ///
+    /// ## Example
+    /// ```
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (_, io) = SocketIo::new_svc();
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     println!("Socket connected on / namespace with id: {}", socket.id);
+    /// });
+    ///
+    /// // Later in your code you can emit an already compressed image without compressing it again
+    /// let image: Vec<u8> = vec![0xff, 0xd8, 0xff];
+    /// io.compress(false).bin(vec![image]).emit("image", ()).await;
+    /// # }
+    #[inline]
+    pub fn compress(&self, compress: bool) -> Operators<A> {
+        self.get_default_op().compress(compress)
+    }
+
     /// Set a custom timeout when sending a message with an acknowledgement.
     ///
     /// Alias for `io.of("/").unwrap().timeout(duration)`
@@ -488,12 +846,14 @@ impl<A: Adapter> SocketIo<A> {
     ///
     /// ### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// # use futures::stream::StreamExt;
     /// # use std::time::Duration;
+    /// # #[tokio::main]
+    /// # async fn main() {
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
     ///     println!("Socket connected on / namespace with id: {}", socket.id);
     /// });
     ///
@@ -504,6 +864,7 @@ impl<A: Adapter> SocketIo<A> {
     ///   .except("room2")
     ///   .timeout(Duration::from_secs(5))
     ///   .emit_with_ack::<Value>("message-back", "I expect an ack in 5s!")
+    ///   .await
     ///   .unwrap()
     ///   .for_each(|ack| async move {
     ///      match ack {
@@ -511,6 +872,7 @@ impl<A: Adapter> SocketIo<A> {
     ///          Err(err) => println!("Ack error {:?}", err),
     ///      }
     ///   });
+    /// # }
     #[inline]
     pub fn timeout(&self, timeout: Duration) -> Operators<A> {
         self.get_default_op().timeout(timeout)
@@ -525,10 +887,12 @@ impl<A: Adapter> SocketIo<A> {
     ///
     /// ## Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
+    /// # #[tokio::main]
+    /// # async fn main() {
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
     ///     println!("Socket connected on / namespace with id: {}", socket.id);
     /// });
     ///
@@ -538,7 +902,9 @@ impl<A: Adapter> SocketIo<A> {
     ///   .to("room3")
     ///   .except("room2")
     ///   .bin(vec![vec![1, 2, 3, 4]])
-    ///   .emit("test", ());
+    ///   .emit("test", ())
+    ///   .await;
+    /// # }
     #[inline]
     pub fn bin(&self, binary: Vec<Vec<u8>>) -> Operators<A> {
         self.get_default_op().bin(binary)
@@ -553,10 +919,12 @@ impl<A: Adapter> SocketIo<A> {
     ///
     /// ## Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
+    /// # #[tokio::main]
+    /// # async fn main() {
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
     ///     println!("Socket connected on / namespace with id: {}", socket.id);
     /// });
     ///
@@ -565,14 +933,16 @@ impl<A: Adapter> SocketIo<A> {
     /// io.to("room1")
     ///   .to("room3")
     ///   .except("room2")
-    ///   .emit("Hello World!", ());
+    ///   .emit("Hello World!", ())
+    ///   .await;
+    /// # }
     #[inline]
-    pub fn emit(
+    pub async fn emit(
         &self,
         event: impl Into<String>,
         data: impl serde::Serialize,
-    ) -> Result<(), serde_json::Error> {
-        self.get_default_op().emit(event, data)
+    ) -> Result<(), BroadcastError> {
+        self.get_default_op().emit(event, data).await
     }
 
     /// Emit a message to all sockets selected with the previous operators and return a stream of acknowledgements.
@@ -586,11 +956,13 @@ impl<A: Adapter> SocketIo<A> {
     ///
     /// ## Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// # use futures::stream::StreamExt;
+    /// # #[tokio::main]
+    /// # async fn main() {
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
     ///     println!("Socket connected on / namespace with id: {}", socket.id);
     /// });
     ///
@@ -599,19 +971,20 @@ impl<A: Adapter> SocketIo<A> {
     /// io.to("room1")
     ///   .to("room3")
     ///   .except("room2")
-    ///   .emit_with_ack::<Value>("message-back", "I expect an ack!").unwrap().for_each(|ack| async move {
+    ///   .emit_with_ack::<Value>("message-back", "I expect an ack!").await.unwrap().for_each(|ack| async move {
     ///      match ack {
     ///          Ok(ack) => println!("Ack received {:?}", ack),
     ///          Err(err) => println!("Ack error {:?}", err),
     ///      }
     ///   });
+    /// # }
     #[inline]
-    pub fn emit_with_ack<V: DeserializeOwned + Send>(
+    pub async fn emit_with_ack<V: DeserializeOwned + Send>(
         &self,
         event: impl Into<String>,
         data: impl serde::Serialize,
     ) -> Result<BoxStream<'static, Result<AckResponse<V>, AckError>>, BroadcastError> {
-        self.get_default_op().emit_with_ack(event, data)
+        self.get_default_op().emit_with_ack(event, data).await
     }
 
     /// Get all sockets selected with the previous operators.
@@ -625,22 +998,25 @@ impl<A: Adapter> SocketIo<A> {
     ///
     /// ## Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
+    /// # #[tokio::main]
+    /// # async fn main() {
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
     ///     println!("Socket connected on / namespace with id: {}", socket.id);
     /// });
     ///
     /// // Later in your code you can select all sockets in the room "room1"
     /// // and for example show all sockets connected to it
-    /// let sockets = io.within("room1").sockets().unwrap();
+    /// let sockets = io.within("room1").sockets().await.unwrap();
     /// for socket in sockets {
     ///   println!("found socket on / ns in room1 with id: {}", socket.id);
     /// }
+    /// # }
     #[inline]
-    pub fn sockets(&self) -> Result<Vec<Arc<Socket<A>>>, A::Error> {
-        self.get_default_op().sockets()
+    pub async fn sockets(&self) -> Result<Vec<Arc<Socket<A>>>, A::Error> {
+        self.get_default_op().sockets().await
     }
 
     /// Disconnect all sockets selected with the previous operators.
@@ -652,17 +1028,20 @@ impl<A: Adapter> SocketIo<A> {
     ///
     /// ## Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # #[tokio::main]
+    /// # async fn main() {
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
     ///     println!("Socket connected on / namespace with id: {}", socket.id);
     /// });
     ///
     /// // Later in your code you can disconnect all sockets in the root namespace
-    /// io.disconnect();
+    /// io.disconnect().await;
+    /// # }
     #[inline]
-    pub fn disconnect(&self) -> Result<(), BroadcastError> {
-        self.get_default_op().disconnect()
+    pub async fn disconnect(&self) -> Result<(), BroadcastError> {
+        self.get_default_op().disconnect().await
     }
 
     /// Make all sockets selected with the previous operators join the given room(s).
@@ -674,17 +1053,20 @@ impl<A: Adapter> SocketIo<A> {
     ///
     /// ### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # #[tokio::main]
+    /// # async fn main() {
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
     ///     println!("Socket connected on / namespace with id: {}", socket.id);
     /// });
     ///
     /// // Later in your code you can for example add all sockets on the root namespace to the room1 and room3
-    /// io.join(["room1", "room3"]).unwrap();
+    /// io.join(["room1", "room3"]).await.unwrap();
+    /// # }
     #[inline]
-    pub fn join(self, rooms: impl RoomParam) -> Result<(), A::Error> {
-        self.get_default_op().join(rooms)
+    pub async fn join(self, rooms: impl RoomParam) -> Result<(), A::Error> {
+        self.get_default_op().join(rooms).await
     }
 
     /// Make all sockets selected with the previous operators leave the given room(s).
@@ -696,17 +1078,126 @@ impl<A: Adapter> SocketIo<A> {
     ///
     /// ### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # #[tokio::main]
+    /// # async fn main() {
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
     ///     println!("Socket connected on / namespace with id: {}", socket.id);
     /// });
     ///
     /// // Later in your code you can for example remove all sockets on the root namespace from the room1 and room3
-    /// io.leave(["room1", "room3"]).unwrap();
+    /// io.leave(["room1", "room3"]).await.unwrap();
+    /// # }
     #[inline]
-    pub fn leave(self, rooms: impl RoomParam) -> Result<(), A::Error> {
-        self.get_default_op().leave(rooms)
+    pub async fn leave(self, rooms: impl RoomParam) -> Result<(), A::Error> {
+        self.get_default_op().leave(rooms).await
+    }
+
+    /// Emit a message to all the other servers of the cluster.
+    ///
+    /// The event is not sent to any client, it can be received with [`SocketIo::on_server_event`] on the other servers.
+    /// With the [`LocalAdapter`] there is no other server so the event is not sent anywhere.
+    ///
+    /// ## Panics
+    /// If the **default namespace "/" is not found** this fn will panic!
+    ///
+    /// ## Example
+    /// ```
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (_, io) = SocketIo::new_svc();
+    /// io.ns("/", || async move {});
+    ///
+    /// // Later in your code you can ask the other servers to invalidate their cache
+    /// io.server_side_emit("invalidate", "user:42").await.unwrap();
+    /// # }
+    pub async fn server_side_emit(
+        &self,
+        event: impl Into<String>,
+        data: impl serde::Serialize,
+    ) -> Result<(), BroadcastError> {
+        let data = serde_json::to_value(data)?;
+        self.get_default_ns()
+            .adapter
+            .server_side_emit(event.into(), data)
+            .await
+            .map_err(|e| BroadcastError::Adapter(e.into()))
+    }
+
+    /// Emit a message to all the other servers of the cluster and return a stream of their acknowledgements.
+    ///
+    /// Each server answers at most once with the [`ServerAckSender`](crate::ServerAckSender) given to its handler.
+    /// If some servers did not answer before the adapter timeout, the stream yields an [`AckError::AckTimeoutError`] and ends.
+    ///
+    /// ## Panics
+    /// If the **default namespace "/" is not found** this fn will panic!
+    ///
+    /// ## Example
+    /// ```
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # use futures::stream::StreamExt;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (_, io) = SocketIo::new_svc();
+    /// io.ns("/", || async move {});
+    ///
+    /// // Later in your code you can ask the other servers for their number of running jobs
+    /// let jobs = io
+    ///     .server_side_emit_with_ack::<usize>("jobs", ())
+    ///     .await
+    ///     .unwrap()
+    ///     .filter_map(|ack| async move { ack.ok() })
+    ///     .collect::<Vec<_>>()
+    ///     .await;
+    /// # }
+    pub async fn server_side_emit_with_ack<V: DeserializeOwned + Send>(
+        &self,
+        event: impl Into<String>,
+        data: impl serde::Serialize,
+    ) -> Result<BoxStream<'static, Result<V, AckError>>, BroadcastError> {
+        let data = serde_json::to_value(data)?;
+        let acks = self
+            .get_default_ns()
+            .adapter
+            .server_side_emit_with_ack(event.into(), data)
+            .await
+            .map_err(|e| BroadcastError::Adapter(e.into()))?;
+        #[allow(clippy::result_large_err)]
+        let acks = acks.map(|ack| Ok(serde_json::from_value(ack?)?));
+        Ok(acks.boxed())
+    }
+
+    /// ### Register a handler for the events emitted by the other servers of the cluster
+    /// with [`SocketIo::server_side_emit`] or [`SocketIo::server_side_emit_with_ack`].
+    ///
+    /// The data parameter can be typed with anything that implement [serde::Deserialize](https://docs.rs/serde/latest/serde/).
+    ///
+    /// ## Panics
+    /// If the **default namespace "/" is not found** this fn will panic!
+    ///
+    /// ## Example
+    /// ```
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// let (_, io) = SocketIo::new_svc();
+    /// io.ns("/", || async move {});
+    ///
+    /// io.on_server_event("invalidate", |key: String, _| async move {
+    ///     println!("Invalidating cache entry {}", key);
+    /// });
+    /// io.on_server_event("jobs", |_: (), ack| async move {
+    ///     ack.send(3).ok();
+    /// });
+    /// ```
+    pub fn on_server_event<C, F, V>(&self, event: impl Into<String>, callback: C)
+    where
+        C: Fn(V, ServerAckSender) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+        V: DeserializeOwned + Send + Sync + 'static,
+    {
+        self.get_default_ns()
+            .on_server_event(event.into(), callback);
     }
 
     /// Returns a new operator on the given namespace
@@ -724,6 +1215,16 @@ impl<A: Adapter> SocketIo<A> {
     fn get_default_op(&self) -> Operators<A> {
         self.get_op("/").expect("default namespace not found")
     }
+
+    /// Returns the default namespace "/" (root namespace)
+    ///
+    /// # Panics
+    ///
+    /// If the **default namespace "/" is not found** this fn will panic!
+    #[inline(always)]
+    fn get_default_ns(&self) -> Arc<Namespace<A>> {
+        self.0.get_ns("/").expect("default namespace not found")
+    }
 }
 
 impl<A: Adapter> Clone for SocketIo<A> {
@@ -739,7 +1240,7 @@ mod tests {
     #[tokio::test]
     async fn get_default_op() {
         let (_, io) = SocketIo::builder().build_svc();
-        io.ns("/", |_, _: ()| async move {});
+        io.ns("/", || async move {});
         let _ = io.get_default_op();
     }
 
@@ -753,8 +1254,21 @@ mod tests {
     #[tokio::test]
     async fn get_op() {
         let (_, io) = SocketIo::builder().build_svc();
-        io.ns("test", |_, _: ()| async move {});
+        io.ns("test", || async move {});
         assert!(io.get_op("test").is_some());
         assert!(io.get_op("test2").is_none());
     }
+
+    #[tokio::test]
+    async fn server_side_emit_with_ack_local() {
+        let (_, io) = SocketIo::builder().build_svc();
+        io.ns("/", || async move {});
+        let acks = io
+            .server_side_emit_with_ack::<()>("test", ())
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+        assert!(acks.is_empty());
+    }
 }
@@ -11,7 +11,10 @@
 //! use axum::routing::get;
 //! use axum::Server;
 //! use serde::{Serialize, Deserialize};
-//! use socketioxide::SocketIo;
+//! use socketioxide::{
+//!     extract::{AckSender, Bin, Data, SocketRef},
+//!     SocketIo,
+//! };
 //! use serde_json::Value;
 //!
 //! #[derive(Debug, Serialize, Deserialize)]
@@ -27,19 +30,19 @@
 //!
 //!     let (layer, io) = SocketIo::new_layer();
 //!
-//!     io.ns("/", |socket, auth: Value| async move {
+//!     io.ns("/", |socket: SocketRef, Data(auth): Data<Value>| async move {
 //!         println!("Socket connected on / namespace with id: {}", socket.id);
 //!
 //!         // Add a callback triggered when the socket receive an 'abc' event
 //!         // The json data will be deserialized to MyData
-//!         socket.on("abc", |socket, data: MyData, bin, _| async move {
+//!         socket.on("abc", |socket: SocketRef, Data(data): Data<MyData>, Bin(bin)| async move {
 //!             println!("Received abc event: {:?} {:?}", data, bin);
-//!             socket.bin(bin).emit("abc", data).ok();
+//!             socket.bin(bin).emit("abc", data).await.ok();
 //!         });
 //!
 //!         // Add a callback triggered when the socket receive an 'acb' event
 //!         // Ackknowledge the message with the ack callback
-//!         socket.on("acb", |_, data: Value, bin, ack| async move {
+//!         socket.on("acb", |Data(data): Data<Value>, Bin(bin), ack: AckSender| async move {
 //!             println!("Received acb event: {:?} {:?}", data, bin);
 //!             ack.bin(bin).send(data).ok();
 //!         });
@@ -50,7 +53,7 @@
 //!         });
 //!     });
 //!     
-//!     io.ns("/custom", |socket, auth: Value| async move {
+//!     io.ns("/custom", |socket: SocketRef, Data(auth): Data<Value>| async move {
 //!         println!("Socket connected on /custom namespace with id: {}", socket.id);
 //!     });
 //!
@@ -70,25 +73,44 @@
 compile_error!("At least one protocol version must be enabled");
 
 pub mod adapter;
+#[cfg(feature = "admin-ui")]
+pub mod admin;
 
 #[cfg(feature = "extensions")]
 pub mod extensions;
+pub mod handler;
 pub mod layer;
+pub mod packet;
+pub mod parser;
 pub mod service;
+#[cfg(feature = "test-utils")]
+pub mod testing;
 
-pub use engineioxide::config::TransportType;
-pub use errors::{AckError, AckSenderError, BroadcastError, Error as SocketError, SendError};
+pub use async_trait::async_trait;
+pub use engineioxide::{
+    config::{DeflateConfig, OverflowPolicy, TransportType},
+    sid::Sid,
+};
+pub use errors::{
+    AckError, AckSenderError, BroadcastError, ConnectError, Error as SocketError, NsPatternError,
+    ParserError, SendError,
+};
+pub use handler::{extract, ServerAckSender};
 pub use io::{SocketIo, SocketIoBuilder, SocketIoConfig};
-pub use socket::{DisconnectReason, Socket};
+pub use ns::{DynNsHandle, NsHandle};
+pub use operators::Operators;
+pub use socket::{DisconnectReason, Handshake, Socket};
+
+#[cfg(feature = "macros")]
+pub use socketioxide_macros::{ClientEvents, ServerEvents};
 
 mod client;
 mod errors;
-mod handler;
 mod io;
 mod ns;
 mod operators;
-mod packet;
 mod socket;
+mod state;
 
 /// Socket.IO protocol version
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -1,12 +1,19 @@
 use std::{
     collections::HashMap,
-    sync::{Arc, RwLock},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock, Weak,
+    },
 };
 
 use crate::{
-    adapter::Adapter,
+    adapter::{Adapter, RoomEvent},
     errors::Error,
-    handler::{BoxedNamespaceHandler, CallbackHandler},
+    handler::{
+        BoxedConnectHandler, BoxedConnectMiddleware, BoxedRoomEventHandler,
+        BoxedServerEventHandler, CallbackHandler, ConnectHandler, ConnectMiddleware,
+        MakeErasedConnectHandler, ServerAckSender,
+    },
     packet::{Packet, PacketData},
     socket::Socket,
     SocketIoConfig,
@@ -15,60 +22,254 @@ use crate::{client::SocketData, errors::AdapterError};
 use engineioxide::sid::Sid;
 use futures::Future;
 use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+pub(crate) use pattern::NsPattern;
+
+mod pattern;
 
 pub struct Namespace<A: Adapter> {
     pub path: String,
     pub(crate) adapter: A,
-    handler: BoxedNamespaceHandler<A>,
+    pub(crate) config: Arc<SocketIoConfig>,
+    handler: BoxedConnectHandler<A>,
     sockets: RwLock<HashMap<Sid, Arc<Socket<A>>>>,
+    server_handlers: RwLock<HashMap<String, BoxedServerEventHandler>>,
+    room_event_handlers: RwLock<Vec<BoxedRoomEventHandler>>,
+    middlewares: RwLock<Vec<BoxedConnectMiddleware<A>>>,
+    /// The parameters captured from the path if the namespace was created from a dynamic namespace
+    params: HashMap<String, String>,
+    /// The dynamic namespace that created this namespace, if any
+    parent: Option<Weak<DynNamespace<A>>>,
 }
 
 impl<A: Adapter> Namespace<A> {
-    pub fn new<C, F, V>(path: String, callback: C) -> Arc<Self>
+    pub fn new<H, T>(path: String, handler: H, config: Arc<SocketIoConfig>) -> Arc<Self>
     where
-        C: Fn(Arc<Socket<A>>, V) -> F + Send + Sync + 'static,
-        F: Future<Output = ()> + Send + 'static,
-        V: DeserializeOwned + Send + Sync + 'static,
+        H: ConnectHandler<A, T>,
+        T: Send + Sync + 'static,
     {
-        let handler = Box::new(move |s, v| Box::pin(callback(s, v)) as _);
         Arc::new_cyclic(|ns| Self {
             path,
-            handler: CallbackHandler::boxed_ns_handler(handler),
+            handler: MakeErasedConnectHandler::<H, A, T>::new_arc(handler),
+            sockets: HashMap::new().into(),
+            server_handlers: HashMap::new().into(),
+            room_event_handlers: Vec::new().into(),
+            middlewares: Vec::new().into(),
+            params: HashMap::new(),
+            parent: None,
+            adapter: A::new(ns.clone()),
+            config,
+        })
+    }
+
+    /// Create a child namespace of a dynamic namespace, sharing its handler and its middlewares
+    fn new_child(
+        path: String,
+        params: HashMap<String, String>,
+        parent: &Arc<DynNamespace<A>>,
+    ) -> Arc<Self> {
+        Arc::new_cyclic(|ns| Self {
+            path,
+            handler: parent.handler.clone(),
             sockets: HashMap::new().into(),
+            server_handlers: HashMap::new().into(),
+            room_event_handlers: Vec::new().into(),
+            middlewares: parent.middlewares.read().unwrap().clone().into(),
+            params,
+            parent: Some(Arc::downgrade(parent)),
             adapter: A::new(ns.clone()),
+            config: parent.config.clone(),
         })
     }
 
     /// Connects a socket to a namespace
+    ///
+    /// If the namespace has middlewares or if the client sent the private id of a previous session,
+    /// the connection is made asynchronously once the middlewares ran and the session is restored.
     pub fn connect(
         self: Arc<Self>,
         sid: Sid,
         esocket: Arc<engineioxide::Socket<SocketData>>,
         auth: Option<String>,
-        config: Arc<SocketIoConfig>,
-    ) -> Result<(), serde_json::Error> {
-        let socket: Arc<Socket<A>> = Socket::new(sid, self.clone(), esocket.clone(), config).into();
+    ) {
+        let socket = Socket::new(
+            sid,
+            self.clone(),
+            esocket.clone(),
+            self.config.clone(),
+            auth.clone(),
+        );
+
+        let session = socket.pid().and(auth.as_deref()).and_then(session_auth);
+        if session.is_none() && self.middlewares.read().unwrap().is_empty() {
+            // Without session to recover nor middleware, the socket is connected right away
+            // unless the adapter has to wait to join the private room
+            if self.adapter.try_add_all_now(sid, sid) {
+                self.connect_socket(socket.into(), esocket, auth, Vec::new());
+                return;
+            }
+        }
+        tokio::spawn(self.connect_async(socket, session, esocket, auth));
+    }
+
+    /// Restore the session of the socket if there is one, then run the middlewares
+    /// and connect the socket to its private room if none of them refused the connection
+    async fn connect_async(
+        self: Arc<Self>,
+        socket: Socket<A>,
+        session: Option<(Sid, Option<String>)>,
+        esocket: Arc<engineioxide::Socket<SocketData>>,
+        auth: Option<String>,
+    ) {
+        let (socket, missed_packets) = match session {
+            Some((pid, offset)) => self.recover(socket, pid, offset.as_deref()).await,
+            None => (socket, Vec::new()),
+        };
+        let socket = Arc::new(socket);
+
+        let middlewares = self.middlewares.read().unwrap().clone();
+        for middleware in middlewares {
+            if let Err(err) = middleware.call(socket.clone(), &auth).await {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("connection of socket {} refused: {}", socket.id, err);
+                // The restored rooms and the rooms joined by the middlewares are left
+                self.adapter.del_all(socket.id).await.ok();
+                if let Err(_e) = socket.send(Packet::connect_error(self.path.clone(), err)) {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!("error sending connect error packet: {:?}", _e);
+                }
+                return;
+            }
+        }
+
+        if let Err(_e) = self.adapter.add_all(socket.id, socket.id).await {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                "error joining the private room of socket {}: {_e}",
+                socket.id
+            );
+        }
+        self.connect_socket(socket, esocket, auth, missed_packets);
+    }
+
+    /// Restore the session of a socket and its rooms if it still exists
+    /// and return the packets it missed after the `offset` of the last packet it received
+    async fn recover(
+        &self,
+        socket: Socket<A>,
+        pid: Sid,
+        offset: Option<&str>,
+    ) -> (Socket<A>, Vec<Packet>) {
+        let session = self
+            .adapter
+            .restore_session(pid, offset)
+            .await
+            .map_err(|_e| {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("error restoring session {pid}: {_e}");
+            })
+            .unwrap_or_default();
+        let session = match session {
+            Some(session) => session,
+            None => return (socket, Vec::new()),
+        };
+
+        let socket = socket.restore(&session);
+        if let Err(_e) = self.adapter.add_all(socket.id, session.rooms).await {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("error restoring rooms of socket {}: {_e}", socket.id);
+        }
+        (socket, session.missed_packets)
+    }
+
+    /// Add a middleware called before a socket is connected to the namespace.
+    /// The middlewares are called in the order they were added.
+    pub fn add_middleware<M, T>(&self, middleware: M)
+    where
+        M: ConnectMiddleware<A, T>,
+        T: Send + Sync + 'static,
+    {
+        self.middlewares
+            .write()
+            .unwrap()
+            .push(MakeErasedConnectHandler::<M, A, T>::new_arc(middleware));
+    }
+
+    /// Add the socket to the namespace, send the connect packet followed by the missed packets
+    /// and call the namespace handler
+    fn connect_socket(
+        self: Arc<Self>,
+        socket: Arc<Socket<A>>,
+        esocket: Arc<engineioxide::Socket<SocketData>>,
+        auth: Option<String>,
+        missed_packets: Vec<Packet>,
+    ) {
+        self.sockets
+            .write()
+            .unwrap()
+            .insert(socket.id, socket.clone());
 
-        self.sockets.write().unwrap().insert(sid, socket.clone());
+        // A recovered socket keeps its previous id, so the packets of this connection are routed to it
+        let mut recovered_sids = esocket.data.recovered_sids.lock().unwrap();
+        if socket.recovered() {
+            recovered_sids.insert(self.path.clone(), socket.id);
+        } else {
+            recovered_sids.remove(&self.path);
+        }
+        drop(recovered_sids);
 
         let protocol = esocket.protocol.into();
-        if let Err(_e) = socket.send(Packet::connect(self.path.clone(), socket.id, protocol)) {
+        let packet = Packet::connect(self.path.clone(), socket.id, socket.pid(), protocol);
+        if let Err(_e) = socket.send(packet) {
             #[cfg(feature = "tracing")]
             tracing::debug!("error sending connect packet: {:?}, closing conn", _e);
             esocket.close(engineioxide::DisconnectReason::PacketParsingError);
-            return Ok(());
+            return;
+        }
+        for packet in missed_packets {
+            if let Err(_e) = socket.send(packet) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("error sending missed packet: {:?}", _e);
+            }
         }
 
-        self.handler.call(socket, auth)?;
-        Ok(())
+        #[cfg(feature = "admin-ui")]
+        if let Some(admin_ui) = &self.config.admin_ui {
+            admin_ui.on_connect(&socket);
+        }
+
+        self.handler.call(socket, auth);
     }
 
     /// Remove a socket from a namespace and propagate the event to the adapter
-    pub fn remove_socket(&self, sid: Sid) -> Result<(), AdapterError> {
+    ///
+    /// If the namespace was created by a dynamic namespace with cleanup enabled
+    /// and it is now empty, it is removed and its adapter is closed.
+    pub async fn remove_socket(&self, sid: Sid) -> Result<(), AdapterError> {
         self.sockets.write().unwrap().remove(&sid);
         self.adapter
             .del_all(sid)
-            .map_err(|err| AdapterError(Box::new(err)))
+            .await
+            .map_err(|err| AdapterError(Box::new(err)))?;
+
+        if let Some(parent) = self.parent.as_ref().and_then(Weak::upgrade) {
+            if parent.remove_child_if_empty(self) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("removing empty dynamic namespace {}", self.path);
+                self.adapter
+                    .close()
+                    .await
+                    .map_err(|err| AdapterError(Box::new(err)))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get a parameter captured from the path of the namespace
+    /// if it was created from a dynamic namespace pattern
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(String::as_str)
     }
 
     pub fn has(&self, sid: Sid) -> bool {
@@ -94,12 +295,68 @@ impl<A: Adapter> Namespace<A> {
         self.sockets.read().unwrap().values().cloned().collect()
     }
 
+    /// Register a handler for the events emitted by the other servers of the cluster
+    pub fn on_server_event<C, F, V>(&self, event: String, callback: C)
+    where
+        C: Fn(V, ServerAckSender) -> F + Send + Sync + 'static,
+        F: Future<Output = ()> + Send + 'static,
+        V: DeserializeOwned + Send + Sync + 'static,
+    {
+        let handler = Box::new(move |v, ack| Box::pin(callback(v, ack)) as _);
+        self.server_handlers.write().unwrap().insert(
+            event,
+            CallbackHandler::<_, _, A>::boxed_server_handler(handler),
+        );
+    }
+
+    /// Call the handler of an event emitted by another server, if there is one
+    pub fn recv_server_event(
+        &self,
+        event: &str,
+        data: Value,
+        ack: ServerAckSender,
+    ) -> Result<(), serde_json::Error> {
+        match self.server_handlers.read().unwrap().get(event) {
+            Some(handler) => handler.call(data, ack),
+            None => Ok(()),
+        }
+    }
+
+    /// Register a handler for the room events of this namespace
+    pub fn on_room_event<F>(&self, handler: F)
+    where
+        F: Fn(&RoomEvent) + Send + Sync + 'static,
+    {
+        self.room_event_handlers
+            .write()
+            .unwrap()
+            .push(Arc::new(handler));
+    }
+
+    pub(crate) fn has_room_event_handlers(&self) -> bool {
+        !self.room_event_handlers.read().unwrap().is_empty()
+    }
+
+    /// Call the room event handlers for each event, in order.
+    /// The handlers are cloned so that they can register other handlers.
+    pub(crate) fn on_room_events(&self, events: &[RoomEvent]) {
+        if events.is_empty() {
+            return;
+        }
+        let handlers = self.room_event_handlers.read().unwrap().clone();
+        for event in events {
+            for handler in &handlers {
+                handler(event);
+            }
+        }
+    }
+
     /// Close the entire namespace :
     /// * Close the adapter
     /// * Close all the sockets and their underlying connections
     /// * Remove all the sockets from the namespace
     pub async fn close(&self) {
-        self.adapter.close().ok();
+        self.adapter.close().await.ok();
         #[cfg(feature = "tracing")]
         tracing::debug!("closing all sockets in namespace {}", self.path);
         let sockets = self.sockets.read().unwrap().clone();
@@ -113,7 +370,11 @@ impl<A: Adapter> Namespace<A> {
 #[cfg(test)]
 impl<A: Adapter> Namespace<A> {
     pub fn new_dummy<const S: usize>(sockets: [Sid; S]) -> Arc<Self> {
-        let ns = Namespace::new("/".to_string(), |_, _: ()| async {});
+        let ns = Namespace::new(
+            "/".to_string(),
+            || async {},
+            Arc::new(SocketIoConfig::default()),
+        );
         for sid in sockets {
             ns.sockets
                 .write()
@@ -127,6 +388,14 @@ impl<A: Adapter> Namespace<A> {
     }
 }
 
+/// Get the private id of a previous session and the offset of the last packet received by the client
+/// from the auth payload of a connect packet
+fn session_auth(auth: &str) -> Option<(Sid, Option<String>)> {
+    let auth: Value = serde_json::from_str(auth).ok()?;
+    let pid = auth["pid"].as_str()?.parse().ok()?;
+    Some((pid, auth["offset"].as_str().map(str::to_string)))
+}
+
 impl<A: Adapter + std::fmt::Debug> std::fmt::Debug for Namespace<A> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Namespace")
@@ -136,3 +405,184 @@ impl<A: Adapter + std::fmt::Debug> std::fmt::Debug for Namespace<A> {
             .finish()
     }
 }
+
+/// A namespace registered with a path pattern, it lazily creates a child [`Namespace`]
+/// with its own adapter for each path that matches the pattern.
+pub struct DynNamespace<A: Adapter> {
+    pattern: NsPattern,
+    handler: BoxedConnectHandler<A>,
+    middlewares: RwLock<Vec<BoxedConnectMiddleware<A>>>,
+    config: Arc<SocketIoConfig>,
+    children: RwLock<HashMap<String, Arc<Namespace<A>>>>,
+    cleanup: AtomicBool,
+}
+
+impl<A: Adapter> DynNamespace<A> {
+    pub fn new<H, T>(pattern: NsPattern, handler: H, config: Arc<SocketIoConfig>) -> Arc<Self>
+    where
+        H: ConnectHandler<A, T>,
+        T: Send + Sync + 'static,
+    {
+        Arc::new(Self {
+            pattern,
+            handler: MakeErasedConnectHandler::<H, A, T>::new_arc(handler),
+            middlewares: Vec::new().into(),
+            config,
+            children: HashMap::new().into(),
+            cleanup: AtomicBool::new(false),
+        })
+    }
+
+    pub fn pattern(&self) -> &str {
+        self.pattern.as_str()
+    }
+
+    /// Get the child namespace with the given path if it exists
+    pub fn get_child(&self, path: &str) -> Option<Arc<Namespace<A>>> {
+        self.children.read().unwrap().get(path).cloned()
+    }
+
+    pub fn children(&self) -> Vec<Arc<Namespace<A>>> {
+        self.children.read().unwrap().values().cloned().collect()
+    }
+
+    /// Get the child namespace with the given path or create it if the path matches the pattern.
+    ///
+    /// The boolean is true if the namespace was just created.
+    pub fn get_or_create_child(self: &Arc<Self>, path: &str) -> Option<(Arc<Namespace<A>>, bool)> {
+        if let Some(ns) = self.get_child(path) {
+            return Some((ns, false));
+        }
+        let params = self.pattern.matches(path)?;
+
+        let mut children = self.children.write().unwrap();
+        if let Some(ns) = children.get(path) {
+            return Some((ns.clone(), false));
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            "creating namespace {} from pattern {}",
+            path,
+            self.pattern()
+        );
+        let ns = Namespace::new_child(path.to_string(), params, self);
+        children.insert(path.to_string(), ns.clone());
+        Some((ns, true))
+    }
+
+    /// Remove a child namespace if the cleanup is enabled and it has no sockets anymore.
+    ///
+    /// Returns true if the namespace was removed.
+    fn remove_child_if_empty(&self, ns: &Namespace<A>) -> bool {
+        if !self.cleanup.load(Ordering::Relaxed) {
+            return false;
+        }
+        let mut children = self.children.write().unwrap();
+        let is_child = children
+            .get(&ns.path)
+            .map_or(false, |child| std::ptr::eq(child.as_ref(), ns));
+        if is_child && ns.sockets.read().unwrap().is_empty() {
+            children.remove(&ns.path);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl<A: Adapter> std::fmt::Debug for DynNamespace<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DynNamespace")
+            .field("pattern", &self.pattern)
+            .field("children", &self.children.read().unwrap().keys())
+            .finish()
+    }
+}
+
+/// A handle to a namespace returned by [`SocketIo::ns`](crate::SocketIo::ns),
+/// used to configure it after its creation.
+pub struct NsHandle<A: Adapter>(pub(crate) Arc<Namespace<A>>);
+
+impl<A: Adapter> NsHandle<A> {
+    /// Add a middleware to the namespace.
+    ///
+    /// Middlewares are called in the order they were added, before the socket is connected
+    /// and before the connect handler is called. Like the connect handler, a middleware is an async
+    /// function or closure taking any number of [extractors](crate::extract), such as the socket
+    /// and the auth data sent by the client, and it returns a `Result<(), ConnectError>`.
+    ///
+    /// If a middleware returns an error, the following ones are not called, the socket is not connected
+    /// and a `CONNECT_ERROR` packet is sent to the client with the message and data of the [`ConnectError`](crate::ConnectError).
+    pub fn with_middleware<M, T>(self, middleware: M) -> Self
+    where
+        M: ConnectMiddleware<A, T>,
+        T: Send + Sync + 'static,
+    {
+        self.0.add_middleware(middleware);
+        self
+    }
+}
+
+/// A handle to a dynamic namespace returned by [`SocketIo::dyn_ns`](crate::SocketIo::dyn_ns),
+/// used to configure it after its creation.
+pub struct DynNsHandle<A: Adapter>(pub(crate) Arc<DynNamespace<A>>);
+
+impl<A: Adapter> DynNsHandle<A> {
+    /// Add a middleware to the dynamic namespace.
+    ///
+    /// It is called for the sockets connecting to any namespace created from the pattern.
+    /// See [`NsHandle::with_middleware`] for more details.
+    pub fn with_middleware<M, T>(self, middleware: M) -> Self
+    where
+        M: ConnectMiddleware<A, T>,
+        T: Send + Sync + 'static,
+    {
+        self.0
+            .middlewares
+            .write()
+            .unwrap()
+            .push(MakeErasedConnectHandler::<M, A, T>::new_arc(middleware));
+        self
+    }
+
+    /// Remove the namespaces created from the pattern once their last socket is disconnected.
+    ///
+    /// Their adapter is closed and they are created again if a new socket connects to them.
+    pub fn with_cleanup(self) -> Self {
+        self.0.cleanup.store(true, Ordering::Relaxed);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::adapter::LocalAdapter;
+
+    #[tokio::test]
+    async fn connect_without_middleware() {
+        let ns = Namespace::<LocalAdapter>::new_dummy([]);
+        let sid = Sid::new();
+        let esocket = engineioxide::Socket::new_dummy(sid, Box::new(|_, _| ()));
+        ns.clone().connect(sid, esocket.into(), None);
+
+        // The socket is connected in its private room before connect returns
+        assert!(ns.get_socket(sid).is_ok());
+        let rooms = ns.adapter.socket_rooms(sid).await.unwrap();
+        assert_eq!(rooms, [sid.to_string()]);
+    }
+
+    #[tokio::test]
+    async fn connect_with_middleware() {
+        let ns = Namespace::<LocalAdapter>::new_dummy([]);
+        ns.add_middleware(|| async { Ok(()) });
+        let sid = Sid::new();
+        let esocket = engineioxide::Socket::new_dummy(sid, Box::new(|_, _| ()));
+        ns.clone().connect(sid, esocket.into(), None);
+
+        // The middlewares are awaited in a task before the socket is connected
+        assert!(ns.get_socket(sid).is_err());
+        tokio::task::yield_now().await;
+        assert!(ns.get_socket(sid).is_ok());
+    }
+}
@@ -0,0 +1,141 @@
+//! Patterns used to match the path of dynamic namespaces, e.g `/tenant-{id}` or `/{org}/{team}`.
+use std::collections::HashMap;
+
+use crate::errors::NsPatternError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Param(String),
+}
+
+/// A namespace path pattern with named parameters between braces.
+///
+/// A parameter matches at least one character and never matches a `/`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NsPattern {
+    pattern: String,
+    parts: Vec<Part>,
+}
+
+impl NsPattern {
+    pub fn parse(pattern: impl Into<String>) -> Result<Self, NsPatternError> {
+        let pattern = pattern.into();
+        if !pattern.starts_with('/') {
+            return Err(NsPatternError::MissingLeadingSlash);
+        }
+
+        let mut parts = Vec::new();
+        let mut rest = pattern.as_str();
+        while !rest.is_empty() {
+            match rest.find(['{', '}']) {
+                Some(i) if rest[i..].starts_with('}') => {
+                    return Err(NsPatternError::UnbalancedBrace)
+                }
+                Some(i) => {
+                    if i > 0 {
+                        parts.push(Part::Literal(rest[..i].to_string()));
+                    } else if matches!(parts.last(), Some(Part::Param(_))) {
+                        return Err(NsPatternError::AdjacentParams);
+                    }
+                    let end = rest[i..].find('}').ok_or(NsPatternError::UnbalancedBrace)?;
+                    let name = &rest[i + 1..i + end];
+                    if name.contains('{') {
+                        return Err(NsPatternError::UnbalancedBrace);
+                    }
+                    if name.is_empty() {
+                        return Err(NsPatternError::EmptyParam);
+                    }
+                    parts.push(Part::Param(name.to_string()));
+                    rest = &rest[i + end + 1..];
+                }
+                None => {
+                    parts.push(Part::Literal(rest.to_string()));
+                    rest = "";
+                }
+            }
+        }
+        Ok(Self { pattern, parts })
+    }
+
+    /// Match a namespace path against the pattern and return the captured parameters
+    pub fn matches(&self, path: &str) -> Option<HashMap<String, String>> {
+        let mut params = HashMap::new();
+        match_parts(&self.parts, path, &mut params).then_some(params)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+}
+
+fn match_parts(parts: &[Part], path: &str, params: &mut HashMap<String, String>) -> bool {
+    match parts.split_first() {
+        None => path.is_empty(),
+        Some((Part::Literal(lit), rest)) => match path.strip_prefix(lit.as_str()) {
+            Some(path) => match_parts(rest, path, params),
+            None => false,
+        },
+        Some((Part::Param(name), rest)) => {
+            // A parameter stops at the first '/', every shorter split is tried in order
+            let max = path.find('/').unwrap_or(path.len());
+            let found = path[..max]
+                .char_indices()
+                .skip(1)
+                .map(|(i, _)| i)
+                .chain(std::iter::once(max))
+                .filter(|&i| i > 0)
+                .find(|&i| match_parts(rest, &path[i..], params));
+            match found {
+                Some(i) => {
+                    params.insert(name.clone(), path[..i].to_string());
+                    true
+                }
+                None => false,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn params<const N: usize>(params: [(&str, &str); N]) -> HashMap<String, String> {
+        params
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_errors() {
+        use NsPatternError::*;
+        assert_eq!(NsPattern::parse("tenant"), Err(MissingLeadingSlash));
+        assert_eq!(NsPattern::parse("/tenant-{id"), Err(UnbalancedBrace));
+        assert_eq!(NsPattern::parse("/tenant-id}"), Err(UnbalancedBrace));
+        assert_eq!(NsPattern::parse("/tenant-{i{d}"), Err(UnbalancedBrace));
+        assert_eq!(NsPattern::parse("/tenant-{}"), Err(EmptyParam));
+        assert_eq!(NsPattern::parse("/{a}{b}"), Err(AdjacentParams));
+    }
+
+    #[test]
+    fn match_params() {
+        let pattern = NsPattern::parse("/tenant-{id}").unwrap();
+        assert_eq!(pattern.matches("/tenant-42"), Some(params([("id", "42")])));
+        assert_eq!(pattern.matches("/tenant-"), None);
+        assert_eq!(pattern.matches("/tenant-42/room"), None);
+        assert_eq!(pattern.matches("/admin"), None);
+
+        let pattern = NsPattern::parse("/{org}/{team}-chat").unwrap();
+        assert_eq!(
+            pattern.matches("/acme/dev-ops-chat"),
+            Some(params([("org", "acme"), ("team", "dev-ops")]))
+        );
+        assert_eq!(pattern.matches("/acme/chat"), None);
+
+        let pattern = NsPattern::parse("/static").unwrap();
+        assert_eq!(pattern.matches("/static"), Some(HashMap::new()));
+        assert_eq!(pattern.matches("/static2"), None);
+    }
+}
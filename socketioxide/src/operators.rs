@@ -7,7 +7,7 @@ use serde::de::DeserializeOwned;
 
 use crate::errors::BroadcastError;
 use crate::{
-    adapter::{Adapter, BroadcastFlags, BroadcastOptions, Room},
+    adapter::{Adapter, BroadcastFlags, BroadcastOptions, Room, RoomEvent},
     errors::AckError,
     handler::AckResponse,
     ns::Namespace,
@@ -17,8 +17,9 @@ use crate::{
 
 /// A trait for types that can be used as a room parameter.
 ///
-/// String, Vec<String>, Vec<&str> and &'static str are implemented by default.
-pub trait RoomParam: 'static {
+/// String, Vec<String>, Vec<&str>, &'static str and [`Sid`] are implemented by default.
+/// A [`Sid`] is the private room of the socket with this id.
+pub trait RoomParam: Send + 'static {
     type IntoIter: Iterator<Item = Room>;
     fn into_room_iter(self) -> Self::IntoIter;
 }
@@ -41,6 +42,12 @@ impl RoomParam for &'static str {
         std::iter::once(self.to_string())
     }
 }
+impl RoomParam for Sid {
+    type IntoIter = std::iter::Once<Room>;
+    fn into_room_iter(self) -> Self::IntoIter {
+        std::iter::once(self.to_string())
+    }
+}
 impl<const COUNT: usize> RoomParam for [&'static str; COUNT] {
     type IntoIter =
         std::iter::Map<std::array::IntoIter<&'static str, COUNT>, fn(&'static str) -> Room>;
@@ -73,18 +80,19 @@ impl<A: Adapter> Operators<A> {
     /// If you want to include the current socket, use the `within()` operator.
     /// #### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, data: ()| async move {
-    ///     socket.on("test", |socket, data: Value, _, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<Value>| async move {
     ///         let other_rooms = "room4".to_string();
     ///         // In room1, room2, room3 and room4 except the current
     ///         socket
     ///             .to("room1")
     ///             .to(["room2", "room3"])
     ///             .to(vec![other_rooms])
-    ///             .emit("test", data);
+    ///             .emit("test", data)
+    ///             .await;
     ///     });
     /// });
     pub fn to(mut self, rooms: impl RoomParam) -> Self {
@@ -99,18 +107,19 @@ impl<A: Adapter> Operators<A> {
     /// If it is called from the `Namespace` level there will be no difference with the `to()` operator
     /// #### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, data: ()| async move {
-    ///     socket.on("test", |socket, data: Value, _, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<Value>| async move {
     ///         let other_rooms = "room4".to_string();
     ///         // In room1, room2, room3 and room4 including the current socket
     ///         socket
     ///             .within("room1")
     ///             .within(["room2", "room3"])
     ///             .within(vec![other_rooms])
-    ///             .emit("test", data);
+    ///             .emit("test", data)
+    ///             .await;
     ///     });
     /// });
     pub fn within(mut self, rooms: impl RoomParam) -> Self {
@@ -121,20 +130,20 @@ impl<A: Adapter> Operators<A> {
     /// Filter out all sockets selected with the previous operators which are in the given rooms.
     /// #### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, data: ()| async move {
-    ///     socket.on("register1", |socket, data: Value, _, _| async move {
-    ///         socket.join("room1");
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("register1", |socket: SocketRef, Data(data): Data<Value>| async move {
+    ///         socket.join("room1").await;
     ///     });
-    ///     socket.on("register2", |socket, data: Value, _, _| async move {
-    ///         socket.join("room2");
+    ///     socket.on("register2", |socket: SocketRef, Data(data): Data<Value>| async move {
+    ///         socket.join("room2").await;
     ///     });
-    ///     socket.on("test", |socket, data: Value, _, _| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<Value>| async move {
     ///         // This message will be broadcast to all sockets in the Namespace
     ///         // except for ones in room1 and the current socket
-    ///         socket.broadcast().except("room1").emit("test", data);
+    ///         socket.broadcast().except("room1").emit("test", data).await;
     ///     });
     /// });
     pub fn except(mut self, rooms: impl RoomParam) -> Self {
@@ -147,13 +156,13 @@ impl<A: Adapter> Operators<A> {
     /// When using the default in-memory adapter, this operator is a no-op.
     /// #### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, data: ()| async move {
-    ///     socket.on("test", |socket, data: Value, _, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<Value>| async move {
     ///         // This message will be broadcast to all sockets in this namespace and connected on this node
-    ///         socket.local().emit("test", data);
+    ///         socket.local().emit("test", data).await;
     ///     });
     /// });
     pub fn local(mut self) -> Self {
@@ -164,13 +173,13 @@ impl<A: Adapter> Operators<A> {
     /// Broadcast to all sockets without any filtering (except the current socket).
     /// #### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, data: ()| async move {
-    ///     socket.on("test", |socket, data: Value, _, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<Value>| async move {
     ///         // This message will be broadcast to all sockets in this namespace
-    ///         socket.broadcast().emit("test", data);
+    ///         socket.broadcast().emit("test", data).await;
     ///     });
     /// });
     pub fn broadcast(mut self) -> Self {
@@ -182,20 +191,20 @@ impl<A: Adapter> Operators<A> {
     ///
     /// #### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// # use futures::stream::StreamExt;
     /// # use std::time::Duration;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, data: ()| async move {
-    ///    socket.on("test", |socket, data: Value, bin, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///    socket.on("test", |socket: SocketRef, Data(data): Data<Value>, Bin(bin)| async move {
     ///       // Emit a test message in the room1 and room3 rooms, except for the room2 room with the binary payload received, wait for 5 seconds for an acknowledgement
     ///       socket.to("room1")
     ///             .to("room3")
     ///             .except("room2")
     ///             .bin(bin)
     ///             .timeout(Duration::from_secs(5))
-    ///             .emit_with_ack::<Value>("message-back", data).unwrap().for_each(|ack| async move {
+    ///             .emit_with_ack::<Value>("message-back", data).await.unwrap().for_each(|ack| async move {
     ///                match ack {
     ///                    Ok(ack) => println!("Ack received {:?}", ack),
     ///                    Err(err) => println!("Ack error {:?}", err),
@@ -209,16 +218,58 @@ impl<A: Adapter> Operators<A> {
         self
     }
 
+    /// Drop the message for the sockets whose transport is not currently writable
+    /// (a polling client without pending request or a full buffer) rather than buffering it or returning an error.
+    /// The message is not kept for the disconnected sessions either.
+    /// #### Example
+    /// ```
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # use serde_json::Value;
+    /// let (_, io) = SocketIo::new_svc();
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("position", |socket: SocketRef, Data(data): Data<Value>| async move {
+    ///         // The position may not be received by all the clients of room1, the next one will be
+    ///         socket.to("room1").volatile().emit("position", data).await;
+    ///     });
+    /// });
+    pub fn volatile(mut self) -> Self {
+        self.opts.flags.insert(BroadcastFlags::Volatile);
+        self
+    }
+
+    /// Set if the message can be compressed, for the clients whose websocket transport negotiated the
+    /// permessage-deflate extension (see [`SocketIoBuilder::per_message_deflate`](crate::SocketIoBuilder::per_message_deflate)).
+    /// The messages can be compressed by default.
+    /// #### Example
+    /// ```
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # use serde_json::Value;
+    /// let (_, io) = SocketIo::new_svc();
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("image", |socket: SocketRef, Data(data): Data<Value>, Bin(bin)| async move {
+    ///         // The image is already compressed, it is not worth compressing it again
+    ///         socket.to("room1").compress(false).bin(bin).emit("image", data).await;
+    ///     });
+    /// });
+    pub fn compress(mut self, compress: bool) -> Self {
+        if compress {
+            self.opts.flags.remove(&BroadcastFlags::NoCompress);
+        } else {
+            self.opts.flags.insert(BroadcastFlags::NoCompress);
+        }
+        self
+    }
+
     /// Add a binary payload to the message.
     /// #### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, data: ()| async move {
-    ///     socket.on("test", |socket, data: Value, bin, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<Value>, Bin(bin)| async move {
     ///         // This will send the binary payload received to all sockets in this namespace with the test message
-    ///         socket.bin(bin).emit("test", data);
+    ///         socket.bin(bin).emit("test", data).await;
     ///     });
     /// });
     pub fn bin(mut self, binary: Vec<Vec<u8>>) -> Self {
@@ -227,28 +278,26 @@ impl<A: Adapter> Operators<A> {
     }
 
     /// Emit a message to all sockets selected with the previous operators.
+    ///
+    /// A tuple or an array is sent as multiple arguments, see [`Socket::emit`].
     /// #### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, data: ()| async move {
-    ///     socket.on("test", |socket, data: Value, bin, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<Value>, Bin(bin)| async move {
     ///         // Emit a test message in the room1 and room3 rooms, except for the room2 room with the binary payload received
-    ///         socket.to("room1").to("room3").except("room2").bin(bin).emit("test", data);
+    ///         socket.to("room1").to("room3").except("room2").bin(bin).emit("test", data).await;
     ///     });
     /// });
-    pub fn emit(
+    pub async fn emit(
         mut self,
         event: impl Into<String>,
         data: impl serde::Serialize,
-    ) -> Result<(), serde_json::Error> {
+    ) -> Result<(), BroadcastError> {
         let packet = self.get_packet(event, data)?;
-        if let Err(_e) = self.ns.adapter.broadcast(packet, self.opts) {
-            #[cfg(feature = "tracing")]
-            tracing::debug!("broadcast error: {_e:?}");
-        }
-        Ok(())
+        self.ns.adapter.broadcast(packet, self.opts).await
     }
 
     /// Emit a message to all sockets selected with the previous operators and return a stream of acknowledgements.
@@ -256,18 +305,18 @@ impl<A: Adapter> Operators<A> {
     /// Each acknowledgement has a timeout specified in the config (5s by default) or with the `timeout()` operator.
     /// #### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// # use futures::stream::StreamExt;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, data: ()| async move {
-    ///    socket.on("test", |socket, data: Value, bin, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///    socket.on("test", |socket: SocketRef, Data(data): Data<Value>, Bin(bin)| async move {
     ///       // Emit a test message in the room1 and room3 rooms, except for the room2 room with the binary payload received
     ///       socket.to("room1")
     ///             .to("room3")
     ///             .except("room2")
     ///             .bin(bin)
-    ///             .emit_with_ack::<Value>("message-back", data).unwrap().for_each(|ack| async move {
+    ///             .emit_with_ack::<Value>("message-back", data).await.unwrap().for_each(|ack| async move {
     ///                match ack {
     ///                    Ok(ack) => println!("Ack received {:?}", ack),
     ///                    Err(err) => println!("Ack error {:?}", err),
@@ -275,13 +324,13 @@ impl<A: Adapter> Operators<A> {
     ///             }).await;
     ///    });
     /// });
-    pub fn emit_with_ack<V: DeserializeOwned + Send>(
+    pub async fn emit_with_ack<V: DeserializeOwned + Send>(
         mut self,
         event: impl Into<String>,
         data: impl serde::Serialize,
     ) -> Result<BoxStream<'static, Result<AckResponse<V>, AckError>>, BroadcastError> {
         let packet = self.get_packet(event, data)?;
-        self.ns.adapter.broadcast_with_ack(packet, self.opts)
+        self.ns.adapter.broadcast_with_ack(packet, self.opts).await
     }
 
     /// Get all sockets selected with the previous operators.
@@ -290,67 +339,120 @@ impl<A: Adapter> Operators<A> {
     ///
     /// ### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, data: ()| async move {
-    ///   socket.on("test", |socket, _: (), _, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///   socket.on("test", |socket: SocketRef| async move {
     ///     // Find an extension data in each sockets in the room1 and room3 rooms, except for the room2
-    ///     let sockets = socket.within("room1").within("room3").except("room2").sockets().unwrap();
+    ///     let sockets = socket.within("room1").within("room3").except("room2").sockets().await.unwrap();
     ///     for socket in sockets {
     ///         println!("Socket custom string: {:?}", socket.extensions.get::<String>());
     ///     }
     ///   });
     /// });
-    pub fn sockets(self) -> Result<Vec<Arc<Socket<A>>>, A::Error> {
-        self.ns.adapter.fetch_sockets(self.opts)
+    pub async fn sockets(self) -> Result<Vec<Arc<Socket<A>>>, A::Error> {
+        self.ns.adapter.fetch_sockets(self.opts).await
     }
 
     /// Disconnect all sockets selected with the previous operators.
     ///
     /// ### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, data: ()| async move {
-    ///   socket.on("test", |socket, _: (), _, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///   socket.on("test", |socket: SocketRef| async move {
     ///     // Disconnect all sockets in the room1 and room3 rooms, except for the room2
-    ///     socket.within("room1").within("room3").except("room2").disconnect().unwrap();
+    ///     socket.within("room1").within("room3").except("room2").disconnect().await.unwrap();
     ///   });
     /// });
-    pub fn disconnect(self) -> Result<(), BroadcastError> {
-        self.ns.adapter.disconnect_socket(self.opts)
+    pub async fn disconnect(self) -> Result<(), BroadcastError> {
+        self.ns.adapter.disconnect_socket(self.opts).await
     }
 
     /// Make all sockets selected with the previous operators join the given room(s).
     ///
     /// ### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, data: ()| async move {
-    ///   socket.on("test", |socket, _: (), _, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///   socket.on("test", |socket: SocketRef| async move {
     ///     // Add all sockets that are in the room1 and room3 to the room4 and room5
-    ///     socket.within("room1").within("room3").join(["room4", "room5"]).unwrap();
+    ///     socket.within("room1").within("room3").join(["room4", "room5"]).await.unwrap();
     ///   });
     /// });
-    pub fn join(self, rooms: impl RoomParam) -> Result<(), A::Error> {
-        self.ns.adapter.add_sockets(self.opts, rooms)
+    pub async fn join(self, rooms: impl RoomParam) -> Result<(), A::Error> {
+        #[cfg(feature = "admin-ui")]
+        if let Some(admin_ui) = self.ns.config.admin_ui.clone() {
+            let rooms: Vec<Room> = rooms.into_room_iter().collect();
+            let sockets = self.ns.adapter.fetch_sockets(self.opts.clone()).await?;
+            self.ns
+                .adapter
+                .add_sockets(self.opts, rooms.clone())
+                .await?;
+            for socket in sockets {
+                admin_ui.on_rooms_joined(&self.ns.path, socket.id, &rooms);
+            }
+            return Ok(());
+        }
+        self.ns.adapter.add_sockets(self.opts, rooms).await
     }
 
     /// Make all sockets selected with the previous operators leave the given room(s).
     ///
     /// ### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, data: ()| async move {
-    /// socket.on("test", |socket, _: (), _, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    /// socket.on("test", |socket: SocketRef| async move {
     ///     // Remove all sockets that are in the room1 and room3 from the room4 and room5
-    ///     socket.within("room1").within("room3").leave(["room4", "room5"]).unwrap();
+    ///     socket.within("room1").within("room3").leave(["room4", "room5"]).await.unwrap();
     ///   });
     /// });
-    pub fn leave(self, rooms: impl RoomParam) -> Result<(), A::Error> {
-        self.ns.adapter.del_sockets(self.opts, rooms)
+    pub async fn leave(self, rooms: impl RoomParam) -> Result<(), A::Error> {
+        #[cfg(feature = "admin-ui")]
+        if let Some(admin_ui) = self.ns.config.admin_ui.clone() {
+            let rooms: Vec<Room> = rooms.into_room_iter().collect();
+            let sockets = self.ns.adapter.fetch_sockets(self.opts.clone()).await?;
+            self.ns
+                .adapter
+                .del_sockets(self.opts, rooms.clone())
+                .await?;
+            for socket in sockets {
+                admin_ui.on_rooms_left(&self.ns.path, socket.id, &rooms);
+            }
+            return Ok(());
+        }
+        self.ns.adapter.del_sockets(self.opts, rooms).await
+    }
+
+    /// Register a handler called when a room of the namespace is created or deleted,
+    /// or when a socket joins or leaves a room, on this server.
+    ///
+    /// The rooms are deleted once their last socket left them.
+    /// The handler is called synchronously and in order with the room changes,
+    /// spawn a task if some async work has to be done.
+    ///
+    /// The rooms selected with the previous operators are ignored.
+    /// ### Example
+    /// ```
+    /// # use socketioxide::{SocketIo, adapter::RoomEvent, extract::*};
+    /// let (_, io) = SocketIo::new_svc();
+    /// io.ns("/", || async move {});
+    /// io.of("/").unwrap().on_room_event(|event| match event {
+    ///     RoomEvent::Create(room) => println!("room {room} created"),
+    ///     RoomEvent::Join(room, sid) => println!("socket {sid} joined room {room}"),
+    ///     RoomEvent::Leave(room, sid) => println!("socket {sid} left room {room}"),
+    ///     RoomEvent::Delete(room) => println!("room {room} deleted"),
+    /// });
+    /// ```
+    pub fn on_room_event<F>(&self, handler: F)
+    where
+        F: Fn(&RoomEvent) + Send + Sync + 'static,
+    {
+        self.ns.on_room_event(handler);
     }
 
     /// Create a packet with the given event and data.
@@ -1,9 +1,10 @@
+//! Socket.io packets, encoded and decoded to the wire format by a [`Parser`](crate::parser::Parser).
 use crate::ProtocolVersion;
 use itertools::{Itertools, PeekingNext};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::{json, Value};
 
-use crate::errors::Error;
+use crate::errors::{ConnectError, ParserError};
 use engineioxide::sid::Sid;
 
 /// The socket.io packet type.
@@ -16,14 +17,17 @@ pub struct Packet {
 
 impl Packet {
     /// Send a connect packet with a default payload for v5 and no payload for v4
+    ///
+    /// The private session id `pid` is only sent with v5 if connection state recovery is enabled
     pub fn connect(
         ns: String,
         #[allow(unused_variables)] sid: Sid,
+        #[allow(unused_variables)] pid: Option<Sid>,
         #[allow(unused_variables)] protocol: ProtocolVersion,
     ) -> Self {
         #[cfg(all(feature = "v5", not(feature = "v4")))]
         {
-            Self::connect_v5(ns, sid)
+            Self::connect_v5(ns, sid, pid)
         }
 
         #[cfg(all(feature = "v4", not(feature = "v5")))]
@@ -35,7 +39,7 @@ impl Packet {
         {
             match protocol {
                 ProtocolVersion::V4 => Self::connect_v4(ns),
-                ProtocolVersion::V5 => Self::connect_v5(ns, sid),
+                ProtocolVersion::V5 => Self::connect_v5(ns, sid, pid),
             }
         }
     }
@@ -51,8 +55,8 @@ impl Packet {
 
     /// Sends a connect packet with payload.
     #[cfg(feature = "v5")]
-    fn connect_v5(ns: String, sid: Sid) -> Self {
-        let val = serde_json::to_string(&ConnectPacket { sid }).unwrap();
+    fn connect_v5(ns: String, sid: Sid, pid: Option<Sid>) -> Self {
+        let val = serde_json::to_string(&ConnectPacket { sid, pid }).unwrap();
         Self {
             inner: PacketData::Connect(Some(val)),
             ns,
@@ -72,6 +76,18 @@ impl Packet {
         Self {
             inner: PacketData::ConnectError(ConnectErrorPacket {
                 message: "Invalid namespace".to_string(),
+                data: None,
+            }),
+            ns,
+        }
+    }
+
+    /// A connect error packet sent when a namespace middleware refuses the connection
+    pub fn connect_error(ns: String, err: ConnectError) -> Self {
+        Self {
+            inner: PacketData::ConnectError(ConnectErrorPacket {
+                message: err.message,
+                data: err.data,
             }),
             ns,
         }
@@ -219,14 +235,37 @@ impl BinaryPacket {
             payload_count,
         }
     }
+    /// Create a complete binary packet from incoming data and all its payloads,
+    /// for parsers that receive the payloads within the packet instead of as separate attachments
+    pub fn with_payloads(data: Value, bin: Vec<Vec<u8>>) -> Self {
+        let mut packet = Self::incoming(data);
+        packet.payload_count = bin.len();
+        packet.bin = bin;
+        packet
+    }
+
+    /// Add an incoming binary attachment to the packet
     pub fn add_payload(&mut self, payload: Vec<u8>) {
         self.bin.push(payload);
     }
+
+    /// Check if all the binary attachments of the packet were received
     pub fn is_complete(&self) -> bool {
         self.payload_count == self.bin.len()
     }
 }
 
+/// Prepend the event name to its arguments, as the `data` array of a `socket.io-parser` event packet
+#[cfg(any(feature = "redis", feature = "admin-ui"))]
+pub(crate) fn event_args(event: &str, data: &Value) -> Value {
+    let mut args = vec![Value::String(event.to_string())];
+    match data {
+        Value::Array(data) => args.extend(data.iter().cloned()),
+        data => args.push(data.clone()),
+    }
+    Value::Array(args)
+}
+
 impl TryInto<String> for Packet {
     type Error = serde_json::Error;
 
@@ -312,19 +351,19 @@ impl TryInto<String> for Packet {
 /// ```text
 /// ["<event name>", ...<JSON-stringified payload without binary>]
 /// ```
-fn deserialize_event_packet(data: &str) -> Result<(String, Value), Error> {
+fn deserialize_event_packet(data: &str) -> Result<(String, Value), ParserError> {
     #[cfg(feature = "tracing")]
     tracing::debug!("Deserializing event packet: {:?}", data);
     let packet = match serde_json::from_str::<Value>(data)? {
         Value::Array(packet) => packet,
-        _ => return Err(Error::InvalidEventName),
+        _ => return Err(ParserError::InvalidEventName),
     };
 
     let event = packet
         .get(0)
-        .ok_or(Error::InvalidEventName)?
+        .ok_or(ParserError::InvalidEventName)?
         .as_str()
-        .ok_or(Error::InvalidEventName)?
+        .ok_or(ParserError::InvalidEventName)?
         .to_string();
     let payload = Value::from_iter(packet.into_iter().skip(1));
     Ok((event, payload))
@@ -348,11 +387,11 @@ fn deserialize_packet<T: DeserializeOwned>(data: &str) -> Result<Option<T>, serd
 /// + binary attachments extracted
 /// ```
 impl TryFrom<String> for Packet {
-    type Error = Error;
+    type Error = ParserError;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
         let mut chars = value.chars();
-        let index = chars.next().ok_or(Error::InvalidPacketType)?;
+        let index = chars.next().ok_or(ParserError::InvalidPacketType)?;
 
         let attachments: u8 = if index == '5' || index == '6' {
             chars
@@ -367,9 +406,14 @@ impl TryFrom<String> for Packet {
         // If there are attachments, skip the `-` separator
         chars.peeking_next(|c| attachments > 0 && !c.is_ascii_digit());
 
-        let mut ns: String = chars
-            .take_while_ref(|c| *c != ',' && *c != '{' && *c != '[' && !c.is_ascii_digit())
-            .collect();
+        // A namespace starting with a `/` ends with the `,` separator and may contain digits
+        let mut ns: String = if chars.clone().next() == Some('/') {
+            chars.take_while_ref(|c| *c != ',').collect()
+        } else {
+            chars
+                .take_while_ref(|c| *c != ',' && *c != '{' && *c != '[' && !c.is_ascii_digit())
+                .collect()
+        };
 
         // If there is a namespace, skip the `,` separator
         if !ns.is_empty() {
@@ -394,11 +438,11 @@ impl TryFrom<String> for Packet {
                 PacketData::Event(event, payload, ack)
             }
             '3' => {
-                let packet = deserialize_packet(data)?.ok_or(Error::InvalidPacketType)?;
-                PacketData::EventAck(packet, ack.ok_or(Error::InvalidPacketType)?)
+                let packet = deserialize_packet(data)?.ok_or(ParserError::InvalidPacketType)?;
+                PacketData::EventAck(packet, ack.ok_or(ParserError::InvalidPacketType)?)
             }
             '4' => {
-                let payload = deserialize_packet(data)?.ok_or(Error::InvalidPacketType)?;
+                let payload = deserialize_packet(data)?.ok_or(ParserError::InvalidPacketType)?;
                 PacketData::ConnectError(payload)
             }
             '5' => {
@@ -406,28 +450,34 @@ impl TryFrom<String> for Packet {
                 PacketData::BinaryEvent(event, BinaryPacket::incoming(payload), ack)
             }
             '6' => {
-                let packet = deserialize_packet(data)?.ok_or(Error::InvalidPacketType)?;
+                let packet = deserialize_packet(data)?.ok_or(ParserError::InvalidPacketType)?;
                 PacketData::BinaryAck(
                     BinaryPacket::incoming(packet),
-                    ack.ok_or(Error::InvalidPacketType)?,
+                    ack.ok_or(ParserError::InvalidPacketType)?,
                 )
             }
-            _ => return Err(Error::InvalidPacketType),
+            _ => return Err(ParserError::InvalidPacketType),
         };
 
         Ok(Self { inner, ns })
     }
 }
 
-/// Connect packet sent by the client
+/// Connect packet payload sent by the server when a socket is connected to a namespace
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ConnectPacket {
-    sid: Sid,
+    pub sid: Sid,
+    /// The private session id used to recover the socket state
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<Sid>,
 }
 
+/// Connect error packet sent by the server when a connection to a namespace is refused
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ConnectErrorPacket {
-    message: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
 }
 
 #[cfg(test)]
@@ -443,7 +493,7 @@ mod test {
         let packet = Packet::try_from(payload).unwrap();
 
         assert_eq!(
-            Packet::connect("/".to_string(), sid, ProtocolVersion::V5),
+            Packet::connect("/".to_string(), sid, None, ProtocolVersion::V5),
             packet
         );
 
@@ -451,7 +501,7 @@ mod test {
         let packet = Packet::try_from(payload).unwrap();
 
         assert_eq!(
-            Packet::connect("/admin™".to_string(), sid, ProtocolVersion::V5),
+            Packet::connect("/admin™".to_string(), sid, None, ProtocolVersion::V5),
             packet
         );
     }
@@ -462,13 +512,24 @@ mod test {
 
         let sid = Sid::new();
         let payload = format!("0{}", json!({"sid": sid}));
-        let packet: String = Packet::connect("/".to_string(), sid, ProtocolVersion::V5)
+        let packet: String = Packet::connect("/".to_string(), sid, None, ProtocolVersion::V5)
             .try_into()
             .unwrap();
         assert_eq!(packet, payload);
 
         let payload = format!("0/admin™,{}", json!({"sid": sid}));
-        let packet: String = Packet::connect("/admin™".to_string(), sid, ProtocolVersion::V5)
+        let packet: String = Packet::connect("/admin™".to_string(), sid, None, ProtocolVersion::V5)
+            .try_into()
+            .unwrap();
+        assert_eq!(packet, payload);
+    }
+
+    #[test]
+    fn packet_encode_connect_with_pid() {
+        let sid = Sid::new();
+        let pid = Sid::new();
+        let payload = format!("0{{\"sid\":\"{sid}\",\"pid\":\"{pid}\"}}");
+        let packet: String = Packet::connect("/".to_string(), sid, Some(pid), ProtocolVersion::V5)
             .try_into()
             .unwrap();
         assert_eq!(packet, payload);
@@ -553,6 +614,19 @@ mod test {
         comparison_packet.inner.set_ack_id(1);
 
         assert_eq!(packet, comparison_packet);
+
+        // Check with digits in the NS
+        let payload = format!("2/tenant-42,12{}", json!(["event", { "data": "value™" }]));
+        let packet = Packet::try_from(payload).unwrap();
+
+        let mut comparison_packet = Packet::event(
+            "/tenant-42".to_string(),
+            "event".to_string(),
+            json!([{"data": "value™"}]),
+        );
+        comparison_packet.inner.set_ack_id(12);
+
+        assert_eq!(packet, comparison_packet);
     }
 
     #[test]
@@ -663,6 +737,16 @@ mod test {
             .unwrap();
         assert_eq!(packet, payload);
     }
+
+    #[test]
+    fn packet_encode_connect_error_with_data() {
+        let err = ConnectError::new("Not authorized").with_data(json!({ "code": 401 }));
+        let payload = r#"4/admin™,{"message":"Not authorized","data":{"code":401}}"#;
+        let packet: String = Packet::connect_error("/admin™".to_string(), err)
+            .try_into()
+            .unwrap();
+        assert_eq!(packet, payload);
+    }
     // BinaryEvent(String, BinaryPacket, Option<i64>),
     #[test]
     fn packet_encode_binary_event() {
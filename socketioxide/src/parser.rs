@@ -0,0 +1,319 @@
+//! Encoding and decoding of socket.io packets to the engine.io messages sent on the wire.
+//!
+//! The parser is chosen per [`SocketIo`](crate::SocketIo) instance with
+//! [`SocketIoBuilder::with_parser`](crate::SocketIoBuilder::with_parser):
+//! * [`DefaultParser`]: the default socket.io parser, each packet is a json text message
+//!   followed by its binary attachments.
+//! * [`MsgPackParser`]: a parser compatible with the js msgpack parser (requires the `msgpack` feature).
+//! * Any type implementing the [`Parser`] trait, to use a custom wire encoding.
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use engineioxide::{Permit, Socket as EIoSocket};
+
+use crate::{
+    errors::{ParserError, SendError},
+    packet::{Packet, PacketData},
+};
+
+#[cfg(any(feature = "msgpack", feature = "redis"))]
+pub(crate) mod msgpack;
+#[cfg(feature = "msgpack")]
+pub use msgpack::MsgPackParser;
+
+/// A parser encodes the packets sent to the clients and decodes the messages received from them.
+///
+/// A parser is shared by all the sockets of a server,
+/// any state needed to decode a packet split over multiple messages must be kept in the [`ParserState`]
+/// of the socket that is given to the decode methods.
+pub trait Parser: std::fmt::Debug + Send + Sync + 'static {
+    /// Encode a packet to the message(s) sent to the client
+    fn encode(&self, packet: Packet) -> Result<EncodedPacket, ParserError>;
+
+    /// Decode a text message received from the client.
+    ///
+    /// Returns `None` if the packet is not complete yet, for example if it is waiting for binary attachments
+    fn decode_str(&self, state: &ParserState, data: String) -> Result<Option<Packet>, ParserError>;
+
+    /// Decode a binary message received from the client.
+    ///
+    /// Returns `None` if the packet is not complete yet, for example if it is waiting for other binary attachments
+    fn decode_bin(&self, state: &ParserState, data: Vec<u8>)
+        -> Result<Option<Packet>, ParserError>;
+}
+
+/// A packet encoded by a [`Parser`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodedPacket {
+    /// A text message followed by binary attachments
+    Text(String, Vec<Vec<u8>>),
+    /// A single binary message
+    Binary(Vec<u8>),
+}
+
+impl EncodedPacket {
+    /// The number of engine.io messages of the packet
+    fn len(&self) -> usize {
+        match self {
+            EncodedPacket::Text(_, bin) => bin.len() + 1,
+            EncodedPacket::Binary(_) => 1,
+        }
+    }
+
+    /// Send the encoded message(s) through an engine.io socket, `compress` tells if the websocket transport can compress them.
+    ///
+    /// The places of all the messages are reserved at once,
+    /// so that the overflow policy of the socket applies to the whole packet.
+    pub(crate) fn send<D>(self, esocket: &EIoSocket<D>, compress: bool) -> Result<(), SendError>
+    where
+        D: Default + Send + Sync + 'static,
+    {
+        let mut permit = esocket.reserve(self.len())?;
+        permit.compress(compress);
+        self.emit(&mut permit);
+        Ok(())
+    }
+
+    /// Send the encoded message(s) through an engine.io socket, waiting for places in its buffer if it is full.
+    ///
+    /// The `timeout` applies to all the messages of the packet.
+    pub(crate) async fn send_async<D>(
+        self,
+        esocket: &EIoSocket<D>,
+        timeout: Option<Duration>,
+    ) -> Result<(), SendError>
+    where
+        D: Default + Send + Sync + 'static,
+    {
+        let mut permit = esocket.reserve_async(self.len(), timeout).await?;
+        self.emit(&mut permit);
+        Ok(())
+    }
+
+    fn emit<D>(self, permit: &mut Permit<'_, D>)
+    where
+        D: Default + Send + Sync + 'static,
+    {
+        match self {
+            EncodedPacket::Text(msg, bin) => {
+                permit.emit(msg);
+                for bin in bin {
+                    permit.emit_binary(bin);
+                }
+            }
+            EncodedPacket::Binary(data) => permit.emit_binary(data),
+        }
+    }
+}
+
+/// An [`EncodedPacket`] whose messages are reference-counted,
+/// so that it can be sent to many sockets without being encoded or copied again.
+#[derive(Debug, Clone)]
+pub(crate) enum SharedPacket {
+    /// A text message followed by binary attachments
+    Text(Arc<str>, Vec<Bytes>),
+    /// A single binary message
+    Binary(Bytes),
+}
+
+impl From<EncodedPacket> for SharedPacket {
+    fn from(packet: EncodedPacket) -> Self {
+        match packet {
+            EncodedPacket::Text(msg, bin) => {
+                SharedPacket::Text(msg.into(), bin.into_iter().map(Bytes::from).collect())
+            }
+            EncodedPacket::Binary(data) => SharedPacket::Binary(data.into()),
+        }
+    }
+}
+
+impl SharedPacket {
+    /// The number of engine.io messages of the packet
+    fn len(&self) -> usize {
+        match self {
+            SharedPacket::Text(_, bin) => bin.len() + 1,
+            SharedPacket::Binary(_) => 1,
+        }
+    }
+
+    /// Send the shared message(s) through an engine.io socket, `compress` tells if the websocket transport can compress them.
+    ///
+    /// The places of all the messages are reserved at once,
+    /// so that the overflow policy of the socket applies to the whole packet.
+    pub(crate) fn send<D>(&self, esocket: &EIoSocket<D>, compress: bool) -> Result<(), SendError>
+    where
+        D: Default + Send + Sync + 'static,
+    {
+        let mut permit = esocket.reserve(self.len())?;
+        permit.compress(compress);
+        self.emit(&mut permit);
+        Ok(())
+    }
+
+    /// Send the shared message(s) through an engine.io socket only if its transport is writable,
+    /// without buffering them. Returns true if the message(s) were sent.
+    pub(crate) fn send_volatile<D>(&self, esocket: &EIoSocket<D>, compress: bool) -> bool
+    where
+        D: Default + Send + Sync + 'static,
+    {
+        match esocket.try_reserve_volatile(self.len()) {
+            Some(mut permit) => {
+                permit.compress(compress);
+                self.emit(&mut permit);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn emit<D>(&self, permit: &mut Permit<'_, D>)
+    where
+        D: Default + Send + Sync + 'static,
+    {
+        match self {
+            SharedPacket::Text(msg, bin) => {
+                permit.emit_shared(msg.clone());
+                for bin in bin {
+                    permit.emit_shared_binary(bin.clone());
+                }
+            }
+            SharedPacket::Binary(data) => permit.emit_shared_binary(data.clone()),
+        }
+    }
+}
+
+/// The decoding state of a socket, kept between the messages received from the client
+#[derive(Debug, Default)]
+pub struct ParserState {
+    /// Partial binary packet that is being received
+    /// Stored here until all the binary payloads are received
+    pub partial_bin_packet: Mutex<Option<Packet>>,
+}
+
+/// The default socket.io parser.
+///
+/// Packets are encoded as json text messages, binary payloads are sent as separate binary attachments
+/// after the packet and replaced by placeholders in the json data.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultParser;
+
+impl Parser for DefaultParser {
+    fn encode(&self, mut packet: Packet) -> Result<EncodedPacket, ParserError> {
+        let bin = match packet.inner {
+            PacketData::BinaryEvent(_, ref mut bin, _) | PacketData::BinaryAck(ref mut bin, _) => {
+                std::mem::take(&mut bin.bin)
+            }
+            _ => Vec::new(),
+        };
+        Ok(EncodedPacket::Text(packet.try_into()?, bin))
+    }
+
+    /// Decode a packet, a binary packet is cached in the state until all its attachments are received
+    fn decode_str(&self, state: &ParserState, data: String) -> Result<Option<Packet>, ParserError> {
+        let packet = Packet::try_from(data)?;
+        match packet.inner {
+            PacketData::BinaryEvent(_, ref bin, _) | PacketData::BinaryAck(ref bin, _)
+                if !bin.is_complete() =>
+            {
+                state.partial_bin_packet.lock().unwrap().replace(packet);
+                Ok(None)
+            }
+            _ => Ok(Some(packet)),
+        }
+    }
+
+    /// Apply a binary attachment to the partial binary packet,
+    /// the packet is returned once all its attachments are received
+    fn decode_bin(
+        &self,
+        state: &ParserState,
+        data: Vec<u8>,
+    ) -> Result<Option<Packet>, ParserError> {
+        let mut partial = state.partial_bin_packet.lock().unwrap();
+        let complete = match partial.as_mut().map(|packet| &mut packet.inner) {
+            Some(PacketData::BinaryEvent(_, bin, _)) | Some(PacketData::BinaryAck(bin, _)) => {
+                bin.add_payload(data);
+                bin.is_complete()
+            }
+            _ => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("received unexpected binary data");
+                false
+            }
+        };
+        Ok(if complete { partial.take() } else { None })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn default_parser_encode_binary() {
+        let packet = Packet::bin_event("/".into(), "event".into(), json!("foo"), vec![vec![1]]);
+        let encoded = DefaultParser.encode(packet).unwrap();
+        assert_eq!(
+            encoded,
+            EncodedPacket::Text(
+                r#"51-["event","foo",{"_placeholder":true,"num":0}]"#.into(),
+                vec![vec![1]]
+            )
+        );
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[tokio::test]
+    async fn shared_packet_send() {
+        use engineioxide::{packet::Packet as EPacket, sid::Sid};
+
+        let packet = Packet::bin_event("/".into(), "event".into(), json!("foo"), vec![vec![1]]);
+        let shared: SharedPacket = DefaultParser.encode(packet).unwrap().into();
+        let mut received = Vec::new();
+        for _ in 0..2 {
+            let (esocket, mut rx) =
+                EIoSocket::<()>::new_dummy_piped(Sid::new(), Box::new(|_, _| ()), 10);
+            shared.send(&esocket, true).unwrap();
+            received.push((rx.recv().await.unwrap(), rx.recv().await.unwrap()));
+        }
+
+        // Both sockets receive the same text and binary messages, without copying them
+        match &received[..] {
+            [(EPacket::SharedMessage(msg1), EPacket::SharedBinary(bin1)), (EPacket::SharedMessage(msg2), EPacket::SharedBinary(bin2))] =>
+            {
+                assert_eq!(
+                    &**msg1,
+                    r#"51-["event","foo",{"_placeholder":true,"num":0}]"#
+                );
+                assert!(Arc::ptr_eq(msg1, msg2));
+                assert_eq!(&bin1[..], [1]);
+                assert_eq!(bin1.as_ptr(), bin2.as_ptr());
+            }
+            packets => panic!("unexpected packets: {:?}", packets),
+        }
+    }
+
+    #[test]
+    fn default_parser_decode_binary() {
+        let state = ParserState::default();
+        let msg = r#"52-["event",{"_placeholder":true,"num":0},{"_placeholder":true,"num":1}]"#;
+        assert_eq!(DefaultParser.decode_str(&state, msg.into()).unwrap(), None);
+        assert_eq!(DefaultParser.decode_bin(&state, vec![1]).unwrap(), None);
+        let packet = DefaultParser.decode_bin(&state, vec![2]).unwrap().unwrap();
+        match packet.inner {
+            PacketData::BinaryEvent(event, bin, None) => {
+                assert_eq!(event, "event");
+                assert_eq!(bin.bin, vec![vec![1], vec![2]]);
+            }
+            inner => panic!("unexpected packet: {:?}", inner),
+        }
+
+        // Unexpected binary data is ignored
+        assert_eq!(DefaultParser.decode_bin(&state, vec![3]).unwrap(), None);
+    }
+}
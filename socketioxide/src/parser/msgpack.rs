@@ -0,0 +1,318 @@
+//! Msgpack encoding of socket.io packets, compatible with the
+//! [`socket.io-msgpack-parser`](https://github.com/socketio/socket.io-msgpack-parser).
+//!
+//! Each packet is encoded as a single msgpack map `{ type, nsp, data, id }`,
+//! the binary payloads are embedded in the data as msgpack binary values instead of being sent as attachments.
+//!
+//! The conversion between msgpack and json values is also used by the redis adapter.
+use serde_json::{json, Value};
+
+#[cfg(feature = "msgpack")]
+use super::{EncodedPacket, Parser, ParserState};
+#[cfg(feature = "msgpack")]
+use crate::{
+    errors::ParserError,
+    packet::{BinaryPacket, ConnectErrorPacket, Packet, PacketData},
+};
+
+/// A [`Parser`] compatible with the
+/// [`socket.io-msgpack-parser`](https://github.com/socketio/socket.io-msgpack-parser).
+///
+/// Every packet is sent as a single binary message, text messages are rejected.
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MsgPackParser;
+
+#[cfg(feature = "msgpack")]
+impl Parser for MsgPackParser {
+    fn encode(&self, packet: Packet) -> Result<EncodedPacket, ParserError> {
+        Ok(EncodedPacket::Binary(encode(packet)))
+    }
+
+    fn decode_str(&self, _: &ParserState, _: String) -> Result<Option<Packet>, ParserError> {
+        Err(ParserError::UnsupportedMessage)
+    }
+
+    fn decode_bin(&self, _: &ParserState, data: Vec<u8>) -> Result<Option<Packet>, ParserError> {
+        decode(&data).map(Some)
+    }
+}
+
+/// Encode a packet as a msgpack map.
+///
+/// Binary events and acks are sent with the `EVENT` and `ACK` types, like the js msgpack parser does.
+#[cfg(feature = "msgpack")]
+fn encode(packet: Packet) -> Vec<u8> {
+    let (ty, data, id, bin) = match packet.inner {
+        PacketData::Connect(data) => {
+            let data = data.and_then(|d| serde_json::from_str(&d).ok());
+            (0, data, None, vec![])
+        }
+        PacketData::Disconnect => (1, None, None, vec![]),
+        PacketData::Event(event, data, id) => (2, Some(event_args(event, data)), id, vec![]),
+        PacketData::EventAck(data, id) => (3, Some(ack_args(data)), Some(id), vec![]),
+        PacketData::ConnectError(err) => (4, Some(json!(err)), None, vec![]),
+        PacketData::BinaryEvent(event, packet, id) => {
+            (2, Some(event_args(event, packet.data)), id, packet.bin)
+        }
+        PacketData::BinaryAck(packet, id) => (3, Some(ack_args(packet.data)), Some(id), packet.bin),
+    };
+
+    let mut map = vec![
+        (rmpv::Value::from("type"), rmpv::Value::from(ty)),
+        (rmpv::Value::from("nsp"), rmpv::Value::from(packet.ns)),
+    ];
+    if let Some(data) = data {
+        map.push((rmpv::Value::from("data"), json_to_msgpack(data, &bin)));
+    }
+    if let Some(id) = id {
+        map.push((rmpv::Value::from("id"), rmpv::Value::from(id)));
+    }
+
+    let mut buf = Vec::new();
+    // Writing to a Vec cannot fail
+    rmpv::encode::write_value(&mut buf, &rmpv::Value::Map(map)).unwrap();
+    buf
+}
+
+/// Decode a packet encoded as a msgpack map.
+///
+/// If the data contains binary values, the packet is decoded as a complete binary event or ack.
+#[cfg(feature = "msgpack")]
+fn decode(data: &[u8]) -> Result<Packet, ParserError> {
+    let mut bin = Vec::new();
+    let mut packet = match msgpack_to_json(rmpv::decode::read_value(&mut &data[..])?, &mut bin) {
+        Value::Object(packet) => packet,
+        _ => return Err(ParserError::InvalidPacketType),
+    };
+    let ns = match packet.remove("nsp") {
+        Some(Value::String(ns)) => ns,
+        _ => "/".to_string(),
+    };
+    let id = packet.get("id").and_then(Value::as_i64);
+    let data = packet.remove("data");
+
+    let inner = match packet.get("type").and_then(Value::as_u64) {
+        Some(0) => PacketData::Connect(data.map(|d| d.to_string())),
+        Some(1) => PacketData::Disconnect,
+        Some(2) | Some(5) => {
+            let mut args = match data {
+                Some(Value::Array(args)) if !args.is_empty() => args,
+                _ => return Err(ParserError::InvalidEventName),
+            };
+            let event = match args.remove(0) {
+                Value::String(event) => event,
+                _ => return Err(ParserError::InvalidEventName),
+            };
+            if bin.is_empty() {
+                PacketData::Event(event, Value::Array(args), id)
+            } else {
+                PacketData::BinaryEvent(
+                    event,
+                    BinaryPacket::with_payloads(Value::Array(args), bin),
+                    id,
+                )
+            }
+        }
+        Some(3) | Some(6) => {
+            let id = id.ok_or(ParserError::InvalidPacketType)?;
+            let data = data.ok_or(ParserError::InvalidPacketType)?;
+            if bin.is_empty() {
+                PacketData::EventAck(data, id)
+            } else {
+                PacketData::BinaryAck(BinaryPacket::with_payloads(data, bin), id)
+            }
+        }
+        Some(4) => {
+            let err: ConnectErrorPacket =
+                serde_json::from_value(data.ok_or(ParserError::InvalidPacketType)?)?;
+            PacketData::ConnectError(err)
+        }
+        _ => return Err(ParserError::InvalidPacketType),
+    };
+    Ok(Packet { inner, ns })
+}
+
+/// Prepend the event name to its arguments, an array is expanded to multiple arguments.
+#[cfg(feature = "msgpack")]
+fn event_args(event: String, data: Value) -> Value {
+    let mut args = match data {
+        Value::Array(args) => args,
+        data => vec![data],
+    };
+    args.insert(0, Value::String(event));
+    Value::Array(args)
+}
+
+/// Enforce that the ack data is an array of arguments
+#[cfg(feature = "msgpack")]
+fn ack_args(data: Value) -> Value {
+    match data {
+        Value::Array(_) => data,
+        Value::Null => Value::Array(vec![]),
+        data => Value::Array(vec![data]),
+    }
+}
+
+/// Convert a msgpack value to a json value.
+/// Binary values are extracted to `bin` and replaced by placeholders.
+pub fn msgpack_to_json(value: rmpv::Value, bin: &mut Vec<Vec<u8>>) -> Value {
+    use rmpv::Value as Msg;
+    match value {
+        Msg::Nil | Msg::Ext(_, _) => Value::Null,
+        Msg::Boolean(b) => Value::Bool(b),
+        Msg::Integer(i) => i
+            .as_i64()
+            .map(Value::from)
+            .or_else(|| i.as_u64().map(Value::from))
+            .unwrap_or_default(),
+        Msg::F32(f) => Value::from(f),
+        Msg::F64(f) => Value::from(f),
+        Msg::String(s) => s.into_str().map(Value::String).unwrap_or_default(),
+        Msg::Binary(data) => {
+            bin.push(data);
+            json!({ "_placeholder": true, "num": bin.len() - 1 })
+        }
+        Msg::Array(values) => values
+            .into_iter()
+            .map(|v| msgpack_to_json(v, bin))
+            .collect(),
+        Msg::Map(entries) => Value::Object(
+            entries
+                .into_iter()
+                .map(|(k, v)| {
+                    let key = match k {
+                        Msg::String(k) => k.into_str().unwrap_or_default(),
+                        k => k.to_string(),
+                    };
+                    (key, msgpack_to_json(v, bin))
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// Convert a json value to a msgpack value.
+/// Placeholders are replaced by their binary value in `bin`.
+pub fn json_to_msgpack(value: Value, bin: &[Vec<u8>]) -> rmpv::Value {
+    use rmpv::Value as Msg;
+    match value {
+        Value::Null => Msg::Nil,
+        Value::Bool(b) => Msg::Boolean(b),
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(n), _) => Msg::from(n),
+            (_, Some(n)) => Msg::from(n),
+            _ => Msg::F64(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => Msg::String(s.into()),
+        Value::Array(values) => Msg::Array(
+            values
+                .into_iter()
+                .map(|v| json_to_msgpack(v, bin))
+                .collect(),
+        ),
+        Value::Object(obj) => {
+            let placeholder = obj.get("_placeholder").and_then(Value::as_bool) == Some(true);
+            let data = obj
+                .get("num")
+                .and_then(Value::as_u64)
+                .and_then(|num| bin.get(num as usize));
+            match data {
+                Some(data) if placeholder => Msg::Binary(data.clone()),
+                _ => Msg::Map(
+                    obj.into_iter()
+                        .map(|(k, v)| (Msg::String(k.into()), json_to_msgpack(v, bin)))
+                        .collect(),
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "msgpack"))]
+mod test {
+    use engineioxide::sid::Sid;
+
+    use super::*;
+    use crate::ConnectError;
+
+    fn roundtrip(packet: Packet) -> Packet {
+        decode(&encode(packet)).unwrap()
+    }
+
+    #[test]
+    fn event_roundtrip() {
+        let packet = Packet::event("/".into(), "event".into(), json!(["foo", { "bar": 1 }]));
+        assert_eq!(roundtrip(packet.clone()), packet);
+
+        let mut packet = Packet::event("/admin".into(), "event".into(), json!([1]));
+        packet.inner.set_ack_id(42);
+        assert_eq!(roundtrip(packet.clone()), packet);
+    }
+
+    #[test]
+    fn binary_event() {
+        let packet = Packet::bin_event(
+            "/".into(),
+            "event".into(),
+            json!(["foo"]),
+            vec![vec![1, 2, 3]],
+        );
+        let msg = encode(packet);
+
+        // The binary payload is embedded in the data and the packet is sent as a regular event
+        let raw = rmpv::decode::read_value(&mut &msg[..]).unwrap();
+        assert_eq!(raw["type"], rmpv::Value::from(2));
+        assert_eq!(raw["data"][2], rmpv::Value::Binary(vec![1, 2, 3]));
+
+        match decode(&msg).unwrap().inner {
+            PacketData::BinaryEvent(event, packet, None) => {
+                assert_eq!(event, "event");
+                assert_eq!(packet.data, json!(["foo"]));
+                assert_eq!(packet.bin, vec![vec![1, 2, 3]]);
+                assert!(packet.is_complete());
+            }
+            inner => panic!("unexpected packet: {:?}", inner),
+        }
+    }
+
+    #[test]
+    fn ack_roundtrip() {
+        let packet = Packet::ack("/".into(), json!(["foo"]), 3);
+        assert_eq!(roundtrip(packet.clone()), packet);
+
+        let packet = Packet::bin_ack("/".into(), json!("foo"), vec![vec![1]], 3);
+        match roundtrip(packet).inner {
+            PacketData::BinaryAck(packet, 3) => {
+                assert_eq!(packet.data, json!(["foo"]));
+                assert_eq!(packet.bin, vec![vec![1]]);
+            }
+            inner => panic!("unexpected packet: {:?}", inner),
+        }
+    }
+
+    #[test]
+    fn connect_packets() {
+        let sid = Sid::new();
+        let packet = Packet::connect("/".into(), sid, None, crate::ProtocolVersion::V5);
+        let raw = rmpv::decode::read_value(&mut &encode(packet)[..]).unwrap();
+        assert_eq!(raw["data"]["sid"], rmpv::Value::from(sid.to_string()));
+
+        let err = ConnectError::new("unauthorized").with_data(json!({ "code": 401 }));
+        let packet = Packet::connect_error("/admin".into(), err);
+        assert_eq!(roundtrip(packet.clone()), packet);
+    }
+
+    #[test]
+    fn decode_errors() {
+        let encode_value = |value: rmpv::Value| {
+            let mut buf = Vec::new();
+            rmpv::encode::write_value(&mut buf, &value).unwrap();
+            buf
+        };
+        assert!(decode(&[0xc1]).is_err());
+        assert!(decode(&encode_value(rmpv::Value::from(2))).is_err());
+        let packet = rmpv::Value::Map(vec![("type".into(), 2.into()), ("nsp".into(), "/".into())]);
+        assert!(decode(&encode_value(packet)).is_err());
+    }
+}
@@ -1,16 +1,20 @@
 use std::{
     collections::HashMap,
     fmt::Debug,
+    net::IpAddr,
     sync::Mutex,
     sync::{
         atomic::{AtomicI64, Ordering},
         Arc, RwLock,
     },
-    time::Duration,
+    time::{Duration, Instant, SystemTime},
 };
 
-use engineioxide::{sid::Sid, socket::DisconnectReason as EIoDisconnectReason};
+use engineioxide::{
+    config::OverflowPolicy, sid::Sid, socket::DisconnectReason as EIoDisconnectReason,
+};
 use futures::{future::BoxFuture, Future};
+use http::{HeaderMap, Uri};
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
 use tokio::sync::oneshot;
@@ -19,13 +23,16 @@ use tokio::sync::oneshot;
 use crate::extensions::Extensions;
 
 use crate::{
-    adapter::{Adapter, Room},
+    adapter::{Adapter, Room, Session},
     errors::{AckError, Error},
-    handler::{AckResponse, AckSender, BoxedMessageHandler, CallbackHandler},
+    handler::{
+        from_args, AckResponse, BoxedMessageHandler, MakeErasedMessageHandler, MessageHandler,
+    },
     ns::Namespace,
     operators::{Operators, RoomParam},
     packet::{BinaryPacket, Packet, PacketData},
-    SocketIoConfig,
+    parser::SharedPacket,
+    ProtocolVersion, SocketIoConfig, TransportType,
 };
 use crate::{
     client::SocketData,
@@ -36,6 +43,10 @@ pub type DisconnectCallback<A> = Box<
     dyn FnOnce(Arc<Socket<A>>, DisconnectReason) -> BoxFuture<'static, ()> + Send + Sync + 'static,
 >;
 
+/// A callback called for each event sent to the client, see [`Socket::on_any_outgoing`].
+pub type AnyOutgoingCallback<A> =
+    Arc<dyn Fn(&Socket<A>, &str, &Value, &[Vec<u8>]) + Send + Sync + 'static>;
+
 /// All the possible reasons for a [`Socket`] to be disconnected.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum DisconnectReason {
@@ -62,6 +73,18 @@ pub enum DisconnectReason {
 
     /// The server is being closed
     ClosingServer,
+
+    /// The buffer of the socket was full with the [`OverflowPolicy::Disconnect`](crate::OverflowPolicy::Disconnect) policy,
+    /// the client did not read the messages sent to it fast enough
+    BufferOverflow,
+}
+
+impl DisconnectReason {
+    /// The connection was lost without the client leaving the namespace, so it may reconnect and recover its state
+    pub(crate) fn is_recoverable(&self) -> bool {
+        use DisconnectReason::*;
+        matches!(self, TransportClose | TransportError | HeartbeatTimeout)
+    }
 }
 
 impl std::fmt::Display for DisconnectReason {
@@ -76,6 +99,7 @@ impl std::fmt::Display for DisconnectReason {
             ClientNSDisconnect => "client has manually disconnected the socket from the namespace",
             ServerNSDisconnect => "socket was forcefully disconnected from the namespace",
             ClosingServer => "server is being closed",
+            BufferOverflow => "client did not read the messages sent to it fast enough",
         };
         f.write_str(str)
     }
@@ -91,24 +115,57 @@ impl From<EIoDisconnectReason> for DisconnectReason {
             EIoDisconnectReason::MultipleHttpPollingError => MultipleHttpPollingError,
             EIoDisconnectReason::PacketParsingError => PacketParsingError,
             EIoDisconnectReason::ClosingServer => ClosingServer,
+            EIoDisconnectReason::BufferOverflow => BufferOverflow,
         }
     }
 }
 
+/// The details of the handshake of a [`Socket`], returned by [`Socket::handshake`].
+#[derive(Debug, Clone)]
+pub struct Handshake {
+    /// The headers of the http request that opened the connection
+    pub headers: HeaderMap,
+    /// The uri of the http request that opened the connection
+    pub url: Uri,
+    /// The ip address of the client, `None` if the remote address of the connection is not available.
+    ///
+    /// It is resolved from the forwarding headers if the connection comes from one of the
+    /// [`trusted_proxies`](crate::SocketIoBuilder::trusted_proxies).
+    pub address: Option<IpAddr>,
+    /// The decoded query parameters of the uri
+    pub query: HashMap<String, String>,
+    /// The auth payload sent by the client when it connected to the namespace,
+    /// an empty object if there is none or if it is not valid json
+    pub auth: Value,
+    /// The time at which the socket connected to the namespace
+    pub issued: SystemTime,
+    /// Whether the request is cross-domain, i.e. it has an `Origin` header
+    pub xdomain: bool,
+    /// The current transport of the connection, it changes from polling to websocket when the connection is upgraded
+    pub transport: TransportType,
+}
+
 /// A Socket represents a client connected to a namespace.
 /// It is used to send and receive messages from the client, join and leave rooms, etc.
 pub struct Socket<A: Adapter> {
     config: Arc<SocketIoConfig>,
     ns: Arc<Namespace<A>>,
     message_handlers: RwLock<HashMap<String, BoxedMessageHandler<A>>>,
+    any_message_handler: RwLock<Option<BoxedMessageHandler<A>>>,
+    any_outgoing_handler: RwLock<Option<AnyOutgoingCallback<A>>>,
     disconnect_handler: Mutex<Option<DisconnectCallback<A>>>,
     ack_message: Mutex<HashMap<i64, oneshot::Sender<AckResponse<Value>>>>,
     ack_counter: AtomicI64,
     pub id: Sid,
+    /// The private session id, only set if connection state recovery is enabled
+    pid: Option<Sid>,
+    recovered: bool,
+    auth: Option<String>,
+    issued: SystemTime,
 
     #[cfg(feature = "extensions")]
     pub extensions: Extensions,
-    esocket: Arc<engineioxide::Socket<SocketData>>,
+    pub(crate) esocket: Arc<engineioxide::Socket<SocketData>>,
 }
 
 impl<A: Adapter> Socket<A> {
@@ -117,14 +174,23 @@ impl<A: Adapter> Socket<A> {
         ns: Arc<Namespace<A>>,
         esocket: Arc<engineioxide::Socket<SocketData>>,
         config: Arc<SocketIoConfig>,
+        auth: Option<String>,
     ) -> Self {
+        let recovery = config.max_disconnection_duration.is_some()
+            && ProtocolVersion::from(esocket.protocol) == ProtocolVersion::V5;
         Self {
             ns,
             message_handlers: RwLock::new(HashMap::new()),
+            any_message_handler: RwLock::new(None),
+            any_outgoing_handler: RwLock::new(None),
             disconnect_handler: Mutex::new(None),
             ack_message: Mutex::new(HashMap::new()),
             ack_counter: AtomicI64::new(0),
             id: sid,
+            pid: recovery.then(Sid::new),
+            recovered: false,
+            auth,
+            issued: SystemTime::now(),
             #[cfg(feature = "extensions")]
             extensions: Extensions::new(),
             config,
@@ -132,9 +198,26 @@ impl<A: Adapter> Socket<A> {
         }
     }
 
+    /// Restore the id, the private session id and the extensions of a previous [`Session`].
+    /// The rooms and the missed packets are restored by the namespace.
+    pub(crate) fn restore(mut self, session: &Session) -> Self {
+        self.id = session.sid;
+        self.pid = Some(session.pid);
+        self.recovered = true;
+        #[cfg(feature = "extensions")]
+        {
+            self.extensions = session.extensions.share();
+        }
+        self
+    }
+
     /// ### Register a message handler for the given event.
     ///
-    /// The data parameter can be typed with anything that implement [serde::Deserialize](https://docs.rs/serde/latest/serde/)
+    /// The handler is an async function or closure taking any number of extractors
+    /// implementing [`FromMessageParts`](crate::handler::FromMessageParts),
+    /// see the [`extract`](crate::extract) module for the available extractors.
+    /// The data of the event can be extracted with [`Data`](crate::extract::Data),
+    /// typed with anything that implements [serde::Deserialize](https://docs.rs/serde/latest/serde/).
     ///
     /// ### Acknowledgements
     /// The ack can be sent only once and take a `Serializable` value as parameter.
@@ -145,8 +228,7 @@ impl<A: Adapter> Socket<A> {
     ///
     /// #### Simple example with a closure:
     /// ```
-    /// # use socketioxide::SocketIo;
-    /// # use serde_json::Value;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde::{Serialize, Deserialize};
     ///
     /// #[derive(Debug, Serialize, Deserialize)]
@@ -156,8 +238,8 @@ impl<A: Adapter> Socket<A> {
     /// }
     ///
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
-    ///     socket.on("test", |socket, data: MyData, _, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<MyData>| async move {
     ///         println!("Received a test message {:?}", data);
     ///         socket.emit("test-test", MyData { name: "Test".to_string(), age: 8 }).ok(); // Emit a message to the client
     ///     });
@@ -167,8 +249,7 @@ impl<A: Adapter> Socket<A> {
     ///
     /// #### Example with a closure and an ackknowledgement + binary data:
     /// ```
-    /// # use socketioxide::SocketIo;
-    /// # use serde_json::Value;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde::{Serialize, Deserialize};
     ///
     /// #[derive(Debug, Serialize, Deserialize)]
@@ -178,39 +259,91 @@ impl<A: Adapter> Socket<A> {
     /// }
     ///
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
-    ///     socket.on("test", |socket, data: MyData, bin, ack| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<MyData>, Bin(bin), ack: AckSender| async move {
     ///         println!("Received a test message {:?}", data);
     ///         ack.bin(bin).send(data).ok(); // The data received is sent back to the client through the ack
     ///         socket.emit("test-test", MyData { name: "Test".to_string(), age: 8 }).ok(); // Emit a message to the client
     ///     });
     /// });
     /// ```
-    pub fn on<C, F, V>(&self, event: impl Into<String>, callback: C)
+    pub fn on<H, T>(&self, event: impl Into<String>, handler: H)
     where
-        C: Fn(Arc<Socket<A>>, V, Vec<Vec<u8>>, AckSender<A>) -> F + Send + Sync + 'static,
-        F: Future<Output = ()> + Send + 'static,
-        V: DeserializeOwned + Send + Sync + 'static,
+        H: MessageHandler<A, T>,
+        T: Send + Sync + 'static,
     {
-        let handler = Box::new(move |s, v, p, ack_fn| Box::pin(callback(s, v, p, ack_fn)) as _);
         self.message_handlers.write().unwrap().insert(
             event.into(),
-            CallbackHandler::boxed_message_handler(handler),
+            MakeErasedMessageHandler::<H, A, T>::new_boxed(handler),
         );
     }
 
+    /// ### Register a catch-all handler for the incoming events.
+    ///
+    /// It is called for every event received from the client,
+    /// before the handler registered with [`Socket::on`] for this event, if there is one.
+    /// The name of the event can be extracted with [`Event`](crate::extract::Event).
+    /// Registering a new catch-all handler replaces the previous one.
+    ///
+    /// Only one ack response is sent to the client, so if both handlers answer
+    /// the ack of an event the second response is ignored by the client.
+    ///
+    /// #### Example
+    /// ```
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # use serde_json::Value;
+    /// let (_, io) = SocketIo::new_svc();
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on_any(|socket: SocketRef, Event(event), Data(data): Data<Value>| async move {
+    ///         println!("Socket {} received {}: {:?}", socket.id, event, data);
+    ///     });
+    /// });
+    /// ```
+    pub fn on_any<H, T>(&self, handler: H)
+    where
+        H: MessageHandler<A, T>,
+        T: Send + Sync + 'static,
+    {
+        *self.any_message_handler.write().unwrap() =
+            Some(MakeErasedMessageHandler::<H, A, T>::new_boxed(handler));
+    }
+
+    /// ### Register a catch-all listener for the outgoing events.
+    ///
+    /// It is called synchronously for every event sent to the client, either with an emit
+    /// on this socket or with a broadcast, with the name of the event, its data and its binary payloads.
+    /// Acknowledgements are not passed to the listener.
+    /// Registering a new listener replaces the previous one.
+    ///
+    /// #### Example
+    /// ```
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// let (_, io) = SocketIo::new_svc();
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on_any_outgoing(|socket, event, data, _| {
+    ///         println!("Sending {} to socket {}: {:?}", event, socket.id, data);
+    ///     });
+    /// });
+    /// ```
+    pub fn on_any_outgoing<C>(&self, callback: C)
+    where
+        C: Fn(&Socket<A>, &str, &Value, &[Vec<u8>]) + Send + Sync + 'static,
+    {
+        *self.any_outgoing_handler.write().unwrap() = Some(Arc::new(callback));
+    }
+
     /// ## Register a disconnect handler.
     /// The callback will be called when the socket is disconnected from the server or the client or when the underlying connection crashes.
     /// A [`DisconnectReason`](crate::DisconnectReason) is passed to the callback to indicate the reason for the disconnection.
     /// ### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
-    ///     socket.on("test", |socket, data: Value, bin, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<Value>, Bin(bin)| async move {
     ///         // Close the current socket
-    ///         socket.disconnect().ok();
+    ///         socket.disconnect().await.ok();
     ///     });
     ///     socket.on_disconnect(|socket, reason| async move {
     ///         println!("Socket {} on ns {} disconnected, reason: {:?}", socket.id, socket.ns(), reason);
@@ -226,41 +359,83 @@ impl<A: Adapter> Socket<A> {
     }
 
     /// Emit a message to the client
+    ///
+    /// The data is sent as the arguments of the event: a tuple or an array is sent as multiple arguments
+    /// and any other value as a single argument. To send an array as a single argument, wrap it in a tuple: `(vec,)`.
+    ///
+    /// If the buffer of the socket is full, the [`OverflowPolicy`] of the socket is applied,
+    /// an error is returned with the default [`OverflowPolicy::Error`] policy.
     /// ##### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
-    ///     socket.on("test", |socket, data: Value, bin, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<Value>, Bin(bin)| async move {
     ///         // Emit a test message to the client
     ///         socket.emit("test", data);
+    ///         // Emit a message with multiple arguments
+    ///         socket.emit("move", (1, 2, "fast"));
     ///     });
     /// });
-    pub fn emit(
+    pub fn emit(&self, event: impl Into<String>, data: impl Serialize) -> Result<(), SendError> {
+        let ns = self.ns.path.clone();
+        let data = serde_json::to_value(data)?;
+        self.send(Packet::event(ns, event.into(), data))
+    }
+
+    /// Emit a message to the client, waiting for a place in the buffer of the socket if it is full
+    /// instead of applying its [`OverflowPolicy`].
+    ///
+    /// If the `timeout` elapses before the message is buffered, a [`SendError::Timeout`] is returned.
+    /// Without timeout, it waits until the client reads the buffered messages or the socket is closed.
+    /// ##### Example
+    /// ```
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # use std::time::Duration;
+    /// let (_, io) = SocketIo::new_svc();
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     for i in 0..10_000 {
+    ///         let timeout = Some(Duration::from_secs(1));
+    ///         if socket.emit_async("chunk", i, timeout).await.is_err() {
+    ///             break;
+    ///         }
+    ///     }
+    /// });
+    /// ```
+    pub async fn emit_async(
         &self,
         event: impl Into<String>,
         data: impl Serialize,
-    ) -> Result<(), serde_json::Error> {
+        timeout: Option<Duration>,
+    ) -> Result<(), SendError> {
         let ns = self.ns.path.clone();
         let data = serde_json::to_value(data)?;
-        if let Err(_e) = self.send(Packet::event(ns, event.into(), data)) {
-            #[cfg(feature = "tracing")]
-            tracing::debug!("sending error during emit message: {_e:?}");
-        }
-        Ok(())
+        self.send_async(Packet::event(ns, event.into(), data), timeout)
+            .await
+    }
+
+    /// Set the [`OverflowPolicy`] applied when a message is emitted while the buffer of this socket is full.
+    ///
+    /// It defaults to the [`overflow_policy`](crate::SocketIoBuilder::overflow_policy) of the server.
+    /// The buffer is shared by all the namespaces of the connection.
+    pub fn set_overflow_policy(&self, policy: OverflowPolicy) {
+        self.esocket.set_overflow_policy(policy);
     }
 
     /// Emit a message to the client and wait for acknowledgement.
     ///
     /// The acknowledgement has a timeout specified in the config (5s by default) or with the `timeout()` operator.
+    ///
+    /// The arguments of the acknowledgement are deserialized to `V` like with the [`Data`](crate::extract::Data) extractor,
+    /// so an acknowledgement with multiple arguments can be deserialized to a tuple or a `Vec<Value>`.
     /// ##### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
-    ///     socket.on("test", |socket, data: Value, bin, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<Value>, Bin(bin)| async move {
     ///         // Emit a test message and wait for an acknowledgement
     ///         match socket.emit_with_ack::<Value>("test", data).await {
     ///             Ok(ack) => println!("Ack received {:?}", ack),
@@ -280,29 +455,57 @@ impl<A: Adapter> Socket<A> {
         let data = serde_json::to_value(data)?;
         let packet = Packet::event(ns, event.into(), data);
 
-        self.send_with_ack(packet, None).await
+        let (args, bin) = self.send_with_ack(packet, None, true).await?;
+        Ok((from_args(args)?, bin))
     }
 
     // Room actions
 
     /// Join the given rooms.
-    pub fn join(&self, rooms: impl RoomParam) -> Result<(), A::Error> {
-        self.ns.adapter.add_all(self.id, rooms)
+    pub async fn join(&self, rooms: impl RoomParam) -> Result<(), A::Error> {
+        #[cfg(feature = "admin-ui")]
+        if let Some(admin_ui) = &self.config.admin_ui {
+            let rooms: Vec<Room> = rooms.into_room_iter().collect();
+            self.ns.adapter.add_all(self.id, rooms.clone()).await?;
+            admin_ui.on_rooms_joined(self.ns(), self.id, &rooms);
+            return Ok(());
+        }
+        self.ns.adapter.add_all(self.id, rooms).await
     }
 
     /// Leave the given rooms.
-    pub fn leave(&self, rooms: impl RoomParam) -> Result<(), A::Error> {
-        self.ns.adapter.del(self.id, rooms)
+    pub async fn leave(&self, rooms: impl RoomParam) -> Result<(), A::Error> {
+        #[cfg(feature = "admin-ui")]
+        if let Some(admin_ui) = &self.config.admin_ui {
+            let rooms: Vec<Room> = rooms.into_room_iter().collect();
+            self.ns.adapter.del(self.id, rooms.clone()).await?;
+            admin_ui.on_rooms_left(self.ns(), self.id, &rooms);
+            return Ok(());
+        }
+        self.ns.adapter.del(self.id, rooms).await
     }
 
-    /// Leave all rooms where the socket is connected.
-    pub fn leave_all(&self) -> Result<(), A::Error> {
-        self.ns.adapter.del_all(self.id)
+    /// Leave all rooms where the socket is connected, except its private room named after its id.
+    pub async fn leave_all(&self) -> Result<(), A::Error> {
+        let sid_room = self.id.to_string();
+        let mut rooms = self.ns.adapter.socket_rooms(self.id).await?;
+        rooms.retain(|room| *room != sid_room);
+        self.leave(rooms).await
     }
 
-    /// Get all rooms where the socket is connected.
-    pub fn rooms(&self) -> Result<Vec<Room>, A::Error> {
-        self.ns.adapter.socket_rooms(self.id)
+    /// Get all rooms where the socket is connected, starting with its private room named after its id.
+    ///
+    /// The private room is not returned if [`SocketIoConfig::exclude_sid_room`] is enabled.
+    pub async fn rooms(&self) -> Result<Vec<Room>, A::Error> {
+        let sid_room = self.id.to_string();
+        let mut rooms = self.ns.adapter.socket_rooms(self.id).await?;
+        if let Some(i) = rooms.iter().position(|room| *room == sid_room) {
+            let room = rooms.remove(i);
+            if !self.config.exclude_sid_room {
+                rooms.insert(0, room);
+            }
+        }
+        Ok(rooms)
     }
 
     // Socket operators
@@ -312,18 +515,19 @@ impl<A: Adapter> Socket<A> {
     /// If you want to include the current socket, use the `within()` operator.
     /// ##### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
-    ///     socket.on("test", |socket, data: Value, _, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<Value>| async move {
     ///         let other_rooms = "room4".to_string();
     ///         // In room1, room2, room3 and room4 except the current
     ///         socket
     ///             .to("room1")
     ///             .to(["room2", "room3"])
     ///             .to(vec![other_rooms])
-    ///             .emit("test", data);
+    ///             .emit("test", data)
+    ///             .await;
     ///     });
     /// });
     pub fn to(&self, rooms: impl RoomParam) -> Operators<A> {
@@ -335,18 +539,19 @@ impl<A: Adapter> Socket<A> {
     /// It does include the current socket contrary to the `to()` operator.
     /// #### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
-    ///     socket.on("test", |socket, data: Value, _, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<Value>| async move {
     ///         let other_rooms = "room4".to_string();
     ///         // In room1, room2, room3 and room4 including the current socket
     ///         socket
     ///             .within("room1")
     ///             .within(["room2", "room3"])
     ///             .within(vec![other_rooms])
-    ///             .emit("test", data);
+    ///             .emit("test", data)
+    ///             .await;
     ///     });
     /// });
     pub fn within(&self, rooms: impl RoomParam) -> Operators<A> {
@@ -356,20 +561,20 @@ impl<A: Adapter> Socket<A> {
     /// Filter out all clients selected with the previous operators which are in the given rooms.
     /// ##### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
-    ///     socket.on("register1", |socket, data: Value, _, _| async move {
-    ///         socket.join("room1");
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("register1", |socket: SocketRef, Data(data): Data<Value>| async move {
+    ///         socket.join("room1").await;
     ///     });
-    ///     socket.on("register2", |socket, data: Value, _, _| async move {
-    ///         socket.join("room2");
+    ///     socket.on("register2", |socket: SocketRef, Data(data): Data<Value>| async move {
+    ///         socket.join("room2").await;
     ///     });
-    ///     socket.on("test", |socket, data: Value, _, _| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<Value>| async move {
     ///         // This message will be broadcast to all clients in the Namespace
     ///         // except for ones in room1 and the current socket
-    ///         socket.broadcast().except("room1").emit("test", data);
+    ///         socket.broadcast().except("room1").emit("test", data).await;
     ///     });
     /// });
     pub fn except(&self, rooms: impl RoomParam) -> Operators<A> {
@@ -380,37 +585,72 @@ impl<A: Adapter> Socket<A> {
     /// When using the default in-memory adapter, this operator is a no-op.
     /// ##### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
-    ///     socket.on("test", |socket, data: Value, _, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<Value>| async move {
     ///         // This message will be broadcast to all clients in this namespace and connected on this node
-    ///         socket.local().emit("test", data);
+    ///         socket.local().emit("test", data).await;
     ///     });
     /// });
     pub fn local(&self) -> Operators<A> {
         Operators::new(self.ns.clone(), Some(self.id)).local()
     }
 
+    /// Drop the message if the transport of the client is not currently writable
+    /// (no pending polling request or a full buffer) rather than buffering it or returning an error.
+    /// ##### Example
+    /// ```
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # use serde_json::Value;
+    /// let (_, io) = SocketIo::new_svc();
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("position", |socket: SocketRef, Data(data): Data<Value>| async move {
+    ///         // This message may be dropped, the client will receive the next position anyway
+    ///         socket.volatile().emit("position", data).await;
+    ///     });
+    /// });
+    pub fn volatile(&self) -> Operators<A> {
+        Operators::new(self.ns.clone(), Some(self.id)).volatile()
+    }
+
+    /// Set if the message can be compressed, when the websocket transport of the client negotiated the
+    /// permessage-deflate extension (see [`SocketIoBuilder::per_message_deflate`](crate::SocketIoBuilder::per_message_deflate)).
+    /// The messages can be compressed by default.
+    /// ##### Example
+    /// ```
+    /// # use socketioxide::{SocketIo, extract::*};
+    /// # use serde_json::Value;
+    /// let (_, io) = SocketIo::new_svc();
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("image", |socket: SocketRef, Data(data): Data<Value>, Bin(bin)| async move {
+    ///         // The image is already compressed, it is not worth compressing it again
+    ///         socket.compress(false).bin(bin).emit("image", data).await;
+    ///     });
+    /// });
+    pub fn compress(&self, compress: bool) -> Operators<A> {
+        Operators::new(self.ns.clone(), Some(self.id)).compress(compress)
+    }
+
     /// Set a custom timeout when sending a message with an acknowledgement.
     ///
     /// ##### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// # use futures::stream::StreamExt;
     /// # use std::time::Duration;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
-    ///    socket.on("test", |socket, data: Value, bin, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///    socket.on("test", |socket: SocketRef, Data(data): Data<Value>, Bin(bin)| async move {
     ///       // Emit a test message in the room1 and room3 rooms, except for the room2 room with the binary payload received, wait for 5 seconds for an acknowledgement
     ///       socket.to("room1")
     ///             .to("room3")
     ///             .except("room2")
     ///             .bin(bin)
     ///             .timeout(Duration::from_secs(5))
-    ///             .emit_with_ack::<Value>("message-back", data).unwrap().for_each(|ack| async move {
+    ///             .emit_with_ack::<Value>("message-back", data).await.unwrap().for_each(|ack| async move {
     ///                match ack {
     ///                    Ok(ack) => println!("Ack received {:?}", ack),
     ///                    Err(err) => println!("Ack error {:?}", err),
@@ -426,13 +666,13 @@ impl<A: Adapter> Socket<A> {
     /// Add a binary payload to the message.
     /// ##### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
-    ///     socket.on("test", |socket, data: Value, bin, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<Value>, Bin(bin)| async move {
     ///         // This will send the binary payload received to all clients in this namespace with the test message
-    ///         socket.bin(bin).emit("test", data);
+    ///         socket.bin(bin).emit("test", data).await;
     ///     });
     /// });
     pub fn bin(&self, binary: Vec<Vec<u8>>) -> Operators<A> {
@@ -442,13 +682,13 @@ impl<A: Adapter> Socket<A> {
     /// Broadcast to all clients without any filtering (except the current socket).
     /// ##### Example
     /// ```
-    /// # use socketioxide::SocketIo;
+    /// # use socketioxide::{SocketIo, extract::*};
     /// # use serde_json::Value;
     /// let (_, io) = SocketIo::new_svc();
-    /// io.ns("/", |socket, _: ()| async move {
-    ///     socket.on("test", |socket, data: Value, _, _| async move {
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     socket.on("test", |socket: SocketRef, Data(data): Data<Value>| async move {
     ///         // This message will be broadcast to all clients in this namespace
-    ///         socket.broadcast().emit("test", data);
+    ///         socket.broadcast().emit("test", data).await;
     ///     });
     /// });
     pub fn broadcast(&self) -> Operators<A> {
@@ -458,9 +698,9 @@ impl<A: Adapter> Socket<A> {
     /// Disconnect the socket from the current namespace,
     ///
     /// It will also call the disconnect handler if it is set.
-    pub fn disconnect(self: Arc<Self>) -> Result<(), SendError> {
+    pub async fn disconnect(self: Arc<Self>) -> Result<(), SendError> {
         self.send(Packet::disconnect(self.ns.path.clone()))?;
-        self.close(DisconnectReason::ServerNSDisconnect)?;
+        self.close(DisconnectReason::ServerNSDisconnect).await?;
         Ok(())
     }
 
@@ -475,54 +715,238 @@ impl<A: Adapter> Socket<A> {
         self.esocket.closed().await;
     }
 
+    /// Get the transport type of the underlying engine.io connection,
+    /// it changes from polling to websocket when the connection is upgraded.
+    pub fn transport_type(&self) -> TransportType {
+        self.esocket.transport_type()
+    }
+
+    /// Get the details of the handshake of this socket: the http request that opened the connection,
+    /// the auth payload sent when connecting to the namespace and the time of the connection.
+    ///
+    /// It mirrors `socket.handshake` of the javascript server.
+    /// #### Example
+    /// ```
+    /// # use socketioxide::{SocketIo, extract::SocketRef};
+    /// let (_, io) = SocketIo::new_svc();
+    /// io.ns("/", |socket: SocketRef| async move {
+    ///     let handshake = socket.handshake();
+    ///     println!("Socket connected from {} with query {:?}", handshake.url, handshake.query);
+    ///     if let Some(cookie) = handshake.headers.get("cookie") {
+    ///         println!("Cookie: {:?}", cookie);
+    ///     }
+    /// });
+    /// ```
+    pub fn handshake(&self) -> Handshake {
+        let req = &self.esocket.req_data;
+        let query = req
+            .uri
+            .query()
+            .map(|query| {
+                form_urlencoded::parse(query.as_bytes())
+                    .into_owned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        let auth = self
+            .auth
+            .as_deref()
+            .and_then(|auth| serde_json::from_str(auth).ok())
+            .unwrap_or_else(|| Value::Object(Default::default()));
+
+        Handshake {
+            headers: req.headers.clone(),
+            url: req.uri.clone(),
+            address: req.client_ip,
+            query,
+            auth,
+            issued: self.issued,
+            xdomain: req.headers.contains_key(http::header::ORIGIN),
+            transport: self.transport_type(),
+        }
+    }
+
     /// Get the current namespace path.
     pub fn ns(&self) -> &String {
         &self.ns.path
     }
 
-    pub(crate) fn send(&self, mut packet: Packet) -> Result<(), SendError> {
-        let bin_payloads = match packet.inner {
-            PacketData::BinaryEvent(_, ref mut bin, _) | PacketData::BinaryAck(ref mut bin, _) => {
-                Some(std::mem::take(&mut bin.bin))
-            }
-            _ => None,
-        };
+    /// Get a parameter captured from the namespace path
+    /// if the namespace was created from a pattern with [`SocketIo::dyn_ns`](crate::SocketIo::dyn_ns).
+    pub fn ns_param(&self, name: &str) -> Option<&str> {
+        self.ns.param(name)
+    }
 
-        let msg = packet.try_into()?;
-        self.esocket.emit(msg)?;
-        if let Some(bin_payloads) = bin_payloads {
-            for bin in bin_payloads {
-                self.esocket.emit_binary(bin)?;
-            }
+    /// Get a reference to the application state of type `S`
+    /// added with [`SocketIoBuilder::with_state`](crate::SocketIoBuilder::with_state).
+    pub fn state<S: Send + Sync + 'static>(&self) -> Option<&S> {
+        self.config.state.get::<S>()
+    }
+
+    /// Check if the state of the socket was recovered from a previous connection,
+    /// with its id, rooms, extensions and the packets broadcast while it was disconnected.
+    ///
+    /// It is only possible if connection state recovery is enabled with
+    /// [`SocketIoBuilder::max_disconnection_duration`](crate::SocketIoBuilder::max_disconnection_duration).
+    pub fn recovered(&self) -> bool {
+        self.recovered
+    }
+
+    /// The private session id sent to the client to recover the socket state
+    pub(crate) fn pid(&self) -> Option<Sid> {
+        self.pid
+    }
+
+    pub(crate) fn send(&self, packet: Packet) -> Result<(), SendError> {
+        self.send_compressed(packet, true)
+    }
+
+    /// Send a packet, `compress` tells if the websocket transport can compress it
+    fn send_compressed(&self, packet: Packet, compress: bool) -> Result<(), SendError> {
+        let sent = self.observes_sent_packets().then(|| packet.clone());
+        self.config
+            .parser
+            .encode(packet)?
+            .send(&self.esocket, compress)?;
+        if let Some(packet) = sent {
+            self.on_packet_sent(&packet);
         }
+        Ok(())
+    }
 
+    /// Send a packet, waiting for a place in the buffer of the engine.io socket if it is full
+    pub(crate) async fn send_async(
+        &self,
+        packet: Packet,
+        timeout: Option<Duration>,
+    ) -> Result<(), SendError> {
+        let sent = self.observes_sent_packets().then(|| packet.clone());
+        self.config
+            .parser
+            .encode(packet)?
+            .send_async(&self.esocket, timeout)
+            .await?;
+        if let Some(packet) = sent {
+            self.on_packet_sent(&packet);
+        }
         Ok(())
     }
 
-    pub(crate) async fn send_with_ack<V: DeserializeOwned>(
+    /// Encode a packet once with the parser of the socket, to send it to many sockets with [`Socket::send_shared`]
+    pub(crate) fn encode_shared(&self, packet: Packet) -> Result<SharedPacket, SendError> {
+        Ok(self.config.parser.encode(packet)?.into())
+    }
+
+    /// Send a packet already encoded once for all the sockets it is broadcast to
+    pub(crate) fn send_shared(
+        &self,
+        packet: &Packet,
+        encoded: &SharedPacket,
+        compress: bool,
+    ) -> Result<(), SendError> {
+        encoded.send(&self.esocket, compress)?;
+        self.on_packet_sent(packet);
+        Ok(())
+    }
+
+    /// Send a packet already encoded for all the sockets it is broadcast to,
+    /// it is dropped if the transport is not writable
+    pub(crate) fn send_volatile(&self, packet: &Packet, encoded: &SharedPacket, compress: bool) {
+        if encoded.send_volatile(&self.esocket, compress) {
+            self.on_packet_sent(packet);
+        } else {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                "[sid={}] transport not writable, dropping volatile packet",
+                self.id
+            );
+        }
+    }
+
+    /// Whether the admin ui or the outgoing packet handler need the packets sent to the client,
+    /// to avoid keeping a copy of the packets that are encoded otherwise
+    fn observes_sent_packets(&self) -> bool {
+        #[cfg(feature = "admin-ui")]
+        if self.config.admin_ui.is_some() {
+            return true;
+        }
+        self.any_outgoing_handler.read().unwrap().is_some()
+    }
+
+    /// Notify the admin ui and the outgoing packet handler of a packet sent to the client
+    fn on_packet_sent(&self, packet: &Packet) {
+        #[cfg(feature = "admin-ui")]
+        if let Some(admin_ui) = &self.config.admin_ui {
+            admin_ui.on_packet_sent(self, packet);
+        }
+
+        // The handler is cloned so that it can emit without holding the lock
+        let any_outgoing = self.any_outgoing_handler.read().unwrap().clone();
+        if let Some(handler) = any_outgoing {
+            match packet.inner {
+                PacketData::Event(ref e, ref data, _) => handler(self, e, data, &[]),
+                PacketData::BinaryEvent(ref e, ref bin, _) => handler(self, e, &bin.data, &bin.bin),
+                _ => (),
+            }
+        }
+    }
+
+    /// Send a packet and wait for the ack response of the client, with its arguments not deserialized yet.
+    /// `compress` tells if the websocket transport can compress the packet.
+    pub(crate) async fn send_with_ack(
         &self,
         mut packet: Packet,
         timeout: Option<Duration>,
-    ) -> Result<AckResponse<V>, AckError> {
+        compress: bool,
+    ) -> Result<AckResponse<Value>, AckError> {
         let (tx, rx) = oneshot::channel();
         let ack = self.ack_counter.fetch_add(1, Ordering::SeqCst) + 1;
         self.ack_message.lock().unwrap().insert(ack, tx);
         packet.inner.set_ack_id(ack);
-        self.send(packet)?;
+        self.send_compressed(packet, compress)?;
         let timeout = timeout.unwrap_or(self.config.ack_timeout);
-        let v = tokio::time::timeout(timeout, rx).await??;
-        Ok((serde_json::from_value(v.0)?, v.1))
+        Ok(tokio::time::timeout(timeout, rx).await??)
     }
 
     /// Called when the socket is gracefully disconnected from the server or the client
     ///
     /// It maybe also close when the underlying transport is closed or failed.
-    pub(crate) fn close(self: Arc<Self>, reason: DisconnectReason) -> Result<(), AdapterError> {
+    pub(crate) async fn close(
+        self: Arc<Self>,
+        reason: DisconnectReason,
+    ) -> Result<(), AdapterError> {
+        #[cfg(feature = "admin-ui")]
+        if let Some(admin_ui) = &self.config.admin_ui {
+            admin_ui.on_disconnect(self.ns(), self.id, &reason);
+        }
+
         if let Some(handler) = self.disconnect_handler.lock().unwrap().take() {
-            tokio::spawn(handler(self.clone(), reason));
+            tokio::spawn(handler(self.clone(), reason.clone()));
         }
 
-        self.ns.remove_socket(self.id)?;
+        if let (Some(pid), true) = (self.pid, reason.is_recoverable()) {
+            let session = Session {
+                sid: self.id,
+                pid,
+                rooms: self
+                    .ns
+                    .adapter
+                    .socket_rooms(self.id)
+                    .await
+                    .map_err(Into::into)?,
+                #[cfg(feature = "extensions")]
+                extensions: self.extensions.share(),
+                missed_packets: Vec::new(),
+                disconnected_at: Instant::now(),
+            };
+            self.ns
+                .adapter
+                .persist_session(session)
+                .await
+                .map_err(Into::into)?;
+        }
+
+        self.ns.remove_socket(self.id).await?;
         Ok(())
     }
 
@@ -533,16 +957,27 @@ impl<A: Adapter> Socket<A> {
             PacketData::EventAck(data, ack_id) => self.recv_ack(data, ack_id),
             PacketData::BinaryEvent(e, packet, ack) => self.recv_bin_event(e, packet, ack),
             PacketData::BinaryAck(packet, ack) => self.recv_bin_ack(packet, ack),
-            PacketData::Disconnect => self
-                .close(DisconnectReason::ClientNSDisconnect)
-                .map_err(Error::from),
+            PacketData::Disconnect => {
+                tokio::spawn(async move {
+                    if let Err(_e) = self.close(DisconnectReason::ClientNSDisconnect).await {
+                        #[cfg(feature = "tracing")]
+                        tracing::debug!("error while closing socket: {}", _e);
+                    }
+                });
+                Ok(())
+            }
             _ => unreachable!(),
         }
     }
 
     fn recv_event(self: Arc<Self>, e: String, data: Value, ack: Option<i64>) -> Result<(), Error> {
+        #[cfg(feature = "admin-ui")]
+        if let Some(admin_ui) = &self.config.admin_ui {
+            admin_ui.on_event_received(self.ns(), self.id, &e, &data);
+        }
+        self.call_any_handler(&e, &data, &[], ack);
         if let Some(handler) = self.message_handlers.read().unwrap().get(&e) {
-            handler.call(self.clone(), data, vec![], ack)?;
+            handler.call(self.clone(), e, data, vec![], ack);
         }
         Ok(())
     }
@@ -553,12 +988,30 @@ impl<A: Adapter> Socket<A> {
         packet: BinaryPacket,
         ack: Option<i64>,
     ) -> Result<(), Error> {
+        #[cfg(feature = "admin-ui")]
+        if let Some(admin_ui) = &self.config.admin_ui {
+            admin_ui.on_event_received(self.ns(), self.id, &e, &packet.data);
+        }
+        self.call_any_handler(&e, &packet.data, &packet.bin, ack);
         if let Some(handler) = self.message_handlers.read().unwrap().get(&e) {
-            handler.call(self.clone(), packet.data, packet.bin, ack)?;
+            handler.call(self.clone(), e, packet.data, packet.bin, ack);
         }
         Ok(())
     }
 
+    /// Call the catch-all handler registered with [`Socket::on_any`], if there is one
+    fn call_any_handler(
+        self: &Arc<Self>,
+        e: &str,
+        data: &Value,
+        bin: &[Vec<u8>],
+        ack: Option<i64>,
+    ) {
+        if let Some(handler) = self.any_message_handler.read().unwrap().as_ref() {
+            handler.call(self.clone(), e.to_string(), data.clone(), bin.to_vec(), ack);
+        }
+    }
+
     fn recv_ack(self: Arc<Self>, data: Value, ack: i64) -> Result<(), Error> {
         if let Some(tx) = self.ack_message.lock().unwrap().remove(&ack) {
             tx.send((data, vec![])).ok();
@@ -594,6 +1047,7 @@ impl<A: Adapter> Socket<A> {
             ns,
             engineioxide::Socket::new_dummy(sid, close_fn).into(),
             Arc::new(SocketIoConfig::default()),
+            None,
         )
     }
 }
@@ -0,0 +1,49 @@
+//! Typed application state shared by all the handlers of a server,
+//! set with [`SocketIoBuilder::with_state`](crate::SocketIoBuilder::with_state).
+//!
+//! Unlike the socket extensions, the state is set once when the server is built and is read-only,
+//! so it is stored in a plain `HashMap` without any lock.
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+};
+
+#[derive(Default)]
+pub(crate) struct StateMap(HashMap<TypeId, Box<dyn Any + Send + Sync>>);
+
+impl StateMap {
+    /// Insert a value in the state, replacing the previous value of the same type
+    pub fn insert<S: Send + Sync + 'static>(&mut self, state: S) {
+        self.0.insert(TypeId::of::<S>(), Box::new(state));
+    }
+
+    /// Get a reference to the value of type `S` if it exists
+    pub fn get<S: Send + Sync + 'static>(&self) -> Option<&S> {
+        self.0
+            .get(&TypeId::of::<S>())
+            .and_then(|boxed| boxed.downcast_ref())
+    }
+}
+
+impl fmt::Debug for StateMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StateMap").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn get_state() {
+        let mut state = StateMap::default();
+        state.insert(1u32);
+        state.insert("state");
+        state.insert(2u32);
+        assert_eq!(state.get::<u32>(), Some(&2));
+        assert_eq!(state.get::<&str>(), Some(&"state"));
+        assert_eq!(state.get::<u64>(), None);
+    }
+}
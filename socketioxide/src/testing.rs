@@ -0,0 +1,323 @@
+//! An in-memory test harness to test the namespace handlers without a network, available with the `test-utils` feature.
+//!
+//! A [`TestServer`] drives the socket.io server directly as an engine.io handler, with a mock transport
+//! for each [`TestClient`] instead of a http or websocket connection.
+//!
+//! #### Example
+//! ```
+//! # use socketioxide::{SocketIo, extract::*, packet::*};
+//! # use serde_json::{json, Value};
+//! # #[tokio::main]
+//! # async fn main() {
+//! let (server, io) = SocketIo::builder().build_test();
+//! io.ns("/", |socket: SocketRef| async move {
+//!     socket.on("echo", |Data(data): Data<Value>, ack: AckSender| async move {
+//!         ack.send(data).ok();
+//!     });
+//! });
+//!
+//! let mut client = server.new_client();
+//! client.connect("/", json!({ "token": "secret" })).await.unwrap();
+//! let ack = client.emit_with_ack("/", "echo", "hello").await.unwrap();
+//! assert_eq!(ack.inner, PacketData::EventAck(json!(["hello"]), 1));
+//! # }
+//! ```
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, Weak},
+};
+
+use engineioxide::{
+    handler::EngineIoHandler, packet::Packet as EPacket, sid::Sid,
+    DisconnectReason as EIoDisconnectReason, Socket as EIoSocket,
+};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use crate::{
+    adapter::Adapter,
+    client::{Client, SocketData},
+    packet::{ConnectErrorPacket, ConnectPacket, Packet, PacketData},
+    parser::{EncodedPacket, ParserState},
+    DisconnectReason,
+};
+
+/// The maximum number of packets buffered for a [`TestClient`] before the server fails to send them
+const CLIENT_BUFFER_SIZE: usize = 256;
+
+type Sockets = Mutex<HashMap<Sid, Arc<EIoSocket<SocketData>>>>;
+
+/// An in-memory socket.io server, created with
+/// [`SocketIoBuilder::build_test`](crate::SocketIoBuilder::build_test).
+#[derive(Debug)]
+pub struct TestServer<A: Adapter> {
+    client: Arc<Client<A>>,
+    sockets: Arc<Sockets>,
+}
+
+impl<A: Adapter> TestServer<A> {
+    pub(crate) fn new(client: Arc<Client<A>>) -> Self {
+        Self {
+            client,
+            sockets: Arc::default(),
+        }
+    }
+
+    /// Open a new engine.io connection to the server, it is not connected to any namespace yet.
+    pub fn new_client(&self) -> TestClient<A> {
+        let client = Arc::downgrade(&self.client);
+        let sockets = Arc::downgrade(&self.sockets);
+        let close_fn = Box::new(move |sid, reason| close_session(&client, &sockets, sid, reason));
+        let (esocket, rx) = EIoSocket::new_dummy_piped(Sid::new(), close_fn, CLIENT_BUFFER_SIZE);
+
+        self.sockets
+            .lock()
+            .unwrap()
+            .insert(esocket.id, esocket.clone());
+        self.client.on_connect(esocket.clone());
+        TestClient {
+            client: self.client.clone(),
+            esocket,
+            rx,
+            state: ParserState::default(),
+            buffer: VecDeque::new(),
+            ack_counter: 0,
+        }
+    }
+}
+
+/// Remove a closed socket and notify the server, like the engine does for a real connection
+fn close_session<A: Adapter>(
+    client: &Weak<Client<A>>,
+    sockets: &Weak<Sockets>,
+    sid: Sid,
+    reason: EIoDisconnectReason,
+) {
+    if let (Some(client), Some(sockets)) = (client.upgrade(), sockets.upgrade()) {
+        let socket = sockets.lock().unwrap().remove(&sid);
+        if let Some(socket) = socket {
+            client.on_disconnect(socket, reason);
+        }
+    }
+}
+
+/// A mock client connected to a [`TestServer`].
+///
+/// The packets received from the server are decoded with the parser of the server.
+#[derive(Debug)]
+pub struct TestClient<A: Adapter> {
+    client: Arc<Client<A>>,
+    esocket: Arc<EIoSocket<SocketData>>,
+    rx: mpsc::Receiver<EPacket>,
+    state: ParserState,
+    /// Packets received while waiting for a specific packet
+    buffer: VecDeque<Packet>,
+    ack_counter: i64,
+}
+
+impl<A: Adapter> TestClient<A> {
+    /// The engine.io session id of the client
+    pub fn id(&self) -> Sid {
+        self.esocket.id
+    }
+
+    /// Send a packet to the server, it is encoded with the parser of the server
+    pub fn send(&self, packet: Packet) {
+        let encoded = self
+            .client
+            .config
+            .parser
+            .encode(packet)
+            .expect("failed to encode packet");
+        match encoded {
+            EncodedPacket::Text(msg, bin) => {
+                self.client.on_message(msg, self.esocket.clone());
+                for bin in bin {
+                    self.client.on_binary(bin, self.esocket.clone());
+                }
+            }
+            EncodedPacket::Binary(data) => self.client.on_binary(data, self.esocket.clone()),
+        }
+    }
+
+    /// Receive the next packet sent by the server.
+    ///
+    /// Returns `None` once the connection is closed.
+    pub async fn recv(&mut self) -> Option<Packet> {
+        if let Some(packet) = self.buffer.pop_front() {
+            return Some(packet);
+        }
+        self.recv_packet().await
+    }
+
+    /// Connect to a namespace with an auth payload and wait for the response of the server.
+    /// A `null` auth payload, like `()`, is not sent.
+    ///
+    /// Returns the id of the socket, or the connect error sent if the connection is refused.
+    pub async fn connect(
+        &mut self,
+        ns: impl Into<String>,
+        auth: impl Serialize,
+    ) -> Result<Sid, ConnectErrorPacket> {
+        let ns = ns.into();
+        let auth = match serde_json::to_value(auth).expect("failed to serialize auth") {
+            Value::Null => None,
+            auth => Some(auth.to_string()),
+        };
+        self.send(Packet {
+            inner: PacketData::Connect(auth),
+            ns: ns.clone(),
+        });
+        let packet = self
+            .wait_for(|p| {
+                p.ns == ns
+                    && matches!(
+                        p.inner,
+                        PacketData::Connect(_) | PacketData::ConnectError(_)
+                    )
+            })
+            .await;
+        match packet.map(|p| p.inner) {
+            Some(PacketData::Connect(Some(data))) => {
+                let data: ConnectPacket =
+                    serde_json::from_str(&data).expect("invalid connect packet");
+                Ok(data.sid)
+            }
+            Some(PacketData::ConnectError(err)) => Err(err),
+            _ => Err(ConnectErrorPacket {
+                message: "connection closed".into(),
+                data: None,
+            }),
+        }
+    }
+
+    /// Emit an event to a namespace.
+    ///
+    /// Like with the server, a tuple or an array is sent as multiple arguments.
+    pub fn emit(&self, ns: impl Into<String>, event: impl Into<String>, data: impl Serialize) {
+        let data = serde_json::to_value(data).expect("failed to serialize data");
+        self.send(Packet::event(ns.into(), event.into(), data));
+    }
+
+    /// Emit an event with binary payloads to a namespace
+    pub fn emit_bin(
+        &self,
+        ns: impl Into<String>,
+        event: impl Into<String>,
+        data: impl Serialize,
+        bin: Vec<Vec<u8>>,
+    ) {
+        let data = serde_json::to_value(data).expect("failed to serialize data");
+        self.send(Packet::bin_event(ns.into(), event.into(), data, bin));
+    }
+
+    /// Emit an event to a namespace and wait for its acknowledgement.
+    ///
+    /// Returns the ack packet, or `None` if the connection is closed before.
+    pub async fn emit_with_ack(
+        &mut self,
+        ns: impl Into<String>,
+        event: impl Into<String>,
+        data: impl Serialize,
+    ) -> Option<Packet> {
+        let ns = ns.into();
+        let data = serde_json::to_value(data).expect("failed to serialize data");
+        self.ack_counter += 1;
+        let ack_id = self.ack_counter;
+        self.send(Packet {
+            inner: PacketData::Event(event.into(), data, Some(ack_id)),
+            ns: ns.clone(),
+        });
+        self.wait_for(|p| {
+            p.ns == ns
+                && matches!(p.inner, PacketData::EventAck(_, id) | PacketData::BinaryAck(_, id) if id == ack_id)
+        })
+        .await
+    }
+
+    /// Answer an event emitted by the server with an ack id
+    pub fn ack(&self, ns: impl Into<String>, ack_id: i64, data: impl Serialize) {
+        let data = serde_json::to_value(data).expect("failed to serialize data");
+        self.send(Packet::ack(ns.into(), data, ack_id));
+    }
+
+    /// Leave a namespace, the server socket is disconnected
+    /// with [`DisconnectReason::ClientNSDisconnect`].
+    pub fn disconnect(&self, ns: impl Into<String>) {
+        self.send(Packet::disconnect(ns.into()));
+    }
+
+    /// Close the connection as if the transport was closed with the given reason,
+    /// all the sockets of the client are disconnected with this reason.
+    ///
+    /// # Panics
+    /// If the reason is a namespace disconnection reason, use [`TestClient::disconnect`] instead.
+    pub fn close(&self, reason: DisconnectReason) {
+        let reason = match reason {
+            DisconnectReason::TransportClose => EIoDisconnectReason::TransportClose,
+            DisconnectReason::MultipleHttpPollingError => {
+                EIoDisconnectReason::MultipleHttpPollingError
+            }
+            DisconnectReason::PacketParsingError => EIoDisconnectReason::PacketParsingError,
+            DisconnectReason::TransportError => EIoDisconnectReason::TransportError,
+            DisconnectReason::HeartbeatTimeout => EIoDisconnectReason::HeartbeatTimeout,
+            DisconnectReason::ClosingServer => EIoDisconnectReason::ClosingServer,
+            DisconnectReason::BufferOverflow => EIoDisconnectReason::BufferOverflow,
+            reason => panic!("{reason:?} is not a transport disconnect reason"),
+        };
+        self.esocket.close(reason);
+    }
+
+    /// Check if the connection is closed
+    pub fn is_closed(&self) -> bool {
+        self.esocket.is_closed()
+    }
+
+    /// Receive and decode packets until a complete socket.io packet is received
+    async fn recv_packet(&mut self) -> Option<Packet> {
+        loop {
+            let res = match self.rx.recv().await? {
+                EPacket::Message(msg) => self.client.config.parser.decode_str(&self.state, msg),
+                EPacket::SharedMessage(msg) => {
+                    let msg = msg.to_string();
+                    self.client.config.parser.decode_str(&self.state, msg)
+                }
+                EPacket::Binary(data) | EPacket::BinaryV3(data) => {
+                    self.client.config.parser.decode_bin(&self.state, data)
+                }
+                EPacket::SharedBinary(data) | EPacket::SharedBinaryV3(data) => {
+                    let data = data.to_vec();
+                    self.client.config.parser.decode_bin(&self.state, data)
+                }
+                EPacket::Close => return None,
+                _ => Ok(None),
+            };
+            if let Some(packet) = res.expect("failed to decode packet") {
+                return Some(packet);
+            }
+        }
+    }
+
+    /// Wait for a packet matching the predicate, the other packets are kept to be received later
+    async fn wait_for(&mut self, predicate: impl Fn(&Packet) -> bool) -> Option<Packet> {
+        if let Some(i) = self.buffer.iter().position(&predicate) {
+            return self.buffer.remove(i);
+        }
+        loop {
+            let packet = self.recv_packet().await?;
+            if predicate(&packet) {
+                return Some(packet);
+            }
+            self.buffer.push_back(packet);
+        }
+    }
+}
+
+impl<A: Adapter> Drop for TestClient<A> {
+    fn drop(&mut self) {
+        if !self.esocket.is_closed() {
+            self.esocket.close(EIoDisconnectReason::TransportClose);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Tests for the admin UI instrumentation, with the in-memory test harness.
+#![cfg(all(feature = "admin-ui", feature = "test-utils"))]
+
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use socketioxide::{
+    adapter::LocalAdapter,
+    admin::{AdminUiConfig, AdminUiMode},
+    extract::{Data, SocketRef},
+    packet::{Packet, PacketData},
+    testing::TestClient,
+    SocketIo,
+};
+
+/// "password" hashed with bcrypt and a low cost
+const HASH: &str = "$2b$04$Mo2R5PglK1SZC69omGtKcOahazUqotwOa0GBwAm17a6ccUnY9HdO6";
+
+/// Receive the arguments of the next event with the given name sent to the admin namespace,
+/// the other events are skipped
+async fn recv_event(admin: &mut TestClient<LocalAdapter>, event: &str) -> Vec<Value> {
+    let recv = async {
+        loop {
+            match admin.recv().await.expect("connection closed").inner {
+                PacketData::Event(e, Value::Array(args), _) if e == event => break args,
+                _ => continue,
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_millis(500), recv)
+        .await
+        .unwrap_or_else(|_| panic!("timeout waiting for {event}"))
+}
+
+fn admin_io(config: AdminUiConfig) -> (socketioxide::testing::TestServer<LocalAdapter>, SocketIo) {
+    let (server, io) = SocketIo::builder().admin_ui(config).build_test();
+    io.ns("/", |socket: SocketRef| async move {
+        socket.on(
+            "join",
+            |socket: SocketRef, Data(room): Data<String>| async move {
+                socket.join(room).await.ok();
+            },
+        );
+        socket.on(
+            "echo",
+            |socket: SocketRef, Data(data): Data<Value>| async move {
+                socket.emit("echo", data).ok();
+            },
+        );
+    });
+    (server, io)
+}
+
+#[tokio::test]
+pub async fn authentication() {
+    let (server, _io) = admin_io(AdminUiConfig::new("admin", HASH));
+    let mut admin = server.new_client();
+
+    let auth = json!({ "username": "admin", "password": "wrong" });
+    let err = admin.connect("/admin", auth).await.unwrap_err();
+    assert_eq!(err.message, "Authentication error");
+
+    let auth = json!({ "username": "admin", "password": "password" });
+    admin.connect("/admin", auth).await.unwrap();
+    let config = recv_event(&mut admin, "config").await;
+    assert_eq!(
+        config[0]["supportedFeatures"],
+        json!([
+            "ALL_EVENTS",
+            "AGGREGATED_EVENTS",
+            "JOIN",
+            "LEAVE",
+            "DISCONNECT",
+            "MJOIN",
+            "MLEAVE",
+            "MDISCONNECT"
+        ])
+    );
+}
+
+#[tokio::test]
+pub async fn all_sockets() {
+    let (server, _io) = admin_io(AdminUiConfig::without_auth());
+    let mut client = server.new_client();
+    let sid = client.connect("/", ()).await.unwrap();
+    client.emit("/", "join", "room1");
+    let mut admin = server.new_client();
+    admin.connect("/admin", ()).await.unwrap();
+
+    let sockets = recv_event(&mut admin, "all_sockets").await;
+    let socket = &sockets[0][0];
+    assert_eq!(socket["id"], sid.to_string());
+    assert_eq!(socket["clientId"], sid.to_string());
+    assert_eq!(socket["nsp"], "/");
+    assert_eq!(socket["transport"], "websocket");
+    let rooms = socket["rooms"].as_array().unwrap();
+    assert_eq!(rooms.len(), 2);
+    assert!(rooms.contains(&json!(sid.to_string())));
+    assert!(rooms.contains(&json!("room1")));
+}
+
+#[tokio::test]
+pub async fn socket_events() {
+    let (server, _io) = admin_io(AdminUiConfig::without_auth());
+    let mut admin = server.new_client();
+    admin.connect("/admin", ()).await.unwrap();
+    let mut client = server.new_client();
+    let sid = client
+        .connect("/", json!({ "token": "foo" }))
+        .await
+        .unwrap();
+    let id = json!(sid.to_string());
+
+    let connected = recv_event(&mut admin, "socket_connected").await;
+    assert_eq!(connected[0]["id"], id);
+    assert_eq!(connected[0]["nsp"], "/");
+    assert_eq!(connected[0]["handshake"]["auth"], json!({ "token": "foo" }));
+    assert_eq!(connected[0]["rooms"], json!([id]));
+
+    client.emit("/", "join", "room1");
+    let received = recv_event(&mut admin, "event_received").await;
+    assert_eq!(
+        received[..3],
+        [json!("/"), id.clone(), json!(["join", "room1"])]
+    );
+    let joined = recv_event(&mut admin, "room_joined").await;
+    assert_eq!(joined[..3], [json!("/"), json!("room1"), id.clone()]);
+
+    client.emit("/", "echo", (1, 2));
+    let received = recv_event(&mut admin, "event_received").await;
+    assert_eq!(received[2], json!(["echo", 1, 2]));
+    let sent = recv_event(&mut admin, "event_sent").await;
+    assert_eq!(sent[..3], [json!("/"), id.clone(), json!(["echo", 1, 2])]);
+
+    client.disconnect("/");
+    let disconnected = recv_event(&mut admin, "socket_disconnected").await;
+    assert_eq!(
+        disconnected[..3],
+        [json!("/"), id, json!("client namespace disconnect")]
+    );
+}
+
+#[tokio::test]
+pub async fn admin_actions() {
+    let (server, _io) = admin_io(AdminUiConfig::without_auth());
+    let mut admin = server.new_client();
+    admin.connect("/admin", ()).await.unwrap();
+    let mut client = server.new_client();
+    let sid = client.connect("/", ()).await.unwrap();
+    let id = json!(sid.to_string());
+    client.emit("/", "join", "room1");
+    recv_event(&mut admin, "room_joined").await;
+
+    // A single socket selected by its id
+    admin.emit("/admin", "_join", ("/", "room2", sid.to_string()));
+    let joined = recv_event(&mut admin, "room_joined").await;
+    assert_eq!(joined[..3], [json!("/"), json!("room2"), id.clone()]);
+
+    // The sockets of a room
+    admin.emit("/admin", "_leave", ("/", "room2", "room1"));
+    let left = recv_event(&mut admin, "room_left").await;
+    assert_eq!(left[..3], [json!("/"), json!("room2"), id.clone()]);
+
+    admin.emit("/admin", "_disconnect", ("/", false, "room1"));
+    assert_eq!(client.recv().await.unwrap(), Packet::disconnect("/".into()));
+    let disconnected = recv_event(&mut admin, "socket_disconnected").await;
+    assert_eq!(
+        disconnected[..3],
+        [json!("/"), id, json!("server namespace disconnect")]
+    );
+}
+
+#[tokio::test]
+pub async fn server_stats() {
+    let config = AdminUiConfig {
+        mode: AdminUiMode::Production,
+        readonly: true,
+        server_id: Some("server".into()),
+        server_stats_interval: Duration::from_millis(50),
+        ..AdminUiConfig::without_auth()
+    };
+    let (server, _io) = admin_io(config);
+    let mut client = server.new_client();
+    client.connect("/", ()).await.unwrap();
+    let mut admin = server.new_client();
+    admin.connect("/admin", ()).await.unwrap();
+
+    let config = recv_event(&mut admin, "config").await;
+    assert_eq!(config[0]["supportedFeatures"], json!(["AGGREGATED_EVENTS"]));
+
+    let stats = recv_event(&mut admin, "server_stats").await;
+    let stats = &stats[0];
+    assert_eq!(stats["serverId"], "server");
+    assert_eq!(stats["pid"], std::process::id());
+    assert_eq!(stats["clientsCount"], 2);
+    assert_eq!(stats["pollingClientsCount"], 0);
+    let mut namespaces = stats["namespaces"].as_array().unwrap().clone();
+    namespaces.sort_by_key(|ns| ns["name"].as_str().unwrap().to_string());
+    assert_eq!(
+        namespaces,
+        [
+            json!({ "name": "/", "socketsCount": 1 }),
+            json!({ "name": "/admin", "socketsCount": 1 })
+        ]
+    );
+    let connection = stats["aggregatedEvents"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|e| e["type"] == "connection")
+        .expect("no aggregated connection event");
+    assert_eq!(connection["subType"], "/");
+    assert_eq!(connection["count"], 1);
+}
@@ -0,0 +1,132 @@
+//! Tests for the catch-all event handlers `on_any` and `on_any_outgoing`.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use hyper::Server;
+use serde_json::Value;
+use socketioxide::{
+    extract::{AckSender, Data, Event, SocketRef},
+    SocketIo,
+};
+use tokio::{
+    net::TcpStream,
+    sync::mpsc::{self, UnboundedReceiver},
+};
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+type Ws = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Start a server whose catch-all handlers forward what they see to the returned channels
+fn create_server(port: u16) -> (UnboundedReceiver<String>, UnboundedReceiver<String>) {
+    let (svc, io) = SocketIo::new_svc();
+
+    let addr = &SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+    let server = Server::bind(addr).serve(svc.into_make_service());
+    tokio::spawn(server);
+
+    let (in_tx, in_rx) = mpsc::unbounded_channel();
+    let (out_tx, out_rx) = mpsc::unbounded_channel();
+    io.ns("/", move |socket: SocketRef| {
+        let (in_tx, out_tx) = (in_tx.clone(), out_tx.clone());
+        async move {
+            socket.on_any(
+                move |Event(event), Data(data): Data<Value>, ack: AckSender| {
+                    in_tx.send(format!("{event}:{data}")).unwrap();
+                    async move {
+                        ack.send("any").ok();
+                    }
+                },
+            );
+            socket.on_any_outgoing(move |_, event, data, _| {
+                out_tx.send(format!("{event}:{data}")).unwrap();
+            });
+            socket.on(
+                "echo",
+                |socket: SocketRef, Data(data): Data<Value>| async move {
+                    socket.emit("echo", data).ok();
+                },
+            );
+            socket.emit("welcome", ()).ok();
+        }
+    });
+    (in_rx, out_rx)
+}
+
+async fn connect(port: u16) -> Ws {
+    let mut ws = tokio_tungstenite::connect_async(format!(
+        "ws://127.0.0.1:{port}/socket.io/?EIO=4&transport=websocket"
+    ))
+    .await
+    .unwrap()
+    .0;
+    ws.send(Message::Text("40{}".to_string())).await.unwrap();
+
+    recv(&mut ws).await; // engine.io open packet
+    recv(&mut ws).await; // socket.io connect packet
+    ws
+}
+
+async fn recv(ws: &mut Ws) -> String {
+    let msg = tokio::time::timeout(Duration::from_millis(500), ws.next())
+        .await
+        .expect("timeout waiting for a message")
+        .unwrap()
+        .unwrap();
+    match msg {
+        Message::Text(msg) => msg,
+        msg => panic!("unexpected message: {:?}", msg),
+    }
+}
+
+async fn next(rx: &mut UnboundedReceiver<String>) -> String {
+    tokio::time::timeout(Duration::from_millis(500), rx.recv())
+        .await
+        .expect("timeout waiting for a handler call")
+        .unwrap()
+}
+
+#[tokio::test]
+pub async fn on_any() {
+    let (mut in_rx, _out_rx) = create_server(2500);
+    let mut ws = connect(2500).await;
+    assert_eq!(recv(&mut ws).await, r#"42["welcome",null]"#);
+
+    // An event without handler
+    ws.send(Message::Text(r#"421["unknown",1]"#.into()))
+        .await
+        .unwrap();
+    assert_eq!(next(&mut in_rx).await, "unknown:1");
+    assert_eq!(recv(&mut ws).await, r#"431["any"]"#);
+
+    // An event with a handler is passed to both handlers
+    ws.send(Message::Text(r#"42["echo","hello"]"#.into()))
+        .await
+        .unwrap();
+    assert_eq!(next(&mut in_rx).await, r#"echo:"hello""#);
+    assert_eq!(recv(&mut ws).await, r#"42["echo","hello"]"#);
+}
+
+#[tokio::test]
+pub async fn on_any_outgoing() {
+    let (_in_rx, mut out_rx) = create_server(2502);
+    let mut ws = connect(2502).await;
+    assert_eq!(recv(&mut ws).await, r#"42["welcome",null]"#);
+    assert_eq!(next(&mut out_rx).await, "welcome:null");
+
+    ws.send(Message::Text(r#"42["echo","hello"]"#.into()))
+        .await
+        .unwrap();
+    assert_eq!(recv(&mut ws).await, r#"42["echo","hello"]"#);
+    assert_eq!(next(&mut out_rx).await, r#"echo:"hello""#);
+
+    // The listener is not called for acks
+    ws.send(Message::Text(r#"421["unknown"]"#.into()))
+        .await
+        .unwrap();
+    assert_eq!(recv(&mut ws).await, r#"431["any"]"#);
+    assert!(out_rx.try_recv().is_err());
+}
@@ -11,8 +11,7 @@
 use std::time::Duration;
 
 use futures::{SinkExt, StreamExt};
-use serde_json::Value;
-use socketioxide::{DisconnectReason, SocketIo};
+use socketioxide::{extract::SocketRef, DisconnectReason, SocketIo};
 use tokio::sync::mpsc;
 
 mod fixture;
@@ -24,7 +23,7 @@ use crate::fixture::{create_polling_connection, create_ws_connection};
 
 fn attach_handler(io: &SocketIo, chan_size: usize) -> mpsc::Receiver<DisconnectReason> {
     let (tx, rx) = mpsc::channel::<DisconnectReason>(chan_size);
-    io.ns("/", move |socket, _: Value| {
+    io.ns("/", move |socket: SocketRef| {
         println!("Socket connected on / namespace with id: {}", socket.id);
         let tx = tx.clone();
         socket.on_disconnect(move |socket, reason| {
@@ -213,13 +212,13 @@ pub async fn server_ns_disconnect() {
     let (tx, mut rx) = mpsc::channel::<DisconnectReason>(1);
     let io = create_server(12349);
 
-    io.ns("/", move |socket, _: Value| {
+    io.ns("/", move |socket: SocketRef| {
         println!("Socket connected on / namespace with id: {}", socket.id);
         let sock = socket.clone();
         let tx = tx.clone();
         tokio::spawn(async move {
             tokio::time::sleep(Duration::from_millis(10)).await;
-            sock.disconnect().unwrap();
+            sock.disconnect().await.unwrap();
         });
 
         socket.on_disconnect(move |socket, reason| {
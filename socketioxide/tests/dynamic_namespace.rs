@@ -0,0 +1,107 @@
+//! Tests for dynamic namespaces:
+//! namespaces matching a pattern are created when a client connects to them.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use hyper::Server;
+use socketioxide::{extract::SocketRef, ConnectError, SocketIo};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+type Ws = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+fn create_server(port: u16) -> SocketIo {
+    let (svc, io) = SocketIo::new_svc();
+
+    let addr = &SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+    let server = Server::bind(addr).serve(svc.into_make_service());
+    tokio::spawn(server);
+
+    io.dyn_ns("/tenant-{id}", |socket: SocketRef| async move {
+        let tenant = socket.ns_param("id").unwrap().to_string();
+        socket.emit("tenant", tenant).ok();
+    })
+    .unwrap()
+    .with_middleware(|socket: SocketRef| async move {
+        match socket.ns_param("id") {
+            Some("banned") => Err(ConnectError::new("banned tenant")),
+            _ => Ok(()),
+        }
+    })
+    .with_cleanup();
+    io
+}
+
+/// Connect a websocket client to the given namespace and return the first socket.io packet sent by the server.
+async fn connect(port: u16, ns: &str) -> (Ws, String) {
+    let mut ws = tokio_tungstenite::connect_async(format!(
+        "ws://127.0.0.1:{port}/socket.io/?EIO=4&transport=websocket"
+    ))
+    .await
+    .unwrap()
+    .0;
+    ws.send(Message::Text(format!("40{ns},"))).await.unwrap();
+
+    recv(&mut ws).await; // engine.io open packet
+    let packet = recv(&mut ws).await;
+    (ws, packet)
+}
+
+async fn recv(ws: &mut Ws) -> String {
+    let msg = tokio::time::timeout(Duration::from_millis(500), ws.next())
+        .await
+        .expect("timeout waiting for a message")
+        .unwrap()
+        .unwrap();
+    match msg {
+        Message::Text(msg) => msg,
+        msg => panic!("unexpected message: {:?}", msg),
+    }
+}
+
+#[tokio::test]
+pub async fn connect_to_dynamic_namespace() {
+    let io = create_server(2400);
+    let (mut ws, packet) = connect(2400, "/tenant-42").await;
+    assert!(packet.starts_with("40/tenant-42,"), "{packet}");
+    assert_eq!(recv(&mut ws).await, r#"42/tenant-42,["tenant","42"]"#);
+
+    assert!(io.of("/tenant-42").is_some());
+    assert!(io.of("/tenant-43").is_none());
+}
+
+#[tokio::test]
+pub async fn unmatched_namespace_is_invalid() {
+    let _io = create_server(2402);
+    let (_ws, packet) = connect(2402, "/admin").await;
+    assert_eq!(packet, r#"44/admin,{"message":"Invalid namespace"}"#);
+}
+
+#[tokio::test]
+pub async fn dynamic_namespace_middleware() {
+    let io = create_server(2404);
+    let (_ws, packet) = connect(2404, "/tenant-banned").await;
+    assert_eq!(packet, r#"44/tenant-banned,{"message":"banned tenant"}"#);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(io.of("/tenant-banned").is_some());
+}
+
+#[tokio::test]
+pub async fn empty_dynamic_namespace_is_cleaned_up() {
+    let io = create_server(2406);
+    let (mut ws, _) = connect(2406, "/tenant-1").await;
+    assert_eq!(recv(&mut ws).await, r#"42/tenant-1,["tenant","1"]"#);
+    assert!(io.of("/tenant-1").is_some());
+
+    ws.send(Message::Text("41/tenant-1,".into())).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(io.of("/tenant-1").is_none());
+
+    // The namespace is created again on the next connection
+    let (mut ws, _) = connect(2406, "/tenant-1").await;
+    assert_eq!(recv(&mut ws).await, r#"42/tenant-1,["tenant","1"]"#);
+}
@@ -0,0 +1,117 @@
+//! Tests for the extractors of the connect and message handlers.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use hyper::Server;
+use serde_json::Value;
+use socketioxide::{
+    extract::{AckSender, Bin, Data, SocketRef, TryData},
+    SocketIo,
+};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+type Ws = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+fn create_server(port: u16) -> SocketIo {
+    let (svc, io) = SocketIo::new_svc();
+
+    let addr = &SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+    let server = Server::bind(addr).serve(svc.into_make_service());
+    tokio::spawn(server);
+    io
+}
+
+/// Connect a websocket client with the given auth data and return the first socket.io packet sent by the server.
+async fn connect(port: u16, auth: &str) -> (Ws, String) {
+    let mut ws = tokio_tungstenite::connect_async(format!(
+        "ws://127.0.0.1:{port}/socket.io/?EIO=4&transport=websocket"
+    ))
+    .await
+    .unwrap()
+    .0;
+    ws.send(Message::Text(format!("40{auth}"))).await.unwrap();
+
+    recv(&mut ws).await; // engine.io open packet
+    let packet = recv(&mut ws).await;
+    (ws, packet)
+}
+
+async fn recv(ws: &mut Ws) -> String {
+    let msg = tokio::time::timeout(Duration::from_millis(500), ws.next())
+        .await
+        .expect("timeout waiting for a message")
+        .unwrap()
+        .unwrap();
+    match msg {
+        Message::Text(msg) => msg,
+        msg => panic!("unexpected message: {:?}", msg),
+    }
+}
+
+#[tokio::test]
+pub async fn connect_extractors() {
+    let io = create_server(2600);
+    io.ns(
+        "/",
+        |socket: SocketRef, TryData(auth): TryData<[u32; 1]>| async move {
+            socket.emit("auth", auth.is_ok()).ok();
+        },
+    );
+
+    let (mut ws, packet) = connect(2600, "[1]").await;
+    assert!(packet.starts_with("40{"), "{packet}");
+    assert_eq!(recv(&mut ws).await, r#"42["auth",true]"#);
+
+    let (mut ws, _) = connect(2600, r#"{"token":"abc"}"#).await;
+    assert_eq!(recv(&mut ws).await, r#"42["auth",false]"#);
+}
+
+#[tokio::test]
+pub async fn message_extractors() {
+    let io = create_server(2602);
+    io.ns("/", |socket: SocketRef| async move {
+        socket.on(
+            "number",
+            |socket: SocketRef, Data(n): Data<u32>| async move {
+                socket.emit("number", n + 1).ok();
+            },
+        );
+        socket.on(
+            "try",
+            |socket: SocketRef, TryData(data): TryData<u32>| async move {
+                socket.emit("try", data.is_ok()).ok();
+            },
+        );
+        socket.on(
+            "ack",
+            |Data(data): Data<Value>, Bin(bin), ack: AckSender| async move {
+                ack.bin(bin).send(data).ok();
+            },
+        );
+    });
+    let (mut ws, _) = connect(2602, "{}").await;
+
+    // The handler is not called if the data can't be deserialized
+    ws.send(Message::Text(r#"42["number","abc"]"#.into()))
+        .await
+        .unwrap();
+    ws.send(Message::Text(r#"42["number",1]"#.into()))
+        .await
+        .unwrap();
+    assert_eq!(recv(&mut ws).await, r#"42["number",2]"#);
+
+    ws.send(Message::Text(r#"42["try","abc"]"#.into()))
+        .await
+        .unwrap();
+    assert_eq!(recv(&mut ws).await, r#"42["try",false]"#);
+
+    ws.send(Message::Text(r#"421["ack",{"a":1}]"#.into()))
+        .await
+        .unwrap();
+    assert_eq!(recv(&mut ws).await, r#"431[{"a":1}]"#);
+}
@@ -0,0 +1,198 @@
+//! Tests for the handshake details of a socket.
+
+use std::{
+    convert::Infallible,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::{Duration, SystemTime},
+};
+
+use futures::{SinkExt, StreamExt};
+use hyper::{
+    server::conn::AddrStream,
+    service::{make_service_fn, service_fn},
+    Server,
+};
+use serde_json::{json, Value};
+use socketioxide::{extract::SocketRef, SocketIo, SocketIoBuilder};
+use tokio_tungstenite::tungstenite::{
+    client::IntoClientRequest, handshake::client::Request, Message,
+};
+use tower::Service;
+
+fn create_server(port: u16) -> SocketIo {
+    let (svc, io) = SocketIo::new_svc();
+
+    let addr = &SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+    let server = Server::bind(addr).serve(svc.into_make_service());
+    tokio::spawn(server);
+    io
+}
+
+/// Create a server that inserts the remote address of each connection in the request extensions
+fn create_server_with_addr(port: u16, builder: SocketIoBuilder) -> SocketIo {
+    let (svc, io) = builder.build_svc();
+
+    let addr = &SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+    let make_svc = make_service_fn(move |conn: &AddrStream| {
+        let remote_addr = conn.remote_addr();
+        let svc = svc.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |mut req: hyper::Request<hyper::Body>| {
+                req.extensions_mut().insert(remote_addr);
+                svc.clone().call(req)
+            }))
+        }
+    });
+    tokio::spawn(Server::bind(addr).serve(make_svc));
+    io
+}
+
+/// Connect to the server with the given request and receive the first event
+async fn connect_and_recv(req: Request) -> Value {
+    let mut ws = tokio_tungstenite::connect_async(req).await.unwrap().0;
+    ws.send(Message::Text("40".to_string())).await.unwrap();
+
+    ws.next().await; // engine.io open packet
+    ws.next().await; // socket.io connect packet
+    let packet = tokio::time::timeout(Duration::from_millis(500), ws.next())
+        .await
+        .expect("timeout waiting for an event")
+        .unwrap()
+        .unwrap()
+        .into_text()
+        .unwrap();
+    serde_json::from_str(packet.strip_prefix("42").unwrap()).unwrap()
+}
+
+fn emit_address(io: &SocketIo) {
+    io.ns("/", |socket: SocketRef| async move {
+        let address = socket.handshake().address.map(|ip| ip.to_string());
+        socket.emit("address", address).ok();
+    });
+}
+
+#[tokio::test]
+pub async fn handshake() {
+    let io = create_server(3000);
+    let start = SystemTime::now();
+    io.ns("/", move |socket: SocketRef| async move {
+        let handshake = socket.handshake();
+        let transport: &'static str = handshake.transport.into();
+        let data = json!({
+            "cookie": handshake.headers.get("cookie").unwrap().to_str().unwrap(),
+            "path": handshake.url.path(),
+            "query": handshake.query,
+            "auth": handshake.auth,
+            "xdomain": handshake.xdomain,
+            "transport": transport,
+            "issued": handshake.issued >= start,
+        });
+        socket.emit("handshake", data).ok();
+    });
+
+    let mut req =
+        "ws://127.0.0.1:3000/socket.io/?EIO=4&transport=websocket&token=abc&name=caf%C3%A9"
+            .into_client_request()
+            .unwrap();
+    req.headers_mut()
+        .insert("cookie", "session=123".parse().unwrap());
+    let mut ws = tokio_tungstenite::connect_async(req).await.unwrap().0;
+    ws.send(Message::Text(r#"40{"user":"foo"}"#.to_string()))
+        .await
+        .unwrap();
+
+    ws.next().await; // engine.io open packet
+    ws.next().await; // socket.io connect packet
+    let packet = tokio::time::timeout(Duration::from_millis(500), ws.next())
+        .await
+        .expect("timeout waiting for the handshake event")
+        .unwrap()
+        .unwrap()
+        .into_text()
+        .unwrap();
+    let data: Value = serde_json::from_str(packet.strip_prefix("42").unwrap()).unwrap();
+    assert_eq!(
+        data,
+        json!([
+            "handshake",
+            {
+                "cookie": "session=123",
+                "path": "/socket.io/",
+                "query": { "EIO": "4", "transport": "websocket", "token": "abc", "name": "café" },
+                "auth": { "user": "foo" },
+                "xdomain": false,
+                "transport": "websocket",
+                "issued": true,
+            }
+        ])
+    );
+}
+
+#[tokio::test]
+pub async fn address() {
+    let io = create_server_with_addr(3001, SocketIo::builder());
+    emit_address(&io);
+
+    let mut req = "ws://127.0.0.1:3001/socket.io/?EIO=4&transport=websocket"
+        .into_client_request()
+        .unwrap();
+    req.headers_mut()
+        .insert("x-forwarded-for", "203.0.113.7".parse().unwrap());
+    // The peer is not a trusted proxy so the forwarded address is ignored
+    assert_eq!(connect_and_recv(req).await, json!(["address", "127.0.0.1"]));
+}
+
+#[tokio::test]
+pub async fn trusted_proxy_address() {
+    let builder = SocketIo::builder().trusted_proxies([
+        IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+    ]);
+    let io = create_server_with_addr(3002, builder);
+    emit_address(&io);
+
+    let mut req = "ws://127.0.0.1:3002/socket.io/?EIO=4&transport=websocket"
+        .into_client_request()
+        .unwrap();
+    req.headers_mut().insert(
+        "x-forwarded-for",
+        "198.51.100.2, 203.0.113.7, 10.0.0.1".parse().unwrap(),
+    );
+    assert_eq!(
+        connect_and_recv(req).await,
+        json!(["address", "203.0.113.7"])
+    );
+
+    let mut req = "ws://127.0.0.1:3002/socket.io/?EIO=4&transport=websocket"
+        .into_client_request()
+        .unwrap();
+    req.headers_mut().insert(
+        "forwarded",
+        r#"for="[2001:db8:cafe::17]:4711";proto=https, for=10.0.0.1"#
+            .parse()
+            .unwrap(),
+    );
+    req.headers_mut()
+        .insert("x-forwarded-for", "203.0.113.7".parse().unwrap());
+    assert_eq!(
+        connect_and_recv(req).await,
+        json!(["address", "2001:db8:cafe::17"])
+    );
+}
+
+#[cfg(feature = "axum")]
+#[tokio::test]
+pub async fn axum_connect_info_address() {
+    let (layer, io) = SocketIo::new_layer();
+    emit_address(&io);
+    let app = axum::Router::new().layer(layer);
+    let addr = &SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 3003);
+    let server =
+        axum::Server::bind(addr).serve(app.into_make_service_with_connect_info::<SocketAddr>());
+    tokio::spawn(server);
+
+    let req = "ws://127.0.0.1:3003/socket.io/?EIO=4&transport=websocket"
+        .into_client_request()
+        .unwrap();
+    assert_eq!(connect_and_recv(req).await, json!(["address", "127.0.0.1"]));
+}
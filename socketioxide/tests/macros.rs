@@ -0,0 +1,112 @@
+//! Tests for the typed events derive macros, with the in-memory test harness.
+#![cfg(all(feature = "macros", feature = "test-utils"))]
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use socketioxide::{
+    extract::SocketRef,
+    packet::{Packet, PacketData},
+    ClientEvents, ServerEvents, SocketIo,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Message {
+    room: String,
+    text: String,
+}
+
+#[derive(ClientEvents)]
+enum ClientEvent {
+    #[socketio(ack = Vec<String>)]
+    Join(String),
+    #[socketio(rename = "new message")]
+    NewMessage(Message),
+    Move(i32, i32),
+    AskRoom(String),
+    Ping,
+}
+
+#[derive(ServerEvents)]
+enum ServerEvent {
+    #[socketio(rename = "new message")]
+    NewMessage(Message),
+    Moved(i32, i32),
+    #[socketio(ack = String)]
+    Question(String),
+    Answers(Vec<String>),
+    Pong,
+}
+
+#[tokio::test]
+pub async fn typed_events() {
+    let (server, io) = SocketIo::builder().build_test();
+    io.ns("/", |socket: SocketRef| async move {
+        socket.on_join(|socket, room, ack| async move {
+            socket.join(room).await.ok();
+            ack.send(socket.rooms().await.unwrap()).ok();
+        });
+        socket.on_new_message(|socket, msg| async move {
+            socket
+                .within(msg.room.clone())
+                .emit_new_message(msg)
+                .await
+                .ok();
+        });
+        socket.on_move(|socket, (x, y)| async move {
+            socket.emit_moved((x + 1, y + 1)).ok();
+        });
+        socket.on_ask_room(|socket, room| async move {
+            let acks = socket.to(room).emit_question("name?".into()).await.unwrap();
+            let answers = acks.map(|ack| ack.unwrap().0).collect().await;
+            socket.emit_answers(answers).ok();
+        });
+        socket.on_ping(|socket| async move {
+            socket.emit_pong().ok();
+        });
+    });
+
+    let mut client1 = server.new_client();
+    let mut client2 = server.new_client();
+    let sid1 = client1.connect("/", ()).await.unwrap();
+    client2.connect("/", ()).await.unwrap();
+
+    let ack = client1.emit_with_ack("/", "join", "room1").await.unwrap();
+    assert_eq!(
+        ack.inner,
+        PacketData::EventAck(json!([[sid1.to_string(), "room1"]]), 1)
+    );
+
+    let msg = json!({ "room": "room1", "text": "hello" });
+    client2.emit("/", "new message", msg.clone());
+    assert_eq!(
+        client1.recv().await.unwrap(),
+        Packet::event("/".into(), "new message".into(), json!([msg]))
+    );
+
+    client1.emit("/", "move", (1, 2));
+    assert_eq!(
+        client1.recv().await.unwrap(),
+        Packet::event("/".into(), "moved".into(), json!([2, 3]))
+    );
+
+    client1.emit("/", "ping", ());
+    assert_eq!(
+        client1.recv().await.unwrap(),
+        Packet::event("/".into(), "pong".into(), json!([]))
+    );
+
+    client2.emit("/", "ask_room", "room1");
+    let ack_id = match client1.recv().await.unwrap().inner {
+        PacketData::Event(event, data, Some(ack_id)) if event == "question" => {
+            assert_eq!(data, json!(["name?"]));
+            ack_id
+        }
+        packet => panic!("unexpected packet: {:?}", packet),
+    };
+    client1.ack("/", ack_id, "client1");
+    assert_eq!(
+        client2.recv().await.unwrap(),
+        Packet::event("/".into(), "answers".into(), json!([["client1"]]))
+    );
+}
@@ -0,0 +1,145 @@
+//! Tests for namespace middlewares:
+//! they are called in order before the connect handler and can refuse the connection with a `ConnectError`.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use hyper::Server;
+use serde_json::{json, Value};
+use socketioxide::{
+    adapter::RoomEvent,
+    extract::{Data, SocketRef},
+    ConnectError, SocketIo,
+};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+type Ws = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+fn create_server(port: u16) -> SocketIo {
+    let (svc, io) = SocketIo::new_svc();
+
+    let addr = &SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+    let server = Server::bind(addr).serve(svc.into_make_service());
+    tokio::spawn(server);
+    io
+}
+
+/// Connect a websocket client to the given namespace with the given auth payload
+/// and return the first socket.io packet sent by the server.
+async fn connect(port: u16, ns: &str, auth: Value) -> (Ws, String) {
+    let mut ws = tokio_tungstenite::connect_async(format!(
+        "ws://127.0.0.1:{port}/socket.io/?EIO=4&transport=websocket"
+    ))
+    .await
+    .unwrap()
+    .0;
+    let packet = match ns {
+        "/" => format!("40{auth}"),
+        ns => format!("40{ns},{auth}"),
+    };
+    ws.send(Message::Text(packet)).await.unwrap();
+
+    recv(&mut ws).await; // engine.io open packet
+    let packet = recv(&mut ws).await;
+    (ws, packet)
+}
+
+async fn recv(ws: &mut Ws) -> String {
+    let msg = tokio::time::timeout(Duration::from_millis(500), ws.next())
+        .await
+        .expect("timeout waiting for a message")
+        .unwrap()
+        .unwrap();
+    match msg {
+        Message::Text(msg) => msg,
+        msg => panic!("unexpected message: {:?}", msg),
+    }
+}
+
+#[tokio::test]
+pub async fn middleware_refuses_connection() {
+    let io = create_server(2300);
+    io.ns("/admin", |socket: SocketRef| async move {
+        socket.emit("connected", ()).ok();
+    })
+    .with_middleware(|Data(auth): Data<Value>| async move {
+        match auth["token"].as_str() {
+            Some("secret") => Ok(()),
+            _ => Err(ConnectError::new("unauthorized").with_data(json!({ "code": 401 }))),
+        }
+    });
+
+    let (_ws, packet) = connect(2300, "/admin", json!({ "token": "wrong" })).await;
+    assert_eq!(
+        packet,
+        r#"44/admin,{"message":"unauthorized","data":{"code":401}}"#
+    );
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(io.of("/admin").unwrap().sockets().await.unwrap().is_empty());
+
+    let (mut ws, packet) = connect(2300, "/admin", json!({ "token": "secret" })).await;
+    assert!(packet.starts_with("40/admin,"), "{packet}");
+    assert_eq!(recv(&mut ws).await, r#"42/admin,["connected",null]"#);
+}
+
+#[tokio::test]
+pub async fn middlewares_are_called_in_order() {
+    let io = create_server(2302);
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let (calls1, calls2, calls3) = (calls.clone(), calls.clone(), calls.clone());
+    io.ns("/", move || {
+        calls3.lock().unwrap().push("handler");
+        async move {}
+    })
+    .with_middleware(move || {
+        let calls = calls1.clone();
+        async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            calls.lock().unwrap().push("first");
+            Ok(())
+        }
+    })
+    .with_middleware(move || {
+        calls2.lock().unwrap().push("second");
+        async move { Ok(()) }
+    });
+
+    let (_ws, packet) = connect(2302, "/", json!({})).await;
+    assert!(packet.starts_with("40{"), "{packet}");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(*calls.lock().unwrap(), ["first", "second", "handler"]);
+}
+
+#[tokio::test]
+pub async fn refused_socket_leaves_its_rooms() {
+    let io = create_server(2304);
+    io.ns("/", || async move {})
+        .with_middleware(|socket: SocketRef| async move {
+            socket.join("room1").await.unwrap();
+            Err::<(), _>(ConnectError::new("unauthorized"))
+        });
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events1 = events.clone();
+    io.of("/").unwrap().on_room_event(move |event| match event {
+        RoomEvent::Create(room) | RoomEvent::Delete(room) if room == "room1" => {
+            events1.lock().unwrap().push(event.clone())
+        }
+        _ => (),
+    });
+
+    let (_ws, packet) = connect(2304, "/", json!({})).await;
+    assert_eq!(packet, r#"44{"message":"unauthorized"}"#);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            RoomEvent::Create("room1".into()),
+            RoomEvent::Delete("room1".into())
+        ]
+    );
+}
@@ -0,0 +1,144 @@
+//! Tests for the msgpack parser, every socket.io packet is sent as a single binary message.
+#![cfg(feature = "msgpack")]
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use hyper::Server;
+use rmpv::Value as Msg;
+use serde_json::Value;
+use socketioxide::{
+    extract::{AckSender, Bin, Data, SocketRef},
+    parser::MsgPackParser,
+    SocketIo,
+};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+type Ws = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+fn create_server(port: u16) -> SocketIo {
+    let (svc, io) = SocketIo::builder().with_parser(MsgPackParser).build_svc();
+
+    let addr = &SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+    let server = Server::bind(addr).serve(svc.into_make_service());
+    tokio::spawn(server);
+
+    io.ns("/", |socket: SocketRef| async move {
+        socket.emit("welcome", "hello").ok();
+        socket.on(
+            "echo",
+            |Data(data): Data<Value>, Bin(bin), ack: AckSender| async move {
+                ack.bin(bin).send(data).ok();
+            },
+        );
+    });
+    io
+}
+
+fn encode(packet: Vec<(&str, Msg)>) -> Message {
+    let packet = packet.into_iter().map(|(k, v)| (k.into(), v)).collect();
+    let mut buf = Vec::new();
+    rmpv::encode::write_value(&mut buf, &Msg::Map(packet)).unwrap();
+    Message::Binary(buf)
+}
+
+async fn connect(port: u16) -> Ws {
+    let mut ws = tokio_tungstenite::connect_async(format!(
+        "ws://127.0.0.1:{port}/socket.io/?EIO=4&transport=websocket"
+    ))
+    .await
+    .unwrap()
+    .0;
+    ws.next().await.unwrap().unwrap(); // engine.io open packet
+    ws.send(encode(vec![("type", 0.into()), ("nsp", "/".into())]))
+        .await
+        .unwrap();
+    ws
+}
+
+async fn recv(ws: &mut Ws) -> Msg {
+    let msg = tokio::time::timeout(Duration::from_millis(500), ws.next())
+        .await
+        .expect("timeout waiting for a message")
+        .unwrap()
+        .unwrap();
+    match msg {
+        Message::Binary(msg) => rmpv::decode::read_value(&mut &msg[..]).unwrap(),
+        msg => panic!("unexpected message: {:?}", msg),
+    }
+}
+
+#[tokio::test]
+pub async fn msgpack_connect_and_emit() {
+    create_server(2800);
+    let mut ws = connect(2800).await;
+
+    let packet = recv(&mut ws).await;
+    assert_eq!(packet["type"], Msg::from(0));
+    assert_eq!(packet["nsp"], Msg::from("/"));
+    assert!(packet["data"]["sid"].is_str());
+
+    let packet = recv(&mut ws).await;
+    assert_eq!(packet["type"], Msg::from(2));
+    assert_eq!(
+        packet["data"],
+        Msg::Array(vec!["welcome".into(), "hello".into()])
+    );
+}
+
+#[tokio::test]
+pub async fn msgpack_binary_ack() {
+    create_server(2802);
+    let mut ws = connect(2802).await;
+    recv(&mut ws).await; // connect packet
+    recv(&mut ws).await; // welcome event
+
+    let data = Msg::Array(vec![
+        "echo".into(),
+        Msg::Map(vec![("foo".into(), "bar".into())]),
+        Msg::Binary(vec![1, 2, 3]),
+    ]);
+    ws.send(encode(vec![
+        ("type", 2.into()),
+        ("nsp", "/".into()),
+        ("data", data),
+        ("id", 7.into()),
+    ]))
+    .await
+    .unwrap();
+
+    // The binary payload is sent back embedded in the ack packet
+    let packet = recv(&mut ws).await;
+    assert_eq!(packet["type"], Msg::from(3));
+    assert_eq!(packet["id"], Msg::from(7));
+    assert_eq!(
+        packet["data"],
+        Msg::Array(vec![
+            Msg::Map(vec![("foo".into(), "bar".into())]),
+            Msg::Binary(vec![1, 2, 3]),
+        ])
+    );
+}
+
+#[tokio::test]
+pub async fn msgpack_rejects_text_messages() {
+    create_server(2804);
+    let mut ws = connect(2804).await;
+    recv(&mut ws).await; // connect packet
+    recv(&mut ws).await; // welcome event
+
+    ws.send(Message::Text(r#"42["echo"]"#.into()))
+        .await
+        .unwrap();
+    let msg = tokio::time::timeout(Duration::from_millis(500), ws.next())
+        .await
+        .expect("timeout waiting for the socket to be closed");
+    assert!(matches!(
+        msg,
+        None | Some(Ok(Message::Close(_))) | Some(Err(_))
+    ));
+}
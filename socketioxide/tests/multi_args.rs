@@ -0,0 +1,131 @@
+//! Tests for the events and acks with multiple arguments.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use hyper::Server;
+use serde_json::Value;
+use socketioxide::{
+    extract::{AckSender, Data, SocketRef},
+    SocketIo,
+};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+type Ws = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+fn create_server(port: u16) -> SocketIo {
+    let (svc, io) = SocketIo::new_svc();
+
+    let addr = &SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+    let server = Server::bind(addr).serve(svc.into_make_service());
+    tokio::spawn(server);
+    io
+}
+
+async fn connect(port: u16) -> Ws {
+    let mut ws = tokio_tungstenite::connect_async(format!(
+        "ws://127.0.0.1:{port}/socket.io/?EIO=4&transport=websocket"
+    ))
+    .await
+    .unwrap()
+    .0;
+    ws.send(Message::Text("40{}".into())).await.unwrap();
+
+    recv(&mut ws).await; // engine.io open packet
+    recv(&mut ws).await; // socket.io connect packet
+    ws
+}
+
+async fn recv(ws: &mut Ws) -> String {
+    let msg = tokio::time::timeout(Duration::from_millis(500), ws.next())
+        .await
+        .expect("timeout waiting for a message")
+        .unwrap()
+        .unwrap();
+    match msg {
+        Message::Text(msg) => msg,
+        msg => panic!("unexpected message: {:?}", msg),
+    }
+}
+
+async fn send(ws: &mut Ws, msg: &str) {
+    ws.send(Message::Text(msg.into())).await.unwrap();
+}
+
+#[tokio::test]
+pub async fn multi_args_events() {
+    let io = create_server(3100);
+    io.ns("/", |socket: SocketRef| async move {
+        socket.on(
+            "move",
+            |socket: SocketRef, Data((x, y, meta)): Data<(i32, i32, Value)>| async move {
+                socket.emit("move", (x + 1, y + 1, meta)).ok();
+            },
+        );
+        socket.on(
+            "count",
+            |socket: SocketRef, Data(args): Data<Vec<Value>>| async move {
+                socket.emit("count", args.len()).ok();
+            },
+        );
+        socket.on(
+            "single",
+            |socket: SocketRef, Data(data): Data<Value>| async move {
+                // A vec wrapped in a tuple is sent as a single argument
+                socket.emit("single", (vec![data],)).ok();
+            },
+        );
+    });
+    let mut ws = connect(3100).await;
+
+    send(&mut ws, r#"42["move",1,2,{"fast":true}]"#).await;
+    assert_eq!(recv(&mut ws).await, r#"42["move",2,3,{"fast":true}]"#);
+
+    send(&mut ws, r#"42["count","a","b","c"]"#).await;
+    assert_eq!(recv(&mut ws).await, r#"42["count",3]"#);
+
+    // A single argument that is not an array is still extracted as a vec of arguments
+    send(&mut ws, r#"42["count","a"]"#).await;
+    assert_eq!(recv(&mut ws).await, r#"42["count",1]"#);
+
+    send(&mut ws, r#"42["count"]"#).await;
+    assert_eq!(recv(&mut ws).await, r#"42["count",0]"#);
+
+    send(&mut ws, r#"42["single","a"]"#).await;
+    assert_eq!(recv(&mut ws).await, r#"42["single",["a"]]"#);
+
+    // An event without arguments is extracted as an empty array
+    send(&mut ws, r#"42["single"]"#).await;
+    assert_eq!(recv(&mut ws).await, r#"42["single",[[]]]"#);
+}
+
+#[tokio::test]
+pub async fn multi_args_acks() {
+    let io = create_server(3102);
+    io.ns("/", |socket: SocketRef| async move {
+        socket.on(
+            "swap",
+            |Data((a, b)): Data<(String, String)>, ack: AckSender| async move {
+                ack.send((b, a)).ok();
+            },
+        );
+        socket.on("ping", |socket: SocketRef| async move {
+            let res = socket.emit_with_ack::<(String, u32)>("ping", ()).await;
+            let (data, _) = res.unwrap();
+            socket.emit("pong", data).ok();
+        });
+    });
+    let mut ws = connect(3102).await;
+
+    send(&mut ws, r#"421["swap","a","b"]"#).await;
+    assert_eq!(recv(&mut ws).await, r#"431["b","a"]"#);
+
+    send(&mut ws, r#"42["ping"]"#).await;
+    assert_eq!(recv(&mut ws).await, r#"421["ping",null]"#);
+    send(&mut ws, r#"431["foo",2]"#).await;
+    assert_eq!(recv(&mut ws).await, r#"42["pong","foo",2]"#);
+}
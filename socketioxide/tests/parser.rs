@@ -0,0 +1,118 @@
+//! Tests for a custom parser set with `SocketIoBuilder::with_parser`.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use hyper::Server;
+use serde_json::Value;
+use socketioxide::{
+    extract::{AckSender, Bin, Data, SocketRef},
+    packet::Packet,
+    parser::{DefaultParser, EncodedPacket, Parser, ParserState},
+    ParserError, SocketIo,
+};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+type Ws = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// A parser counting the packets it encodes and decodes with the default parser
+#[derive(Debug, Clone, Default)]
+struct CountingParser {
+    encoded: Arc<AtomicUsize>,
+    decoded: Arc<AtomicUsize>,
+}
+
+impl Parser for CountingParser {
+    fn encode(&self, packet: Packet) -> Result<EncodedPacket, ParserError> {
+        self.encoded.fetch_add(1, Ordering::SeqCst);
+        DefaultParser.encode(packet)
+    }
+
+    fn decode_str(&self, state: &ParserState, data: String) -> Result<Option<Packet>, ParserError> {
+        let packet = DefaultParser.decode_str(state, data)?;
+        if packet.is_some() {
+            self.decoded.fetch_add(1, Ordering::SeqCst);
+        }
+        Ok(packet)
+    }
+
+    fn decode_bin(
+        &self,
+        state: &ParserState,
+        data: Vec<u8>,
+    ) -> Result<Option<Packet>, ParserError> {
+        let packet = DefaultParser.decode_bin(state, data)?;
+        if packet.is_some() {
+            self.decoded.fetch_add(1, Ordering::SeqCst);
+        }
+        Ok(packet)
+    }
+}
+
+fn create_server(port: u16, parser: CountingParser) -> SocketIo {
+    let (svc, io) = SocketIo::builder().with_parser(parser).build_svc();
+
+    let addr = &SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+    let server = Server::bind(addr).serve(svc.into_make_service());
+    tokio::spawn(server);
+
+    io.ns("/", |socket: SocketRef| async move {
+        socket.on(
+            "echo",
+            |Data(data): Data<Value>, Bin(bin), ack: AckSender| async move {
+                ack.bin(bin).send(data).ok();
+            },
+        );
+    });
+    io
+}
+
+async fn recv(ws: &mut Ws) -> Message {
+    tokio::time::timeout(Duration::from_millis(500), ws.next())
+        .await
+        .expect("timeout waiting for a message")
+        .unwrap()
+        .unwrap()
+}
+
+#[tokio::test]
+pub async fn custom_parser() {
+    let parser = CountingParser::default();
+    create_server(2900, parser.clone());
+
+    let mut ws = tokio_tungstenite::connect_async(
+        "ws://127.0.0.1:2900/socket.io/?EIO=4&transport=websocket",
+    )
+    .await
+    .unwrap()
+    .0;
+    recv(&mut ws).await; // engine.io open packet
+    ws.send(Message::Text("40{}".into())).await.unwrap();
+    recv(&mut ws).await; // socket.io connect packet
+
+    ws.send(Message::Text(
+        r#"451-1["echo","foo",{"_placeholder":true,"num":0}]"#.into(),
+    ))
+    .await
+    .unwrap();
+    ws.send(Message::Binary(vec![1, 2, 3])).await.unwrap();
+
+    assert_eq!(
+        recv(&mut ws).await,
+        Message::Text(r#"461-1["foo",{"_placeholder":true,"num":0}]"#.into())
+    );
+    assert_eq!(recv(&mut ws).await, Message::Binary(vec![1, 2, 3]));
+
+    // The connect and binary event packets are decoded by the custom parser,
+    // the connect and binary ack packets are encoded by it
+    assert_eq!(parser.decoded.load(Ordering::SeqCst), 2);
+    assert_eq!(parser.encoded.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,103 @@
+//! Tests for the compression of the messages with the permessage-deflate websocket extension,
+//! with a raw websocket client that only checks which frames are compressed.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use hyper::Server;
+use socketioxide::{extract::SocketRef, DeflateConfig, SocketIo};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+/// Read a frame sent by the server, returns its RSV1 bit and its payload
+async fn read_frame(stream: &mut TcpStream) -> (bool, Vec<u8>) {
+    let (first, second) = (
+        stream.read_u8().await.unwrap(),
+        stream.read_u8().await.unwrap(),
+    );
+    let len = match second & 0x7f {
+        126 => stream.read_u16().await.unwrap() as usize,
+        127 => stream.read_u64().await.unwrap() as usize,
+        len => len as usize,
+    };
+    let mut payload = vec![0; len];
+    stream.read_exact(&mut payload).await.unwrap();
+    (first & 0x40 != 0, payload)
+}
+
+/// Write a masked text frame with a zero mask key
+async fn write_text(stream: &mut TcpStream, text: &str) {
+    let mut frame = vec![0x81, 0x80 | text.len() as u8, 0, 0, 0, 0];
+    frame.extend(text.as_bytes());
+    stream.write_all(&frame).await.unwrap();
+}
+
+#[tokio::test]
+pub async fn compress_flag() {
+    let (svc, io) = SocketIo::builder()
+        .per_message_deflate(DeflateConfig {
+            threshold: 100,
+            ..Default::default()
+        })
+        .build_svc();
+    let addr = &SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 3300);
+    tokio::spawn(Server::bind(addr).serve(svc.into_make_service()));
+
+    io.ns("/", |socket: SocketRef| async move {
+        let data = "a".repeat(500);
+        socket.emit("compressed", &data).unwrap();
+        socket.compress(false).emit("plain", &data).await.unwrap();
+        socket.join("room1").await.unwrap();
+        socket
+            .within("room1")
+            .compress(false)
+            .emit("plain", &data)
+            .await
+            .unwrap();
+        socket
+            .compress(false)
+            .compress(true)
+            .emit("compressed", &data)
+            .await
+            .unwrap();
+    });
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(
+            b"GET /socket.io/?EIO=4&transport=websocket HTTP/1.1\r\n\
+            Host: 127.0.0.1:3300\r\n\
+            Upgrade: websocket\r\n\
+            Connection: Upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\
+            Sec-WebSocket-Extensions: permessage-deflate\r\n\r\n",
+        )
+        .await
+        .unwrap();
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(stream.read_u8().await.unwrap());
+    }
+    let head = String::from_utf8(head).unwrap().to_lowercase();
+    assert!(head.contains("\r\nsec-websocket-extensions: permessage-deflate\r\n"));
+
+    let (rsv1, open) = read_frame(&mut stream).await;
+    assert!(!rsv1 && open.starts_with(b"0{"));
+    write_text(&mut stream, "40{}").await;
+    let (rsv1, connect) = read_frame(&mut stream).await;
+    assert!(!rsv1 && connect.starts_with(b"40{"));
+
+    let mut frames = Vec::new();
+    for _ in 0..4 {
+        frames.push(read_frame(&mut stream).await);
+    }
+    let compressed: Vec<_> = frames.iter().map(|(rsv1, _)| *rsv1).collect();
+    assert_eq!(compressed, [true, false, false, true]);
+    let data = "a".repeat(500);
+    assert_eq!(
+        frames[1].1,
+        format!("42[\"plain\",\"{data}\"]").into_bytes()
+    );
+}
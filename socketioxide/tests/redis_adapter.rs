@@ -0,0 +1,185 @@
+//! Tests for the redis adapter, two servers are started and share the same redis instance.
+//! A redis server should be listening on the `REDIS_URL` env variable (defaults to `redis://127.0.0.1:6379`).
+#![cfg(feature = "redis")]
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use hyper::Server;
+use socketioxide::{
+    adapter::{RedisAdapter, RedisAdapterConfig},
+    extract::{Data, SocketRef},
+    SocketIo,
+};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+type Ws = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+fn create_redis_server(port: u16, prefix: &str) -> SocketIo<RedisAdapter> {
+    let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".into());
+    let mut config = RedisAdapterConfig::new(redis::Client::open(url).unwrap());
+    config.prefix = prefix.to_string();
+
+    let (svc, io) = SocketIo::builder()
+        .redis(config)
+        .build_svc_with_adapter::<RedisAdapter>();
+
+    let addr = &SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+    let server = Server::bind(addr).serve(svc.into_make_service());
+    tokio::spawn(server);
+
+    io.ns("/", |socket: SocketRef<RedisAdapter>| async move {
+        socket.on(
+            "join",
+            |socket: SocketRef<RedisAdapter>, Data(room): Data<String>| async move {
+                socket.join(room.clone()).await.unwrap();
+                socket.emit("joined", room).ok();
+            },
+        );
+    });
+    io
+}
+
+/// Start two servers and wait for their adapters to subscribe to the redis channels.
+async fn create_cluster(
+    port: u16,
+    prefix: &str,
+) -> (SocketIo<RedisAdapter>, SocketIo<RedisAdapter>) {
+    let io1 = create_redis_server(port, prefix);
+    let io2 = create_redis_server(port + 1, prefix);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    (io1, io2)
+}
+
+/// Connect a websocket client and skip the handshake packets.
+async fn connect(port: u16) -> Ws {
+    let mut ws = tokio_tungstenite::connect_async(format!(
+        "ws://127.0.0.1:{port}/socket.io/?EIO=4&transport=websocket"
+    ))
+    .await
+    .unwrap()
+    .0;
+    ws.send(Message::Text("40{}".to_string())).await.unwrap();
+
+    recv(&mut ws).await; // engine.io open packet
+    recv(&mut ws).await; // socket.io connect packet
+    ws
+}
+
+async fn recv(ws: &mut Ws) -> String {
+    let msg = tokio::time::timeout(Duration::from_millis(500), ws.next())
+        .await
+        .expect("timeout waiting for a message")
+        .unwrap()
+        .unwrap();
+    match msg {
+        Message::Text(msg) => msg,
+        msg => panic!("unexpected message: {:?}", msg),
+    }
+}
+
+#[tokio::test]
+pub async fn broadcast_to_remote_server() {
+    let (_io1, io2) = create_cluster(2100, "broadcast").await;
+    let mut ws = connect(2100).await;
+
+    io2.emit("test", "hello").await.unwrap();
+    assert_eq!(recv(&mut ws).await, r#"42["test","hello"]"#);
+}
+
+#[tokio::test]
+pub async fn broadcast_to_remote_room() {
+    let (_io1, io2) = create_cluster(2102, "room").await;
+    let mut ws1 = connect(2102).await;
+    let mut ws2 = connect(2102).await;
+
+    ws1.send(Message::Text(r#"42["join","room1"]"#.into()))
+        .await
+        .unwrap();
+    assert_eq!(recv(&mut ws1).await, r#"42["joined","room1"]"#);
+
+    io2.to("room1").emit("test", "hello").await.unwrap();
+    assert_eq!(recv(&mut ws1).await, r#"42["test","hello"]"#);
+    let res = tokio::time::timeout(Duration::from_millis(200), ws2.next()).await;
+    assert!(
+        res.is_err(),
+        "socket outside of the room received a message"
+    );
+}
+
+#[tokio::test]
+pub async fn local_broadcast_is_not_published() {
+    let (_io1, io2) = create_cluster(2104, "local").await;
+    let mut ws = connect(2104).await;
+
+    io2.local().emit("test", "hello").await.unwrap();
+    let res = tokio::time::timeout(Duration::from_millis(200), ws.next()).await;
+    assert!(
+        res.is_err(),
+        "local broadcast was received by a remote socket"
+    );
+}
+
+#[tokio::test]
+pub async fn broadcast_with_ack_to_remote_server() {
+    let (_io1, io2) = create_cluster(2106, "ack").await;
+    let mut ws = connect(2106).await;
+
+    let acks = tokio::spawn(async move {
+        io2.timeout(Duration::from_millis(500))
+            .emit_with_ack::<[String; 1]>("test", "hello")
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await
+    });
+    assert_eq!(recv(&mut ws).await, r#"421["test","hello"]"#);
+    ws.send(Message::Text(r#"431["world"]"#.into()))
+        .await
+        .unwrap();
+
+    let acks = acks.await.unwrap();
+    assert_eq!(acks.len(), 1);
+    let (data, bin) = acks.into_iter().next().unwrap().unwrap();
+    assert_eq!(data, ["world".to_string()]);
+    assert!(bin.is_empty());
+}
+
+#[tokio::test]
+pub async fn server_side_emit() {
+    let (io1, io2) = create_cluster(2108, "server-side").await;
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    io1.on_server_event("invalidate", move |key: String, _| {
+        let tx = tx.clone();
+        async move {
+            tx.send(key).unwrap();
+        }
+    });
+
+    io2.server_side_emit("invalidate", "user:42").await.unwrap();
+    let key = tokio::time::timeout(Duration::from_millis(500), rx.recv())
+        .await
+        .unwrap();
+    assert_eq!(key.unwrap(), "user:42");
+}
+
+#[tokio::test]
+pub async fn server_side_emit_with_ack() {
+    let (io1, io2) = create_cluster(2110, "server-side-ack").await;
+    io1.on_server_event("jobs", |count: usize, ack| async move {
+        ack.send(count * 2).unwrap();
+    });
+
+    let acks = io2
+        .server_side_emit_with_ack::<usize>("jobs", 21)
+        .await
+        .unwrap()
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(acks.len(), 1);
+    assert_eq!(*acks[0].as_ref().unwrap(), 42);
+}
@@ -0,0 +1,168 @@
+//! Tests for the rooms of the sockets: their private room named after their id and the room events,
+//! with the in-memory test harness.
+#![cfg(feature = "test-utils")]
+
+use std::{str::FromStr, time::Duration};
+
+use serde_json::json;
+use socketioxide::{
+    adapter::{LocalAdapter, RoomEvent},
+    extract::{AckSender, Data, SocketRef},
+    packet::{Packet, PacketData},
+    testing::TestServer,
+    Sid, SocketIo, SocketIoBuilder,
+};
+use tokio::sync::mpsc;
+
+fn rooms_io(builder: SocketIoBuilder) -> (TestServer<LocalAdapter>, SocketIo) {
+    let (server, io) = builder.build_test();
+    io.ns("/", |socket: SocketRef| async move {
+        socket.on(
+            "join",
+            |socket: SocketRef, Data(room): Data<String>| async move {
+                socket.join(room).await.ok();
+            },
+        );
+        socket.on("leave_all", |socket: SocketRef| async move {
+            socket.leave_all().await.ok();
+        });
+        socket.on("rooms", |socket: SocketRef, ack: AckSender| async move {
+            ack.send((socket.rooms().await.unwrap(),)).ok();
+        });
+        socket.on(
+            "private",
+            |socket: SocketRef, Data(sid): Data<String>| async move {
+                let sid = Sid::from_str(&sid).unwrap();
+                socket.within(sid).emit("private", "hello").await.ok();
+            },
+        );
+    });
+    (server, io)
+}
+
+#[tokio::test]
+pub async fn emit_to_sid_room() {
+    let (server, _io) = rooms_io(SocketIo::builder());
+    let mut client1 = server.new_client();
+    let mut client2 = server.new_client();
+    client1.connect("/", ()).await.unwrap();
+    let sid2 = client2.connect("/", ()).await.unwrap();
+
+    client1.emit("/", "private", sid2.to_string());
+    assert_eq!(
+        client2.recv().await.unwrap(),
+        Packet::event("/".into(), "private".into(), json!(["hello"]))
+    );
+    let res = tokio::time::timeout(Duration::from_millis(50), client1.recv()).await;
+    assert!(
+        res.is_err(),
+        "the private message was sent to another socket"
+    );
+}
+
+#[tokio::test]
+pub async fn sid_room_in_rooms() {
+    let (server, _io) = rooms_io(SocketIo::builder());
+    let mut client = server.new_client();
+    let sid = client.connect("/", ()).await.unwrap();
+
+    client.emit("/", "join", "room1");
+    let ack = client.emit_with_ack("/", "rooms", ()).await.unwrap();
+    assert_eq!(
+        ack.inner,
+        PacketData::EventAck(json!([[sid.to_string(), "room1"]]), 1)
+    );
+
+    // The private room is kept when leaving all the rooms
+    client.emit("/", "leave_all", ());
+    let ack = client.emit_with_ack("/", "rooms", ()).await.unwrap();
+    assert_eq!(
+        ack.inner,
+        PacketData::EventAck(json!([[sid.to_string()]]), 2)
+    );
+}
+
+#[tokio::test]
+pub async fn exclude_sid_room() {
+    let (server, _io) = rooms_io(SocketIo::builder().exclude_sid_room(true));
+    let mut client1 = server.new_client();
+    let mut client2 = server.new_client();
+    client1.connect("/", ()).await.unwrap();
+    let sid2 = client2.connect("/", ()).await.unwrap();
+
+    client2.emit("/", "join", "room1");
+    let ack = client2.emit_with_ack("/", "rooms", ()).await.unwrap();
+    assert_eq!(ack.inner, PacketData::EventAck(json!([["room1"]]), 1));
+
+    // The socket is still in its private room
+    client1.emit("/", "private", sid2.to_string());
+    assert_eq!(
+        client2.recv().await.unwrap(),
+        Packet::event("/".into(), "private".into(), json!(["hello"]))
+    );
+}
+
+#[tokio::test]
+pub async fn room_events() {
+    let (server, io) = rooms_io(SocketIo::builder());
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    io.of("/").unwrap().on_room_event(move |event| {
+        tx.send(event.clone()).unwrap();
+    });
+    let mut recv_events = |n: usize| {
+        let mut events = Vec::new();
+        for _ in 0..n {
+            events.push(rx.try_recv().expect("missing room event"));
+        }
+        assert!(rx.try_recv().is_err(), "unexpected room event");
+        events
+    };
+
+    let mut client = server.new_client();
+    let sid = client.connect("/", ()).await.unwrap();
+    let sid_room = sid.to_string();
+    assert_eq!(
+        recv_events(2),
+        [
+            RoomEvent::Create(sid_room.clone()),
+            RoomEvent::Join(sid_room.clone(), sid)
+        ]
+    );
+
+    client.emit("/", "join", "room1");
+    client.emit("/", "join", "room1");
+    client.emit_with_ack("/", "rooms", ()).await.unwrap();
+    assert_eq!(
+        recv_events(2),
+        [
+            RoomEvent::Create("room1".into()),
+            RoomEvent::Join("room1".into(), sid)
+        ]
+    );
+
+    client.emit("/", "leave_all", ());
+    client.emit("/", "join", "room2");
+    client.emit_with_ack("/", "rooms", ()).await.unwrap();
+    assert_eq!(
+        recv_events(4),
+        [
+            RoomEvent::Leave("room1".into(), sid),
+            RoomEvent::Delete("room1".into()),
+            RoomEvent::Create("room2".into()),
+            RoomEvent::Join("room2".into(), sid)
+        ]
+    );
+
+    client.disconnect("/");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let events = recv_events(4);
+    for room in [sid_room, "room2".into()] {
+        let leave = events
+            .iter()
+            .position(|e| *e == RoomEvent::Leave(room.clone(), sid));
+        let delete = events
+            .iter()
+            .position(|e| *e == RoomEvent::Delete(room.clone()));
+        assert!(leave.unwrap() < delete.unwrap());
+    }
+}
@@ -0,0 +1,102 @@
+//! Tests for the application state added with `SocketIoBuilder::with_state`.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use hyper::Server;
+use socketioxide::{
+    extract::{SocketRef, State},
+    SocketIo,
+};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+type Ws = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+#[derive(Clone, Default)]
+struct Counter(Arc<AtomicUsize>);
+
+fn create_server(port: u16, counter: Counter) -> SocketIo {
+    let (svc, io) = SocketIo::builder()
+        .with_state(counter)
+        .with_state("state")
+        .build_svc();
+
+    let addr = &SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+    let server = Server::bind(addr).serve(svc.into_make_service());
+    tokio::spawn(server);
+    io
+}
+
+async fn on_connect(socket: SocketRef, State(counter): State<Counter>) {
+    counter.0.fetch_add(1, Ordering::SeqCst);
+    socket.on("name", on_name);
+    socket.on("missing", |socket: SocketRef, _: State<u32>| async move {
+        socket.emit("missing", ()).ok();
+    });
+    socket.on_disconnect(|socket, _| async move {
+        let counter = socket.state::<Counter>().unwrap();
+        counter.0.fetch_sub(1, Ordering::SeqCst);
+    });
+}
+
+async fn on_name(socket: SocketRef, State(name): State<&'static str>) {
+    socket.emit("name", name).ok();
+}
+
+async fn connect(port: u16) -> Ws {
+    let mut ws = tokio_tungstenite::connect_async(format!(
+        "ws://127.0.0.1:{port}/socket.io/?EIO=4&transport=websocket"
+    ))
+    .await
+    .unwrap()
+    .0;
+    ws.send(Message::Text("40{}".to_string())).await.unwrap();
+
+    recv(&mut ws).await; // engine.io open packet
+    recv(&mut ws).await; // socket.io connect packet
+    ws
+}
+
+async fn recv(ws: &mut Ws) -> String {
+    let msg = tokio::time::timeout(Duration::from_millis(500), ws.next())
+        .await
+        .expect("timeout waiting for a message")
+        .unwrap()
+        .unwrap();
+    match msg {
+        Message::Text(msg) => msg,
+        msg => panic!("unexpected message: {:?}", msg),
+    }
+}
+
+#[tokio::test]
+pub async fn state_in_handlers() {
+    let counter = Counter::default();
+    let io = create_server(2700, counter.clone());
+    io.ns("/", on_connect);
+
+    let mut ws = connect(2700).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+
+    // A handler is not called if the state does not exist
+    ws.send(Message::Text(r#"42["missing"]"#.into()))
+        .await
+        .unwrap();
+    ws.send(Message::Text(r#"42["name"]"#.into()))
+        .await
+        .unwrap();
+    assert_eq!(recv(&mut ws).await, r#"42["name","state"]"#);
+
+    ws.close(None).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+}
@@ -0,0 +1,184 @@
+//! Tests for connection state recovery:
+//! a client that reconnects with the private id of its previous session recovers its id, rooms and missed packets.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use hyper::Server;
+use serde_json::{json, Value};
+use socketioxide::{extract::SocketRef, SocketIo};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+type Ws = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+fn create_server(port: u16, max_disconnection_duration: Duration) -> SocketIo {
+    let (svc, io) = SocketIo::builder()
+        .max_disconnection_duration(max_disconnection_duration)
+        .build_svc();
+
+    let addr = &SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port);
+    let server = Server::bind(addr).serve(svc.into_make_service());
+    tokio::spawn(server);
+
+    io.ns("/", |socket: SocketRef| async move {
+        if !socket.recovered() {
+            socket.join("room1").await.unwrap();
+        }
+        socket.on("private", |socket: SocketRef| async move {
+            socket.bin(vec![vec![1]]).emit("private", ()).await.unwrap();
+        });
+        socket.emit("recovered", socket.recovered()).ok();
+    });
+    io
+}
+
+/// Connect a websocket client to the root namespace with the given auth payload
+/// and return the payload of the connect packet sent by the server.
+async fn connect(port: u16, auth: Value) -> (Ws, Value) {
+    let mut ws = tokio_tungstenite::connect_async(format!(
+        "ws://127.0.0.1:{port}/socket.io/?EIO=4&transport=websocket"
+    ))
+    .await
+    .unwrap()
+    .0;
+    ws.send(Message::Text(format!("40{auth}"))).await.unwrap();
+
+    recv(&mut ws).await; // engine.io open packet
+    let connect = recv(&mut ws).await;
+    let connect = connect.strip_prefix("40").expect("connect packet expected");
+    (ws, serde_json::from_str(connect).unwrap())
+}
+
+/// Receive an event and split the offset appended to its arguments.
+async fn recv_event(ws: &mut Ws) -> (String, String) {
+    let msg = recv(ws).await;
+    let mut args: Vec<Value> = serde_json::from_str(msg.strip_prefix("42").unwrap()).unwrap();
+    let offset = args.pop().unwrap().as_str().unwrap().to_string();
+    (serde_json::to_string(&args).unwrap(), offset)
+}
+
+async fn recv(ws: &mut Ws) -> String {
+    let msg = tokio::time::timeout(Duration::from_millis(500), ws.next())
+        .await
+        .expect("timeout waiting for a message")
+        .unwrap()
+        .unwrap();
+    match msg {
+        Message::Text(msg) => msg,
+        msg => panic!("unexpected message: {:?}", msg),
+    }
+}
+
+#[tokio::test]
+pub async fn recover_session() {
+    let io = create_server(2200, Duration::from_secs(10));
+    let (mut ws, session) = connect(2200, json!({})).await;
+    assert!(session["pid"].is_string());
+    assert_eq!(recv(&mut ws).await, r#"42["recovered",false]"#);
+
+    io.to("room1").emit("msg", "received").await.unwrap();
+    let (event, offset) = recv_event(&mut ws).await;
+    assert_eq!(event, r#"["msg","received"]"#);
+    // Sent before the disconnection is detected, but never read by the client
+    io.to("room1").emit("msg", "unread").await.unwrap();
+
+    ws.close(None).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    io.to("room1").emit("msg", "missed").await.unwrap();
+    io.to("room2").emit("msg", "other room").await.unwrap();
+
+    let auth = json!({ "pid": session["pid"], "offset": offset });
+    let (mut ws, recovered) = connect(2200, auth).await;
+    assert_eq!(recovered, session);
+    assert_eq!(recv_event(&mut ws).await.0, r#"["msg","unread"]"#);
+    assert_eq!(recv_event(&mut ws).await.0, r#"["msg","missed"]"#);
+    assert_eq!(recv(&mut ws).await, r#"42["recovered",true]"#);
+
+    let sockets = io.within("room1").sockets().await.unwrap();
+    assert_eq!(sockets.len(), 1);
+    assert_eq!(sockets[0].id.to_string(), session["sid"].as_str().unwrap());
+
+    // The packets of the new connection are routed to the recovered socket
+    ws.send(Message::Text("41".into())).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(io.sockets().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+pub async fn expired_session() {
+    let _io = create_server(2202, Duration::from_millis(50));
+    let (mut ws, session) = connect(2202, json!({})).await;
+    assert_eq!(recv(&mut ws).await, r#"42["recovered",false]"#);
+
+    ws.close(None).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let (mut ws, connect) = connect(2202, json!({ "pid": session["pid"] })).await;
+    assert_ne!(connect["sid"], session["sid"]);
+    assert_eq!(recv(&mut ws).await, r#"42["recovered",false]"#);
+}
+
+#[tokio::test]
+pub async fn client_disconnect_is_not_recovered() {
+    let _io = create_server(2204, Duration::from_secs(10));
+    let (mut ws, session) = connect(2204, json!({})).await;
+    assert_eq!(recv(&mut ws).await, r#"42["recovered",false]"#);
+
+    ws.send(Message::Text("41".into())).await.unwrap();
+    ws.close(None).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let (mut ws, connect) = connect(2204, json!({ "pid": session["pid"] })).await;
+    assert_ne!(connect["sid"], session["sid"]);
+    assert_eq!(recv(&mut ws).await, r#"42["recovered",false]"#);
+}
+
+#[tokio::test]
+pub async fn unknown_offset_is_not_recovered() {
+    let io = create_server(2206, Duration::from_secs(10));
+    let (mut ws, session) = connect(2206, json!({})).await;
+    assert_eq!(recv(&mut ws).await, r#"42["recovered",false]"#);
+
+    ws.close(None).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    io.to("room1").emit("msg", "missed").await.unwrap();
+
+    let auth = json!({ "pid": session["pid"], "offset": "unknown" });
+    let (mut ws, connect) = connect(2206, auth).await;
+    assert_ne!(connect["sid"], session["sid"]);
+    assert_eq!(recv(&mut ws).await, r#"42["recovered",false]"#);
+}
+
+#[tokio::test]
+pub async fn private_packet_is_not_replayed_to_other_sessions() {
+    let io = create_server(2208, Duration::from_secs(10));
+    let (mut ws1, _) = connect(2208, json!({})).await;
+    assert_eq!(recv(&mut ws1).await, r#"42["recovered",false]"#);
+    let (mut ws2, session) = connect(2208, json!({})).await;
+    assert_eq!(recv(&mut ws2).await, r#"42["recovered",false]"#);
+
+    io.to("room1").emit("msg", "received").await.unwrap();
+    recv_event(&mut ws1).await;
+    let (_, offset) = recv_event(&mut ws2).await;
+
+    ws2.close(None).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    ws1.send(Message::Text(r#"42["private"]"#.into()))
+        .await
+        .unwrap();
+    assert!(recv(&mut ws1)
+        .await
+        .starts_with(r#"451-["private",null,{"_placeholder":true,"num":0},"#));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let auth = json!({ "pid": session["pid"], "offset": offset });
+    let (mut ws2, recovered) = connect(2208, auth).await;
+    assert_eq!(recovered, session);
+    assert_eq!(recv(&mut ws2).await, r#"42["recovered",true]"#);
+}
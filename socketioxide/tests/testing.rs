@@ -0,0 +1,361 @@
+//! Tests for the in-memory test harness, no network connection is used.
+#![cfg(feature = "test-utils")]
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+use socketioxide::{
+    extract::{AckSender, Bin, Data, SocketRef},
+    packet::{Packet, PacketData},
+    ConnectError, DisconnectReason, OverflowPolicy, SocketIo,
+};
+use tokio::sync::mpsc;
+
+async fn timeout_rcv<T>(rx: &mut mpsc::Receiver<T>) -> T {
+    tokio::time::timeout(Duration::from_millis(200), rx.recv())
+        .await
+        .expect("timeout waiting for a message")
+        .unwrap()
+}
+
+#[tokio::test]
+pub async fn connect_with_auth() {
+    let (server, io) = SocketIo::builder().build_test();
+    io.ns(
+        "/",
+        |socket: SocketRef, Data(auth): Data<Value>| async move {
+            socket.emit("auth", auth).ok();
+        },
+    )
+    .with_middleware(|Data(auth): Data<Value>| async move {
+        match auth["token"].as_str() {
+            Some("secret") => Ok(()),
+            _ => Err(ConnectError::new("unauthorized")),
+        }
+    });
+
+    let mut client = server.new_client();
+    let err = client.connect("/", json!({ "token": "wrong" })).await;
+    assert_eq!(err.unwrap_err().message, "unauthorized");
+
+    let sid = client
+        .connect("/", json!({ "token": "secret" }))
+        .await
+        .unwrap();
+    assert_eq!(sid, client.id());
+    assert_eq!(
+        client.recv().await.unwrap(),
+        Packet::event("/".into(), "auth".into(), json!([{ "token": "secret" }]))
+    );
+}
+
+#[tokio::test]
+pub async fn emit_and_acks() {
+    let (server, io) = SocketIo::builder().build_test();
+    io.ns("/chat", |socket: SocketRef| async move {
+        socket.on(
+            "echo",
+            |socket: SocketRef, Data(data): Data<Value>, Bin(bin)| async move {
+                socket.bin(bin).emit("echo", data).await.ok();
+            },
+        );
+        socket.on(
+            "ack",
+            |Data(data): Data<Value>, ack: AckSender| async move {
+                ack.send(data).ok();
+            },
+        );
+        socket.on("ask", |socket: SocketRef| async move {
+            let (res, _) = socket.emit_with_ack::<String>("ask", ()).await.unwrap();
+            socket.emit("answer", res).ok();
+        });
+    });
+
+    let mut client = server.new_client();
+    client.connect("/chat", ()).await.unwrap();
+
+    client.emit("/chat", "echo", ("foo", 1));
+    assert_eq!(
+        client.recv().await.unwrap(),
+        Packet::event("/chat".into(), "echo".into(), json!(["foo", 1]))
+    );
+
+    client.emit_bin("/chat", "echo", "bin", vec![vec![1, 2, 3]]);
+    match client.recv().await.unwrap().inner {
+        PacketData::BinaryEvent(event, bin, None) => {
+            assert_eq!(event, "echo");
+            assert_eq!(bin.data, json!(["bin"]));
+            assert_eq!(bin.bin, vec![vec![1, 2, 3]]);
+        }
+        packet => panic!("unexpected packet: {:?}", packet),
+    }
+
+    let ack = client.emit_with_ack("/chat", "ack", "bar").await.unwrap();
+    assert_eq!(ack.inner, PacketData::EventAck(json!(["bar"]), 1));
+
+    client.emit("/chat", "ask", ());
+    let ack_id = match client.recv().await.unwrap().inner {
+        PacketData::Event(event, _, Some(ack_id)) if event == "ask" => ack_id,
+        packet => panic!("unexpected packet: {:?}", packet),
+    };
+    client.ack("/chat", ack_id, "response");
+    assert_eq!(
+        client.recv().await.unwrap(),
+        Packet::event("/chat".into(), "answer".into(), json!(["response"]))
+    );
+}
+
+#[tokio::test]
+pub async fn broadcast_binary() {
+    let (server, io) = SocketIo::builder().build_test();
+    io.ns("/", |socket: SocketRef| async move {
+        socket.on("broadcast", |socket: SocketRef, Bin(bin)| async move {
+            socket.broadcast().bin(bin).emit("msg", "bin").await.ok();
+        });
+    });
+
+    let mut clients = Vec::new();
+    for _ in 0..3 {
+        let mut client = server.new_client();
+        client.connect("/", ()).await.unwrap();
+        clients.push(client);
+    }
+
+    clients[0].emit_bin("/", "broadcast", (), vec![vec![1, 2], vec![3]]);
+    // Every other client receives the packet encoded once for all of them
+    for client in &mut clients[1..] {
+        match client.recv().await.unwrap().inner {
+            PacketData::BinaryEvent(event, bin, None) => {
+                assert_eq!(event, "msg");
+                assert_eq!(bin.data, json!(["bin"]));
+                assert_eq!(bin.bin, vec![vec![1, 2], vec![3]]);
+            }
+            packet => panic!("unexpected packet: {:?}", packet),
+        }
+    }
+}
+
+#[tokio::test]
+pub async fn emit_backpressure() {
+    let (server, io) = SocketIo::builder().build_test();
+    io.ns("/", |socket: SocketRef| async move {
+        socket.on("flood", |socket: SocketRef| async move {
+            for i in 0..1000 {
+                socket.emit_async("msg", i, None).await.unwrap();
+            }
+        });
+        socket.on("burst", |socket: SocketRef| async move {
+            let errors = (0..1000).filter(|i| socket.emit("msg", i).is_err()).count();
+            socket.emit_async("errors", errors, None).await.unwrap();
+        });
+    });
+
+    let mut client = server.new_client();
+    client.connect("/", ()).await.unwrap();
+
+    // The awaitable emits wait for the client to read the buffered messages, nothing is lost
+    client.emit("/", "flood", ());
+    for i in 0..1000 {
+        assert_eq!(
+            client.recv().await.unwrap(),
+            Packet::event("/".into(), "msg".into(), json!([i]))
+        );
+    }
+
+    // The other emits fail once the buffer is full
+    client.emit("/", "burst", ());
+    let mut received = 0;
+    let errors = loop {
+        match client.recv().await.unwrap().inner {
+            PacketData::Event(event, _, None) if event == "msg" => received += 1,
+            PacketData::Event(event, data, None) if event == "errors" => break data[0].clone(),
+            packet => panic!("unexpected packet: {:?}", packet),
+        }
+    };
+    assert!(errors.as_u64().unwrap() > 0);
+    assert_eq!(received + errors.as_u64().unwrap(), 1000);
+}
+
+#[tokio::test]
+pub async fn volatile_emit() {
+    let (server, io) = SocketIo::builder().build_test();
+    let io2 = io.clone();
+    io.ns("/", move |socket: SocketRef| {
+        let io = io2.clone();
+        async move {
+            socket.join("room").await.unwrap();
+            socket.on("single", |socket: SocketRef| async move {
+                socket.volatile().emit("msg", 0).await.unwrap();
+                socket.emit_async("done", (), None).await.unwrap();
+            });
+            socket.on("burst", move |socket: SocketRef| {
+                let io = io.clone();
+                async move {
+                    for i in 0..1000 {
+                        io.to("room").volatile().emit("msg", i).await.unwrap();
+                    }
+                    socket.emit_async("done", (), None).await.unwrap();
+                }
+            });
+        }
+    });
+
+    let mut client = server.new_client();
+    client.connect("/", ()).await.unwrap();
+
+    client.emit("/", "single", ());
+    assert_eq!(
+        client.recv().await.unwrap(),
+        Packet::event("/".into(), "msg".into(), json!([0]))
+    );
+    assert_eq!(
+        client.recv().await.unwrap(),
+        Packet::event("/".into(), "done".into(), json!([null]))
+    );
+
+    // The messages emitted while the buffer is full are dropped without error
+    client.emit("/", "burst", ());
+    let mut received = vec![];
+    loop {
+        match client.recv().await.unwrap().inner {
+            PacketData::Event(event, data, None) if event == "msg" => {
+                received.push(data[0].as_u64().unwrap())
+            }
+            PacketData::Event(event, _, None) if event == "done" => break,
+            packet => panic!("unexpected packet: {:?}", packet),
+        }
+    }
+    assert!(!received.is_empty() && received.len() < 1000);
+    assert!(received.windows(2).all(|w| w[0] < w[1]));
+}
+
+/// Emit binary packets with two attachments until the buffer of the client overflows with the given policy,
+/// returns the ids of the packets received, the number of emit errors if the client is still connected
+/// and the number of packets seen by the outgoing handler.
+async fn binary_overflow(policy: OverflowPolicy) -> (Vec<u64>, Option<u64>, usize) {
+    let (server, io) = SocketIo::builder().build_test();
+    let outgoing = Arc::new(AtomicUsize::new(0));
+    let outgoing2 = outgoing.clone();
+    io.ns("/", move |socket: SocketRef| {
+        let outgoing = outgoing2.clone();
+        async move {
+            socket.on_any_outgoing(move |_, event, _, _| {
+                if event == "msg" {
+                    outgoing.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+            socket.on("burst", move |socket: SocketRef| async move {
+                socket.set_overflow_policy(policy);
+                let mut errors = 0;
+                for i in 0..500u64 {
+                    let bin = vec![vec![i as u8], vec![(i >> 8) as u8]];
+                    // The broadcast errors of the packets that can't be buffered are returned
+                    if socket.bin(bin).emit("msg", i).await.is_err() {
+                        errors += 1;
+                    }
+                }
+                // The places left by the last packet that did not fit are still available
+                socket.emit("done", errors).ok();
+            });
+        }
+    });
+
+    let mut client = server.new_client();
+    client.connect("/", ()).await.unwrap();
+    client.emit("/", "burst", ());
+
+    let mut received = vec![];
+    loop {
+        let packet = tokio::time::timeout(Duration::from_millis(200), client.recv())
+            .await
+            .expect("timeout waiting for a packet");
+        match packet.map(|p| p.inner) {
+            Some(PacketData::BinaryEvent(event, bin, None)) if event == "msg" => {
+                // Each packet is received with all its attachments
+                let i = bin.data[0].as_u64().unwrap();
+                assert_eq!(bin.bin, vec![vec![i as u8], vec![(i >> 8) as u8]]);
+                received.push(i);
+            }
+            Some(PacketData::Event(event, data, None)) if event == "done" => {
+                break (received, data[0].as_u64(), outgoing.load(Ordering::Relaxed));
+            }
+            None => break (received, None, outgoing.load(Ordering::Relaxed)),
+            packet => panic!("unexpected packet: {:?}", packet),
+        }
+    }
+}
+
+#[tokio::test]
+pub async fn binary_overflow_policies() {
+    // The buffer of the test client has 200 places, 66 packets of 3 messages fit in it
+    let (received, errors, outgoing) = binary_overflow(OverflowPolicy::Error).await;
+    assert_eq!(received, (0..66).collect::<Vec<_>>());
+    assert_eq!(errors, Some(500 - 66));
+    // The packets that failed to be sent are not seen by the outgoing handler
+    assert_eq!(outgoing, 66);
+
+    let (received, errors, _) = binary_overflow(OverflowPolicy::DropNewest).await;
+    assert_eq!(received, (0..66).collect::<Vec<_>>());
+    assert_eq!(errors, Some(0));
+
+    let (received, errors, _) = binary_overflow(OverflowPolicy::DropOldest).await;
+    assert_eq!(received.len(), 66);
+    assert!(received.windows(2).all(|w| w[0] < w[1]));
+    assert_eq!(errors, Some(0));
+
+    let (received, errors, outgoing) = binary_overflow(OverflowPolicy::Disconnect).await;
+    assert_eq!(received, (0..66).collect::<Vec<_>>());
+    assert_eq!(errors, None);
+    assert_eq!(outgoing, 66);
+}
+
+#[tokio::test]
+pub async fn disconnect_reasons() {
+    let (server, io) = SocketIo::builder().build_test();
+    let (tx, mut rx) = mpsc::channel::<DisconnectReason>(4);
+    io.ns("/", move |socket: SocketRef| {
+        let tx = tx.clone();
+        async move {
+            socket.on_disconnect(move |_, reason| {
+                let tx = tx.clone();
+                async move {
+                    tx.try_send(reason).unwrap();
+                }
+            });
+            socket.on("leave", |socket: SocketRef| async move {
+                socket.disconnect().await.ok();
+            });
+        }
+    });
+
+    let mut client = server.new_client();
+    client.connect("/", ()).await.unwrap();
+    client.disconnect("/");
+    assert_eq!(
+        timeout_rcv(&mut rx).await,
+        DisconnectReason::ClientNSDisconnect
+    );
+
+    client.connect("/", ()).await.unwrap();
+    client.emit("/", "leave", ());
+    assert_eq!(client.recv().await.unwrap(), Packet::disconnect("/".into()));
+    assert_eq!(
+        timeout_rcv(&mut rx).await,
+        DisconnectReason::ServerNSDisconnect
+    );
+
+    client.connect("/", ()).await.unwrap();
+    client.close(DisconnectReason::HeartbeatTimeout);
+    assert_eq!(
+        timeout_rcv(&mut rx).await,
+        DisconnectReason::HeartbeatTimeout
+    );
+    assert_eq!(client.recv().await, None);
+    assert!(client.is_closed());
+}